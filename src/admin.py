@@ -0,0 +1,319 @@
+"""
+archie-admin: command line operations for ArchieAI.
+Works on the data directory directly through the lib modules, so it doesn't need the web
+server running. Run from the project root: python src/admin.py --help
+"""
+import os
+import sys
+import json
+import getpass
+import argparse
+from datetime import datetime, timedelta
+from lib.Config import load_config
+from lib.SessionManager import SessionManager
+from lib.DataCollector import DataCollector, EXPORT_FORMATS
+from lib.Migrations import run_migrations
+from lib.KnowledgeBase import refresh_knowledge_base
+from lib.Crawler import Crawler
+from lib.AcademicCalendar import AcademicCalendar
+from lib.UserMemory import UserMemory
+
+
+def confirm(prompt: str, assume_yes: bool) -> bool:
+    if assume_yes:
+        return True
+    return input(f"{prompt} [y/N] ").strip().lower() in ("y", "yes")
+
+
+def cmd_users_list(config, args) -> int:
+    users = SessionManager(config=config).list_users()
+    if args.json:
+        print(json.dumps(users, indent=2))
+        return 0
+    for user in users:
+        role = "admin" if user["is_admin"] else user["role"]
+        print(f"{user['email']:<40} {role:<6} {user['session_count']:>4} sessions  created {user['created_at']}")
+    print(f"{len(users)} users")
+    return 0
+
+
+def cmd_users_delete(config, args) -> int:
+    session_manager = SessionManager(config=config)
+    if session_manager.get_user(args.email) is None:
+        print(f"No such user: {args.email}")
+        return 1
+    if not confirm(f"Delete {args.email} and all of their sessions?", args.yes):
+        return 1
+    session_manager.delete_user(args.email)
+    UserMemory(None, config.data_dir).clear(args.email)
+    print(f"Deleted {args.email}")
+    return 0
+
+
+def cmd_users_reset_password(config, args) -> int:
+    session_manager = SessionManager(config=config)
+    if session_manager.get_user(args.email) is None:
+        print(f"No such user: {args.email}")
+        return 1
+
+    password = args.password
+    if password is None:
+        password = getpass.getpass("New password: ")
+        if password != getpass.getpass("Repeat password: "):
+            print("Passwords don't match")
+            return 1
+    if not password:
+        print("Password must not be empty")
+        return 1
+
+    session_manager.set_password(args.email, password)
+    print(f"Password reset for {args.email}")
+    return 0
+
+
+def cmd_sessions_purge(config, args) -> int:
+    if args.older_than is None and args.user is None and not args.guests:
+        print("Refusing to purge every session; pass --older-than, --user, or --guests")
+        return 1
+
+    older_than = datetime.now() - timedelta(days=args.older_than) if args.older_than is not None else None
+    session_manager = SessionManager(config=config)
+    matching = session_manager.purge_sessions(older_than=older_than, user_email=args.user,
+                                              guests_only=args.guests, dry_run=True)
+    if args.dry_run:
+        for session_id in matching:
+            print(session_id)
+        print(f"{len(matching)} sessions would be purged")
+        return 0
+    if not matching:
+        print("No matching sessions")
+        return 0
+    if not confirm(f"Purge {len(matching)} sessions?", args.yes):
+        return 1
+
+    purged = session_manager.purge_sessions(older_than=older_than, user_email=args.user, guests_only=args.guests)
+    print(f"Purged {len(purged)} sessions")
+    return 0
+
+
+def cmd_analytics_export(config, args) -> int:
+    chunks = DataCollector(config=config).export_interactions(
+        args.format, start=args.start, end=args.end, user_email=args.user
+    )
+    if args.output in (None, "-"):
+        for chunk in chunks:
+            sys.stdout.write(chunk)
+        return 0
+
+    with open(args.output, "w", encoding="utf-8", newline="") as f:
+        for chunk in chunks:
+            f.write(chunk)
+    print(f"Wrote {args.output}", file=sys.stderr)
+    return 0
+
+
+def cmd_analytics_topics(config, args) -> int:
+    from lib.GemInterface import AiInterface
+    from lib.TopicModeling import TopicModeler
+
+    modeler = TopicModeler(AiInterface(config=config), DataCollector(config=config), data_dir=config.data_dir)
+    report = modeler.run(num_topics=args.topics)
+    print(f"{len(report['topics'])} topics from {report['total_questions']} questions ({report['method']})")
+    for topic in report["topics"]:
+        print(f"{topic['count']:>5}  {topic['label']}  ({topic['representative']})")
+    return 0
+
+
+def cmd_analytics_reports(config, args) -> int:
+    from lib.AnalyticsReports import AnalyticsReports
+
+    result = AnalyticsReports(DataCollector(config=config), data_dir=config.data_dir, write_csv=config.reports.csv).run()
+    print(f"Wrote {len(result['daily'])} daily and {len(result['weekly'])} weekly reports; "
+          f"summary as of {result['summary_as_of']}")
+    return 0
+
+
+def cmd_check_data(config, args) -> int:
+    from lib.DataIntegrity import DataIntegrityChecker, format_report
+
+    report = DataIntegrityChecker(SessionManager(config=config)).check(repair=args.repair)
+    if args.json:
+        print(json.dumps(report, indent=2))
+    else:
+        print(format_report(report, repair=args.repair))
+    return 0 if all(issue["repaired"] for issue in report["issues"]) else 1
+
+
+def cmd_migrate(config, args) -> int:
+    return 0 if run_migrations(config) else 1
+
+
+def cmd_reindex(config, args) -> int:
+    print("Refreshing the knowledge base...")
+    crawler = Crawler.from_config(config.crawl, config.data_dir)
+    if crawler is not None:
+        print(f"Crawling {', '.join(config.crawl.start_urls)} (up to {config.crawl.max_pages} pages)...")
+    results = refresh_knowledge_base(config.data_dir, crawler=crawler, full=args.full)
+    for name, text in results.items():
+        print(f"  {name}: {len(text)} characters")
+    if crawler is not None:
+        print("  Crawled pages: " + ", ".join(f"{count} {outcome.replace('_', ' ')}" for outcome, count in sorted(crawler.last_stats.items())))
+
+    # Imported here so the other commands don't need the Ollama client installed
+    from lib.GemInterface import AiInterface
+    print("Rebuilding the search index...")
+    index = AiInterface(config=config).knowledge.build(full=args.full)
+    print(f"  {index['chunks']} chunks ({'keywords and vectors' if index['embedded'] else 'keywords only'}), "
+          f"{index['last_build']['changed_sources']} sources rebuilt, {index['last_build']['reused_sources']} unchanged")
+
+    if config.tools.academic_calendar_url:
+        print("Refreshing the academic calendar...")
+        calendar = AcademicCalendar(config.data_dir, config.tools.academic_calendar_url).ingest()
+        print(f"  {len(calendar['events'])} dates ({calendar['source_type']})")
+
+    if args.clusters:
+        from lib.QuestionClustering import QuestionClusterer
+
+        print("Rebuilding question clusters...")
+        clusterer = QuestionClusterer(AiInterface(config=config), DataCollector(config=config), data_dir=config.data_dir)
+        report = clusterer.run()
+        print(f"  {len(report['clusters'])} clusters ({report['method']})")
+    return 0
+
+
+def cmd_eval(config, args) -> int:
+    # Imported here so the other commands don't need the Ollama client installed
+    from lib.GemInterface import AiInterface
+    from lib.Evaluation import Evaluator, SuiteError, resolve_suite
+
+    def progress(result):
+        status = "PASS" if result["passed"] else "FAIL"
+        detail = result.get("error") or ", ".join(
+            [f"missing {fact}" for fact in result.get("missing_facts", [])]
+            + [f"said {phrase}" for phrase in result.get("forbidden_found", [])]
+        )
+        judge = result.get("judge")
+        if judge:
+            detail = f"{detail}; judge {judge['score']}: {judge['reason']}".lstrip("; ")
+        print(f"  {status}  {result['id']}" + (f"  ({detail})" if detail else ""))
+
+    try:
+        suite_path = resolve_suite(config.evals_dir, args.suite)
+        report = Evaluator(AiInterface(config=config), data_dir=config.data_dir).run(
+            suite_path, use_judge=args.judge, progress=progress
+        )
+    except SuiteError as e:
+        print(e)
+        return 1
+
+    summary = report["summary"]
+    print(f"{summary['passed']}/{summary['total']} passed ({summary['pass_rate']:.0%}), "
+          f"model {report['model']}, prompt {report['prompt_sha256']}")
+    print(f"Report: {os.path.join(config.data_dir, 'eval_reports', report['report_file'])}")
+    return 0 if summary["pass_rate"] >= args.fail_under else 1
+
+
+def cmd_digest(config, args) -> int:
+    from lib.Mailer import Mailer, MailError
+    from lib.Digest import DigestScheduler, render_digest
+
+    scheduler = DigestScheduler(
+        DataCollector(config=config),
+        SessionManager(config=config),
+        Mailer.from_config(config),
+        config.admin.emails,
+        data_dir=config.data_dir,
+        top_questions=config.digest.top_questions,
+    )
+    if not args.send:
+        print(render_digest(scheduler.build()), end="")
+        return 0
+    try:
+        scheduler.send()
+    except MailError as e:
+        print(e)
+        return 1
+    print(f"Sent the digest to {', '.join(config.admin.emails)}")
+    return 0
+
+
+def build_arg_parser() -> argparse.ArgumentParser:
+    parser = argparse.ArgumentParser(prog="archie-admin", description="ArchieAI admin operations")
+    parser.add_argument("--config", help="Path to the TOML config file (default: archie.toml)")
+    parser.add_argument("--data-dir", help="Data directory (overrides config)")
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    users = commands.add_parser("users", help="Manage user accounts").add_subparsers(dest="action", required=True)
+    users_list = users.add_parser("list", help="List users")
+    users_list.add_argument("--json", action="store_true", help="Print JSON instead of a table")
+    users_list.set_defaults(func=cmd_users_list)
+    users_delete = users.add_parser("delete", help="Delete a user and their sessions")
+    users_delete.add_argument("email")
+    users_delete.add_argument("-y", "--yes", action="store_true", help="Don't ask for confirmation")
+    users_delete.set_defaults(func=cmd_users_delete)
+    users_reset = users.add_parser("reset-password", help="Set a new password for a user")
+    users_reset.add_argument("email")
+    users_reset.add_argument("--password", help="New password (prompted for if omitted)")
+    users_reset.set_defaults(func=cmd_users_reset_password)
+
+    sessions = commands.add_parser("sessions", help="Manage chat sessions").add_subparsers(dest="action", required=True)
+    purge = sessions.add_parser("purge", help="Delete sessions in bulk")
+    purge.add_argument("--older-than", type=int, metavar="DAYS", help="Only sessions inactive for this many days")
+    purge.add_argument("--user", help="Only this user's sessions")
+    purge.add_argument("--guests", action="store_true", help="Only guest sessions")
+    purge.add_argument("--dry-run", action="store_true", help="List matching sessions without deleting")
+    purge.add_argument("-y", "--yes", action="store_true", help="Don't ask for confirmation")
+    purge.set_defaults(func=cmd_sessions_purge)
+
+    analytics = commands.add_parser("analytics", help="Analytics data").add_subparsers(dest="action", required=True)
+    export = analytics.add_parser("export", help="Export interactions")
+    export.add_argument("--from", dest="start", help="Start date/datetime (ISO)")
+    export.add_argument("--to", dest="end", help="End date/datetime (ISO)")
+    export.add_argument("--user", help="Only this user's interactions")
+    export.add_argument("--format", choices=sorted(EXPORT_FORMATS), default="json")
+    export.add_argument("-o", "--output", help="Output file (default: stdout)")
+    export.set_defaults(func=cmd_analytics_export)
+    topics = analytics.add_parser("topics", help="Group logged questions into topics (writes data/question_topics.json)")
+    topics.add_argument("--topics", type=int, metavar="N", help="Number of topics (default: based on the number of questions)")
+    topics.set_defaults(func=cmd_analytics_topics)
+    reports = analytics.add_parser("reports", help="Write any missing daily/weekly rollups to data/reports/")
+    reports.set_defaults(func=cmd_analytics_reports)
+
+    commands.add_parser("migrate", help="Create/upgrade files in the data directory").set_defaults(func=cmd_migrate)
+
+    check_data = commands.add_parser("check-data", help="Cross-check account and session files for drift and corruption")
+    check_data.add_argument("--repair", action="store_true", help="Fix what can be fixed; quarantine bad session files")
+    check_data.add_argument("--json", action="store_true", help="Print the full report as JSON")
+    check_data.set_defaults(func=cmd_check_data)
+
+    reindex = commands.add_parser("reindex", help="Re-scrape the knowledge base sources and academic calendar, and rebuild the search index")
+    reindex.add_argument("--clusters", action="store_true", help="Also rebuild the question clustering report")
+    reindex.add_argument("--full", action="store_true", help="Re-extract, re-chunk, and re-embed every page, even unchanged ones")
+    reindex.set_defaults(func=cmd_reindex)
+
+    evaluate = commands.add_parser("eval", help="Run an evaluation suite through the answer pipeline")
+    evaluate.add_argument("suite", nargs="?", default="default", help="Suite name in the evals directory, or a YAML file")
+    judge = evaluate.add_mutually_exclusive_group()
+    judge.add_argument("--judge", dest="judge", action="store_true", default=None, help="Grade answers with the LLM judge")
+    judge.add_argument("--no-judge", dest="judge", action="store_false", help="Only check expected facts")
+    evaluate.add_argument("--fail-under", type=float, default=1.0, metavar="RATE",
+                          help="Exit with 1 if the pass rate is below this (default: 1.0)")
+    evaluate.set_defaults(func=cmd_eval)
+
+    digest = commands.add_parser("digest", help="Print (or email) the weekly admin digest")
+    digest.add_argument("--send", action="store_true", help="Email it to the admins instead of printing it")
+    digest.set_defaults(func=cmd_digest)
+    return parser
+
+
+def main(argv=None) -> int:
+    args = build_arg_parser().parse_args(argv)
+    config = load_config(args.config)
+    if args.data_dir:
+        config.paths.data_dir = args.data_dir
+    config.layout.ensure()
+    return args.func(config, args)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
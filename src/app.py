@@ -6,36 +6,1049 @@ import asyncio
 import time
 import flask as fk
 import json
+import logging
+import queue
+import hashlib
+import math
+import functools
+import re
+import urllib.parse
+from collections import Counter
+from dataclasses import dataclass
+from typing import Optional
+import argparse
+import requests
 proj_root = os.path.dirname(__file__)         
 src_dir = os.path.join(proj_root, "src")
 sys.path.insert(0, src_dir)
 from lib import GemInterface
 from lib import qrCodeGen
-from lib.SessionManager import SessionManager
-from lib.DataCollector import DataCollector
+from lib.SessionManager import SessionManager, MAX_CHECKPOINTS, MAX_PINNED, USER_STATUSES
+from lib.DataCollector import DataCollector, EXPORT_FORMATS, TIMESERIES_METRICS, TIMESERIES_INTERVALS, MAX_TIMESERIES_BUCKETS, normalize_question
+from lib.QuestionClustering import QuestionClusterer
+from lib.TopicModeling import TopicModeler, MAX_TOPICS
+from lib.Evaluation import Evaluator, list_suites, resolve_suite
+from lib.ModelComparison import compare_models
+from lib.Canary import CanaryRollout, compare_arms
+from lib.SloTracker import SloTracker
+from lib.InteractionTail import InteractionTail
+from lib.InstanceLock import InstanceLock, InstanceLockHeld, ReadOnlyStorage
+from lib.SessionTokens import SessionTokens, SESSION_COOKIE, SECRET_FILE, load_secret
+from lib.Passwords import argon2_available, hash_scheme
+from lib.WebSocket import WebSocket, WebSocketClosed
+from lib.Config import load_config
+from lib.Migrations import run_migrations
+from lib.KnowledgeBase import load_scrape_results, load_source_info, refresh_knowledge_base
+from lib.Crawler import Crawler, CRAWL_STATE_FILE
+from lib.IndexJobs import IndexJobQueue
+from lib.FaqStore import FaqStore
+from lib.BackendHealth import BackendHealth
+from lib.GenerationQueue import QueueFull, PRIORITIES
+from lib.CircuitBreaker import CircuitOpen, STATE_VALUES
+from lib.AnswerStyle import STYLES
+from lib.StreamBuffer import StreamRegistry, parse_event_id
+from lib.Shutdown import ShutdownCoordinator, STOP_GRACE_SECONDS
+from lib.Cancellation import CancellationRegistry, GenerationCancelled
+from lib.Logging import setup_logging, bind_request, update_request, fields
+from lib.Inflight import InflightGenerations, close_async_generator
+from lib.Drafts import DraftStore
+from lib.ProfanityFilter import ProfanityFilter
+from lib.Frustration import ReviewQueue, frustration_signals
+from lib.Kiosk import new_kiosk_session_id, is_kiosk_session
+from lib.Transcript import render_text, render_html, transcript_subject, SendQuota
+from lib.AbuseDetector import AbuseDetector
+from lib.RateLimiter import RateLimiter
+from lib.UserMemory import UserMemory
+from lib.SessionSummary import SessionSummarizer, history_summary_coverage
+from lib.FeatureFlags import FeatureFlags, FLAG_DEFINITIONS
+from lib.AccountExport import export_account
+from lib.SpeechToText import SpeechToText, TranscriptionError, is_audio_type
+from lib.Validation import validate_question, validate_faq_entry, validate_announcement, validate_feedback, validate_checkpoint, validate_session_title, validate_model, validate_style, validate_flag, validate_merge, validate_timeseries, validate_date_range, validate_user_status, validate_comparison, validate_canary, validate_canary_percent, parse_window
+from lib.Webhooks import WebhookNotifier
+from lib.Mailer import Mailer, MailError
+from lib.Digest import DigestScheduler, render_digest
+from lib.AnalyticsReports import AnalyticsReports, PERIODS as REPORT_PERIODS
+from lib.DataIntegrity import DataIntegrityChecker, format_report
+from lib.SessionEvents import SessionEventBroker
+from lib import GraphQLApi
+from lib.Locale import translate, negotiate_locale, SUPPORTED_LOCALES, DEFAULT_LOCALE
+from lib.Errors import AppError, ValidationError, Unauthorized, Forbidden, AccountSuspended, TermsNotAccepted, NotFound, Conflict, PayloadTooLarge, TooManyRequests, Upstream, Unavailable, ReadOnly, Internal
+from werkzeug.exceptions import HTTPException
 from werkzeug.security import generate_password_hash
+from werkzeug.serving import make_server
+from werkzeug.middleware.proxy_fix import ProxyFix
 
-gemini = GemInterface.AiInterface()
+app = fk.Flask(__name__)
+logger = logging.getLogger("archie")
 
-session_manager = SessionManager(data_dir="data")
-data_collector = DataCollector(data_dir="data")
+class BasePathMiddleware:
+    """
+    Mounts the app under a sub-path (e.g. /archie) for the university's shared reverse proxy.
+    Setting SCRIPT_NAME makes url_for, redirects, and static URLs include the prefix. Requests
+    work whether or not the proxy strips the prefix before forwarding.
+    """
+    def __init__(self, wsgi_app):
+        self.wsgi_app = wsgi_app
+        self.base_path = ""
 
-app = fk.Flask(__name__)
+    def __call__(self, environ, start_response):
+        if self.base_path:
+            path = environ.get("PATH_INFO", "")
+            if path == self.base_path or path.startswith(self.base_path + "/"):
+                environ["PATH_INFO"] = path[len(self.base_path):] or "/"
+            environ["SCRIPT_NAME"] = self.base_path
+        return self.wsgi_app(environ, start_response)
+
+# remote_addr is the client's address rather than the proxy's once server.trusted_proxies is set
+proxy_fix = ProxyFix(app.wsgi_app, x_for=0, x_proto=0, x_host=0, x_port=0, x_prefix=0)
+base_path_middleware = BasePathMiddleware(proxy_fix)
+app.wsgi_app = base_path_middleware
+
+def init_app(cfg):
+    """(Re)create the shared services from a Config. Called at import and again if the CLI changes the config."""
+    global config, gemini, session_manager, data_collector, question_clusterer, topic_modeler, webhooks, session_events, faq_store, evaluator, feature_flags, speech_to_text, digest_scheduler, backend_health, abuse_detector, user_memory, session_summarizer, analytics_reports, answer_streams, drafts, kiosk_quota, mailer, transcript_quota, inflight, profanity_filter, review_queue, canary, slo_tracker, interaction_tail, instance_lock, session_tokens, rate_limiter, shutdown, cancellations
+    config = cfg
+    setup_logging(config.logging.format, config.logging.level)
+    # The XDG directories usually don't exist yet on a fresh install
+    config.layout.ensure()
+    feature_flags = FeatureFlags(config.data_dir, config.flags)
+    # Taken when the server starts serving, see acquire_instance_lock()
+    instance_lock = InstanceLock(config.data_dir)
+    canary = CanaryRollout(config.data_dir, config.prompts_dir)
+    slo_tracker = SloTracker.from_config(config) if config.slo.enabled else None
+    gemini = GemInterface.AiInterface(config=config, flags=feature_flags)
+    session_manager = SessionManager(config=config)
+    # A replica can't create the key file; it reads the primary's
+    session_tokens = SessionTokens(load_secret(config.cookies.secret, config.data_dir, create=config.instance.role != "replica"),
+                                   max_age_seconds=config.cookies.session_max_age_days * 86400)
+    session_events = SessionEventBroker()
+    session_manager.add_listener(session_events.publish)
+    data_collector = DataCollector(config=config)
+    interaction_tail = InteractionTail()
+    data_collector.add_listener(interaction_tail.publish)
+    question_clusterer = QuestionClusterer(gemini, data_collector, data_dir=config.data_dir)
+    topic_modeler = TopicModeler(gemini, data_collector, data_dir=config.data_dir)
+    analytics_reports = AnalyticsReports(data_collector, data_dir=config.data_dir, write_csv=config.reports.csv)
+    evaluator = Evaluator(gemini, data_dir=config.data_dir)
+    speech_to_text = SpeechToText.from_config(config)
+    backend_health = BackendHealth(config.ollama.host, check_interval=config.fallback.health_check_seconds)
+    webhooks = WebhookNotifier(config=config)
+    abuse_detector = AbuseDetector(
+        config.data_dir,
+        max_questions_per_minute=config.abuse.max_questions_per_minute,
+        repeat_threshold=config.abuse.repeat_threshold,
+        repeat_window_seconds=config.abuse.repeat_window_seconds,
+        max_question_length=config.limits.max_question_length,
+        long_question_ratio=config.abuse.long_question_ratio,
+        extra_patterns=config.abuse.jailbreak_patterns,
+    )
+    kiosk_quota = AbuseDetector(
+        config.data_dir,
+        max_questions_per_minute=config.kiosk.max_questions_per_minute,
+        repeat_threshold=config.kiosk.repeat_threshold,
+        repeat_window_seconds=config.abuse.repeat_window_seconds,
+        max_question_length=min(config.limits.max_question_length, config.kiosk.max_question_length),
+        long_question_ratio=config.abuse.long_question_ratio,
+        extra_patterns=config.abuse.jailbreak_patterns,
+    )
+    rate_limiter = RateLimiter.from_config(config) if config.rate_limit.enabled else None
+    profanity_filter = ProfanityFilter.from_config(config)
+    review_queue = ReviewQueue(config.data_dir)
+    mailer = Mailer.from_config(config)
+    transcript_quota = SendQuota(max_sends=config.mail.transcripts_per_hour, window_seconds=3600)
+    digest_scheduler = DigestScheduler(
+        data_collector,
+        session_manager,
+        mailer,
+        config.admin.emails,
+        data_dir=config.data_dir,
+        weekday=config.digest.weekday,
+        hour=config.digest.hour,
+        top_questions=config.digest.top_questions,
+    )
+    faq_store = FaqStore(
+        config.data_dir,
+        embed=(lambda texts: asyncio.run(gemini.embed(texts))) if config.faq.semantic_matching else None,
+        similarity_threshold=config.faq.similarity_threshold,
+    )
+    user_memory = UserMemory(
+        gemini,
+        config.data_dir,
+        max_per_user=config.memory.max_per_user,
+        embed=lambda texts: asyncio.run(gemini.embed(texts)),
+    )
+    session_summarizer = SessionSummarizer(gemini, session_manager)
+    answer_streams = StreamRegistry(grace_seconds=config.streams.resume_grace_seconds,
+                                    ttl_seconds=config.streams.resume_ttl_seconds)
+    inflight = InflightGenerations()
+    cancellations = CancellationRegistry()
+    drafts = DraftStore(ttl_seconds=config.drafts.ttl_seconds, max_drafts=config.drafts.max_pending)
+    shutdown = ShutdownCoordinator(drain_seconds=config.server.drain_seconds)
+    # Open event streams end first so their clients reconnect to another instance
+    shutdown.on_drain("session event streams", session_events.close_all)
+    shutdown.on_drain("admin live feed", interaction_tail.close_all)
+    # Saves the partial answers of those still generating
+    shutdown.on_stop("answer streams", lambda: answer_streams.stop_all(STOP_GRACE_SECONDS))
+    shutdown.on_close("analytics", data_collector.close)
+    shutdown.on_close("instance lock", lambda: instance_lock.release() if instance_lock.held else None)
+    app.template_folder = config.templates_dir
+    app.static_folder = config.static_dir
+    # In dev mode templates are re-read when they change instead of being cached for the process lifetime
+    app.config["TEMPLATES_AUTO_RELOAD"] = config.server.dev_mode
+    app.jinja_env.auto_reload = config.server.dev_mode
+    # Browsers revalidate /static files with the ETag after this many seconds
+    app.config["SEND_FILE_MAX_AGE_DEFAULT"] = config.static.max_age
+    # Global body size limit; Flask answers anything bigger with a 413
+    app.config["MAX_CONTENT_LENGTH"] = config.limits.max_body_bytes
+    base_path_middleware.base_path = config.server.base_path
+    proxy_fix.x_for = config.server.trusted_proxies
+    app.config["APPLICATION_ROOT"] = config.server.base_path or "/"
+
+init_app(load_config())
+clustering_lock = threading.Lock()
+topics_lock = threading.Lock()
+eval_lock = threading.Lock()
+
+def set_cookie(resp, name: str, value: str):
+    """Set a cookie using the configured cookie policy."""
+    resp.set_cookie(
+        name,
+        value,
+        path=config.server.base_path or "/",
+        httponly=config.cookies.httponly,
+        secure=config.cookies.secure,
+        samesite=config.cookies.samesite
+    )
+
+# What sessions were kept in before signed tokens (see legacy_auth())
+LEGACY_COOKIES = ("session_id", "user_email")
+
+def set_session_cookie(resp, session_id: str, user_email: Optional[str] = None):
+    """Point the caller at a session, signed in as user_email (None for guests and kiosks)."""
+    version = session_manager.token_version(session_manager.get_user(user_email)) if user_email else 0
+    set_cookie(resp, SESSION_COOKIE, session_tokens.issue(session_id, user_email, version))
+    for name in LEGACY_COOKIES:
+        if name in fk.request.cookies:
+            resp.delete_cookie(name, path=config.server.base_path or "/")
+    fk.g.session_cookie_set = True
+
+def match_faq(question: str, session_id: Optional[str] = None) -> Optional[dict]:
+    """Curated FAQ entry for a question (see lib/FaqStore.py), or None if there isn't one or FAQs are off."""
+    if not config.faq.enabled or not feature_flags.is_enabled("faq_answers", session_id):
+        return None
+    return faq_store.match(question)
+
+def backend_down() -> bool:
+    """True if offline fallback is on and Ollama can't be reached, or the circuit breaker is open."""
+    if not config.fallback.enabled:
+        return False
+    try:
+        # Counts the question as short-circuited if the breaker is open
+        gemini.breaker.check()
+    except CircuitOpen:
+        return True
+    return not backend_health.is_up()
+
+def offline_answer(question: str, locale: str = DEFAULT_LOCALE):
+    """
+    Answer without the model while it's unreachable.
+
+    Returns:
+        (answer, faq_match): a curated FAQ answer if one matches (whatever the faq_answers flag says),
+        otherwise the limited-mode message with suggested links and None
+    """
+    faq = faq_store.match(question) if config.faq.enabled else None
+    if faq:
+        return faq["entry"]["answer"], faq
+    answer = translate("Archie is temporarily limited and can't answer new questions right now. Please try again in a few minutes.", locale)
+    # The chat renders plain text, so no markdown links
+    links = "\n".join(f"- {link['title']}: {link['url']}" for link in config.fallback.links)
+    if links:
+        answer += "\n\n" + translate("In the meantime, these pages may help:", locale) + "\n" + links
+    return answer, None
+
+def memory_enabled(user_email: Optional[str]) -> bool:
+    """Whether Archie remembers new facts about this user (they have to opt in, see lib/UserMemory.py)."""
+    if not config.memory.enabled or not user_email:
+        return False
+    return session_manager.get_preferences(user_email).get("memory") is True
+
+def personalization_enabled(user_email: Optional[str]) -> bool:
+    """Whether remembered facts are used in this user's answers: memory on and personalization not turned off."""
+    return memory_enabled(user_email) and session_manager.get_preferences(user_email).get("personalization", True) is True
+
+def available_models() -> list:
+    """Chat models users can pick: the two default ones plus ollama.models."""
+    return gemini.allowed_models
+
+def resolve_model(requested: Optional[str], session_id: Optional[str], user_email: Optional[str], default: str) -> str:
+    """
+    The model to answer with: the one asked for in the request, then the session's, then the user's
+    default_model preference, then the server default. Saved picks that are no longer configured are skipped.
+    """
+    allowed = available_models()
+    session_data = session_manager.get_session(session_id) if session_id else None
+    for choice in (requested, (session_data or {}).get("model"), session_manager.get_preferences(user_email).get("default_model")):
+        if choice and choice in allowed:
+            return choice
+    return default
+
+def default_stream_model() -> str:
+    """The server default for streamed answers: the model promoted by a canary, else ollama.stream_model."""
+    promoted = canary.promoted()["model"]
+    return promoted if promoted in available_models() else config.ollama.stream_model
+
+def apply_canary(session_id: Optional[str], model: Optional[str]) -> tuple:
+    """
+    The model and system prompt for a streamed answer once the running canary is applied (lib/Canary.py),
+    and the {"id", "arm"} the answer is tagged with in the analytics (None outside the canary).
+    """
+    model = model or default_stream_model()
+    assignment = canary.assign(session_id, model, default_stream_model())
+    if assignment is None:
+        return model, canary.promoted()["prompt"], None
+    return assignment["model"], assignment["prompt"], {"id": assignment["id"], "arm": assignment["arm"]}
+
+def resolve_style(requested: Optional[str], session_id: Optional[str], default: Optional[str] = None) -> Optional[str]:
+    """
+    The answer style: the one asked for in the request, then the session's default, then default
+    (the kiosk's), else None (no directive).
+    """
+    if requested:
+        return requested
+    session_data = session_manager.get_session(session_id) if session_id else None
+    style = (session_data or {}).get("style") or default
+    return style if style in STYLES else None
+
+def kiosk_request(session_id: Optional[str]) -> bool:
+    """True if this caller is a kiosk: a kiosk session, or any caller when the whole server is one."""
+    return config.kiosk.enabled and (config.kiosk.exclusive or is_kiosk_session(session_id))
+
+def question_limit(kiosk: bool = False) -> int:
+    """Longest question accepted, in characters; kiosks have their own, stricter limit."""
+    if kiosk:
+        return min(config.limits.max_question_length, config.kiosk.max_question_length)
+    return config.limits.max_question_length
+
+def remember_exchange(user_email: Optional[str], session_id: Optional[str], question: str, answer: str):
+    """Pull durable facts about the user out of an exchange, in the background."""
+    if not memory_enabled(user_email) or not answer:
+        return
+    threading.Thread(
+        target=user_memory.remember,
+        args=(user_email, question, answer, session_id),
+        kwargs={"still_allowed": lambda: memory_enabled(user_email)},
+        daemon=True,
+    ).start()
+
+def persist_exchange(session_id: Optional[str], user_email: Optional[str], ip_address: str, device_info: str, question: str,
+                     answer: str, generation_time_seconds: float, request_id: Optional[str], answer_source: str,
+                     faq_entry_id: Optional[str] = None, model: Optional[str] = None, citations: Optional[list] = None,
+                     interrupted: bool = False, remember: bool = False, experiment: Optional[dict] = None,
+                     partial: bool = False):
+    """
+    Save an exchange to its session (if there is one) and the analytics log, and with remember
+    set, learn from it. Runs as soon as an answer is done, or on commit for a draft. experiment
+    is the canary arm the answer came from, see apply_canary(). partial means the answer stopped
+    before it was done (the generation failed, or with interrupted, the client disconnected),
+    so answer is only what was produced by then.
+    """
+    partial = partial or interrupted
+    if session_id:
+        session_manager.add_message(session_id, "user", question)
+        session_manager.add_message(session_id, "assistant", answer, citations=citations, request_id=request_id,
+                                    interrupted=interrupted, partial=partial)
+        check_frustration(session_id, user_email)
+        if not partial:
+            title_session(session_id)
+            compress_history(session_id)
+
+    # Collect analytics data I LOVE DATA COLLECTION
+    data_collector.log_interaction(
+        session_id=session_id if session_id else "no_session",
+        user_email=user_email,
+        ip_address=ip_address,
+        device_info=device_info,
+        question=question,
+        answer=answer,
+        generation_time_seconds=generation_time_seconds,
+        request_id=request_id,
+        answer_source=answer_source,
+        faq_entry_id=faq_entry_id,
+        interrupted=partial,
+        model=model,
+        experiment=experiment
+    )
+    # Don't learn from half an answer
+    if remember and answer_source == "model" and not partial:
+        remember_exchange(user_email, session_id, question, answer)
+
+def title_session(session_id: str):
+    """Name a chat after its first exchange (lib/SessionSummary.py), in the background so the answer isn't held up."""
+    if not feature_flags.is_enabled("session_titles", session_id):
+        return
+    session_data = session_manager.get_session(session_id)
+    if not session_data or session_data.get("title_source") or len(session_data.get("messages", [])) != 2:
+        return
+
+    def generate():
+        try:
+            title = session_summarizer.title(session_id)
+        except Exception as e:
+            # The chat list falls back to the first question
+            logger.warning(f"Could not generate a title for session {session_id}: {e}")
+            return
+        if title:
+            logger.info(f"Session {session_id} titled: {title}")
+
+    threading.Thread(target=generate, daemon=True).start()
 
-def Archie(query: str, conversation_history: list = None) -> str:
+def compress_history(session_id: str):
+    """
+    Fold messages that have aged out of the history window into the session's rolling summary
+    (lib/SessionSummary.py), in the background, once limits.summary_batch_messages are waiting.
+    """
+    batch = config.limits.summary_batch_messages
+    if batch <= 0 or not feature_flags.is_enabled("history_summaries", session_id):
+        return
+    session_data = session_manager.get_session(session_id)
+    if not session_data:
+        return
+    aged_out = len(session_data.get("messages", [])) - config.limits.history_messages
+    if aged_out - history_summary_coverage(session_data) < batch:
+        return
+
+    def generate():
+        try:
+            summary = session_summarizer.compress_history(session_id, keep=config.limits.history_messages, batch=batch)
+        except Exception as e:
+            # The next exchange tries again; until then the window just doesn't reach as far back
+            logger.warning(f"Could not update the history summary of session {session_id}: {e}")
+            return
+        if summary:
+            logger.info(f"Session {session_id} history summarized through message {summary['message_count']}")
+
+    threading.Thread(target=generate, daemon=True).start()
+
+def check_frustration(session_id: str, user_email: Optional[str]):
+    """Queue a session for review and alert staff, once, when the user looks stuck (lib/Frustration.py)."""
+    if not config.frustration.enabled:
+        return
+    session_data = session_manager.get_session(session_id)
+    if not session_data:
+        return
+    messages = session_data.get("messages", [])
+    resolved_at = (session_data.get("review") or {}).get("resolved_at")
+    if resolved_at:
+        # Staff already followed up on what came before
+        messages = [m for m in messages if (m.get("timestamp") or "") > resolved_at]
+    assessment = frustration_signals(messages, window=config.frustration.window)
+    if assessment["score"] < config.frustration.threshold:
+        return
+    review = review_queue.flag(session_id, session_data.get("user_email") or user_email, assessment)
+    if review is None:
+        return
+    session_manager.set_review(session_id, {"reason": review["reason"], "flagged_at": review["flagged_at"]})
+    logger.info(f"Session {session_id} flagged for review (frustration score {assessment['score']})")
+    webhooks.session_frustrated(session_id, review["user_email"], assessment["score"], assessment["signals"])
+    if config.frustration.email_alerts and mailer is not None and config.admin.emails:
+        threading.Thread(target=send_frustration_alert, args=(session_manager.get_session(session_id), review),
+                         daemon=True).start()
+
+def send_frustration_alert(session_data: dict, review: dict):
+    signals = ", ".join(f"{name} x{count}" for name, count in review["signals"].items() if count)
+    body = (f"A student seems to be stuck and may need someone to follow up.\n\n"
+            f"Session: {review['session_id']}\nUser: {review['user_email'] or 'guest'}\n"
+            f"Score: {review['score']} ({signals})\n\n"
+            f"Mark it resolved with POST /api/admin/reviews/{review['session_id']}/resolve once someone has.\n\n"
+            + render_text(session_data))
+    try:
+        mailer.send(config.admin.emails, f"ArchieAI: a student seems stuck (session {review['session_id'][:8]})", body)
+    except MailError as e:
+        logger.warning(f"Failed to email the frustration alert for {review['session_id']}: {e}")
+
+def Archie(query: str, conversation_history: list = None, model: str = None, pinned: list = None, style: str = None,
+           session_id: Optional[str] = None, priority: str = "batch", history_summary: Optional[str] = None) -> str:
     """
     Synchronous wrapper to run the async gemini.Archie in a new event loop.
     """
-    return asyncio.run(gemini.Archie(query, conversation_history=conversation_history))
+    return asyncio.run(gemini.Archie(query, conversation_history=conversation_history, model=model, pinned=pinned, style=style,
+                                     session_id=session_id, priority=priority, history_summary=history_summary))
+
+
+
+
+
+#Request context "extractors". Handlers declare what they need with a decorator and get it as a keyword argument:
+#   @with_session()  -> ctx: SessionCtx   (session cookie, required unless required=False)
+#   @with_user       -> user: AuthedUser  (must be logged in as an existing user)
+#   @with_admin      -> admin: AdminUser  (must be an admin)
+@dataclass
+class SessionCtx:
+    session_id: Optional[str]
+    user_email: Optional[str]
+    # A kiosk caller (lib/Kiosk.py): never signed in, nothing saved to a session
+    kiosk: bool = False
+
+@dataclass
+class AuthedUser:
+    email: str
+    record: dict
+
+@dataclass
+class AdminUser(AuthedUser):
+    pass
+
+# What a suspended or banned account is told when it tries to do anything
+STATUS_MESSAGES = {
+    "suspended": "Your account is suspended pending review",
+    "banned": "This account has been banned",
+}
+
+def require_active(email: Optional[str], record: Optional[dict] = None):
+    """Raise AccountSuspended if email is a suspended or banned account."""
+    if not email:
+        return
+    status = session_manager.user_status(record if record is not None else session_manager.get_user(email))
+    if status != "active":
+        raise AccountSuspended(STATUS_MESSAGES[status], status=status)
+
+#Who the caller is comes from the signed archie_session cookie (lib/SessionTokens.py), read once per request
+@dataclass
+class RequestAuth:
+    session_id: Optional[str] = None
+    user_email: Optional[str] = None
+
+def request_auth() -> RequestAuth:
+    """The caller's session and signed-in email; empty for a missing, forged, expired, or revoked token."""
+    if "auth" in fk.g:
+        return fk.g.auth
+    claims = session_tokens.read(fk.request.cookies.get(SESSION_COOKIE),
+                                 current_version=lambda email: session_manager.token_version(session_manager.get_user(email)))
+    if claims is not None:
+        auth = RequestAuth(session_id=claims["sid"], user_email=claims.get("email"))
+    elif fk.request.cookies.get("session_id"):
+        auth = legacy_auth()
+    else:
+        auth = RequestAuth()
+    fk.g.auth = auth
+    return auth
+
+def legacy_auth() -> RequestAuth:
+    """
+    The session_id and user_email cookies from before signed tokens. Nothing ever checked the email,
+    so it only counts if the session is that account's; upgrade_legacy_session_cookie() then swaps
+    both for a token.
+    """
+    session_id = fk.request.cookies.get("session_id")
+    email = fk.request.cookies.get("user_email")
+    if email:
+        session_data = session_manager.get_session(session_id)
+        if session_data is None or session_data.get("user_email") != email:
+            email = None
+    fk.g.legacy_auth = True
+    return RequestAuth(session_id=session_id, user_email=email)
+
+@app.after_request
+def upgrade_legacy_session_cookie(resp):
+    if fk.g.get("legacy_auth") and not fk.g.get("session_cookie_set"):
+        set_session_cookie(resp, fk.g.auth.session_id, fk.g.auth.user_email)
+    return resp
+
+def _resolve_user() -> Optional[AuthedUser]:
+    if config.kiosk.enabled and config.kiosk.exclusive:
+        # Nobody signs in on a kiosk-only server
+        return None
+    email = request_auth().user_email
+    record = session_manager.get_user(email)
+    if record is None:
+        return None
+    require_active(email, record)
+    return AuthedUser(email=email, record=record)
+
+def with_session(required: bool = True):
+    def decorator(view):
+        @functools.wraps(view)
+        def wrapper(*args, **kwargs):
+            auth = request_auth()
+            if required and not auth.session_id:
+                raise Unauthorized("No session found")
+            kiosk = kiosk_request(auth.session_id)
+            if not kiosk:
+                require_active(auth.user_email)
+            update_request(session_id=auth.session_id)
+            kwargs["ctx"] = SessionCtx(session_id=auth.session_id, user_email=None if kiosk else auth.user_email, kiosk=kiosk)
+            return view(*args, **kwargs)
+        return wrapper
+    return decorator
+
+def with_user(view):
+    @functools.wraps(view)
+    def wrapper(*args, **kwargs):
+        user = _resolve_user()
+        if user is None:
+            raise Unauthorized("Not logged in")
+        kwargs["user"] = user
+        return view(*args, **kwargs)
+    return wrapper
+
+def with_admin(view):
+    @functools.wraps(view)
+    def wrapper(*args, **kwargs):
+        user = _resolve_user()
+        if user is None or not session_manager.is_admin(user.email):
+            raise Forbidden("Admin access required")
+        kwargs["admin"] = AdminUser(email=user.email, record=user.record)
+        return view(*args, **kwargs)
+    return wrapper
+
+def require_terms(ctx: SessionCtx):
+    """Raise TermsNotAccepted unless the caller (or, for guests, their session) accepted the current terms."""
+    if not config.terms.version or ctx.kiosk:
+        # Off, or a kiosk, whose throwaway sessions can't keep an acceptance (it shows the disclaimer on screen)
+        return
+    accepted = session_manager.get_terms_acceptance(user_email=ctx.user_email, session_id=ctx.session_id)
+    if not accepted or accepted.get("version") != config.terms.version:
+        raise TermsNotAccepted(version=config.terms.version, url=config.terms.url or None, text=config.terms.text,
+                               previously_accepted=accepted.get("version") if accepted else None)
+
+def count_strike(email: Optional[str], reasons: list):
+    """Suspend the account once its abuse incidents pile up (abuse.auto_suspend), raising AccountSuspended."""
+    if (config.abuse.auto_suspend and session_manager.get_user(email) is not None and not session_manager.is_admin(email)
+            and abuse_detector.recent_incident_count(email, config.abuse.suspend_window_seconds) >= config.abuse.suspend_after_incidents):
+        session_manager.suspend_user(email, reason="automatic: " + ", ".join(reasons))
+        webhooks.account_suspended(email, reasons)
+        raise AccountSuspended(STATUS_MESSAGES["suspended"], status="suspended")
+
+def screen_question(question: str, ctx: SessionCtx) -> str:
+    """
+    Run the profanity filter (lib/ProfanityFilter.py) and the abuse heuristics (lib/AbuseDetector.py)
+    on a question before it's answered. Raises ValidationError for rejected profanity, AccountSuspended
+    for accounts suspended by it, and TooManyRequests for throttled callers.
+
+    Returns:
+        The question to answer, with any profanity masked
+    """
+    # Kiosk sessions are thrown away, so the kiosk quota is counted per device
+    detector = kiosk_quota if ctx.kiosk else abuse_detector
+    if config.profanity.enabled:
+        found = profanity_filter.check(question)
+        if found["matches"]:
+            reject = config.profanity.action == "reject"
+            detector.record_incident(["profanity"], "reject" if reject else "mask", question=question,
+                                     user_email=ctx.user_email, session_id=ctx.session_id, ip_address=fk.request.remote_addr)
+            logger.warning(f"Profanity filter {'rejected' if reject else 'masked'} {len(found['matches'])} word(s)")
+            count_strike(ctx.user_email, ["profanity"])
+            if reject:
+                raise ValidationError("Please keep it respectful, your question wasn't sent", field="question", reasons=["profanity"])
+            question = found["masked"]
+    
+    if not config.abuse.enabled and not ctx.kiosk:
+        return question
+    
+    if ctx.kiosk:
+        verdict = kiosk_quota.check(question, ip_address=fk.request.remote_addr)
+    else:
+        verdict = abuse_detector.check(question, user_email=ctx.user_email, session_id=ctx.session_id, ip_address=fk.request.remote_addr)
+    if verdict["incident"] is None:
+        return question
+    logger.warning(f"Abuse heuristics {verdict['action']}: {', '.join(verdict['reasons'])}")
+    
+    count_strike(ctx.user_email, verdict["reasons"])
+    if verdict["action"] == "throttle":
+        raise TooManyRequests(reasons=verdict["reasons"])
+    return question
+
+def load_owned_session(session_id: str, ctx: SessionCtx, allow_current: bool = True) -> dict:
+    """Load a session the caller owns (or is currently using), raising NotFound/Forbidden otherwise."""
+    session_data = session_manager.get_session(session_id)
+    if not session_data:
+        raise NotFound("Session not found")
+    
+    owns_session = ctx.user_email is not None and session_data.get("user_email") == ctx.user_email
+    if not owns_session and not (allow_current and session_id == ctx.session_id):
+        raise Forbidden()
+    return session_data
+
+#Content hashes for static files, keyed by path and invalidated when the file changes
+_asset_hashes = {}
+
+@app.template_global()
+def asset_url(filename: str) -> str:
+    """URL for a static file, fingerprinted with a content hash so it can be cached forever."""
+    url = fk.url_for("static", filename=filename)
+    if not config.static.fingerprint:
+        return url
+    
+    path = os.path.join(app.static_folder, filename)
+    try:
+        mtime = os.path.getmtime(path)
+    except OSError:
+        return url
+    
+    cached = _asset_hashes.get(path)
+    if cached is None or cached[0] != mtime:
+        with open(path, "rb") as f:
+            digest = hashlib.md5(f.read()).hexdigest()[:12]
+        cached = (mtime, digest)
+        _asset_hashes[path] = cached
+    return f"{url}?v={cached[1]}"
+
+@app.after_request
+def add_static_cache_headers(resp):
+    # Fingerprinted URLs change whenever the file does, so they never need revalidating
+    if fk.request.path.startswith(app.static_url_path + "/") and fk.request.args.get("v") and resp.status_code == 200:
+        resp.headers["Cache-Control"] = "public, max-age=31536000, immutable"
+    return resp
+
+#Every request gets an ID, returned in X-Request-Id and included in logs, errors, and analytics
+#so a user's bug report can be matched up with what the server saw. A sane incoming ID (e.g.
+#from a reverse proxy) is reused.
+REQUEST_ID_PATTERN = re.compile(r"^[A-Za-z0-9._-]{1,64}$")
+
+@app.before_request
+def assign_request_id():
+    incoming = fk.request.headers.get("X-Request-Id", "")
+    fk.g.request_id = incoming if REQUEST_ID_PATTERN.match(incoming) else uuid.uuid4().hex
+    # Every log line written for this request carries these (lib/Logging.py)
+    bind_request(request_id=fk.g.request_id, method=fk.request.method, path=fk.request.path)
+
+def current_request_id() -> Optional[str]:
+    return fk.g.get("request_id") if fk.has_request_context() else None
+
+@app.after_request
+def add_request_id_header(resp):
+    request_id = current_request_id()
+    if request_id:
+        resp.headers["X-Request-Id"] = request_id
+    return resp
+
+#Graceful shutdown: requests are counted so a SIGTERM can wait for them, and new ones are turned away
+#while the server drains (see lib/Shutdown.py)
+@app.before_request
+def track_in_flight_request():
+    if shutdown.draining:
+        # Health checks and metrics keep working so the load balancer can see the server is going away
+        if fk.request.path in ("/health", "/healthz", "/readyz", "/metrics"):
+            return
+        raise Unavailable("Archie is restarting, please try again in a moment", retry_after_seconds=5)
+    shutdown.request_started()
+    fk.g.in_flight = True
+
+@app.after_request
+def finish_in_flight_request(resp):
+    if fk.g.pop("in_flight", False):
+        if resp.is_streamed:
+            # A streamed answer is still running until its last byte is sent
+            resp.call_on_close(shutdown.request_finished)
+        else:
+            shutdown.request_finished()
+    return resp
+
+#Read-only replicas (instance.role = "replica") answer reads only; the proxy sends writes to the primary
+@app.before_request
+def refuse_writes_on_replica():
+    if config.instance.role == "replica" and fk.request.method not in ("GET", "HEAD", "OPTIONS"):
+        raise ReadOnly()
+
+#Rate limiting: every /api/ request takes a token from its session's and its IP's bucket, and a caller
+#that runs one dry gets a 429 with Retry-After (see lib/RateLimiter.py)
+@app.before_request
+def enforce_rate_limit():
+    path = fk.request.path
+    if rate_limiter is None or not path.startswith("/api/") or path.startswith(tuple(config.rate_limit.exempt_paths)):
+        return
+    auth = request_auth()
+    refused = rate_limiter.check(auth.session_id, fk.request.remote_addr)
+    if refused is None:
+        return
+    if refused["first"]:
+        # One event per flood, not one per refused request
+        logger.warning(f"Rate limited by {refused['scope']} on {path}")
+        data_collector.log_throttle(refused["scope"], auth.session_id, auth.user_email, fk.request.remote_addr,
+                                    path, refused["retry_after"], request_id=current_request_id())
+    raise TooManyRequests(reasons=[f"{refused['scope']}_rate_limit"], retry_after_seconds=round(refused["retry_after"], 1))
+
+#Latency SLOs: every request's duration is checked against its route's threshold and slow ones are
+#logged with what the route noted about them (see lib/SloTracker.py)
+@app.before_request
+def start_request_timing():
+    fk.g.request_started = time.monotonic()
+    fk.g.timing = {}
+
+def request_timing() -> dict:
+    """
+    Details for the slow-request log (model, history_messages, retrieval_seconds, first_token_seconds);
+    routes fill it in, and a streamed answer keeps filling it in after the request context is gone.
+    """
+    return fk.g.setdefault("timing", {}) if fk.has_request_context() else {}
+
+@app.after_request
+def track_request_slo(resp):
+    if slo_tracker is None or "request_started" not in fk.g:
+        return resp
+    route = fk.request.url_rule.rule if fk.request.url_rule is not None else "unmatched"
+    if not slo_tracker.tracks(route):
+        return resp
+    started, details, method, request_id = fk.g.request_started, fk.g.timing, fk.request.method, current_request_id()
 
+    def finish():
+        slo_tracker.record(route, method, resp.status_code, time.monotonic() - started, request_id, details)
+
+    if resp.is_streamed:
+        # Streamed answers are done once the last byte is sent
+        resp.call_on_close(finish)
+    else:
+        finish()
+    return resp
+
+#Server-generated text (errors, login alerts, error pages) is localized. The language is the user's
+#saved preference, then the browser's Accept-Language header, then English.
+@app.template_global()
+def current_locale() -> str:
+    if not fk.has_request_context():
+        return DEFAULT_LOCALE
+    if "locale" not in fk.g:
+        preferred = session_manager.get_preferences(request_auth().user_email).get("locale")
+        fk.g.locale = negotiate_locale(fk.request.accept_languages, preferred)
+    return fk.g.locale
+
+@app.template_global("_")
+def _(message: str, **params) -> str:
+    """Translate a server message into the current request's language."""
+    return translate(message, current_locale(), **params)
+
+# Only these responses carry translated text; static files and 304s don't need a header
+LOCALIZED_MIMETYPES = ("text/html", "application/json")
+
+@app.after_request
+def add_content_language_header(resp):
+    if resp.mimetype not in LOCALIZED_MIMETYPES:
+        return resp
+    # A response that never translated anything hasn't loaded the account; the browser's language is enough
+    resp.headers["Content-Language"] = fk.g.locale if "locale" in fk.g else negotiate_locale(fk.request.accept_languages)
+    return resp
+
+#Friendly titles/messages for the HTML error pages
+ERROR_PAGES = {
+    400: ("Bad request", "Something about that request didn't look right."),
+    401: ("Please log in", "You need to be logged in to see this page."),
+    403: ("No access", "You don't have access to this page."),
+    404: ("Page not found", "We couldn't find the page you were looking for."),
+    405: ("Not allowed", "That page doesn't support this kind of request."),
+    413: ("Too large", "That request was too large for Archie to handle."),
+    500: ("Something went wrong", "Archie ran into a problem on our end. Please try again in a moment."),
+    502: ("Archie is unavailable", "Archie's AI backend isn't responding right now. Please try again soon."),
+}
+
+def wants_json() -> bool:
+    """API routes always get JSON errors, pages get the branded error template."""
+    return fk.request.path.startswith("/api/")
+
+def render_error_page(status: int):
+    title, message = ERROR_PAGES.get(status, ERROR_PAGES[500])
+    return fk.render_template("error.html", status=status, title=_(title), message=_(message), request_id=current_request_id()), status
+
+@app.errorhandler(AppError)
+def handle_app_error(e):
+    if not wants_json():
+        resp = fk.make_response(render_error_page(e.status_code))
+    else:
+        resp = fk.make_response(fk.jsonify(dict(e.to_dict(translate=_), request_id=current_request_id())), e.status_code)
+    # Rate limits and quotas say when to come back
+    if "retry_after_seconds" in e.details:
+        resp.headers["Retry-After"] = str(max(1, math.ceil(e.details["retry_after_seconds"])))
+    return resp
 
+@app.errorhandler(ReadOnlyStorage)
+def handle_read_only_storage(e):
+    # A GET handler that writes (a new guest session, say) reached the storage on a replica
+    return handle_app_error(ReadOnly())
 
+@app.errorhandler(413)
+def handle_request_too_large(e):
+    # Some routes (audio uploads) raise the limit for their own requests
+    max_bytes = fk.request.max_content_length or config.limits.max_body_bytes
+    return handle_app_error(PayloadTooLarge("Request body must be at most {max_bytes} bytes", params={"max_bytes": max_bytes}))
 
+@app.errorhandler(HTTPException)
+def handle_http_error(e):
+    # Flask's own errors (unknown routes, wrong method, ...) get the same treatment as ours
+    if not wants_json():
+        return render_error_page(e.code)
+    return fk.jsonify({"error": _(e.name), "code": e.name.lower().replace(" ", "_"), "request_id": current_request_id()}), e.code
+
+@app.errorhandler(Exception)
+def handle_unexpected_error(e):
+    if isinstance(e, HTTPException):
+        return handle_http_error(e)
+    logger.exception(f"Unhandled error on {fk.request.method} {fk.request.path}: {e}")
+    return handle_app_error(Internal())
+
+
+#Prometheus metrics: generation queue load and per-priority counters
+@app.route("/metrics", methods=["GET"])
+def metrics():
+    """Prometheus text format; counts only, nothing about individual users."""
+    queue_metrics = gemini.queue.metrics()
+    lines = [
+        "# HELP archie_generation_queue_active Model generations running now",
+        "# TYPE archie_generation_queue_active gauge",
+        f"archie_generation_queue_active {queue_metrics['active']}",
+        "# HELP archie_generation_queue_max_concurrent Generation slots (0 = unlimited)",
+        "# TYPE archie_generation_queue_max_concurrent gauge",
+        f"archie_generation_queue_max_concurrent {queue_metrics['max_concurrent']}",
+    ]
+    series = [
+        ("waiting", "gauge", "Requests waiting for a generation slot"),
+        ("served", "counter", "Generations started"),
+        ("rejected", "counter", "Requests turned away because the queue was full or the wait timed out"),
+        ("wait_seconds", "counter", "Total seconds spent waiting for a slot"),
+    ]
+    for name, kind, description in series:
+        metric = f"archie_generation_queue_{name}" + ("_total" if kind == "counter" else "")
+        lines.append(f"# HELP {metric} {description}")
+        lines.append(f"# TYPE {metric} {kind}")
+        for priority in PRIORITIES:
+            lines.append(f'{metric}{{priority="{priority}"}} {queue_metrics[name][priority]}')
+
+    breaker = gemini.breaker.metrics()
+    lines += [
+        "# HELP archie_ollama_circuit_state Model backend circuit breaker (0 = closed, 1 = half-open, 2 = open)",
+        "# TYPE archie_ollama_circuit_state gauge",
+        f"archie_ollama_circuit_state {STATE_VALUES[breaker['state']]}",
+        "# HELP archie_ollama_consecutive_failures Failed Ollama calls since the last success",
+        "# TYPE archie_ollama_consecutive_failures gauge",
+        f"archie_ollama_consecutive_failures {breaker['consecutive_failures']}",
+        "# HELP archie_ollama_failures_total Failed Ollama calls",
+        "# TYPE archie_ollama_failures_total counter",
+        f"archie_ollama_failures_total {breaker['failures']}",
+        "# HELP archie_ollama_circuit_opened_total Times the circuit breaker opened",
+        "# TYPE archie_ollama_circuit_opened_total counter",
+        f"archie_ollama_circuit_opened_total {breaker['opened']}",
+        "# HELP archie_ollama_short_circuited_total Requests refused while the circuit was open",
+        "# TYPE archie_ollama_short_circuited_total counter",
+        f"archie_ollama_short_circuited_total {breaker['short_circuited']}",
+        "# HELP archie_answer_streams_active Answers generating in the background for resumable streams",
+        "# TYPE archie_answer_streams_active gauge",
+        f"archie_answer_streams_active {answer_streams.active()}",
+    ]
+    shared = inflight.metrics()
+    lines += [
+        "# HELP archie_shared_generations_active Generations answering identical questions for several requests at once",
+        "# TYPE archie_shared_generations_active gauge",
+        f"archie_shared_generations_active {shared['active']}",
+        "# HELP archie_generations_coalesced_total Questions that joined a generation already running for the same question",
+        "# TYPE archie_generations_coalesced_total counter",
+        f"archie_generations_coalesced_total {shared['coalesced']}",
+    ]
+    draft_metrics = drafts.metrics()
+    for name, kind, description in [
+        ("pending", "gauge", "Ephemeral answers waiting to be committed"),
+        ("created", "counter", "Ephemeral answers generated"),
+        ("committed", "counter", "Ephemeral answers committed to a session"),
+        ("discarded", "counter", "Ephemeral answers that expired without being committed"),
+    ]:
+        metric = f"archie_draft_answers_{name}" + ("_total" if kind == "counter" else "")
+        lines += [f"# HELP {metric} {description}", f"# TYPE {metric} {kind}", f"{metric} {draft_metrics[name]}"]
+    if gemini.fact_checker is not None:
+        checks = gemini.fact_checker.metrics()
+        lines += ["# HELP archie_fact_checks_total Answers fact-checked against the retrieved pages, by outcome",
+                  "# TYPE archie_fact_checks_total counter"]
+        lines += [f'archie_fact_checks_total{{result="{result}"}} {checks[result]}' for result in ("supported", "unsupported", "failed")]
+        lines += ["# HELP archie_fact_check_regenerations_total Answers written again after failing the fact check",
+                  "# TYPE archie_fact_check_regenerations_total counter",
+                  f"archie_fact_check_regenerations_total {checks['regenerated']}"]
+    if rate_limiter is not None:
+        limits = rate_limiter.metrics()
+        lines += ["# HELP archie_rate_limited_total Requests refused by the rate limiter, by the bucket that ran dry",
+                  "# TYPE archie_rate_limited_total counter"]
+        lines += [f'archie_rate_limited_total{{scope="{scope}"}} {count}' for scope, count in limits["throttled"].items()]
+        lines += ["# HELP archie_rate_limit_buckets Session and IP buckets being tracked",
+                  "# TYPE archie_rate_limit_buckets gauge",
+                  f"archie_rate_limit_buckets {limits['tracked']}"]
+    if slo_tracker is not None:
+        route_metrics = slo_tracker.metrics()
+        for metric, kind, key, description in [
+            ("archie_http_requests_total", "counter", "requests", "Finished requests per route"),
+            ("archie_http_request_seconds_total", "counter", "seconds", "Total seconds spent on requests per route"),
+            ("archie_slo_violations_total", "counter", "violations", "Requests slower than their route's SLO threshold"),
+            ("archie_slo_threshold_seconds", "gauge", "threshold_seconds", "SLO threshold per route"),
+        ]:
+            lines += [f"# HELP {metric} {description}", f"# TYPE {metric} {kind}"]
+            lines += [f'{metric}{{route="{route}"}} {stats[key]}' for route, stats in route_metrics.items()]
+    return fk.Response("\n".join(lines) + "\n", mimetype="text/plain; version=0.0.4")
+
+#Health check for load balancers and uptime monitors
+@app.route("/health", methods=["GET"])
+def health():
+    """
+    200 while the server is up, since questions still get the offline fallback; status is
+    "degraded" while Ollama is unreachable or its circuit breaker is open. 503 with status
+    "draining" once it's shutting down, so load balancers stop sending it requests.
+    """
+    breaker = gemini.breaker.metrics()
+    backend_up = backend_health.is_up()
+    if shutdown.draining:
+        return fk.jsonify({"status": "draining", "in_flight": shutdown.in_flight(), "instance": {"role": config.instance.role}}), 503
+    return fk.jsonify({
+        "status": "ok" if backend_up and breaker["state"] == "closed" else "degraded",
+        "ollama": {"reachable": backend_up, "circuit": breaker},
+        "queue": {key: value for key, value in gemini.queue.metrics().items() if key in ("active", "max_concurrent", "waiting")},
+        "instance": {"role": config.instance.role},
+    })
+
+def data_dir_status() -> dict:
+    """
+    Whether the data directory can be written, by writing and removing a probe file; replicas
+    never write, so for them it only has to be readable.
+    """
+    if config.instance.role == "replica":
+        if os.access(config.data_dir, os.R_OK | os.X_OK):
+            return {"status": "ok", "check": "readable"}
+        return {"status": "fail", "check": "readable", "error": f"{config.data_dir} isn't readable"}
+    probe = os.path.join(config.data_dir, f".write_test-{uuid.uuid4().hex}")
+    try:
+        with open(probe, "w", encoding="utf-8") as f:
+            f.write("ok")
+        os.remove(probe)
+    except OSError as e:
+        return {"status": "fail", "check": "writable", "error": str(e)}
+    return {"status": "ok", "check": "writable"}
+
+#Liveness probe: the process is up and serving. Orchestrators restart the server when this fails,
+#so it stays 200 while draining and whatever Ollama is doing
+@app.route("/healthz", methods=["GET"])
+def healthz():
+    """Always {"status": "ok"} while the process can answer at all."""
+    return fk.jsonify({"status": "ok"})
+
+#Readiness probe: whether this instance should get traffic right now, checked live on every call
+@app.route("/readyz", methods=["GET"])
+def readyz():
+    """
+    200 with status "ready" if Ollama answers and the data directory is usable, else 503 with
+    "not_ready", or "draining" once the server is shutting down. Each component says how it did.
+    """
+    components = {
+        "ollama": asyncio.run(gemini.health_check()),
+        "data_dir": data_dir_status(),
+    }
+    if shutdown.draining:
+        status = "draining"
+    elif all(component["status"] == "ok" for component in components.values()):
+        status = "ready"
+    else:
+        status = "not_ready"
+    body = {"status": status, "components": components, "instance": {"role": config.instance.role}}
+    return fk.jsonify(body), 200 if status == "ready" else 503
 
 @app.route("/", methods=["GET"])
 def home():
     # Check if user has a session
-    session_id = fk.request.cookies.get("session_id")
+    session_id = request_auth().session_id
+    if kiosk_request(session_id):
+        return fk.redirect(fk.url_for("kiosk"))
     if session_id:
         # User has session, redirect to chat
         return fk.redirect(fk.url_for("index"))
@@ -45,284 +1058,1860 @@ def home():
 @app.route("/index", methods=["GET"])
 def index():
     # Main chat interface
-    session_id = fk.request.cookies.get("session_id")
+    session_id = request_auth().session_id
     if not session_id:
         # No session, redirect to login
         return fk.redirect(fk.url_for("home"))
-    return fk.render_template("index.html")
+    if kiosk_request(session_id):
+        return fk.redirect(fk.url_for("kiosk"))
+    return fk.render_template("index.html", voice_input=speech_to_text is not None)
+
+#Touchscreen kiosk (the library lobby): a throwaway session, no history, no sign-in
+@app.route("/kiosk", methods=["GET"])
+def kiosk():
+    """Start a fresh kiosk session and show the chat page in kiosk mode; every visit starts over."""
+    if not config.kiosk.enabled:
+        raise NotFound()
+    resp = fk.make_response(fk.render_template("index.html", voice_input=speech_to_text is not None, kiosk=True,
+                                               idle_reset_seconds=config.kiosk.idle_reset_seconds))
+    # Signed in as nobody, whoever used this browser before
+    set_session_cookie(resp, new_kiosk_session_id())
+    return resp
 
 @app.route("/api/archie", methods=["POST"])
-def api_archie():
+@with_session(required=False)
+def api_archie(ctx: SessionCtx):
     start_time = time.time()
     
-    data = fk.request.get_json()
-    question = data.get("question", "")
-    session_id = fk.request.cookies.get("session_id")
-    user_email = fk.request.cookies.get("user_email")
+    data = fk.request.get_json(silent=True)
+    question = validate_question(data, max_length=question_limit(ctx.kiosk))
+    require_terms(ctx)
+    question = screen_question(question, ctx)
+    # Kiosk questions are answered without history and not saved to a session
+    session_id = None if ctx.kiosk else ctx.session_id
+    user_email = ctx.user_email
+    model = resolve_model(validate_model(data.get("model"), available_models()), session_id, user_email, config.ollama.model)
+    style = resolve_style(validate_style(data.get("style")), session_id, default=config.kiosk.style if ctx.kiosk else None)
+    ephemeral = validate_flag(data.get("ephemeral"), "ephemeral")
     
     # Get conversation history if session exists
     conversation_history = []
+    history_summary = None
+    pinned = []
     if session_id:
-        conversation_history = session_manager.get_conversation_history(session_id)
+        history_summary, conversation_history = session_manager.get_conversation_context(session_id)
+        pinned = session_manager.get_pinned_messages(session_id)
+    request_timing().update(model=model, history_messages=len(conversation_history))
     
-    answer = Archie(question, conversation_history=conversation_history)
+    faq_match = match_faq(question, session_id)
+    source = "faq" if faq_match else "model"
+    if faq_match:
+        answer = faq_match["entry"]["answer"]
+    elif backend_down():
+        answer, faq_match = offline_answer(question, current_locale())
+        source = "faq" if faq_match else "fallback"
+    else:
+        try:
+            answer = Archie(question, conversation_history=conversation_history, model=model, pinned=pinned, style=style,
+                            session_id=session_id, priority="user" if user_email else "guest", history_summary=history_summary)
+        except QueueFull as e:
+            logger.warning(f"Generation queue full: {e}")
+            raise Unavailable()
+        except CircuitOpen as e:
+            # Fallback is off (backend_down() would have caught it otherwise), so shed the request
+            logger.warning(str(e))
+            raise Unavailable()
+        except Exception as e:
+            logger.error(f"Error during generation: {e}")
+            webhooks.generation_failed(e, request_id=current_request_id())
+            # A connection error means the backend just went away; re-check before giving up
+            if not (config.fallback.enabled and not backend_health.check()):
+                raise Upstream()
+            answer, faq_match = offline_answer(question, current_locale())
+            source = "faq" if faq_match else "fallback"
     
     # Calculate generation time
     generation_time = time.time() - start_time
     
-    # Save to session if session_id exists
-    if session_id:
-        session_manager.add_message(session_id, "user", question)
-        session_manager.add_message(session_id, "assistant", answer)
-    
-    # Collect analytics data
-    data_collector.log_interaction(
-        session_id=session_id if session_id else "no_session",
+    exchange = dict(
+        session_id=session_id,
         user_email=user_email,
         ip_address=fk.request.remote_addr,
         device_info=fk.request.user_agent.string,
         question=question,
         answer=answer,
-        generation_time_seconds=generation_time
+        generation_time_seconds=generation_time,
+        request_id=current_request_id(),
+        answer_source=source,
+        faq_entry_id=faq_match["entry"]["entry_id"] if faq_match else None,
+        model=model if source == "model" else None
     )
+    result = {"answer": answer, "source": source, "model": model if source == "model" else None}
+    if ephemeral:
+        # Held until POST /api/sessions/<id>/commit, nothing is saved yet
+        result["draft"] = drafts.add(session_id, exchange)
+    else:
+        persist_exchange(**exchange)
     
-    print(f"Question: {question}\nAnswer: {answer}\n")
-    return fk.jsonify({"answer": answer})
+    logger.info(f"Question: {question}\nAnswer: {answer}",
+                extra=fields(source=source, model=exchange["model"], generation_seconds=round(generation_time, 3)))
+    return fk.jsonify(result)
 import datetime
-@app.route("/api/archie/stream", methods=["POST"])
-def api_archie_stream():
+
+def coalesce_key(question: str, session_id: Optional[str], model: Optional[str], style: Optional[str],
+                 prompt: Optional[str] = None) -> Optional[tuple]:
     """
-    Streaming endpoint that returns AI responses token by token.
-    This provides a better user experience by showing the AI "thinking" in real-time.
+    What a context-free answer depends on, for sharing one generation between identical questions
+    (lib/Inflight.py): the normalized question, model, style, system prompt, and the feature flags
+    (which can change the prompt per session). None when coalescing is off.
     """
-    start_time = time.time()
-    
-    data = fk.request.get_json()
-    question = data.get("question", "")
-    session_id = fk.request.cookies.get("session_id")
-    user_email = fk.request.cookies.get("user_email")
-    
-    # Capture request info for data collection
-    ip_address = fk.request.remote_addr
-    device_info = fk.request.user_agent.string
-    
-    def generate():
-        full_response = ""
-        loop = None
+    if not config.queue.coalesce_identical:
+        return None
+    flags = tuple(feature_flags.is_enabled(name, session_id) for name in sorted(FLAG_DEFINITIONS))
+    return normalize_question(question), model or config.ollama.stream_model, style, prompt, flags
+
+def drive_async_generator(loop, agen):
+    """Iterate an async generator from sync code on the given event loop."""
+    while True:
         try:
-            # Get conversation history if session exists
-            conversation_history = []
-            if session_id:
-                conversation_history = session_manager.get_conversation_history(session_id)
-            
-            # Create a new event loop for this request 
-            loop = asyncio.new_event_loop()
-            
-            async_gen = gemini.Archie_streaming(question, conversation_history=conversation_history)
-            while True:
-                try:
-                    # Get the next item from the async generator
-                    chunk = loop.run_until_complete(async_gen.__anext__())
-                    
-                    
+            yield loop.run_until_complete(agen.__anext__())
+        except StopAsyncIteration:
+            return
+
+def stream_archie_answer(question: str, session_id, user_email, ip_address: str, device_info: str, start_time: float, request_id: Optional[str] = None, locale: str = DEFAULT_LOCALE,
+                         model: Optional[str] = None, style: Optional[str] = None, ephemeral: bool = False,
+                         timing: Optional[dict] = None, cancel_key: Optional[str] = None):
+    """
+    Generator that yields an answer as event dicts ({"token": ...}, {"tool_call": ...}, {"faq": ...},
+    {"citations": [...]} for the knowledge base pages in the prompt, with the date each was fetched,
+    {"fallback": ...} when Ollama is unreachable, {"done": True}, or an error body) and saves the exchange once it's done. Shared by the chat stream endpoint and
+    the edit-and-resubmit endpoint; stream_response() encodes the events as SSE or NDJSON.
+    It runs after the request context is gone, so the request ID, locale, the model resolved by
+    resolve_model() (None for the server default), and the style from resolve_style() are passed in explicitly.
+    An ephemeral answer isn't saved: it's held as a draft, announced with a {"draft": ...} event before done.
+    The running canary, if any, may swap the model and system prompt (apply_canary()). timing is the
+    request's request_timing() dict; the model, history size, and retrieval and first-token times go in it.
+    If the client disconnects mid-answer, the server closes the generator: the model stream is
+    aborted and whatever was generated so far is saved with an interrupted flag. The same happens,
+    followed by {"cancelled": True} and done, when POST /api/archie/cancel comes in for cancel_key
+    (the caller's session, kiosks included; lib/Cancellation.py).
+    """
+    full_response = ""
+    citations = None
+    cancel = cancellations.register(cancel_key)
+    cancelled = False
+    # Token counts Ollama reported for the answer, for the log
+    usage = {}
+    loop = None
+    async_gen = None
+    source = "model"
+    faq_match = None
+    saved = False
+    model, prompt, experiment = apply_canary(session_id, model)
+    timing = timing if timing is not None else {}
+    timing["model"] = model
+
+    def save_exchange(interrupted: bool = False, partial: bool = False) -> Optional[dict]:
+        """Save the exchange, or for an ephemeral answer hold it as a draft and return the draft."""
+        nonlocal saved
+        saved = True
+        exchange = dict(
+            session_id=session_id,
+            user_email=user_email,
+            ip_address=ip_address,
+            device_info=device_info,
+            question=question,
+            answer=full_response,
+            generation_time_seconds=time.time() - start_time,
+            request_id=request_id,
+            answer_source=source,
+            faq_entry_id=faq_match["entry"]["entry_id"] if faq_match else None,
+            model=(model or config.ollama.stream_model) if source == "model" else None,
+            # Offline answers didn't use the retrieved pages
+            citations=citations if source == "model" else None,
+            interrupted=interrupted,
+            partial=partial,
+            remember=True,
+            experiment=experiment if source == "model" else None
+        )
+        if not ephemeral:
+            persist_exchange(**exchange)
+        elif not (interrupted or partial):
+            return drafts.add(session_id, exchange)
+        # An unfinished draft can't be committed by the client, so it's dropped
+        return None
+
+    def save_partial(reason: str):
+        """Keep what was streamed before a failure, so the transcript matches what the user saw."""
+        if saved or not full_response:
+            return
+        try:
+            save_exchange(partial=True)
+        except Exception as e:
+            logger.warning(f"Could not save the partial answer after {reason}: {e}")
+
+    try:
+        # Get conversation history if session exists
+        conversation_history = []
+        history_summary = None
+        pinned = []
+        if session_id:
+            history_summary, conversation_history = session_manager.get_conversation_context(session_id)
+            pinned = session_manager.get_pinned_messages(session_id)
+        timing["history_messages"] = len(conversation_history)
+
+        faq_match = match_faq(question, session_id)
+        source = "faq" if faq_match else "model"
+        offline = not faq_match and backend_down()
+        if faq_match:
+            # Curated answer, no need to ask the model
+            full_response = faq_match["entry"]["answer"]
+            yield {'faq': {'entry_id': faq_match["entry"]["entry_id"], 'match': faq_match["match"]}}
+            yield {'token': full_response}
+        elif not offline:
+            personalization = personalization_enabled(user_email)
+            memories = user_memory.relevant(user_email, question, limit=config.memory.prompt_limit) if personalization else None
+
+            def start_generation(cancel_token=None):
+                return gemini.Archie_streaming(question, conversation_history=conversation_history, session_id=session_id,
+                                               memories=memories, personalization=personalization, model=model,
+                                               priority="user" if user_email else "guest", pinned=pinned, style=style,
+                                               prompt=prompt, history_summary=history_summary, cancel=cancel_token)
+
+            key = coalesce_key(question, session_id, model, style, prompt) if not (conversation_history or pinned or memories) else None
+            if key is not None:
+                # Same question already being answered for someone else: share that generation
+                shared, started = inflight.join(key, start_generation)
+                if not started:
+                    logger.info("Joined the generation already running for this question")
+                chunks = shared.read()
+            else:
+                # Create a new event loop for this request 
+                loop = asyncio.new_event_loop()
+                async_gen = start_generation(cancel)
+                chunks = drive_async_generator(loop, async_gen)
+            try:
+                for chunk in chunks:
+                    # A shared generation doesn't get the token (others are waiting on it), so check here too
+                    cancel.check()
                     if isinstance(chunk, str):
+                        timing.setdefault("first_token_seconds", round(time.time() - start_time, 3))
                         # Append it to the full response and stream it.
                         full_response += chunk
-                        yield f"data: {json.dumps({'token': chunk})}\n\n"
-                    
+                        yield {'token': chunk}
+
                     elif isinstance(chunk, dict):
                         # Make it JSON-safe before streaming. because trial and error is the only way to figure this out apparently
-                        
+
                         if chunk.get('tool_name'):
                             # Create a NEW, safe dictionary for the client
                             json_safe_payload = {
                                 'tool_name': chunk.get('tool_name'),
                                 'tool_result_preview': str(chunk.get('tool_result'))[:500]
                             }
-                            yield f"data: {json.dumps({'tool_call': json_safe_payload})}\n\n"
-                            
+                            yield {'tool_call': json_safe_payload}
+
+                        elif chunk.get('citations') is not None:
+                            citations = chunk['citations']
+                            yield {'citations': citations}
+
+                        elif chunk.get('timing'):
+                            timing.update(chunk['timing'])
+
+                        elif chunk.get('verification'):
+                            # The fact check's verdict; any note already came as tokens
+                            yield {'verification': chunk['verification']}
+
                         elif chunk.get('final'):
-                            # This is just a signal, ignore it.
-                            pass
-                        
-                    
+                            # The end of the model's answer; keep its token counts for the log
+                            usage = chunk.get('usage') or {}
+
+
                     else:
                         # Safely log it and send a debug message.
-                        
+
                         chunk_type = type(chunk).__name__
-                        print(f"Warning: Received unexpected chunk type: {chunk_type}")
-                        
+                        logger.warning(f"Received unexpected chunk type: {chunk_type}")
+
                         # Optionally send a safe representation to the client
-                        yield f"data: {json.dumps({'debug_info': f'Received object: {chunk_type}'})}\n\n"
-                except StopAsyncIteration:
-                    # The generator is done.
+                        yield {'debug_info': f'Received object: {chunk_type}'}
+            except GenerationCancelled:
+                # The user stopped the answer; keep what they saw
+                logger.info(f"Cancelled after {len(full_response)} characters")
+                cancelled = True
+            except CircuitOpen as e:
+                # The breaker opened since backend_down() was checked
+                if full_response or not config.fallback.enabled:
+                    raise
+                logger.warning(f"{e}, answering offline")
+                offline = True
+            except Exception as e:
+                # If the backend went away before anything was streamed, fall back below
+                if full_response or not config.fallback.enabled or backend_health.check():
+                    raise
+                logger.warning(f"Ollama unreachable, answering offline: {e}")
+                webhooks.generation_failed(e, request_id=request_id)
+                offline = True
+            finally:
+                # Leaves a shared generation (it stops once nobody is left on it)
+                chunks.close()
+
+        if cancelled:
+            save_exchange(interrupted=True)
+            yield {'cancelled': True}
+            yield {'done': True}
+            return
+
+        if offline:
+            full_response, faq_match = offline_answer(question, locale)
+            source = "faq" if faq_match else "fallback"
+            yield {'fallback': {'source': source, 'entry_id': faq_match["entry"]["entry_id"] if faq_match else None}}
+            yield {'token': full_response}
+
+        draft = save_exchange()
+        if draft:
+            yield {'draft': draft}
+
+        logger.info(f"Question: {question}\nAnswer: {full_response}",
+                    extra=fields(source=source, model=(model or config.ollama.stream_model) if source == "model" else None,
+                                 generation_seconds=round(time.time() - start_time, 3),
+                                 first_token_seconds=timing.get("first_token_seconds"),
+                                 prompt_tokens=usage.get("prompt_tokens"), completion_tokens=usage.get("completion_tokens")))
+
+        # Send completion signal
+        yield {'done': True}
+    except GeneratorExit:
+        # The client disconnected (closed the tab, lost the connection) before the answer finished
+        if not saved:
+            logger.info(f"Client disconnected after {len(full_response)} characters, saving the partial answer")
+            try:
+                save_exchange(interrupted=True)
+            except Exception as e:
+                logger.warning(f"Could not save the interrupted answer: {e}")
+        raise
+    except (QueueFull, CircuitOpen) as e:
+        # Every generation slot is taken and the line is full, or the backend keeps failing;
+        # ask the user to retry instead of piling on
+        logger.warning(f"Generation refused: {e}")
+        save_partial("the generation was refused")
+        yield dict(Unavailable().to_dict(translate=lambda m, **p: translate(m, locale, **p)), request_id=request_id)
+    except Exception as e:
+        # With the traceback, for debugging
+        logger.exception(f"Error during streaming generation: {e}")
+        webhooks.generation_failed(e, request_id=request_id)
+        save_partial("the generation failed")
+        # Let the client know instead of silently ending the stream
+        yield dict(Upstream().to_dict(translate=lambda m, **p: translate(m, locale, **p)), request_id=request_id)
+    finally:
+        cancellations.release(cancel_key, cancel)
+        # Stop the model if it's still generating (the client left, or something failed mid-stream)
+        if async_gen is not None and loop is not None and not loop.is_closed():
+            try:
+                close_async_generator(loop, async_gen)
+            except Exception as e:
+                logger.warning(f"Error closing the model stream: {e}")
+
+        # Clean up the event loop
+        if loop is not None and not loop.is_closed():
+            loop.close()
+
+#Wire formats for streamed answers. SSE is the default (the web UI uses it); NDJSON is one JSON
+#object per line, easier for curl scripts and the mobile app.
+STREAM_FORMATS = {
+    "sse": ("text/event-stream", lambda event: f"data: {json.dumps(event)}\n\n"),
+    "ndjson": ("application/x-ndjson", lambda event: json.dumps(event) + "\n"),
+}
+# Every token is written as soon as the model produces it; these keep caches and reverse
+# proxies (nginx buffers responses by default) from holding them back until the answer is done
+STREAM_HEADERS = {"Cache-Control": "no-cache", "X-Accel-Buffering": "no"}
+
+def stream_format() -> str:
+    """The wire format picked by ?format= or the Accept header."""
+    fmt = fk.request.args.get("format")
+    if fmt is None:
+        best = fk.request.accept_mimetypes.best_match(["text/event-stream", "application/x-ndjson"])
+        fmt = "ndjson" if best == "application/x-ndjson" else "sse"
+    if fmt not in STREAM_FORMATS:
+        raise ValidationError("format must be one of sse, ndjson", field="format")
+    return fmt
+
+def sse_from(stream, after: int = 0):
+    """SSE response replaying a buffered answer from after event `after`, then following it live."""
+    def body():
+        events = stream.read(after)
+        try:
+            for event_id, event in events:
+                yield f"id: {event_id}\ndata: {json.dumps(event)}\n\n"
+        finally:
+            # Detach; the answer keeps generating for the grace period in case the client comes back
+            events.close()
+
+    return fk.Response(body(), mimetype="text/event-stream", headers=STREAM_HEADERS)
+
+def resume_response(last_event_id: str, ctx: SessionCtx):
+    """Pick up an answer stream where the client lost it (the Last-Event-ID of the last event it got)."""
+    parsed = parse_event_id(last_event_id)
+    stream = answer_streams.get(parsed[0]) if parsed else None
+    if stream is None or stream.owner != ctx.session_id or not stream.can_resume_after(parsed[1]):
+        raise NotFound("This answer can't be resumed anymore, please ask again")
+    logger.info(f"Resuming answer stream {stream.stream_id} after event {parsed[1]}")
+    return sse_from(stream, after=parsed[1])
+
+def stream_response(events, owner: Optional[str] = None):
+    """
+    Encode answer events in the format picked by ?format= or the Accept header. SSE answers are
+    generated in the background and get event IDs, so a dropped client can resume with Last-Event-ID;
+    owner is the session allowed to resume it.
+    """
+    fmt = stream_format()
+    if fmt == "sse" and config.streams.resumable:
+        return sse_from(answer_streams.start(events, owner=owner))
+
+    mimetype, encode = STREAM_FORMATS[fmt]
+
+    def body():
+        try:
+            for event in events:
+                yield encode(event)
+                if shutdown.stopping:
                     break
-            
-            # Calculate generation time 
-            generation_time = time.time() - start_time
-            
-            # Save to session if session_id exists
-            if session_id:
-
-                session_manager.add_message(session_id, "user", question)
-                session_manager.add_message(session_id, "assistant", full_response)
-            
-            # Collect analytics data I LOVE DATA COLLECTION
-            data_collector.log_interaction(
-                session_id=session_id if session_id else "no_session",
-                user_email=user_email,
-                ip_address=ip_address,
-                device_info=device_info,
-                question=question,
-                answer=full_response,
-                generation_time_seconds=generation_time
-            )
-            
-            
-            print(f"Question: {question}\nAnswer: {full_response}\n")
-            
-            # Send completion signal
-            yield f"data: {json.dumps({'done': True})}\n\n"
-        except Exception as e:
-            #print the traceback for debugging I may remove this but for now its useful
-            print(f"Error during streaming generation: {e}")
-            import traceback
-            traceback.print_exc()
         finally:
-            
-            # Clean up the event loop
-            if loop is not None and not loop.is_closed():
-                loop.close()
+            # On a client disconnect the server closes this generator; pass that on so the answer
+            # generator stops the model and saves what it has right away, not whenever it's collected
+            events.close()
+
+    return fk.Response(body(), mimetype=mimetype, headers=STREAM_HEADERS)
+
+@app.route("/api/archie/stream", methods=["POST"])
+@with_session(required=False)
+def api_archie_stream(ctx: SessionCtx):
+    """
+    Streaming endpoint that returns AI responses token by token.
+    This provides a better user experience by showing the AI "thinking" in real-time.
+    A reconnect with a Last-Event-ID header resumes the answer it was getting instead.
+    """
+    if fk.request.headers.get("Last-Event-ID"):
+        return resume_response(fk.request.headers["Last-Event-ID"], ctx)
+    start_time = time.time()
+        
+    data = fk.request.get_json(silent=True)
+    question = validate_question(data, max_length=question_limit(ctx.kiosk))
+    require_terms(ctx)
+    question = screen_question(question, ctx)
+    # Kiosk questions are answered without history and not saved to a session
+    session_id = None if ctx.kiosk else ctx.session_id
+    user_email = ctx.user_email
+    
+    model = resolve_model(validate_model(data.get("model"), available_models()), session_id, user_email, default_stream_model())
+    style = resolve_style(validate_style(data.get("style")), session_id, default=config.kiosk.style if ctx.kiosk else None)
+    ephemeral = validate_flag(data.get("ephemeral"), "ephemeral")
     
-    return fk.Response(generate(), mimetype='text/event-stream')
+    # Capture request info for data collection
+    ip_address = fk.request.remote_addr
+    device_info = fk.request.user_agent.string
+    
+    return stream_response(
+        stream_archie_answer(question, session_id, user_email, ip_address, device_info, start_time, current_request_id(), current_locale(), model, style,
+                             ephemeral, timing=request_timing(), cancel_key=ctx.session_id),
+        owner=session_id
+    )
+
+#Stop the answer the session is getting; what was generated so far is kept, marked interrupted
+@app.route("/api/archie/cancel", methods=["POST"])
+@with_session()
+def cancel_answer(ctx: SessionCtx):
+    """
+    Cancel the session's answer in flight (lib/Cancellation.py). The stream ends with
+    {"cancelled": true} and done; "cancelled" here is false if nothing was generating.
+    """
+    cancelled = cancellations.cancel(ctx.session_id)
+    if cancelled:
+        logger.info(f"Cancel requested for session {ctx.session_id}")
+    return fk.jsonify({"cancelled": cancelled})
+
+#Chat models a question can ask for ("model" in the request body), with whether Ollama has each one pulled
+@app.route("/api/models", methods=["GET"])
+def list_models():
+    """The allowed chat models (ollama.models plus the defaults), marking the default and the installed ones."""
+    installed = None
+    if backend_health.is_up():
+        try:
+            local = set(asyncio.run(gemini.list_local_models()))
+            # Ollama reports untagged pulls as "<name>:latest"
+            installed = {name for name in available_models() if name in local or f"{name}:latest" in local}
+        except Exception as e:
+            logger.warning(f"Couldn't list Ollama's models: {e}")
+    default = default_stream_model()
+    return fk.jsonify({
+        "default": default,
+        "ollama_reachable": installed is not None,
+        # installed is null when Ollama can't be asked
+        "models": [{"name": name, "default": name == default, "installed": None if installed is None else name in installed}
+                   for name in available_models()],
+    })
+
+#Speech-to-text for voice questions (kiosks, the mic button); the frontend drops the text into the chat box
+@app.route("/api/transcribe", methods=["POST"])
+@with_session(required=False)
+def transcribe_audio(ctx: SessionCtx):
+    """Transcribe an uploaded recording (multipart field "audio", optional "language")."""
+    if speech_to_text is None:
+        raise NotFound("Speech-to-text is not enabled")
+    # Recordings are much bigger than the JSON bodies the global limit is sized for
+    fk.request.max_content_length = config.stt.max_audio_bytes
+    
+    audio = fk.request.files.get("audio")
+    if audio is None:
+        raise ValidationError("Send the recording as a multipart file named audio", field="audio")
+    if not is_audio_type(audio.mimetype):
+        raise ValidationError("Unsupported audio type {mimetype}", field="audio", params={"mimetype": audio.mimetype or "unknown"})
+    data = audio.read()
+    if not data:
+        raise ValidationError("The recording is empty", field="audio")
+    language = fk.request.form.get("language") or None
+    if language is not None and not re.fullmatch(r"[a-z]{2}", language):
+        raise ValidationError("language must be a two-letter code like en", field="language")
+    require_terms(ctx)
+    # A recording counts toward the same per-caller quota as a typed question (there's no text to screen yet)
+    screen_question("", ctx)
+    
+    start_time = time.time()
+    try:
+        text = speech_to_text.transcribe(data, filename=audio.filename or "audio", mimetype=audio.mimetype, language=language)
+    except TranscriptionError as e:
+        logger.warning(f"Transcription failed: {e}")
+        raise Upstream("The speech-to-text service is unavailable")
+    logger.info(f"Transcribed {len(data)} bytes in {time.time() - start_time:.2f}s")
+    return fk.jsonify({"text": text})
+
+#Edit a previous question and get a fresh answer, older turns after it are kept as a branch
+@app.route("/api/sessions/<session_id>/messages/<message_id>/resubmit", methods=["POST"])
+@with_session(required=False)
+def resubmit_message(session_id, message_id, ctx: SessionCtx):
+    """Replace a prior user message with edited content and stream a new answer (resumable like /api/archie/stream)."""
+    if fk.request.headers.get("Last-Event-ID"):
+        return resume_response(fk.request.headers["Last-Event-ID"], ctx)
+    start_time = time.time()
+    
+    data = fk.request.get_json(silent=True)
+    question = validate_question(data, max_length=config.limits.max_question_length)
+    require_terms(ctx)
+    question = screen_question(question, ctx)
+    
+    load_owned_session(session_id, ctx)
+    model = resolve_model(validate_model(data.get("model"), available_models()), session_id, ctx.user_email, default_stream_model())
+    style = resolve_style(validate_style(data.get("style")), session_id)
+    
+    message = session_manager.get_message(session_id, message_id)
+    if message is None:
+        raise NotFound("Message not found")
+    if message.get("role") != "user":
+        raise ValidationError("Only user messages can be edited")
+        
+    # Checked first so a bad ?format= is rejected before anything is changed
+    stream_format()
+    
+    # Drop the edited message and everything after it; the new exchange gets appended by the stream
+    session_manager.truncate_at_message(session_id, message_id)
+    return stream_response(
+        stream_archie_answer(question, session_id, ctx.user_email, fk.request.remote_addr, fk.request.user_agent.string, start_time, current_request_id(), current_locale(), model, style,
+                             timing=request_timing(), cancel_key=ctx.session_id),
+        owner=ctx.session_id
+    )
+
+#Thumbs up/down on an answer, counted in the analytics so staff can see which answers miss
+@app.route("/api/sessions/<session_id>/messages/<message_id>/feedback", methods=["POST"])
+@with_session(required=False)
+def rate_message(session_id, message_id, ctx: SessionCtx):
+    """Rate an assistant message up or down (with an optional comment); a null rating clears it."""
+    feedback = validate_feedback(fk.request.get_json(silent=True))
+    load_owned_session(session_id, ctx)
+    
+    message = session_manager.get_message(session_id, message_id)
+    if message is None:
+        raise NotFound("Message not found")
+    if message.get("role") != "assistant":
+        raise ValidationError("Only answers can be rated")
+    
+    stored = dict(feedback, rated_at=datetime.datetime.now().isoformat()) if feedback["rating"] else None
+    session_manager.set_message_feedback(session_id, message_id, stored)
+    check_frustration(session_id, ctx.user_email)
+    # Answers saved before request IDs were stored on messages only keep the rating in the session
+    if message.get("request_id"):
+        data_collector.record_feedback(message["request_id"], feedback["rating"])
+    return fk.jsonify({"message_id": message_id, "feedback": stored})
+
+#Pin a message so it stays in the prompt for the rest of the conversation
+@app.route("/api/sessions/<session_id>/messages/<message_id>/pin", methods=["POST"])
+@with_session(required=False)
+def pin_message(session_id, message_id, ctx: SessionCtx):
+    """Pin a message ("I'm asking about the graduate program") so every answer takes it into account."""
+    load_owned_session(session_id, ctx)
+    message = session_manager.get_message(session_id, message_id)
+    if message is None:
+        raise NotFound("Message not found")
+    if not message.get("pinned") and len(session_manager.get_pinned_messages(session_id)) >= MAX_PINNED:
+        raise Conflict("A conversation can have at most {max} pinned messages, unpin one first", params={"max": MAX_PINNED})
+    session_manager.set_message_pinned(session_id, message_id, True)
+    return fk.jsonify({"message_id": message_id, "pinned": True})
+
+#Unpin a message
+@app.route("/api/sessions/<session_id>/messages/<message_id>/pin", methods=["DELETE"])
+@with_session(required=False)
+def unpin_message(session_id, message_id, ctx: SessionCtx):
+    """Unpin a message; it's then only in the prompt while it's in the recent history."""
+    load_owned_session(session_id, ctx)
+    if session_manager.set_message_pinned(session_id, message_id, False) is None:
+        raise NotFound("Message not found")
+    return fk.jsonify({"message_id": message_id, "pinned": False})
+
+#List the messages pinned in a session
+@app.route("/api/sessions/<session_id>/pins", methods=["GET"])
+@with_session(required=False)
+def list_pinned_messages(session_id, ctx: SessionCtx):
+    """List the session's pinned messages, oldest first."""
+    load_owned_session(session_id, ctx)
+    return fk.jsonify({"messages": session_manager.get_pinned_messages(session_id)})
 
 #Gets conversation history for current session
 @app.route("/api/sessions/history", methods=["GET"])
-def get_session_history():
-    """Get conversation history for current session."""
-    session_id = fk.request.cookies.get("session_id")
-    if not session_id:
-        return fk.jsonify({"error": "No session found"}), 401
+@with_session()
+def get_session_history(ctx: SessionCtx):
+    """Get conversation history for current session, newest messages first page by page."""
+    before = fk.request.args.get("before") or None
+    limit = max(1, min(fk.request.args.get("limit", 10, type=int), 100))
     
-    history = session_manager.get_conversation_history(session_id)
-    return fk.jsonify({"history": history})
+    page = session_manager.get_messages_page(ctx.session_id, before=before, limit=limit)
+    return fk.jsonify({"history": page["messages"], "has_more": page["has_more"]})
 
 #List all sessions for current user
 @app.route("/api/sessions/list", methods=["GET"])
-def list_user_sessions():
+@with_user
+def list_user_sessions(user: AuthedUser):
     """List all sessions for logged-in user."""
-    user_email = fk.request.cookies.get("user_email")
-    if not user_email:
-        return fk.jsonify({"error": "Not logged in"}), 401
-    
-    sessions = session_manager.get_all_user_sessions_with_preview(user_email)
+    sessions = session_manager.get_all_user_sessions_with_preview(user.email)
     return fk.jsonify({"sessions": sessions})
 
+#Live session list updates, so every open tab sees new, changed, and deleted chats without polling
+SESSION_STREAM_KEEPALIVE_SECONDS = 15
+
+@app.route("/api/sessions/stream", methods=["GET"])
+@with_user
+def stream_session_events(user: AuthedUser):
+    """Server-sent events whenever one of the user's sessions is created, updated, or deleted."""
+    def generate():
+        events = session_events.subscribe(user.email)
+        try:
+            # Sent on every (re)connect so the client can refresh anything it missed
+            yield f"data: {json.dumps({'event': 'ready'})}\n\n"
+            while True:
+                try:
+                    payload = events.get(timeout=SESSION_STREAM_KEEPALIVE_SECONDS)
+                except queue.Empty:
+                    # SSE comment line, keeps proxies from closing an idle connection
+                    yield ": keepalive\n\n"
+                    continue
+                if payload is None:
+                    # The server is shutting down; EventSource reconnects on its own
+                    yield f"data: {json.dumps({'event': 'shutdown'})}\n\n"
+                    return
+                yield f"data: {json.dumps(payload)}\n\n"
+        finally:
+            session_events.unsubscribe(user.email, events)
+
+    return fk.Response(generate(), mimetype="text/event-stream", headers={"Cache-Control": "no-cache", "X-Accel-Buffering": "no"})
+
 #get details for a specific session
 @app.route("/api/sessions/<session_id>", methods=["GET"])
-def get_session_details(session_id):
+@with_session(required=False)
+def get_session_details(session_id, ctx: SessionCtx):
     """Get details of a specific session."""
-    user_email = fk.request.cookies.get("user_email")
-    
-    session_data = session_manager.get_session(session_id)
-    if not session_data:
-        return fk.jsonify({"error": "Session not found"}), 404
-    
-    # Check if user owns this session (or it's their current session)
-    current_session_id = fk.request.cookies.get("session_id")
-    if session_data.get("user_email") != user_email and session_id != current_session_id:
-        return fk.jsonify({"error": "Unauthorized"}), 403
-    
+    # User must own this session (or it's their current session)
+    session_data = load_owned_session(session_id, ctx)
     return fk.jsonify(session_data)
 
+#Short model-written summary of a session, cached until new messages arrive
+@app.route("/api/sessions/<session_id>/summary", methods=["GET"])
+@with_session(required=False)
+def get_session_summary(session_id, ctx: SessionCtx):
+    """Get (generating if needed) a one or two sentence summary of a session; ?refresh=true regenerates it."""
+    load_owned_session(session_id, ctx)
+    refresh = fk.request.args.get("refresh", "false").lower() == "true"
+    try:
+        summary = session_summarizer.summarize(session_id, refresh=refresh)
+    except (QueueFull, CircuitOpen):
+        raise Unavailable()
+    except Exception as e:
+        logger.error(f"Error summarizing session {session_id}: {e}")
+        raise Upstream()
+    if summary is None:
+        return fk.jsonify({"session_id": session_id, "summary": None, "message_count": 0, "generated_at": None, "cached": False})
+    return fk.jsonify({
+        "session_id": session_id,
+        "summary": summary["text"],
+        "message_count": summary["message_count"],
+        "generated_at": summary["generated_at"],
+        "cached": summary["cached"],
+    })
+
+#Save the conversation as it is now, so it can be rolled back after a bad tangent
+@app.route("/api/sessions/<session_id>/checkpoint", methods=["POST"])
+@with_session(required=False)
+def create_checkpoint(session_id, ctx: SessionCtx):
+    """Snapshot the session's messages, with an optional {"label": ...}."""
+    label = validate_checkpoint(fk.request.get_json(silent=True))
+    session_data = load_owned_session(session_id, ctx)
+    if len(session_data.get("checkpoints", [])) >= MAX_CHECKPOINTS:
+        raise Conflict("A conversation can have at most {max} checkpoints, delete one first", params={"max": MAX_CHECKPOINTS})
+
+    checkpoint = session_manager.create_checkpoint(session_id, label=label)
+    return fk.jsonify({"checkpoint": checkpoint}), 201
+
+#List a session's checkpoints
+@app.route("/api/sessions/<session_id>/checkpoints", methods=["GET"])
+@with_session(required=False)
+def list_checkpoints(session_id, ctx: SessionCtx):
+    """List the session's checkpoints, oldest first."""
+    load_owned_session(session_id, ctx)
+    return fk.jsonify({"checkpoints": session_manager.list_checkpoints(session_id)})
+
+#Roll a conversation back to a checkpoint
+@app.route("/api/sessions/<session_id>/checkpoints/<checkpoint_id>/restore", methods=["POST"])
+@with_session(required=False)
+def restore_checkpoint(session_id, checkpoint_id, ctx: SessionCtx):
+    """Replace the session's messages with the ones saved in a checkpoint."""
+    load_owned_session(session_id, ctx)
+    checkpoint = session_manager.restore_checkpoint(session_id, checkpoint_id)
+    if checkpoint is None:
+        raise NotFound("Checkpoint not found")
+    return fk.jsonify({"checkpoint": checkpoint, "message_count": checkpoint["message_count"]})
+
+#Delete a checkpoint
+@app.route("/api/sessions/<session_id>/checkpoints/<checkpoint_id>", methods=["DELETE"])
+@with_session(required=False)
+def delete_checkpoint(session_id, checkpoint_id, ctx: SessionCtx):
+    """Delete one of the session's checkpoints; the conversation itself is unchanged."""
+    load_owned_session(session_id, ctx)
+    if not session_manager.delete_checkpoint(session_id, checkpoint_id):
+        raise NotFound("Checkpoint not found")
+    return fk.jsonify({"message": _("Checkpoint deleted")})
+
+#Change a session's settings (its default answer style)
+@app.route("/api/sessions/<session_id>", methods=["PATCH"])
+@with_session(required=False)
+def update_session(session_id, ctx: SessionCtx):
+    """
+    Rename the session and/or set its default answer style: {"title": "..." | null,
+    "style": "concise" | "detailed" | "bullet" | null}. Either can be left out.
+    """
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict) or not ("title" in data or "style" in data):
+        raise ValidationError("Request body must be a JSON object with a title or style")
+    title = validate_session_title(data["title"]) if "title" in data else None
+    style = validate_style(data["style"]) if "style" in data else None
+    session_data = load_owned_session(session_id, ctx)
+    if "title" in data:
+        session_manager.rename_session(session_id, title)
+    if "style" in data:
+        session_manager.set_style(session_id, style)
+    return fk.jsonify({
+        "session_id": session_id,
+        "title": title if "title" in data else session_data.get("title"),
+        "style": style if "style" in data else session_data.get("style"),
+    })
+
+#Email a conversation's transcript to the signed-in user
+@app.route("/api/sessions/<session_id>/email", methods=["POST"])
+@with_user
+def email_session_transcript(session_id, user: AuthedUser):
+    """Send the session as a text and HTML email to the user's own address (a few per hour)."""
+    session_data = load_owned_session(session_id, SessionCtx(session_id=None, user_email=user.email), allow_current=False)
+    if not session_data.get("messages"):
+        raise ValidationError("There's nothing in this conversation to send yet")
+    if mailer is None:
+        raise Unavailable("Email isn't set up on this server")
+    retry_after = transcript_quota.acquire(user.email)
+    if retry_after is not None:
+        raise TooManyRequests("You can email up to {count} transcripts an hour", params={"count": config.mail.transcripts_per_hour},
+                              retry_after_seconds=round(retry_after))
+    
+    try:
+        mailer.send([user.email], transcript_subject(session_data), render_text(session_data), html=render_html(session_data))
+    except MailError as e:
+        logger.warning(f"Transcript not sent: {e}")
+        raise Upstream("The mail server is unavailable")
+    logger.info(f"Emailed the transcript of session {session_id} to {user.email}")
+    return fk.jsonify({"message": _("Transcript sent to {email}", email=user.email)})
+
+#Save a draft answer (asked with "ephemeral": true) into a session after all
+@app.route("/api/sessions/<session_id>/commit", methods=["POST"])
+@with_session(required=False)
+def commit_draft(session_id, ctx: SessionCtx):
+    """
+    Persist a draft exchange: {"draft_id": ...} from the draft the answer came with. It's saved to
+    the session and the analytics as if it had been asked normally, and learned from like one.
+    """
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    draft_id = data.get("draft_id")
+    if not isinstance(draft_id, str) or not draft_id:
+        raise ValidationError("{field} must be a non-empty string", field="draft_id", params={"field": "draft_id"})
+    load_owned_session(session_id, ctx)
+    exchange = drafts.take(draft_id, session_id)
+    if exchange is None:
+        raise NotFound("Draft not found or expired")
+    # A visitor who tried the embed and then signed in commits into their own session
+    persist_exchange(**dict(exchange, session_id=session_id, user_email=ctx.user_email))
+    logger.info(f"Committed draft {draft_id} to session {session_id}")
+    return fk.jsonify({"message": _("Draft saved"), "session_id": session_id})
+
 #Delete a specific session
 @app.route("/api/sessions/<session_id>", methods=["DELETE"])
-def delete_session(session_id):
+@with_session(required=False)
+def delete_session(session_id, ctx: SessionCtx):
     """Delete a specific session."""
-    user_email = fk.request.cookies.get("user_email")
-    current_session_id = fk.request.cookies.get("session_id")
+    load_owned_session(session_id, ctx)
     
-    session_data = session_manager.get_session(session_id)
-    if not session_data:
-        return fk.jsonify({"error": "Session not found"}), 404
-    
-    # Check if user owns this session
-    if session_data.get("user_email") != user_email and session_id != current_session_id:
-        return fk.jsonify({"error": "Unauthorized"}), 403
-    
-    success = session_manager.delete_session(session_id, user_email)
+    success = session_manager.delete_session(session_id, ctx.user_email)
     if success:
-        return fk.jsonify({"message": "Session deleted"})
+        return fk.jsonify({"message": _("Session deleted")})
     else:
-        return fk.jsonify({"error": "Failed to delete session"}), 500
+        raise Internal("Failed to delete session")
 
 #Create a new session
 @app.route("/api/sessions/new", methods=["POST"])
-def create_new_session():
-    """Create a new chat session for the current user."""
-    user_email = fk.request.cookies.get("user_email")
-    
-    session_id = session_manager.create_session(user_email=user_email)
+@with_session(required=False)
+def create_new_session(ctx: SessionCtx):
+    """
+    Create a new chat session for the current user, optionally with {"model": ...} for all its
+    answers and {"style": ...} as its default answer style.
+    """
+    if ctx.kiosk:
+        raise Forbidden("Not available on a kiosk")
+    data = fk.request.get_json(silent=True)
+    if data is not None and not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    model = validate_model((data or {}).get("model"), available_models())
+    style = validate_style((data or {}).get("style"))
+    session_id = session_manager.create_session(user_email=ctx.user_email, model=model, style=style)
     
     resp = fk.make_response(fk.jsonify({"session_id": session_id}))
-    resp.set_cookie("session_id", session_id, httponly=True, samesite="Strict")
+    set_session_cookie(resp, session_id, ctx.user_email)
+    return resp
+
+#Merge two chats the user split one topic across
+@app.route("/api/sessions/merge", methods=["POST"])
+@with_session(required=False)
+def merge_sessions(ctx: SessionCtx):
+    """
+    Combine two of the caller's sessions into a new one, exchanges interleaved in the order they
+    were asked: {"session_ids": [a, b], "delete_originals": false}. Switches to the merged session.
+    """
+    merge = validate_merge(fk.request.get_json(silent=True))
+    for session_id in merge["session_ids"]:
+        # Both have to be the caller's own chats, not just the one they're on
+        load_owned_session(session_id, ctx, allow_current=False)
+    
+    merged = session_manager.merge_sessions(merge["session_ids"], user_email=ctx.user_email)
+    if merged is None:
+        raise NotFound("Session not found")
+    if merge["delete_originals"]:
+        for session_id in merge["session_ids"]:
+            session_manager.delete_session(session_id, ctx.user_email)
+    logger.info(f"Merged sessions {', '.join(merge['session_ids'])} into {merged['session_id']}")
+    
+    resp = fk.make_response(fk.jsonify({
+        "session_id": merged["session_id"],
+        "merged_from": merged["merged_from"],
+        "message_count": len(merged["messages"]),
+        "deleted_originals": merge["delete_originals"],
+    }))
+    set_session_cookie(resp, merged["session_id"], ctx.user_email)
     return resp
 
 #Switch to a different session
 @app.route("/api/sessions/switch/<session_id>", methods=["POST"])
-def switch_session(session_id):
+@with_session(required=False)
+def switch_session(session_id, ctx: SessionCtx):
     """Switch to a different session."""
-    user_email = fk.request.cookies.get("user_email")
+    # Only sessions the user owns, being the current session isn't enough here
+    load_owned_session(session_id, ctx, allow_current=False)
     
-    session_data = session_manager.get_session(session_id)
-    if not session_data:
-        return fk.jsonify({"error": "Session not found"}), 404
+    resp = fk.make_response(fk.jsonify({"message": _("Session switched")}))
+    set_session_cookie(resp, session_id, ctx.user_email)
+    return resp
+
+#The current terms of use / AI disclaimer and whether the caller has accepted them
+@app.route("/api/account/terms", methods=["GET"])
+@with_session(required=False)
+def account_terms(ctx: SessionCtx):
+    """Return the terms version, link, and text, and the caller's acceptance."""
+    accepted = session_manager.get_terms_acceptance(user_email=ctx.user_email, session_id=ctx.session_id)
+    return fk.jsonify({
+        "required": bool(config.terms.version) and not ctx.kiosk,
+        "version": config.terms.version or None,
+        "url": config.terms.url or None,
+        "text": config.terms.text,
+        "accepted": bool(accepted) and accepted.get("version") == config.terms.version,
+        "accepted_version": accepted.get("version") if accepted else None,
+        "accepted_at": accepted.get("accepted_at") if accepted else None,
+    })
+
+#Accept the current terms; recorded on the account, or on the session for guests
+@app.route("/api/account/accept-terms", methods=["POST"])
+@with_session(required=False)
+def accept_terms(ctx: SessionCtx):
+    """Accept the terms: {"version": ...}, which must be the current version."""
+    if not config.terms.version:
+        raise NotFound("There are no terms to accept")
+    if ctx.kiosk:
+        raise Forbidden("Not available on a kiosk")
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    version = data.get("version")
+    if not isinstance(version, str) or not version:
+        raise ValidationError("version must be a non-empty string", field="version")
+    if version != config.terms.version:
+        # Accepting terms the user hasn't seen doesn't count
+        raise Conflict("The terms have changed, please review them again", version=config.terms.version)
+    
+    session_id = ctx.session_id
+    if not ctx.user_email and (not session_id or session_manager.get_session(session_id) is None):
+        # A guest without a session yet (e.g. the embed), so there's somewhere to keep it
+        session_id = session_manager.create_session(user_email=None)
+    accepted = session_manager.accept_terms(version, user_email=ctx.user_email, session_id=session_id)
+    if accepted is None:
+        raise NotFound("User not found")
+    resp = fk.jsonify(accepted)
+    if session_id != ctx.session_id:
+        set_session_cookie(resp, session_id, ctx.user_email)
+    return resp
+
+#Per-user preferences: the language used for server messages, and the memory/personalization toggles
+@app.route("/api/account/preferences", methods=["GET", "PATCH"])
+@with_user
+def account_preferences(user: AuthedUser):
+    """Get or update the logged-in user's preferences."""
+    if fk.request.method == "GET":
+        return fk.jsonify({"preferences": session_manager.get_preferences(user.email), "supported_locales": SUPPORTED_LOCALES,
+                           "available_models": available_models()})
+
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    updates = {}
+    if "locale" in data:
+        locale = data["locale"]
+        if locale is not None and locale not in SUPPORTED_LOCALES:
+            raise ValidationError("locale must be one of {locales}", field="locale",
+                                  params={"locales": ", ".join(SUPPORTED_LOCALES)})
+        updates["locale"] = locale
+    for key in ("memory", "personalization"):
+        if key in data:
+            if not isinstance(data[key], bool):
+                raise ValidationError("{field} must be true or false", field=key, params={"field": key})
+            updates[key] = data[key]
+    if "default_model" in data:
+        updates["default_model"] = validate_model(data["default_model"], available_models(), field="default_model")
+
+    preferences = session_manager.update_preferences(user.email, updates)
+    if preferences is None:
+        raise NotFound("User not found")
+    # The new language applies to this response too
+    fk.g.pop("locale", None)
+    return fk.jsonify({"preferences": preferences})
+
+#What Archie remembers about the logged-in user (only filled in if they turned on the memory preference)
+@app.route("/api/account/memory", methods=["GET"])
+@with_user
+def account_memory(user: AuthedUser):
+    """List the facts remembered about the logged-in user."""
+    return fk.jsonify({
+        "enabled": memory_enabled(user.email),
+        "personalization": personalization_enabled(user.email),
+        "memories": user_memory.list_memories(user.email),
+    })
+
+@app.route("/api/account/memory", methods=["DELETE"])
+@with_user
+def account_forget_all(user: AuthedUser):
+    """Wipe everything remembered about the logged-in user (the preferences stay as they are)."""
+    deleted = user_memory.clear(user.email)
+    return fk.jsonify({"message": _("Memories deleted"), "deleted": deleted})
+
+@app.route("/api/account/memory/<memory_id>", methods=["DELETE"])
+@with_user
+def account_forget(memory_id, user: AuthedUser):
+    """Forget one remembered fact."""
+    if not user_memory.delete_memory(user.email, memory_id):
+        raise NotFound("Memory not found")
+    return fk.jsonify({"message": _("Memory deleted")})
+
+#Download everything stored about the logged-in user (data portability / moving accounts)
+@app.route("/api/account/export", methods=["GET"])
+@with_user
+def account_export(user: AuthedUser):
+    """Stream a zip of the user's profile, sessions, and analytics records."""
+    filename = f"archie-export-{datetime.date.today().isoformat()}.zip"
+    resp = fk.Response(
+        fk.stream_with_context(export_account(session_manager, data_collector, user.email, user_memory=user_memory)),
+        mimetype="application/zip",
+    )
+    resp.headers["Content-Disposition"] = f"attachment; filename={filename}"
+    resp.headers["Cache-Control"] = "no-store"
+    return resp
+
+#GraphQL endpoint for sessions, messages, and admin analytics, see lib/GraphQLApi.py for the schema
+@app.route("/api/graphql", methods=["POST"])
+@with_session(required=False)
+def graphql_endpoint(ctx: SessionCtx):
+    """Run a GraphQL query ({"query": ..., "variables": ..., "operationName": ...})."""
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    query = data.get("query")
+    if not isinstance(query, str) or not query.strip():
+        raise ValidationError("query must be a non-empty string", field="query")
+    variables = data.get("variables")
+    if variables is not None and not isinstance(variables, dict):
+        raise ValidationError("variables must be an object", field="variables")
     
-    # Check if user owns this session
-    if session_data.get("user_email") != user_email:
-        return fk.jsonify({"error": "Unauthorized"}), 403
+    # Only trust the email cookie if it belongs to a real account
+    user = _resolve_user()
+    context = GraphQLApi.GraphQLContext(
+        session_manager=session_manager,
+        data_collector=data_collector,
+        user_email=user.email if user else None,
+        is_admin=user is not None and session_manager.is_admin(user.email),
+        load_session=lambda session_id: load_owned_session(session_id, ctx),
+    )
+    result = GraphQLApi.execute(query, context, variables=variables, operation_name=data.get("operationName"))
+    
+    body = {"data": result.data}
+    if result.errors:
+        for error in result.errors:
+            if error.original_error is not None and not isinstance(error.original_error, AppError):
+                logger.error(f"GraphQL resolver error: {error.original_error}")
+        body["errors"] = [GraphQLApi.format_error(error, translate=_) for error in result.errors]
+    # Parse/validation errors mean nothing ran
+    return fk.jsonify(body), 200 if result.data is not None else 400
+
+#Admin dashboard pages, data is loaded client side from the admin JSON endpoints
+ADMIN_PAGES = {
+    "usage": "Usage",
+    "interactions": "Recent Interactions",
+    "topics": "Topics",
+    "users": "Users",
+    "kb": "Knowledge Base",
+    "live": "Live",
+}
+
+@app.route("/admin", methods=["GET"])
+@app.route("/admin/<page>", methods=["GET"])
+@with_admin
+def admin_dashboard(admin: AdminUser, page="usage"):
+    if page not in ADMIN_PAGES:
+        fk.abort(404)
+    return fk.render_template("admin.html", page=page, pages=ADMIN_PAGES, admin_email=admin.email)
+
+#Live feed of interactions as they're logged, redacted, for watching the system during peak events
+TAIL_KEEPALIVE_SECONDS = 15
+
+@app.route("/ws/admin/tail", methods=["GET"], websocket=True)
+@with_admin
+def admin_tail(admin: AdminUser):
+    """WebSocket: {"event": "ready"}, then {"event": "interaction", "interaction": ...} for each logged one (see lib/InteractionTail.py)."""
+    # Browsers don't apply the same-origin policy to WebSockets, so another site mustn't open this with an admin's cookies
+    origin = fk.request.headers.get("Origin")
+    if origin and urllib.parse.urlsplit(origin).netloc != fk.request.host:
+        raise Forbidden("Cross-origin WebSocket connections are not allowed")
+    try:
+        ws = WebSocket.accept(fk.request.environ)
+    except ValueError as e:
+        raise ValidationError(str(e))
+    events = interaction_tail.subscribe()
+    logger.info(f"{admin.email} is watching the live interaction feed")
+    try:
+        ws.send_json({"event": "ready"})
+        last_sent = time.monotonic()
+        while True:
+            # Answers pings and notices when the admin goes away
+            ws.poll()
+            try:
+                interaction = events.get(timeout=1.0)
+            except queue.Empty:
+                if time.monotonic() - last_sent >= TAIL_KEEPALIVE_SECONDS:
+                    ws.ping()
+                    last_sent = time.monotonic()
+                continue
+            if interaction is None:
+                ws.send_json({"event": "shutdown"})
+                break
+            ws.send_json({"event": "interaction", "interaction": interaction})
+            last_sent = time.monotonic()
+    except WebSocketClosed:
+        pass
+    finally:
+        interaction_tail.unsubscribe(events)
+        ws.close()
+    return WebSocket.response()
+
+#Most recent interactions for the admin dashboard
+@app.route("/api/admin/analytics/recent", methods=["GET"])
+@with_admin
+def admin_recent_interactions(admin: AdminUser):
+    """Return the most recent logged interactions."""
+    limit = max(1, min(fk.request.args.get("limit", 50, type=int), 500))
+    return fk.jsonify({"interactions": data_collector.get_recent_interactions(limit=limit)})
+
+#Find user accounts, a page at a time
+@app.route("/api/admin/users", methods=["GET"])
+@with_admin
+def admin_list_users(admin: AdminUser):
+    """List users, newest first, with session counts and last activity (?q= to search by email, ?offset=, ?limit=)."""
+    offset = max(0, fk.request.args.get("offset", 0, type=int))
+    limit = max(1, min(fk.request.args.get("limit", 50, type=int), 200))
+    result = session_manager.search_users(fk.request.args.get("q") or None, offset=offset, limit=limit)
+    return fk.jsonify({**result, "offset": offset, "limit": limit})
+
+#One account with its sessions and activity
+@app.route("/api/admin/users/<path:email>", methods=["GET"])
+@with_admin
+def admin_get_user(email, admin: AdminUser):
+    """Show a user's account, session list, message counts, and last activity."""
+    detail = session_manager.get_user_detail(email)
+    if detail is None:
+        raise NotFound("User not found")
+    return fk.jsonify(detail)
+
+#Delete a user account along with their sessions
+@app.route("/api/admin/users/<path:email>", methods=["DELETE"])
+@with_admin
+def admin_delete_user(email, admin: AdminUser):
+    """Delete a user and all of their sessions."""
+    if email == admin.email:
+        raise ValidationError("You can't delete your own account from the dashboard")
     
-    resp = fk.make_response(fk.jsonify({"message": "Session switched"}))
-    resp.set_cookie("session_id", session_id, httponly=True, samesite="Lax")
+    if not session_manager.delete_user(email):
+        raise NotFound("User not found")
+    user_memory.clear(email)
+    return fk.jsonify({"message": _("User deleted")})
+
+#Suspend an account (or lift a suspension) after reviewing its abuse incidents
+@app.route("/api/admin/users/<path:email>/suspension", methods=["PUT"])
+@with_admin
+def admin_suspend_user(email, admin: AdminUser):
+    """Suspend a user: {"reason": "..."}."""
+    if email == admin.email:
+        raise ValidationError("You can't suspend your own account")
+    data = fk.request.get_json(silent=True) or {}
+    reason = data.get("reason", "")
+    if not isinstance(reason, str) or len(reason) > 500:
+        raise ValidationError("reason must be text of at most 500 characters", field="reason")
+    if not session_manager.suspend_user(email, reason=reason or "manual", suspended_by=admin.email):
+        raise NotFound("User not found")
+    return fk.jsonify({"message": _("User suspended")})
+
+@app.route("/api/admin/users/<path:email>/suspension", methods=["DELETE"])
+@with_admin
+def admin_unsuspend_user(email, admin: AdminUser):
+    """Lift a user's suspension."""
+    if session_manager.get_user(email) is None:
+        raise NotFound("User not found")
+    if not session_manager.unsuspend_user(email):
+        raise ValidationError("User is not suspended")
+    return fk.jsonify({"message": _("Suspension lifted")})
+
+#Set an account to active, suspended (pending review), or banned
+@app.route("/api/admin/users/<path:email>/status", methods=["PUT"])
+@with_admin
+def admin_set_user_status(email, admin: AdminUser):
+    """Change a user's status: {"status": "active" | "suspended" | "banned", "reason": "..."}."""
+    status, reason = validate_user_status(fk.request.get_json(silent=True), USER_STATUSES)
+    if email == admin.email and status != "active":
+        raise ValidationError("You can't suspend your own account")
+    previous = session_manager.set_status(email, status, reason=reason or "manual", changed_by=admin.email)
+    if previous is None:
+        raise NotFound("User not found")
+    logger.info(f"{admin.email} changed {email} from {previous} to {status}")
+    return fk.jsonify({"email": email, "status": status, "previous_status": previous})
+
+#Abuse incidents recorded by the spam/jailbreak heuristics
+@app.route("/api/admin/abuse/incidents", methods=["GET"])
+@with_admin
+def admin_abuse_incidents(admin: AdminUser):
+    """List abuse incidents, newest first (?user=, ?since=, ?limit=)."""
+    limit = max(1, min(fk.request.args.get("limit", 100, type=int), 1000))
+    incidents = abuse_detector.list_incidents(
+        limit=limit,
+        user_email=fk.request.args.get("user") or None,
+        since=fk.request.args.get("since") or None,
+    )
+    return fk.jsonify({"incidents": incidents})
+
+#Requests turned away by the rate limiter
+@app.route("/api/admin/abuse/throttles", methods=["GET"])
+@with_admin
+def admin_throttle_events(admin: AdminUser):
+    """List rate-limit events, newest first (?since=, ?limit=)."""
+    limit = max(1, min(fk.request.args.get("limit", 100, type=int), 1000))
+    events = data_collector.throttle_events(limit=limit, since=fk.request.args.get("since") or None)
+    return fk.jsonify({"events": events})
+
+#Sessions flagged because the user seemed stuck, for staff to follow up on
+@app.route("/api/admin/reviews", methods=["GET"])
+@with_admin
+def admin_list_reviews(admin: AdminUser):
+    """List flagged sessions, newest first; ?all=true includes resolved ones."""
+    include_resolved = fk.request.args.get("all", "").lower() in ("1", "true", "yes")
+    return fk.jsonify({"reviews": review_queue.list(include_resolved=include_resolved)})
+
+#A flagged session with its conversation
+@app.route("/api/admin/reviews/<session_id>", methods=["GET"])
+@with_admin
+def admin_get_review(session_id, admin: AdminUser):
+    """Return a review and the session's messages."""
+    review = next((r for r in review_queue.list(include_resolved=True) if r["session_id"] == session_id), None)
+    if review is None:
+        raise NotFound("Review not found")
+    session_data = session_manager.get_session(session_id) or {}
+    return fk.jsonify({**review, "messages": session_data.get("messages", [])})
+
+#Mark a flagged session as followed up
+@app.route("/api/admin/reviews/<session_id>/resolve", methods=["POST"])
+@with_admin
+def admin_resolve_review(session_id, admin: AdminUser):
+    """Resolve a review: {"note": "..."} (optional)."""
+    data = fk.request.get_json(silent=True) or {}
+    note = data.get("note")
+    if note is not None and (not isinstance(note, str) or len(note) > 500):
+        raise ValidationError("note must be text of at most 500 characters", field="note")
+    review = review_queue.resolve(session_id, resolved_by=admin.email, note=note)
+    if review is None:
+        raise NotFound("Review not found")
+    # Only what the user says after this counts toward flagging the session again
+    session_manager.set_review(session_id, {"reason": review["reason"], "flagged_at": review["flagged_at"],
+                                            "resolved_at": review["resolved_at"]})
+    return fk.jsonify(review)
+
+#Session files that were corrupt, where they were moved, and how many messages were salvaged
+@app.route("/api/admin/sessions/quarantine", methods=["GET"])
+@with_admin
+def admin_quarantined_sessions(admin: AdminUser):
+    """List quarantined session files, newest first (?limit=)."""
+    limit = max(1, min(fk.request.args.get("limit", 100, type=int), 1000))
+    return fk.jsonify({"incidents": session_manager.quarantine.list_incidents(limit=limit)})
+
+#Status of the scraped university data that gets fed to the model
+@app.route("/api/admin/kb/status", methods=["GET"])
+@with_admin
+def admin_kb_status(admin: AdminUser):
+    """Report what's in the knowledge base, when it was last refreshed and crawled, and any reindex jobs."""
+    index = gemini.knowledge.status()
+    embedding_cache = gemini.embedding_cache.stats() if gemini.embedding_cache else None
+    jobs = kb_jobs.list_jobs()
+    crawl = {"enabled": config.crawl.enabled, "pages": 0, "last_crawled_at": None}
+    try:
+        with open(os.path.join(config.data_dir, CRAWL_STATE_FILE), "r", encoding="utf-8") as f:
+            crawl_state = json.load(f)
+        checked = [entry.get("checked_at") for entry in crawl_state.values() if entry.get("checked_at")]
+        crawl.update(pages=len(crawl_state), last_crawled_at=max(checked, default=None))
+    except (FileNotFoundError, json.JSONDecodeError):
+        pass
+
+    scrape_file = os.path.join(config.data_dir, "scrape_results.json")
+    if not os.path.exists(scrape_file):
+        return fk.jsonify({"scrape_results": {"exists": False, "documents": 0, "sources": []}, "index": index,
+                           "embedding_cache": embedding_cache, "crawl": crawl, "jobs": jobs})
+    
+    try:
+        with open(scrape_file, "r", encoding="utf-8") as f:
+            results = json.load(f)
+    except json.JSONDecodeError:
+        results = {}
+    
+    info = load_source_info(config.data_dir)
+    indexed = index.get("sources", {})
+    sources = [
+        {
+            "name": name,
+            "characters": len(text) if isinstance(text, str) else 0,
+            "type": info.get(name, {}).get("type"),
+            "url": info.get(name, {}).get("url"),
+            "fetched_at": info.get(name, {}).get("fetched_at"),
+            "chunks": indexed.get(name, {}).get("chunks", 0),
+        }
+        for name, text in results.items()
+    ]
+    by_type = {}
+    for source in sources:
+        by_type[source["type"] or "unknown"] = by_type.get(source["type"] or "unknown", 0) + 1
+    last_updated = datetime.datetime.fromtimestamp(os.path.getmtime(scrape_file)).isoformat()
+    return fk.jsonify({
+        "scrape_results": {
+            "exists": True,
+            "last_updated": last_updated,
+            "documents": len(sources),
+            "documents_by_type": by_type,
+            "sources": sources
+        },
+        "index": index,
+        "embedding_cache": embedding_cache,
+        "crawl": crawl,
+        "jobs": jobs,
+    })
+
+#Queue a rebuild of the search index (everything, or just some sources), optionally re-scraping first
+@app.route("/api/admin/kb/reindex", methods=["POST"])
+@with_admin
+def admin_kb_reindex(admin: AdminUser):
+    """Queue a background reindex job ({"full": bool, "sources": [names], "refresh": bool})."""
+    data = fk.request.get_json(silent=True)
+    if data is None:
+        data = {}
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    for field in ("full", "refresh"):
+        if field in data and not isinstance(data[field], bool):
+            raise ValidationError("{field} must be true or false", field=field, params={"field": field})
+    full = data.get("full", False)
+    sources = data.get("sources")
+    if sources is not None:
+        if not isinstance(sources, list) or not sources or not all(isinstance(name, str) and name for name in sources):
+            raise ValidationError("sources must be a non-empty list of source names", field="sources")
+        if full:
+            raise ValidationError("Pass either full or sources, not both")
+        known = set(load_scrape_results(config.data_dir))
+        unknown = [name for name in sources if name not in known]
+        if unknown:
+            raise ValidationError("Unknown source {source}", field="sources", params={"source": unknown[0]})
+
+    job = kb_jobs.submit(full=full, sources=sources, refresh=data.get("refresh", False), requested_by=admin.email)
+    logger.info(f"{admin.email} queued knowledge base job {job['job_id']}")
+    return fk.jsonify({"message": _("Reindex queued"), "job": job}), 202
+
+#See which knowledge base chunks a question retrieves (and through which retriever), for tuning
+@app.route("/api/admin/kb/search", methods=["GET"])
+@with_admin
+def admin_kb_search(admin: AdminUser):
+    """Run the hybrid retriever (and reranker, if on) for ?q= and return the chunks it would add to the prompt."""
+    query = fk.request.args.get("q", "").strip()
+    if not query:
+        raise ValidationError("q must be a non-empty string", field="q")
+    limit = max(1, min(fk.request.args.get("limit", config.retrieval.top_k, type=int), 50))
+    # ?rerank=false shows the ranking before the reranker, to compare
+    rerank = fk.request.args.get("rerank", "true").lower() != "false"
+    return fk.jsonify({"query": query, "results": gemini.knowledge.search(query, limit, rerank=rerank)})
+
+#Campus announcements that are currently active, for the banner in the chat UI
+@app.route("/api/announcements", methods=["GET"])
+def list_active_announcements():
+    """List the announcements that are active right now."""
+    announcements = [
+        {key: a[key] for key in ("announcement_id", "title", "body", "starts_at", "ends_at")}
+        for a in gemini.announcements.active()
+    ]
+    return fk.jsonify({"announcements": announcements})
+
+#Admin management of announcements (all of them, including scheduled and expired ones)
+@app.route("/api/admin/announcements", methods=["GET"])
+@with_admin
+def admin_list_announcements(admin: AdminUser):
+    """List every announcement, newest first."""
+    return fk.jsonify({"announcements": gemini.announcements.list_announcements()})
+
+@app.route("/api/admin/announcements", methods=["POST"])
+@with_admin
+def admin_create_announcement(admin: AdminUser):
+    """Post an announcement ({"title", "body", "starts_at", "ends_at", "in_prompt"})."""
+    fields = validate_announcement(fk.request.get_json(silent=True))
+    announcement = gemini.announcements.create(created_by=admin.email, **fields)
+    return fk.jsonify({"announcement": announcement}), 201
+
+@app.route("/api/admin/announcements/<announcement_id>", methods=["PATCH"])
+@with_admin
+def admin_update_announcement(announcement_id, admin: AdminUser):
+    """Edit an announcement, e.g. to end it early."""
+    fields = validate_announcement(fk.request.get_json(silent=True), partial=True)
+    announcement = gemini.announcements.update(announcement_id, fields)
+    if announcement is None:
+        raise NotFound("Announcement not found")
+    return fk.jsonify({"announcement": announcement})
+
+@app.route("/api/admin/announcements/<announcement_id>", methods=["DELETE"])
+@with_admin
+def admin_delete_announcement(announcement_id, admin: AdminUser):
+    """Delete an announcement."""
+    if not gemini.announcements.delete(announcement_id):
+        raise NotFound("Announcement not found")
+    return fk.jsonify({"message": _("Announcement deleted")})
+
+#Curated FAQ entries, checked before the model so common questions get the official answer instantly
+@app.route("/api/admin/kb/entries", methods=["GET"])
+@with_admin
+def admin_list_faq_entries(admin: AdminUser):
+    """List all curated FAQ entries."""
+    return fk.jsonify({"entries": faq_store.list_entries()})
+
+@app.route("/api/admin/kb/entries", methods=["POST"])
+@with_admin
+def admin_create_faq_entry(admin: AdminUser):
+    """Create a curated FAQ entry ({"question", "answer", "alternate_questions"})."""
+    fields = validate_faq_entry(fk.request.get_json(silent=True), max_length=config.limits.max_question_length)
+    entry = faq_store.create_entry(created_by=admin.email, **fields)
+    return fk.jsonify({"entry": entry}), 201
+
+@app.route("/api/admin/kb/entries/<entry_id>", methods=["GET"])
+@with_admin
+def admin_get_faq_entry(entry_id, admin: AdminUser):
+    """Get one curated FAQ entry."""
+    entry = faq_store.get_entry(entry_id)
+    if entry is None:
+        raise NotFound("FAQ entry not found")
+    return fk.jsonify({"entry": entry})
+
+@app.route("/api/admin/kb/entries/<entry_id>", methods=["PATCH"])
+@with_admin
+def admin_update_faq_entry(entry_id, admin: AdminUser):
+    """Update the question, answer, and/or alternate questions of a curated FAQ entry."""
+    fields = validate_faq_entry(fk.request.get_json(silent=True), max_length=config.limits.max_question_length, partial=True)
+    entry = faq_store.update_entry(entry_id, fields)
+    if entry is None:
+        raise NotFound("FAQ entry not found")
+    return fk.jsonify({"entry": entry})
+
+@app.route("/api/admin/kb/entries/<entry_id>", methods=["DELETE"])
+@with_admin
+def admin_delete_faq_entry(entry_id, admin: AdminUser):
+    """Delete a curated FAQ entry."""
+    if not faq_store.delete_entry(entry_id):
+        raise NotFound("FAQ entry not found")
+    return fk.jsonify({"message": _("FAQ entry deleted")})
+
+#Aggregate analytics stats (categories, unanswered count, engagement)
+@app.route("/api/admin/analytics/stats", methods=["GET"])
+@with_admin
+def admin_analytics_stats(admin: AdminUser):
+    """Return aggregate statistics over all logged interactions."""
+    if not config.reports.enabled:
+        return fk.jsonify(data_collector.get_aggregate_stats())
+    # Last night's rollup plus today, instead of aggregating the whole log on every load
+    return fk.jsonify(analytics_reports.current_stats())
+
+#Headline numbers for a date range and/or one user, for reports that don't need the full stats
+@app.route("/api/admin/analytics/summary", methods=["GET"])
+@with_admin
+def admin_analytics_summary(admin: AdminUser):
+    """Totals, average generation time, questions per day, and top users for ?from=&to=&email=."""
+    start, end = validate_date_range(fk.request.args)
+    top_users = max(1, min(fk.request.args.get("top", 10, type=int), 100))
+    summary = data_collector.get_summary(start=start, end=end, user_email=fk.request.args.get("email") or None,
+                                         top_users=top_users)
+    return fk.jsonify({"from": start, "to": end, **summary})
+
+#Logged interactions filtered by date range and user, a page at a time (export streams all of them instead)
+@app.route("/api/admin/analytics/interactions", methods=["GET"])
+@with_admin
+def admin_analytics_interactions(admin: AdminUser):
+    """Interactions for ?from=&to=&email=, oldest first, ?limit= (default 50, at most 500) from ?offset=."""
+    start, end = validate_date_range(fk.request.args)
+    limit = max(1, min(fk.request.args.get("limit", 50, type=int), 500))
+    offset = max(0, fk.request.args.get("offset", 0, type=int))
+    page = data_collector.query_interactions(start=start, end=end, user_email=fk.request.args.get("email") or None,
+                                             limit=limit, offset=offset)
+    return fk.jsonify(page)
+
+#Materialized daily/weekly analytics rollups
+@app.route("/api/admin/analytics/reports", methods=["GET"])
+@with_admin
+def admin_analytics_reports(admin: AdminUser):
+    """List the rollups for ?period=daily|weekly, newest first (?limit=, default 30)."""
+    period = fk.request.args.get("period", "daily")
+    if period not in REPORT_PERIODS:
+        raise ValidationError("period must be daily or weekly", field="period")
+    limit = max(1, min(fk.request.args.get("limit", 30, type=int), 400))
+    summary = analytics_reports.load_summary()
+    return fk.jsonify({
+        "period": period,
+        "reports": analytics_reports.list_reports(period, limit=limit),
+        "summary_as_of": summary["as_of"] if summary else None,
+    })
+
+#Materialize any missing rollups now instead of waiting for the scheduler
+@app.route("/api/admin/analytics/reports", methods=["POST"])
+@with_admin
+def admin_run_analytics_reports(admin: AdminUser):
+    """Write the reports for every finished day and week that doesn't have one yet."""
+    result = analytics_reports.run()
+    logger.info(f"{admin.email} generated {len(result['daily'])} daily and {len(result['weekly'])} weekly reports")
+    return fk.jsonify(result)
+
+#Admin report of the questions Archie couldn't answer
+@app.route("/api/admin/analytics/unanswered", methods=["GET"])
+@with_admin
+def admin_unanswered_questions(admin: AdminUser):
+    """List the most common unanswered questions so the knowledge base can be prioritized."""
+    limit = max(1, min(fk.request.args.get("limit", 20, type=int), 200))
+    report = data_collector.get_unanswered_report(limit=limit)
+    return fk.jsonify({"unanswered": report})
+
+#Most asked questions with answer quality and how much a FAQ entry would save, to pick FAQ entries
+@app.route("/api/admin/analytics/top-questions", methods=["GET"])
+@with_admin
+def admin_top_questions(admin: AdminUser):
+    """Rank the most asked questions in a window (?window=7d, 24h, 4w, or all) by how often the FAQ could have answered them."""
+    window = fk.request.args.get("window", "7d")
+    span = parse_window(window)
+    limit = max(1, min(fk.request.args.get("limit", 20, type=int), 200))
+    start = (datetime.datetime.now() - span).isoformat() if span else None
+    report = data_collector.get_top_questions(start=start, limit=limit)
+    return fk.jsonify({"window": window, "start": start, **report})
+
+#Bucketed series for the dashboard charts, computed here instead of shipping raw interactions to the browser
+@app.route("/api/admin/analytics/timeseries", methods=["GET"])
+@with_admin
+def admin_analytics_timeseries(admin: AdminUser):
+    """Return ?metric= (default questions) per ?interval= (hour, day, or week) between ?from= and ?to=."""
+    query = validate_timeseries(fk.request.args, TIMESERIES_METRICS, TIMESERIES_INTERVALS, MAX_TIMESERIES_BUCKETS)
+    series = data_collector.get_timeseries(query["metric"], query["interval"], query["start"], query["end"])
+    return fk.jsonify({
+        "metric": query["metric"],
+        "interval": query["interval"],
+        "from": query["start"].isoformat(),
+        "to": query["end"].isoformat(),
+        "buckets": series,
+    })
+
+#Export analytics filtered by date range and user, streamed so big logs don't blow up memory
+@app.route("/api/admin/analytics/export", methods=["GET"])
+@with_admin
+def admin_export_analytics(admin: AdminUser):
+    """Stream a filtered analytics export as json, jsonl, or csv."""
+    start = fk.request.args.get("from") or None
+    end = fk.request.args.get("to") or None
+    user = fk.request.args.get("user") or None
+    export_format = fk.request.args.get("format", "json").lower()
+    
+    if export_format not in EXPORT_FORMATS:
+        raise ValidationError("format must be one of json, jsonl, csv", field="format")
+    
+    chunks = data_collector.export_interactions(export_format, start=start, end=end, user_email=user)
+    resp = fk.Response(fk.stream_with_context(chunks), mimetype=EXPORT_FORMATS[export_format])
+    resp.headers["Content-Disposition"] = f"attachment; filename=analytics.{export_format}"
     return resp
 
+#Get the last duplicate question clustering report
+@app.route("/api/admin/analytics/clusters", methods=["GET"])
+@with_admin
+def admin_question_clusters(admin: AdminUser):
+    """Return the last generated question clustering report."""
+    report = question_clusterer.load_report()
+    if report is None:
+        raise NotFound("No clustering report yet")
+    report["running"] = clustering_lock.locked()
+    return fk.jsonify(report)
+
+#Kick off the clustering job in the background since embedding everything takes a while
+@app.route("/api/admin/analytics/clusters", methods=["POST"])
+@with_admin
+def admin_run_question_clustering(admin: AdminUser):
+    """Start a background job that clusters similar questions."""
+    if not clustering_lock.acquire(blocking=False):
+        raise Conflict("Clustering job already running")
+    
+    def job():
+        try:
+            question_clusterer.run()
+        except Exception as e:
+            logger.error(f"Error during question clustering: {e}")
+        finally:
+            clustering_lock.release()
+    
+    threading.Thread(target=job, daemon=True).start()
+    return fk.jsonify({"message": _("Clustering job started")}), 202
+
+#Get the last topics report (questions grouped into broad labeled topics)
+@app.route("/api/admin/analytics/topics", methods=["GET"])
+@with_admin
+def admin_question_topics(admin: AdminUser):
+    """Return the last generated topics report."""
+    report = topic_modeler.load_report()
+    if report is None:
+        raise NotFound("No topics report yet")
+    report["running"] = topics_lock.locked()
+    return fk.jsonify(report)
+
+#Kick off topic modeling in the background; ?topics=N picks the number of topics
+@app.route("/api/admin/analytics/topics", methods=["POST"])
+@with_admin
+def admin_run_topic_modeling(admin: AdminUser):
+    """Start a background job that groups logged questions into topics."""
+    num_topics = fk.request.args.get("topics", type=int)
+    if num_topics is not None and not 2 <= num_topics <= MAX_TOPICS:
+        raise ValidationError("topics must be between 2 and {max}", field="topics", params={"max": MAX_TOPICS})
+    if not topics_lock.acquire(blocking=False):
+        raise Conflict("Topic modeling job already running")
+    
+    def job():
+        try:
+            topic_modeler.run(num_topics=num_topics)
+        except Exception as e:
+            logger.error(f"Error during topic modeling: {e}")
+        finally:
+            topics_lock.release()
+    
+    threading.Thread(target=job, daemon=True).start()
+    return fk.jsonify({"message": _("Topic modeling job started")}), 202
+
+#Feature flags: see which features are on for what share of sessions and change them without a restart
+@app.route("/api/admin/flags", methods=["GET"])
+@with_admin
+def admin_list_flags(admin: AdminUser):
+    """List every feature flag with its effective state."""
+    return fk.jsonify({"flags": feature_flags.list_flags()})
+
+@app.route("/api/admin/flags/<name>", methods=["PATCH"])
+@with_admin
+def admin_update_flag(name, admin: AdminUser):
+    """Override a flag at runtime ({"enabled": bool, "rollout_percent": 0-100})."""
+    if name not in FLAG_DEFINITIONS and name not in config.flags:
+        raise NotFound("Unknown feature flag")
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    
+    updates = {}
+    if "enabled" in data:
+        if not isinstance(data["enabled"], bool):
+            raise ValidationError("enabled must be true or false", field="enabled")
+        updates["enabled"] = data["enabled"]
+    if "rollout_percent" in data:
+        percent = data["rollout_percent"]
+        if isinstance(percent, bool) or not isinstance(percent, int) or not 0 <= percent <= 100:
+            raise ValidationError("rollout_percent must be a whole number from 0 to 100", field="rollout_percent")
+        updates["rollout_percent"] = percent
+    if not updates:
+        raise ValidationError("Nothing to update")
+    
+    state = feature_flags.set_override(name, updates, updated_by=admin.email)
+    logger.info(f"{admin.email} set feature flag {name}: {state}")
+    return fk.jsonify({"flag": dict(state, name=name)})
+
+@app.route("/api/admin/flags/<name>", methods=["DELETE"])
+@with_admin
+def admin_reset_flag(name, admin: AdminUser):
+    """Drop a flag's runtime override so the config value applies again."""
+    if not feature_flags.clear_override(name):
+        raise NotFound("No override for this feature flag")
+    return fk.jsonify({"flag": dict(feature_flags.get(name), name=name)})
+
+#Evaluation harness: run a YAML question suite through the answer pipeline and score it, see lib/Evaluation.py
+@app.route("/api/admin/eval", methods=["GET"])
+@with_admin
+def admin_eval_status(admin: AdminUser):
+    """List the available suites and saved reports, and whether a run is in progress."""
+    return fk.jsonify({
+        "suites": list_suites(config.evals_dir),
+        "reports": evaluator.list_reports(),
+        "running": eval_lock.locked(),
+    })
+
+@app.route("/api/admin/eval", methods=["POST"])
+@with_admin
+def admin_run_eval(admin: AdminUser):
+    """Start a background evaluation run ({"suite": "default", "judge": true|false})."""
+    data = fk.request.get_json(silent=True) or {}
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    suite = data.get("suite", "default")
+    # Only suites from the evals directory, never arbitrary paths
+    if suite not in list_suites(config.evals_dir):
+        raise ValidationError("Unknown suite {suite}", field="suite", params={"suite": str(suite)})
+    judge = data.get("judge")
+    if judge is not None and not isinstance(judge, bool):
+        raise ValidationError("judge must be true or false", field="judge")
+    suite_path = resolve_suite(config.evals_dir, suite)
+    
+    if not eval_lock.acquire(blocking=False):
+        raise Conflict("Evaluation already running")
+    
+    def job():
+        try:
+            report = evaluator.run(suite_path, use_judge=judge)
+            logger.info(f"Evaluation {report['report_file']}: {report['summary']['passed']}/{report['summary']['total']} passed")
+        except Exception as e:
+            logger.error(f"Error during evaluation: {e}")
+        finally:
+            eval_lock.release()
+    
+    threading.Thread(target=job, daemon=True).start()
+    return fk.jsonify({"message": _("Evaluation started")}), 202
+
+@app.route("/api/admin/eval/reports/<name>", methods=["GET"])
+@with_admin
+def admin_eval_report(name, admin: AdminUser):
+    """Get a saved evaluation report by file name, or "latest"."""
+    report = evaluator.load_report(None if name == "latest" else name)
+    if report is None:
+        raise NotFound("Report not found")
+    return fk.jsonify(report)
+
+#Model comparison: one question through several models at once, to pick models on evidence, see lib/ModelComparison.py
+@app.route("/api/admin/compare", methods=["POST"])
+@with_admin
+def admin_compare_models(admin: AdminUser):
+    """
+    Answer {"question", "models": [two or more configured models], "style"} with every model concurrently
+    and return the answers side by side, with latency and token counts.
+    """
+    comparison = validate_comparison(fk.request.get_json(silent=True), available_models(), config.limits.max_question_length)
+    if backend_down():
+        raise Unavailable()
+    result = compare_models(gemini, comparison["question"], comparison["models"], comparison["style"])
+    logger.info(f"Compared {', '.join(comparison['models'])} for {admin.email}")
+    return fk.jsonify(result)
+
+#Canary rollouts: a share of sessions on a candidate model/prompt, compared with the rest, see lib/Canary.py
+def canary_comparison(active: dict) -> dict:
+    return compare_arms(data_collector.iter_interactions(start=active["started_at"]), active["id"],
+                        min_interactions=config.canary.min_interactions,
+                        max_latency_increase=config.canary.max_latency_increase,
+                        max_satisfaction_drop=config.canary.max_satisfaction_drop)
+
+@app.route("/api/admin/canary", methods=["GET"])
+@with_admin
+def admin_canary(admin: AdminUser):
+    """The running canary with its arms compared, what earlier canaries promoted, and past canaries."""
+    state = canary.state()
+    active = state["active"]
+    return fk.jsonify({
+        "canary": active,
+        "comparison": canary_comparison(active) if active else None,
+        "promoted": state["promoted"],
+        "default_model": default_stream_model(),
+        "history": state["history"],
+    })
+
+@app.route("/api/admin/canary", methods=["POST"])
+@with_admin
+def admin_start_canary(admin: AdminUser):
+    """Start a canary ({"model", "prompt", "percent"}); only one runs at a time."""
+    fields = validate_canary(fk.request.get_json(silent=True), available_models(), canary.prompt_exists)
+    baseline_model = default_stream_model()
+    if fields["model"] == baseline_model:
+        raise ValidationError("model is already the default", field="model")
+    started = canary.start(fields["model"], fields["prompt"], fields["percent"], baseline_model, started_by=admin.email)
+    if started is None:
+        raise Conflict("A canary is already running, promote or roll it back first")
+    logger.info(f"{admin.email} started canary {started['id']}: {started}")
+    return fk.jsonify({"canary": started}), 201
+
+@app.route("/api/admin/canary", methods=["PATCH"])
+@with_admin
+def admin_update_canary(admin: AdminUser):
+    """Change the share of sessions in the running canary ({"percent": 1-100})."""
+    data = fk.request.get_json(silent=True)
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    updated = canary.set_percent(validate_canary_percent(data.get("percent")))
+    if updated is None:
+        raise NotFound("No canary is running")
+    return fk.jsonify({"canary": updated})
+
+def finish_canary(outcome: str, admin: AdminUser):
+    """End the running canary, keeping its final comparison in the history."""
+    active = canary.active()
+    finished = canary.finish(outcome, finished_by=admin.email, comparison=canary_comparison(active)) if active else None
+    if finished is None:
+        raise NotFound("No canary is running")
+    logger.info(f"{admin.email} {outcome} canary {finished['id']}")
+    return fk.jsonify({"canary": finished, "promoted": canary.promoted()})
+
+@app.route("/api/admin/canary/promote", methods=["POST"])
+@with_admin
+def admin_promote_canary(admin: AdminUser):
+    """Make the running canary's candidate model/prompt the default for everyone."""
+    return finish_canary("promoted", admin)
+
+@app.route("/api/admin/canary/rollback", methods=["POST"])
+@with_admin
+def admin_rollback_canary(admin: AdminUser):
+    """Stop the running canary and send everyone back to the baseline."""
+    return finish_canary("rolled_back", admin)
+
+#Weekly admin digest: preview the current week, or send it now instead of waiting for the schedule
+@app.route("/api/admin/digest", methods=["GET"])
+@with_admin
+def admin_digest(admin: AdminUser):
+    """Preview the digest for the past seven days, as JSON or (?format=text) the email body."""
+    digest = digest_scheduler.build()
+    if fk.request.args.get("format") == "text":
+        return fk.Response(render_digest(digest), mimetype="text/plain")
+    last_sent = digest_scheduler.last_sent()
+    return fk.jsonify({
+        "digest": digest,
+        "recipients": digest_scheduler.recipients,
+        "last_sent_at": last_sent.isoformat() if last_sent else None,
+    })
+
+@app.route("/api/admin/digest/send", methods=["POST"])
+@with_admin
+def admin_digest_send(admin: AdminUser):
+    """Email the digest to the admins now."""
+    try:
+        digest = digest_scheduler.send()
+    except MailError as e:
+        logger.warning(f"Digest not sent: {e}")
+        raise Upstream("The mail server is unavailable")
+    return fk.jsonify({"message": _("Digest sent"), "digest": digest})
+
 #This is not used and guests are no longer supported. I am keeping it for potential future use.
 @app.route("/gchats", methods=["GET", "POST"])
 def gchats():
-    session_id = fk.request.cookies.get("session_id")
+    session_id = request_auth().session_id
+    if kiosk_request(session_id):
+        return fk.redirect(fk.url_for("kiosk"))
     if not session_id:
         # Create new guest session
         session_id = session_manager.create_session(user_email=None)
 
     # render template and attach session cookie
     resp = fk.make_response(fk.redirect(fk.url_for("index")))
-    print(f"New guest session started: {session_id}")
-    resp.set_cookie("session_id", session_id, httponly=True, samesite="Strict")
+    logger.info(f"New guest session started: {session_id}")
+    set_session_cookie(resp, session_id, request_auth().user_email)
     return resp
 @app.route("/chats", methods=["GET", "POST"])
 def chats():
+    # No signing in or signing up from a kiosk
+    if kiosk_request(request_auth().session_id):
+        return fk.redirect(fk.url_for("kiosk"))
     if fk.request.method == "POST":
         email = fk.request.form.get("email", "").strip()
         password = fk.request.form.get("password", "")
         
         # Basic email validation
         if not email or "@" not in email or len(email) > 255:
-            return fk.render_template("home.html", error="Please provide a valid email address")
+            return fk.render_template("home.html", error=_("Please provide a valid email address"))
         
         if not password:
-            return fk.render_template("home.html", error="Password is required")
+            return fk.render_template("home.html", error=_("Password is required"))
 
         if email and password:
             # Try to authenticate user
             if session_manager.authenticate_user(email, password):
+                status = session_manager.get_status(email)
+                if status != "active":
+                    return fk.render_template("home.html", error=_(STATUS_MESSAGES[status]))
                 # Create new session for logged-in user
                 session_id = session_manager.create_session(user_email=email)
                 
                 resp = fk.make_response(fk.redirect(fk.url_for("index")))
-                print(f"User {email} logged in with session: {session_id}")
+                logger.info(f"User {email} logged in with session: {session_id}")
 
-                resp.set_cookie("session_id", session_id, httponly=True, samesite="Strict")
-                resp.set_cookie("user_email", email, httponly=True, samesite="Strict")
+                set_session_cookie(resp, session_id, email)
                 return resp
             else:
                 # User doesn't exist, create new account
@@ -330,42 +2919,223 @@ def chats():
                     session_id = session_manager.create_session(user_email=email)
 
                     resp = fk.make_response(fk.redirect(fk.url_for("index")))
-                    print(f"New user {email} created with session: {session_id}")
-                    resp.set_cookie("session_id", session_id, httponly=True, samesite="Strict")
-                    resp.set_cookie("user_email", email, httponly=True, samesite="Strict")
+                    logger.info(f"New user {email} created with session: {session_id}")
+                    webhooks.user_registered(email)
+                    set_session_cookie(resp, session_id, email)
                     return resp
                 else:
-                    return fk.render_template("home.html", error="Failed to create account")
+                    return fk.render_template("home.html", error=_("Failed to create account"))
         else:
-            return fk.render_template("home.html", error="Please provide email and password")
+            return fk.render_template("home.html", error=_("Please provide email and password"))
     return fk.render_template("home.html")
 
 
+def run_kb_job(job: dict) -> dict:
+    """Do one knowledge base job: optionally re-scrape (and crawl), then rebuild the search index."""
+    if job["refresh"]:
+        refresh_knowledge_base(config.data_dir, crawler=Crawler.from_config(config.crawl, config.data_dir), full=job["full"])
+    index = gemini.knowledge.build(full=job["full"], rebuild_sources=job["sources"])
+    # The per-source details are in the status endpoint; a crawled site has hundreds
+    return {key: index[key] for key in ("built_at", "chunks", "embedded", "last_build")}
+
+#Reindex jobs queued from the admin dashboard, run one at a time
+kb_jobs = IndexJobQueue(run_kb_job)
+
 def background_checker():
-    urls = {
-        "website": "https://www.arcadia.edu/",
-        "events": "https://www.arcadia.edu/events/?mode=month",
-        "about": "https://www.arcadia.edu/about-arcadia/",
-        "weather": "https://weather.com/weather/today/l/b0f4fc1167769407f55347d55f492a46e194ccaed63281d2fa3db2e515020994",
-        "diningHours": "https://www.arcadia.edu/life-arcadia/living-commuting/dining/",
-        "ITresources": "https://www.arcadia.edu/life-arcadia/campus-life-resources/information-technology/",
-        "Academic Calendar": "https://www.arcadia.edu/academics/resources/academic-calendars/2025-26/",
-        }
-    
+    """Re-scrape the university pages into the knowledge base and rebuild the search index."""
+    run_kb_job({"refresh": True, "full": False, "sources": None})
+
+def digest_loop(interval_seconds: int = 3600):
+    """Send the weekly admin digest whenever it comes due. Runs for the life of the server."""
+    while True:
+        try:
+            if digest_scheduler.run_if_due():
+                logger.info(f"Sent the weekly digest to {', '.join(digest_scheduler.recipients)}")
+        except MailError as e:
+            logger.warning(f"Weekly digest not sent: {e}")
+        except Exception as e:
+            logger.error(f"Error building the weekly digest: {e}")
+        time.sleep(interval_seconds)
+
+def report_loop(interval_seconds: int = 600):
+    """Materialize the analytics rollups soon after each midnight. Runs for the life of the server."""
+    while True:
+        try:
+            if analytics_reports.is_due():
+                result = analytics_reports.run()
+                logger.info(f"Analytics rollups: {len(result['daily'])} daily and {len(result['weekly'])} weekly reports written")
+        except Exception as e:
+            logger.error(f"Error generating the analytics rollups: {e}")
+        time.sleep(interval_seconds)
 
-    dictionary = {}
-    for name, url in urls.items():
-        result = gemini.scrape_website(url)
-        dictionary[name] = result
+def start_report_scheduler():
+    """Start the rollup thread if rollups are turned on."""
+    if config.reports.enabled:
+        threading.Thread(target=report_loop, daemon=True).start()
 
-    # ensure the data directory exists, then write the collected dictionary as JSON
-    os.makedirs(os.path.dirname("data/scrape_results.json"), exist_ok=True)
-    with open("data/scrape_results.json", "w", encoding="utf-8") as f:
-        json.dump(dictionary, f, ensure_ascii=False, indent=4)
+def start_digest_scheduler():
+    """Start the digest thread if the digest is turned on and has somewhere to go."""
+    if not config.digest.enabled:
+        return
+    if not config.admin.emails or not config.mail.host:
+        logger.warning("The weekly digest needs [admin] emails and [mail] host; not scheduling it")
+        return
+    threading.Thread(target=digest_loop, daemon=True).start()
 
+def check_data_integrity(repair: bool = False) -> bool:
+    """Cross-check the account and session files and log what's wrong. Returns True if nothing is (left)."""
+    report = DataIntegrityChecker(session_manager).check(repair=repair)
+    (logger.warning if report["issues"] else logger.info)(format_report(report, repair=repair))
+    return all(issue["repaired"] for issue in report["issues"])
     
-if __name__ == "__main__":
+def acquire_instance_lock() -> bool:
+    """
+    Take the data directory's lock (lib/InstanceLock.py) before writing to it. Returns False,
+    saying who has it, if another server does. Replicas and instance.lock = false skip it.
+    """
+    if config.instance.role == "replica" or not config.instance.lock:
+        return True
+    try:
+        instance_lock.acquire()
+    except InstanceLockHeld as e:
+        logger.error(str(e))
+        return False
+    return True
+
+def check_deployment() -> bool:
+    """Check that the config, data directory, templates, and Ollama backend are usable."""
+    ok = True
+    print(f"Config file: {config.config_file or '(none, using defaults and environment)'}")
+    print(f"Layout: {config.layout.layout} (cache: {config.cache_dir})")
+
+    if config.instance.role == "replica":
+        print("[ok]   read-only replica, the data directory's lock isn't needed")
+    elif config.instance.lock and instance_lock.is_locked() and not instance_lock.held:
+        owner = instance_lock.owner() or {}
+        print(f"[fail] data directory in use by another server (pid {owner.get('pid', '?')} on {owner.get('host', '?')}); "
+              "a primary can't start until it stops")
+        ok = False
+    else:
+        print(f"[ok]   data directory not in use by another server (role: {config.instance.role})")
+
+    # Data directory must exist and be writable
+    try:
+        os.makedirs(config.data_dir, exist_ok=True)
+    except OSError:
+        pass
+    writable = data_dir_status()
+    if writable["status"] == "ok":
+        print(f"[ok]   data directory {writable['check']}: {config.data_dir}")
+    else:
+        print(f"[fail] data directory not {writable['check']}: {config.data_dir} ({writable['error']})")
+        ok = False
+
+    for name in ("home.html", "index.html"):
+        path = os.path.join(config.templates_dir, name)
+        if os.path.exists(path):
+            print(f"[ok]   template found: {path}")
+        else:
+            print(f"[fail] template missing: {path}")
+            ok = False
 
+    try:
+        resp = requests.get(f"{config.ollama.host.rstrip('/')}/api/version", timeout=5)
+        resp.raise_for_status()
+        print(f"[ok]   Ollama reachable at {config.ollama.host} (version {resp.json().get('version', 'unknown')})")
+    except Exception as e:
+        print(f"[fail] Ollama not reachable at {config.ollama.host}: {e}")
+        ok = False
 
+    # Older hashes are upgraded as their owners log in (lib/Passwords.py)
+    schemes = Counter(hash_scheme(user.get("password_hash")) for user in session_manager.users.all())
+    outdated = sum(count for scheme, count in schemes.items() if scheme != "argon2")
+    if argon2_available():
+        print(f"[ok]   passwords hashed with argon2id ({outdated} accounts still on an older hash until they log in)")
+    else:
+        print("[fail] argon2-cffi isn't installed, new passwords are hashed with scrypt (pip install argon2-cffi)")
+        ok = False
+
+    if config.cookies.secret and len(config.cookies.secret) < 32:
+        print("[fail] cookies.secret is shorter than 32 characters, too easy to guess for signing session tokens")
+        ok = False
+    else:
+        key = "cookies.secret" if config.cookies.secret else os.path.join(config.data_dir, SECRET_FILE)
+        print(f"[ok]   session tokens signed with {key}")
+
+    # Report only; `python src/admin.py check-data --repair` fixes them
+    report = DataIntegrityChecker(session_manager).check()
+    print(f"{'[fail]' if report['issues'] else '[ok]  '} {format_report(report)}")
+    if report["issues"]:
+        ok = False
+
+    return ok
+
+def build_arg_parser() -> argparse.ArgumentParser:
+    parser = argparse.ArgumentParser(prog="archie", description="ArchieAI server")
+    parser.add_argument("--config", help="Path to the TOML config file (default: archie.toml)")
+    parser.add_argument("--port", type=int, help="Port to listen on (overrides config)")
+    parser.add_argument("--data-dir", help="Data directory (overrides config)")
+    parser.add_argument("--dev", action="store_true", help="Dev mode: re-read templates and prompts on every request")
+
+    subcommands = parser.add_subparsers(dest="command")
+    subcommands.add_parser("serve", help="Run the web server (default)")
+    subcommands.add_parser("check", help="Validate the config, data directory, and Ollama connection")
+    subcommands.add_parser("migrate", help="Create/upgrade files in the data directory")
+    return parser
+
+def main(argv=None) -> int:
+    args = build_arg_parser().parse_args(argv)
+
+    if args.config or args.port or args.data_dir or args.dev:
+        cfg = load_config(args.config)
+        if args.port:
+            cfg.server.port = args.port
+        if args.data_dir:
+            cfg.paths.data_dir = args.data_dir
+        if args.dev:
+            cfg.server.dev_mode = True
+        init_app(cfg)
+
+    command = args.command or "serve"
+    if command == "check":
+        return 0 if check_deployment() else 1
+    if command == "migrate":
+        if config.instance.role == "replica":
+            print("Error: a read-only replica can't migrate the data directory; run this on the primary")
+            return 1
+        if not acquire_instance_lock():
+            return 1
+        return 0 if run_migrations(config) else 1
+
+    # The reloader runs main() in a parent and a child; only the child serves requests
+    if not config.server.debug or os.environ.get("WERKZEUG_RUN_MAIN") == "true":
+        if not acquire_instance_lock():
+            return 1
+        # Replicas leave repairs, digests, and rollups (which all write) to the primary
+        if config.instance.role != "replica":
+            if config.integrity.check_on_startup:
+                check_data_integrity(repair=config.integrity.repair)
+            start_digest_scheduler()
+            start_report_scheduler()
     #qrCodeGen.make_qr("https://118ce87f29d4.ngrok-free.app", show=True, save_path="websiteqr.png")
-    app.run(host="0.0.0.0", port=5000, debug=True, threaded=True)
\ No newline at end of file
+    if config.server.debug:
+        # The reloader restarts the process on every edit, there's nothing to drain in development
+        app.run(host=config.server.host, port=config.server.port, debug=True, threaded=True)
+        return 0
+    serve()
+    return 0
+
+def serve():
+    """Run the server until SIGTERM/SIGINT, then drain it (see lib/Shutdown.py)."""
+    server = make_server(config.server.host, config.server.port, app, threaded=True)
+    # shutdown() is called from the drain thread; it stops serve_forever() below once the drain is done
+    shutdown.install_signal_handlers(server.shutdown)
+    logger.info(f"Serving ArchieAI on http://{config.server.host}:{config.server.port}")
+    try:
+        server.serve_forever()
+    finally:
+        server.server_close()
+    logger.info("Shut down cleanly")
+
+if __name__ == "__main__":
+    sys.exit(main())
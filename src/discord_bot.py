@@ -0,0 +1,125 @@
+"""
+ArchieAI Discord bot.
+Lets the student Discord ask Archie directly: mention the bot, DM it, or post in one of the
+configured channels. Answers stream in by editing the reply, and everything is saved to
+sessions and analytics like the web chat (see lib/DiscordBridge.py).
+
+Needs discord.py (pip install -r requirements-discord.txt) and [discord] enabled = true
+with a bot token. Run from the project root: python src/discord_bot.py
+"""
+import sys
+import logging
+import argparse
+from lib.Config import load_config
+from lib.Logging import setup_logging
+from lib.SessionManager import SessionManager
+from lib.DataCollector import DataCollector
+from lib.FaqStore import FaqStore
+from lib.GemInterface import AiInterface
+from lib.Validation import validate_question
+from lib.Errors import ValidationError
+from lib.DiscordBridge import DiscordBridge, DiscordSessionMap, split_message
+
+RESET_COMMAND = "!archie reset"
+THINKING_TEXT = "Thinking..."
+
+logger = logging.getLogger("archie.discord")
+
+
+def build_bridge(config) -> DiscordBridge:
+    ai = AiInterface(config=config)
+    session_manager = SessionManager(config=config)
+    faq_store = None
+    if config.faq.enabled:
+        faq_store = FaqStore(config.data_dir, similarity_threshold=config.faq.similarity_threshold)
+    return DiscordBridge(
+        ai,
+        session_manager,
+        DataCollector(config=config),
+        DiscordSessionMap(session_manager, data_dir=config.data_dir, scope=config.discord.session_scope),
+        faq_store=faq_store,
+        edit_interval_seconds=config.discord.edit_interval_seconds,
+    )
+
+
+def run_bot(config) -> int:
+    try:
+        import discord
+    except ImportError:
+        print("discord.py isn't installed: pip install -r requirements-discord.txt")
+        return 1
+
+    bridge = build_bridge(config)
+    allowed_channels = {str(channel) for channel in config.discord.channels}
+
+    intents = discord.Intents.default()
+    intents.message_content = True
+    client = discord.Client(intents=intents)
+
+    @client.event
+    async def on_ready():
+        logger.info(f"Discord bot logged in as {client.user}")
+
+    @client.event
+    async def on_message(message):
+        if message.author.bot:
+            return
+        is_dm = message.guild is None
+        mentioned = client.user in message.mentions
+        if not (is_dm or mentioned or str(message.channel.id) in allowed_channels):
+            return
+
+        text = message.content.replace(f"<@{client.user.id}>", "").replace(f"<@!{client.user.id}>", "").strip()
+        if text.lower() == RESET_COMMAND:
+            bridge.session_map.reset(message.author.id, message.channel.id)
+            await message.reply("Starting a new conversation.", mention_author=False)
+            return
+
+        try:
+            question = validate_question({"question": text}, max_length=config.limits.max_question_length)
+        except ValidationError as e:
+            await message.reply(e.to_dict()["error"], mention_author=False)
+            return
+
+        reply = await message.reply(THINKING_TEXT, mention_author=False)
+        # Anything past the first 2000 characters goes into follow-up messages
+        overflow = []
+
+        async def on_update(answer: str):
+            pieces = split_message(answer or "Sorry, I couldn't come up with an answer.")
+            await reply.edit(content=pieces[0])
+            overflow[:] = pieces[1:]
+
+        try:
+            async with message.channel.typing():
+                await bridge.answer(question, message.author.id, message.channel.id, on_update)
+        except Exception as e:
+            logger.warning(f"Discord answer failed: {e}")
+            await reply.edit(content="Archie is unavailable right now. Please try again soon.")
+            return
+        for piece in overflow:
+            await message.channel.send(piece)
+
+    # discord.py logs through our handler instead of adding its own
+    client.run(config.discord.token, log_handler=None)
+    return 0
+
+
+def main(argv=None) -> int:
+    parser = argparse.ArgumentParser(description="ArchieAI Discord bot")
+    parser.add_argument("--config", help="Path to the TOML config file (default: archie.toml)")
+    args = parser.parse_args(argv)
+
+    config = load_config(args.config)
+    setup_logging(config.logging.format, config.logging.level)
+    if not config.discord.enabled:
+        print("The Discord bot is disabled; set [discord] enabled = true (or ARCHIE_DISCORD_ENABLED=1)")
+        return 1
+    if not config.discord.token:
+        print("No Discord bot token; set [discord] token or DISCORD_BOT_TOKEN")
+        return 1
+    return run_bot(config)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
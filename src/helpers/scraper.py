@@ -38,14 +38,8 @@ The only reason i am keeping it is so i dont have to re-write GemInterface to no
 This code is unused And will remain used due to the switch to tool calling.
 The only reason i am keeping it is so i dont have to re-write GemInterface to not use this file and in case i need a web scraper in the future.
 """
-def scrape_website(url: str, timeout: Optional[int] = None) -> str:
-    """
-    Improved synchronous web scraper that:
-    - creates a requests.Session with browser-like headers
-    - has a Retry strategy for transient status codes (429, 5xx)
-    - keeps the interface synchronous (requests + BeautifulSoup)
-    """
-    # build a session with a retry strategy
+def make_session() -> requests.Session:
+    """A requests.Session with browser-like headers and retries for transient status codes (429, 5xx)."""
     session = requests.Session()
     headers = {
         "User-Agent": "Mozilla/5.0 (Windows NT 10.0; Win64; x64) "
@@ -65,6 +59,40 @@ def scrape_website(url: str, timeout: Optional[int] = None) -> str:
     adapter = HTTPAdapter(max_retries=retry_strategy)
     session.mount("https://", adapter)
     session.mount("http://", adapter)
+    return session
+
+
+def fetch_document(url: str, timeout: Optional[int] = None) -> bytes:
+    """
+    Download a file (PDF, DOCX, ...) as bytes.
+
+    Raises:
+        requests.RequestException: If the download fails
+    """
+    to = timeout if timeout is not None else 30  # documents are bigger than pages
+    print(f"Downloading {url} with timeout={to}")
+    response = make_session().get(url, timeout=to, allow_redirects=True)
+    response.raise_for_status()
+    return response.content
+
+
+def page_text(html: str, keep_headings: bool = False) -> str:
+    """Text of a page; with keep_headings, <h1>-<h6> become markdown "#" lines (see lib/DocumentText.py)."""
+    if keep_headings:
+        from lib.DocumentText import html_to_text
+        return html_to_text(html)
+    return BeautifulSoup(html, "html.parser").get_text()
+
+
+def scrape_website(url: str, timeout: Optional[int] = None, keep_headings: bool = False) -> str:
+    """
+    Improved synchronous web scraper that:
+    - creates a requests.Session with browser-like headers
+    - has a Retry strategy for transient status codes (429, 5xx)
+    - keeps the interface synchronous (requests + BeautifulSoup)
+    - optionally keeps the page headings for heading-aware chunking
+    """
+    session = make_session()
 
     to = timeout if timeout is not None else 10  # default timeout in seconds
     print(f"Scraping {url} with timeout={to}")
@@ -76,13 +104,11 @@ def scrape_website(url: str, timeout: Optional[int] = None) -> str:
             # provide helpful debug string but still return any HTML body if present
             print(f"HTTP error for {url}: {http_err} (status {getattr(response, 'status_code', 'unknown')})")
             if response.text:
-                soup = BeautifulSoup(response.text, "html.parser")
-                return soup.get_text()
+                return page_text(response.text, keep_headings)
             return f"HTTP error when scraping {url}: {http_err}"
 
         # successful response
-        soup = BeautifulSoup(response.text, "html.parser")
-        return soup.get_text()
+        return page_text(response.text, keep_headings)
     except requests.RequestException as e:
         print(f"RequestException when scraping {url}: {e}")
         return f"An error occurred while scraping the website: {e}"
@@ -90,7 +116,11 @@ def scrape_website(url: str, timeout: Optional[int] = None) -> str:
         print(f"Unexpected error when scraping {url}: {e}")
         return f"An unexpected error occurred while scraping the website: {e}"
 
-def background_checker():
+def background_checker(data_dir: Optional[str] = None):
+    if data_dir is None:
+        # Same data directory as the app (paths.data_dir / paths.layout)
+        from lib.Config import load_config
+        data_dir = load_config().data_dir
     urls = {
         "website": "https://www.arcadia.edu/",
         "events": "https://www.arcadia.edu/events/?mode=month",
@@ -109,14 +139,14 @@ def background_checker():
         dictionary[name] = result
 
     # ensure the data directory exists, then write the collected dictionary as JSON
-    os.makedirs(os.path.dirname("data/scrape_results.json"), exist_ok=True)
+    os.makedirs(data_dir, exist_ok=True)
     #sanitize the data i.e removing /n and \n and other chars like that
-    with open("data/scrape_results.json", "w", encoding="utf-8") as f:
+    with open(os.path.join(data_dir, "scrape_results.json"), "w", encoding="utf-8") as f:
         json.dump(dictionary, f, ensure_ascii=False, indent=4)
 import time
 if __name__ == "__main__":
     while True:
         print("Scraping websites and saving results...")
         background_checker()
-        print("Scraping completed and results saved to data/scrape_results.json")
+        print("Scraping completed and results saved to scrape_results.json in the data directory")
         time.sleep(3600)
\ No newline at end of file
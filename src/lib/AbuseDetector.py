@@ -0,0 +1,134 @@
+"""
+Abuse and spam detection for ArchieAI.
+Cheap heuristics run on every question before it reaches the model:
+  - rapid_fire:        more than max_questions_per_minute from one caller (throttled)
+  - repeated_prompt:   the same question over and over within repeat_window_seconds (throttled)
+  - extreme_length:    questions right up against the length limit (flagged)
+  - jailbreak_pattern: known prompt-injection phrasing like "ignore previous instructions" (flagged)
+Flagged questions are still answered; throttled ones get a 429. Either way an incident is
+appended to data/abuse_incidents.jsonl for admins to review, and app.py can suspend an
+account once it piles up incidents. Counters live in memory, per process.
+"""
+import os
+import re
+import json
+import time
+import threading
+from collections import defaultdict, deque
+from datetime import datetime, timedelta
+from typing import Dict, List, Optional
+
+from lib.DataCollector import normalize_question
+
+INCIDENTS_FILE = "abuse_incidents.jsonl"
+
+JAILBREAK_PATTERNS = [
+    r"\bignore\s+(all\s+|any\s+)?(the\s+)?(previous|prior|above|earlier)\s+(instructions|prompts?|rules)",
+    r"\bdisregard\s+(all\s+|your\s+)?(previous|prior|system)\s+(instructions|prompt)",
+    r"\b(reveal|print|show|repeat)\s+(me\s+)?(your|the)\s+(system\s+prompt|hidden\s+instructions)",
+    r"\byou\s+are\s+now\s+(dan|in\s+developer\s+mode|unrestricted|jailbroken)",
+    r"\bdo\s+anything\s+now\b",
+    r"\bdeveloper\s+mode\s+(enabled|on)\b",
+    r"\bpretend\s+(that\s+)?you\s+(have\s+no|don'?t\s+have\s+any)\s+(rules|restrictions|filters)",
+    r"\bjailbreak\b",
+]
+
+# Incidents that stop the question from being answered
+THROTTLE_REASONS = {"rapid_fire", "repeated_prompt"}
+
+
+class AbuseDetector:
+    """Scores questions against the heuristics and keeps the incident log."""
+
+    def __init__(self, data_dir: str = "data", max_questions_per_minute: int = 20, repeat_threshold: int = 5,
+                 repeat_window_seconds: int = 120, max_question_length: int = 4000, long_question_ratio: float = 0.9,
+                 extra_patterns: Optional[List[str]] = None):
+        self.path = os.path.join(data_dir, INCIDENTS_FILE)
+        self.max_questions_per_minute = max_questions_per_minute
+        self.repeat_threshold = repeat_threshold
+        self.repeat_window_seconds = repeat_window_seconds
+        self.long_question_length = int(max_question_length * long_question_ratio)
+        self.patterns = [re.compile(p, re.IGNORECASE) for p in JAILBREAK_PATTERNS + list(extra_patterns or [])]
+        self._lock = threading.Lock()
+        # caller -> deque of (monotonic time, normalized question)
+        self._recent = defaultdict(deque)
+
+    def check(self, question: str, user_email: Optional[str] = None, session_id: Optional[str] = None,
+              ip_address: Optional[str] = None) -> Dict:
+        """
+        Run the heuristics on a question and record an incident if any of them trip.
+
+        The caller is the account if there is one, otherwise the session, otherwise the IP.
+
+        Returns:
+            {"action": "allow" | "flag" | "throttle", "reasons": [...], "incident": the logged incident or None}
+        """
+        caller = user_email or session_id or ip_address or "unknown"
+        key = normalize_question(question)
+        now = time.monotonic()
+        reasons = []
+
+        with self._lock:
+            recent = self._recent[caller]
+            while recent and now - recent[0][0] > max(60, self.repeat_window_seconds):
+                recent.popleft()
+            recent.append((now, key))
+            if sum(1 for t, _ in recent if now - t <= 60) > self.max_questions_per_minute:
+                reasons.append("rapid_fire")
+            if key and sum(1 for t, k in recent if k == key and now - t <= self.repeat_window_seconds) >= self.repeat_threshold:
+                reasons.append("repeated_prompt")
+
+        if len(question) >= self.long_question_length:
+            reasons.append("extreme_length")
+        if any(pattern.search(question) for pattern in self.patterns):
+            reasons.append("jailbreak_pattern")
+
+        if not reasons:
+            return {"action": "allow", "reasons": [], "incident": None}
+        action = "throttle" if THROTTLE_REASONS.intersection(reasons) else "flag"
+        incident = self.record_incident(
+            reasons, action, question=question, user_email=user_email, session_id=session_id, ip_address=ip_address
+        )
+        return {"action": action, "reasons": reasons, "incident": incident}
+
+    def record_incident(self, reasons: List[str], action: str, question: str = "", user_email: Optional[str] = None,
+                        session_id: Optional[str] = None, ip_address: Optional[str] = None) -> Dict:
+        incident = {
+            "timestamp": datetime.now().isoformat(),
+            "reasons": reasons,
+            "action": action,
+            "user_email": user_email or "guest",
+            "session_id": session_id,
+            "ip_address": ip_address,
+            # Enough to recognize the prompt without keeping huge pastes around
+            "question": question[:500],
+        }
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        with self._lock, open(self.path, "a", encoding="utf-8") as f:
+            f.write(json.dumps(incident, ensure_ascii=False) + "\n")
+        return incident
+
+    def list_incidents(self, limit: int = 100, user_email: Optional[str] = None,
+                       since: Optional[str] = None) -> List[Dict]:
+        """Logged incidents, newest first, optionally for one user and/or since an ISO time."""
+        incidents = []
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                for line in f:
+                    try:
+                        incident = json.loads(line)
+                    except json.JSONDecodeError:
+                        continue
+                    if user_email and incident.get("user_email") != user_email:
+                        continue
+                    if since and incident.get("timestamp", "") < since:
+                        continue
+                    incidents.append(incident)
+        except FileNotFoundError:
+            return []
+        incidents.reverse()
+        return incidents[:limit] if limit > 0 else incidents
+
+    def recent_incident_count(self, user_email: str, window_seconds: int) -> int:
+        since = (datetime.now() - timedelta(seconds=window_seconds)).isoformat()
+        return len(self.list_incidents(limit=0, user_email=user_email, since=since))
@@ -0,0 +1,272 @@
+"""
+Academic calendar store for ArchieAI.
+Ingests the university's academic calendar (an ICS feed, or the calendar web page as a
+fallback) into data/academic_calendar.json and answers date questions ("when is fall
+break?", "last day to drop") from it directly instead of leaving dates to the model.
+"""
+import logging
+import os
+import re
+import json
+from datetime import date, datetime, timedelta
+from typing import Dict, List, Optional
+import requests
+from bs4 import BeautifulSoup
+from lib.CampusTools import CampusTool
+
+logger = logging.getLogger(__name__)
+
+CALENDAR_FILE = "academic_calendar.json"
+
+MONTHS = {
+    name: index for index, name in enumerate(
+        ["january", "february", "march", "april", "may", "june", "july",
+         "august", "september", "october", "november", "december"], start=1)
+}
+MONTH_PATTERN = r"(jan|feb|mar|apr|may|jun|jul|aug|sep|sept|oct|nov|dec)[a-z]*\.?"
+# "October 13", "Oct. 13, 2025", "October 13-14", "October 13 - 14, 2025"
+PAGE_DATE_RE = re.compile(
+    rf"\b{MONTH_PATTERN}\s+(\d{{1,2}})(?:\s*[-–]\s*(\d{{1,2}}))?(?:,?\s+(\d{{4}}))?",
+    re.IGNORECASE,
+)
+
+# Words that don't help pick an event
+STOPWORDS = {
+    "when", "what", "is", "are", "the", "a", "an", "of", "for", "to", "do", "does", "on", "in",
+    "day", "date", "dates", "we", "i", "my", "our", "have", "has", "there", "this", "that", "year",
+    "semester", "it", "be", "will", "start", "starts", "happen", "calendar", "academic",
+}
+# Question word -> words that show up in calendar entries for it
+SYNONYMS = {
+    "drop": ["drop", "withdraw", "withdrawal"],
+    "withdraw": ["withdraw", "withdrawal", "drop"],
+    "finals": ["final", "finals", "exam", "exams", "examination"],
+    "exams": ["final", "finals", "exam", "exams", "examination"],
+    "graduation": ["commencement", "graduation"],
+    "classes": ["classes", "class"],
+    "begin": ["begin", "begins", "start", "first"],
+    "end": ["end", "ends", "last"],
+    "vacation": ["break", "recess", "holiday"],
+}
+
+
+def _parse_ics_date(value: str) -> Optional[date]:
+    value = value.strip()
+    try:
+        return datetime.strptime(value[:8], "%Y%m%d").date()
+    except ValueError:
+        return None
+
+
+def parse_ics(text: str) -> List[Dict]:
+    """Parse the VEVENTs out of an ICS file into event dicts."""
+    # Long lines are folded onto continuation lines that start with a space or tab
+    lines = re.sub(r"\r?\n[ \t]", "", text).splitlines()
+
+    events = []
+    current = None
+    for line in lines:
+        if line == "BEGIN:VEVENT":
+            current = {}
+        elif line == "END:VEVENT" and current is not None:
+            if current.get("summary") and current.get("start"):
+                events.append(current)
+            current = None
+        elif current is not None and ":" in line:
+            key, value = line.split(":", 1)
+            name = key.split(";")[0].upper()
+            if name == "SUMMARY":
+                current["summary"] = value.replace("\\,", ",").replace("\\n", " ").strip()
+            elif name == "DTSTART":
+                start = _parse_ics_date(value)
+                current["start"] = start.isoformat() if start else None
+            elif name == "DTEND":
+                end = _parse_ics_date(value)
+                # All-day DTEND is exclusive, so the last day is the day before
+                if end and "VALUE=DATE" in key.upper():
+                    end -= timedelta(days=1)
+                current["end"] = end.isoformat() if end else None
+            elif name == "DESCRIPTION":
+                current["description"] = value.replace("\\,", ",").replace("\\n", " ").strip()
+
+    for event in events:
+        if not event.get("end") or event["end"] < event["start"]:
+            event["end"] = event["start"]
+    return events
+
+
+def parse_calendar_page(html: str, first_year: Optional[int] = None) -> List[Dict]:
+    """
+    Pull dated entries out of the calendar web page (table rows or list items like
+    "Monday, October 13-14  Fall Break").
+
+    Args:
+        html: Page HTML
+        first_year: Year the academic year starts in; months before August are taken as the
+                    following year. Defaults to a "2025-26" style year found on the page.
+    """
+    soup = BeautifulSoup(html, "html.parser")
+    if first_year is None:
+        match = re.search(r"\b(20\d\d)\s*[-–/]\s*(\d{2,4})\b", soup.get_text())
+        first_year = int(match.group(1)) if match else date.today().year
+
+    events = []
+    for row in soup.find_all(["tr", "li", "p"]):
+        text = " ".join(row.get_text(" ").split())
+        match = PAGE_DATE_RE.search(text)
+        if not match:
+            continue
+        month = next(index for name, index in MONTHS.items() if name.startswith(match.group(1).lower()[:3]))
+        year = int(match.group(4)) if match.group(4) else (first_year if month >= 8 else first_year + 1)
+        try:
+            start = date(year, month, int(match.group(2)))
+            end = date(year, month, int(match.group(3))) if match.group(3) else start
+        except ValueError:
+            continue
+
+        # Whatever isn't the date (or a weekday name) is the description of the entry
+        summary = (text[:match.start()] + " " + text[match.end():])
+        summary = re.sub(r"\b(mon|tues|wednes|thurs|fri|satur|sun)day\b,?", "", summary, flags=re.IGNORECASE)
+        summary = " ".join(summary.strip(" -–:|,").split())
+        if summary:
+            events.append({"summary": summary, "start": start.isoformat(), "end": end.isoformat()})
+    return events
+
+
+def _tokens(text: str) -> List[str]:
+    return re.findall(r"[a-z0-9]+", text.lower())
+
+
+class AcademicCalendar:
+    """The structured academic calendar, stored as JSON in the data directory."""
+
+    def __init__(self, data_dir: str = "data", source_url: str = "", refresh_hours: int = 24):
+        self.path = os.path.join(data_dir, CALENDAR_FILE)
+        self.source_url = source_url
+        self.refresh_hours = refresh_hours
+
+    def load(self) -> Optional[Dict]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return None
+
+    def is_stale(self, calendar: Optional[Dict]) -> bool:
+        if not calendar or not calendar.get("fetched_at"):
+            return True
+        fetched_at = datetime.fromisoformat(calendar["fetched_at"])
+        return datetime.now() - fetched_at > timedelta(hours=self.refresh_hours)
+
+    def ingest(self, source_url: Optional[str] = None, content: Optional[str] = None) -> Dict:
+        """
+        Fetch (or take) the calendar, parse it, and save it.
+
+        Args:
+            source_url: ICS feed or calendar page URL (defaults to the configured one)
+            content: Already downloaded ICS/HTML, skips the fetch
+        """
+        source_url = source_url or self.source_url
+        if content is None:
+            resp = requests.get(source_url, timeout=15, headers={"User-Agent": "ArchieAI campus assistant"})
+            resp.raise_for_status()
+            content = resp.text
+
+        if "BEGIN:VCALENDAR" in content:
+            events = parse_ics(content)
+            source_type = "ics"
+        else:
+            events = parse_calendar_page(content)
+            source_type = "page"
+        events.sort(key=lambda e: e["start"])
+
+        calendar = {
+            "source": source_url,
+            "source_type": source_type,
+            "fetched_at": datetime.now().isoformat(),
+            "events": events,
+        }
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        with open(self.path, "w", encoding="utf-8") as f:
+            json.dump(calendar, f, indent=2, ensure_ascii=False)
+        return calendar
+
+    def events(self) -> List[Dict]:
+        """Saved events, re-ingesting first if the store is missing or stale."""
+        calendar = self.load()
+        if self.is_stale(calendar) and self.source_url:
+            try:
+                calendar = self.ingest()
+            except requests.RequestException as e:
+                # Old dates beat no dates
+                logger.warning(f"Could not refresh the academic calendar: {e}")
+        return (calendar or {}).get("events", [])
+
+    def search(self, question: str, limit: int = 5) -> List[Dict]:
+        """Events whose description best matches the question."""
+        wanted = set()
+        for token in _tokens(question):
+            if token in STOPWORDS:
+                continue
+            wanted.update(SYNONYMS.get(token, [token]))
+        if not wanted:
+            return []
+
+        scored = []
+        for event in self.events():
+            overlap = len(wanted & set(_tokens(event["summary"])))
+            if overlap:
+                scored.append((overlap, event))
+        best = max((score for score, _ in scored), default=0)
+        matches = [event for score, event in scored if score == best]
+        return matches[:limit]
+
+    def upcoming(self, limit: int = 5, today: Optional[date] = None) -> List[Dict]:
+        today = (today or date.today()).isoformat()
+        return [event for event in self.events() if event["end"] >= today][:limit]
+
+    def answer(self, question: str) -> str:
+        """A plain-text answer listing the matching dates (or what's coming up if nothing matches)."""
+        matches = self.search(question)
+        if matches:
+            lines = [f"{event['summary']}: {format_range(event)}" for event in matches]
+            return "From the official academic calendar:\n" + "\n".join(lines)
+
+        upcoming = self.upcoming()
+        if not upcoming:
+            return "The academic calendar isn't available right now."
+        lines = [f"{event['summary']}: {format_range(event)}" for event in upcoming]
+        return "No calendar entry matched. Upcoming dates from the academic calendar:\n" + "\n".join(lines)
+
+
+def format_range(event: Dict) -> str:
+    start = date.fromisoformat(event["start"])
+    end = date.fromisoformat(event.get("end") or event["start"])
+    if start == end:
+        return start.strftime("%A, %B %d, %Y")
+    return f"{start.strftime('%A, %B %d, %Y')} through {end.strftime('%A, %B %d, %Y')}"
+
+
+class AcademicCalendarTool(CampusTool):
+    name = "academic_calendar"
+    description = ("Look up official academic calendar dates: term start/end, breaks, holidays, "
+                   "add/drop and withdrawal deadlines, finals, and commencement.")
+    parameters = {
+        "type": "object",
+        "properties": {
+            "query": {"type": "string", "description": "What date to look up, e.g. 'fall break' or 'last day to drop'"}
+        },
+        "required": ["query"],
+    }
+    keywords = [
+        "fall break", "spring break", "thanksgiving", "winter break", "break", "recess", "holiday",
+        "last day", "first day", "classes begin", "classes end", "add/drop", "drop", "withdraw",
+        "withdrawal", "finals", "final exams", "exam week", "commencement", "graduation",
+        "registration", "semester start", "semester end", "academic calendar",
+    ]
+
+    def __init__(self, calendar: AcademicCalendar):
+        self.calendar = calendar
+
+    def run(self, query: str = "", **kwargs) -> str:
+        return self.calendar.answer(query)
@@ -0,0 +1,85 @@
+"""
+"Export all my data" for ArchieAI accounts.
+Builds a zip of everything stored about a user (profile, chat sessions, analytics
+records) and streams it out chunk by chunk, so big histories never sit in memory and
+the download starts right away.
+"""
+import io
+import json
+import zipfile
+from datetime import datetime
+from typing import Iterator
+
+README_TEXT = """ArchieAI data export for {email}
+Generated {generated_at}
+
+profile.json       Your account: email, sign-up date and device, preferences, session IDs
+sessions/*.json    Every chat session with all of its messages
+analytics.jsonl    One JSON record per question you asked (answer, category, timing, request ID)
+memories.json      Facts Archie remembers about you (if you turned memory on)
+
+Your password is stored only as a one-way hash and is not included.
+"""
+
+
+class _ChunkWriter(io.RawIOBase):
+    """Write-only, non-seekable file that hands back whatever was written since the last drain."""
+
+    def __init__(self):
+        self._chunks = []
+
+    def writable(self) -> bool:
+        return True
+
+    def write(self, data) -> int:
+        self._chunks.append(bytes(data))
+        return len(data)
+
+    def drain(self) -> bytes:
+        data = b"".join(self._chunks)
+        self._chunks = []
+        return data
+
+
+def export_account(session_manager, data_collector, email: str, user_memory=None) -> Iterator[bytes]:
+    """
+    Stream a zip archive of a user's data.
+
+    Args:
+        session_manager: SessionManager holding the account and sessions
+        data_collector: DataCollector holding the analytics log
+        email: The account to export
+        user_memory: UserMemory holding remembered facts, if memory is set up
+
+    Yields:
+        Chunks of the zip file
+    """
+    user = session_manager.get_user(email) or {}
+    out = _ChunkWriter()
+    # zipfile notices the output can't seek and writes data descriptors instead
+    with zipfile.ZipFile(out, "w", compression=zipfile.ZIP_DEFLATED) as archive:
+        archive.writestr("README.txt", README_TEXT.format(email=email, generated_at=datetime.now().isoformat()))
+
+        profile = {key: value for key, value in user.items() if key != "password_hash"}
+        profile["email"] = email
+        archive.writestr("profile.json", json.dumps(profile, ensure_ascii=False, indent=2))
+        yield out.drain()
+
+        for session_id in user.get("sessions", []):
+            session_data = session_manager.get_session(session_id)
+            if session_data is None:
+                continue
+            archive.writestr(f"sessions/{session_id}.json", json.dumps(session_data, ensure_ascii=False, indent=2))
+            yield out.drain()
+
+        if user_memory is not None:
+            archive.writestr("memories.json", json.dumps(user_memory.list_memories(email), ensure_ascii=False, indent=2))
+            yield out.drain()
+
+        with archive.open("analytics.jsonl", "w") as f:
+            for interaction in data_collector.iter_interactions(user_email=email):
+                f.write((json.dumps(interaction, ensure_ascii=False) + "\n").encode("utf-8"))
+                chunk = out.drain()
+                if chunk:
+                    yield chunk
+    yield out.drain()
@@ -0,0 +1,226 @@
+"""
+Precomputed analytics rollups for ArchieAI.
+Every dashboard load used to aggregate the whole interaction log. A scheduled job now
+materializes each finished day and ISO week into data/reports/ (a JSON file per period
+plus daily.csv / weekly.csv tables for spreadsheets), along with summary.json, the
+all-time stats up to last midnight. The dashboard stats are that summary merged with
+today's interactions, so only today has to be aggregated on each request.
+"""
+import os
+import csv
+import json
+import threading
+from collections import Counter, defaultdict
+from datetime import datetime, timedelta
+from typing import Dict, List, Optional
+
+REPORTS_DIR = "reports"
+SUMMARY_FILE = "summary.json"
+PERIODS = ("daily", "weekly")
+CSV_FIELDS = [
+    "period", "start", "end", "total_questions", "unanswered_questions", "faq_answers", "fallback_answers",
+    "interrupted_answers", "avg_generation_time_seconds", "sessions", "users", "avg_sentiment",
+]
+
+# One report run at a time (the scheduler thread and the admin "run now" endpoint)
+_lock = threading.Lock()
+
+
+def _write_json(path: str, data):
+    tmp_path = path + ".tmp"
+    with open(tmp_path, "w", encoding="utf-8") as f:
+        json.dump(data, f, ensure_ascii=False, indent=2)
+    os.replace(tmp_path, path)
+
+
+def period_key(day: str, period: str) -> str:
+    """The report a date (YYYY-MM-DD) belongs to: the date itself, or its ISO week (2026-W42)."""
+    if period == "daily":
+        return day
+    year, week, _ = datetime.fromisoformat(day).isocalendar()
+    return f"{year}-W{week:02d}"
+
+
+def period_bounds(key: str, period: str) -> tuple:
+    """First and last day (YYYY-MM-DD) covered by a report."""
+    if period == "daily":
+        return key, key
+    year, week = key.split("-W")
+    monday = datetime.fromisocalendar(int(year), int(week), 1)
+    return monday.date().isoformat(), (monday + timedelta(days=6)).date().isoformat()
+
+
+def merge_stats(base: Dict, today: Dict) -> Dict:
+    """
+    Combine the all-time stats up to midnight with today's. Counts add up and averages are
+    weighted by question count. Engagement (medians, return visits) can't be added, so it's
+    the one from the summary, as of the last rollup.
+    """
+    base_total, today_total = base["total_questions"], today["total_questions"]
+    total = base_total + today_total
+
+    def weighted(a, b):
+        return round((a * base_total + b * today_total) / total, 2) if total else 0
+
+    merged = {
+        "total_questions": total,
+        "avg_generation_time_seconds": weighted(base["avg_generation_time_seconds"], today["avg_generation_time_seconds"]),
+    }
+    for key in ("unanswered_questions", "faq_answers", "fallback_answers", "interrupted_answers"):
+        merged[key] = base.get(key, 0) + today.get(key, 0)
+    for key in ("models", "categories"):
+        merged[key] = dict((Counter(base.get(key, {})) + Counter(today.get(key, {}))).most_common())
+    merged["questions_per_day"] = dict(sorted({**base["questions_per_day"], **today["questions_per_day"]}.items()))
+    # Summaries written before locations were logged don't have them
+    base_locations, today_locations = base.get("locations") or {}, today.get("locations") or {}
+    merged["locations"] = {
+        "on_campus": base_locations.get("on_campus", 0) + today_locations.get("on_campus", 0),
+        "off_campus": base_locations.get("off_campus", 0) + today_locations.get("off_campus", 0),
+        "countries": dict((Counter(base_locations.get("countries", {}))
+                           + Counter(today_locations.get("countries", {}))).most_common()),
+    }
+    merged["engagement"] = base["engagement"]
+
+    if base.get("sentiment") is None or today.get("sentiment") is None:
+        merged["sentiment"] = today.get("sentiment") or base.get("sentiment")
+    else:
+        labels = Counter(base["sentiment"]["labels"]) + Counter(today["sentiment"]["labels"])
+        scored = sum(labels.values())
+        base_scored = sum(base["sentiment"]["labels"].values())
+        today_scored = sum(today["sentiment"]["labels"].values())
+        merged["sentiment"] = {
+            "average": round((base["sentiment"]["average"] * base_scored + today["sentiment"]["average"] * today_scored)
+                             / scored, 3) if scored else 0,
+            "labels": {label: labels[label] for label in ("positive", "neutral", "negative")},
+            "per_day": dict(sorted({**base["sentiment"]["per_day"], **today["sentiment"]["per_day"]}.items())),
+            "negative_rate": round(labels["negative"] / scored, 3) if scored else 0,
+            "frustrated_sessions": base["sentiment"]["frustrated_sessions"] + today["sentiment"]["frustrated_sessions"],
+        }
+    return merged
+
+
+class AnalyticsReports:
+    """Writes and reads the daily/weekly rollups and the all-time summary."""
+
+    def __init__(self, data_collector, data_dir: str = "data", write_csv: bool = True):
+        self.data_collector = data_collector
+        self.directory = os.path.join(data_dir, REPORTS_DIR)
+        self.summary_path = os.path.join(self.directory, SUMMARY_FILE)
+        self.write_csv = write_csv
+
+    def _path(self, period: str, key: str) -> str:
+        return os.path.join(self.directory, period, f"{key}.json")
+
+    def run(self, now: Optional[datetime] = None) -> Dict:
+        """
+        Materialize every finished day and week that doesn't have a report yet, and refresh
+        summary.json. Periods that have ended don't change, so existing reports are kept.
+
+        Returns:
+            {"daily": [keys written], "weekly": [keys written], "summary_as_of": midnight ISO}
+        """
+        now = now or datetime.now()
+        midnight = now.replace(hour=0, minute=0, second=0, microsecond=0)
+        today = midnight.date().isoformat()
+        current_week = period_key(today, "weekly")
+
+        with _lock:
+            # One pass over the log for everything before today
+            before_today = [i for i in self.data_collector.iter_interactions(end=midnight.isoformat())
+                            if i.get("timestamp", "")[:10] < today]
+            groups = {period: defaultdict(list) for period in PERIODS}
+            for interaction in before_today:
+                day = interaction.get("timestamp", "")[:10]
+                if not day:
+                    continue
+                for period in PERIODS:
+                    groups[period][period_key(day, period)].append(interaction)
+
+            written = {period: [] for period in PERIODS}
+            for period in PERIODS:
+                os.makedirs(os.path.join(self.directory, period), exist_ok=True)
+                for key, interactions in sorted(groups[period].items()):
+                    if (period == "weekly" and key == current_week) or os.path.exists(self._path(period, key)):
+                        continue
+                    start, end = period_bounds(key, period)
+                    _write_json(self._path(period, key), {
+                        "period": key,
+                        "start": start,
+                        "end": end,
+                        "generated_at": now.isoformat(),
+                        "stats": self.data_collector.get_aggregate_stats(interactions),
+                    })
+                    written[period].append(key)
+                if self.write_csv and written[period]:
+                    self._write_csv(period)
+
+            _write_json(self.summary_path, {
+                "as_of": midnight.isoformat(),
+                "generated_at": now.isoformat(),
+                "stats": self.data_collector.get_aggregate_stats(before_today),
+            })
+        return {**written, "summary_as_of": midnight.isoformat()}
+
+    def _write_csv(self, period: str):
+        """Rewrite daily.csv / weekly.csv from the period reports, one row per period."""
+        path = os.path.join(self.directory, f"{period}.csv")
+        tmp_path = path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8", newline="") as f:
+            writer = csv.DictWriter(f, fieldnames=CSV_FIELDS)
+            writer.writeheader()
+            for report in reversed(self.list_reports(period, limit=0)):
+                stats = report["stats"]
+                writer.writerow({
+                    "period": report["period"],
+                    "start": report["start"],
+                    "end": report["end"],
+                    **{key: stats.get(key, 0) for key in CSV_FIELDS[3:9]},
+                    "sessions": stats["engagement"]["sessions"],
+                    "users": stats["engagement"]["users"],
+                    "avg_sentiment": stats["sentiment"]["average"] if stats.get("sentiment") else "",
+                })
+        os.replace(tmp_path, path)
+
+    def list_reports(self, period: str = "daily", limit: int = 30) -> List[Dict]:
+        """Materialized reports for a period type, newest first (limit 0 = all)."""
+        directory = os.path.join(self.directory, period)
+        try:
+            names = sorted((n for n in os.listdir(directory) if n.endswith(".json")), reverse=True)
+        except FileNotFoundError:
+            return []
+        reports = []
+        for name in names[:limit] if limit > 0 else names:
+            try:
+                with open(os.path.join(directory, name), "r", encoding="utf-8") as f:
+                    reports.append(json.load(f))
+            except (OSError, json.JSONDecodeError):
+                continue
+        return reports
+
+    def load_summary(self) -> Optional[Dict]:
+        try:
+            with open(self.summary_path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return None
+
+    def is_due(self, now: Optional[datetime] = None) -> bool:
+        """True once the summary is from before today's midnight (or missing)."""
+        now = now or datetime.now()
+        summary = self.load_summary()
+        midnight = now.replace(hour=0, minute=0, second=0, microsecond=0)
+        return summary is None or summary.get("as_of", "") < midnight.isoformat()
+
+    def current_stats(self, now: Optional[datetime] = None) -> Dict:
+        """
+        All-time stats for the dashboard: the summary up to midnight merged with today's
+        interactions. Falls back to aggregating the whole log when there's no summary for today yet.
+        """
+        now = now or datetime.now()
+        summary = self.load_summary()
+        midnight = now.replace(hour=0, minute=0, second=0, microsecond=0)
+        if summary is None or summary.get("as_of") != midnight.isoformat():
+            return dict(self.data_collector.get_aggregate_stats(), rollup_as_of=None)
+        today = list(self.data_collector.iter_interactions(start=midnight.isoformat()))
+        return dict(merge_stats(summary["stats"], self.data_collector.get_aggregate_stats(today)),
+                    rollup_as_of=summary["as_of"])
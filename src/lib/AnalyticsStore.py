@@ -0,0 +1,299 @@
+"""
+Storage backends for the analytics interaction log (lib/DataCollector.py).
+The original log is analytics.json, one JSON array that is read and written back whole for
+every question, which gets slower with every interaction and keeps the entire history in
+memory on each write. [analytics] backend picks where interactions go:
+
+- "json" (the default): analytics.json, as before
+- "sqlite": analytics.db, an interactions table indexed on timestamp, session_id,
+  request_id, and user_email. Logging is one INSERT; date-range and per-user reads use the
+  indexes. Each row keeps the full record as JSON next to the indexed columns, so fields
+  added to interactions later don't need a schema change
+
+Switching to sqlite imports an existing analytics.json on the next start (or with
+`python src/app.py migrate`) and renames it to analytics.json.migrated.
+"""
+import logging
+import os
+import json
+import sqlite3
+import threading
+from contextlib import closing
+from typing import Dict, Iterator, List, Optional
+
+from lib.InstanceLock import ReadOnlyStorage
+
+logger = logging.getLogger(__name__)
+
+ANALYTICS_BACKENDS = ("json", "sqlite")
+JSON_FILE = "analytics.json"
+SQLITE_FILE = "analytics.db"
+
+
+def _end_bound(end: Optional[str]) -> Optional[str]:
+    # A bare date as the upper bound should cover the whole day
+    if end and len(end) == 10:
+        return end + "T23:59:59.999999"
+    return end
+
+
+class AnalyticsStore:
+    """Base class for where logged interactions are kept."""
+
+    def __init__(self, data_dir: str, read_only: bool = False):
+        self.data_dir = data_dir
+        # A read-only replica reads the primary's log and never writes it (lib/InstanceLock.py)
+        self.read_only = read_only
+
+    def _check_writable(self):
+        if self.read_only:
+            raise ReadOnlyStorage("analytics")
+
+    def append(self, interaction: Dict):
+        raise NotImplementedError
+
+    def set_feedback(self, request_id: str, rating: Optional[str]) -> bool:
+        """Rate the latest interaction logged for a request; False if there's none."""
+        raise NotImplementedError
+
+    def iter(self, start: Optional[str] = None, end: Optional[str] = None,
+             user_email: Optional[str] = None) -> Iterator[Dict]:
+        """Interactions in the order they were logged, optionally filtered (ISO dates, end inclusive)."""
+        raise NotImplementedError
+
+    def load(self) -> List[Dict]:
+        return list(self.iter())
+
+    def recent(self, limit: int) -> List[Dict]:
+        """The last limit interactions, oldest first."""
+        return self.load()[-limit:] if limit > 0 else []
+
+    def close(self):
+        """Finish any write in progress and refuse new ones (the server is shutting down)."""
+        self.read_only = True
+
+
+class JsonAnalyticsStore(AnalyticsStore):
+    """Everything in analytics.json, rewritten on every change."""
+
+    def __init__(self, data_dir: str, read_only: bool = False):
+        super().__init__(data_dir, read_only)
+        self.path = os.path.join(data_dir, JSON_FILE)
+        # Logging reads the whole file and writes it back, so concurrent answers (several finishing at
+        # once off a shared generation, see lib/Inflight.py) would otherwise drop each other's records
+        self._write_lock = threading.Lock()
+        if not read_only and not os.path.exists(self.path):
+            self._write([])
+
+    def close(self):
+        # Waits for a rewrite that's halfway done
+        with self._write_lock:
+            super().close()
+
+    def _write(self, data: List[Dict]):
+        """Replace the log; written to a temp file first so readers never see half of it."""
+        self._check_writable()
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(data, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def load(self) -> List[Dict]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return []
+
+    def append(self, interaction: Dict):
+        with self._write_lock:
+            data = self.load()
+            data.append(interaction)
+            self._write(data)
+
+    def set_feedback(self, request_id: str, rating: Optional[str]) -> bool:
+        with self._write_lock:
+            data = self.load()
+            for interaction in reversed(data):
+                if interaction.get("request_id") == request_id:
+                    interaction["feedback"] = rating
+                    break
+            else:
+                return False
+            self._write(data)
+        return True
+
+    def iter(self, start: Optional[str] = None, end: Optional[str] = None,
+             user_email: Optional[str] = None, chunk_size: int = 65536) -> Iterator[Dict]:
+        """The file is decoded incrementally, chunk_size characters at a time, so large logs never sit fully in memory."""
+        end = _end_bound(end)
+        decoder = json.JSONDecoder()
+        try:
+            f = open(self.path, "r", encoding="utf-8")
+        except FileNotFoundError:
+            return
+
+        with f:
+            buffer = ""
+            started = False
+            eof = False
+            while True:
+                # Skip whitespace and the array punctuation between records
+                buffer = buffer.lstrip()
+                if not started and buffer.startswith("["):
+                    buffer = buffer[1:].lstrip()
+                    started = True
+                if buffer.startswith(","):
+                    buffer = buffer[1:].lstrip()
+                if buffer.startswith("]"):
+                    return
+
+                try:
+                    interaction, index = decoder.raw_decode(buffer)
+                except json.JSONDecodeError:
+                    if eof:
+                        if buffer.strip():
+                            logger.warning("analytics.json ended with malformed data")
+                        return
+                    chunk = f.read(chunk_size)
+                    if not chunk:
+                        eof = True
+                    buffer += chunk
+                    continue
+
+                buffer = buffer[index:]
+
+                timestamp = interaction.get("timestamp", "")
+                if start and timestamp < start:
+                    continue
+                if end and timestamp > end:
+                    continue
+                if user_email and interaction.get("user_email") != user_email:
+                    continue
+                yield interaction
+
+
+class SqliteAnalyticsStore(AnalyticsStore):
+    """Interactions as rows of analytics.db."""
+
+    SCHEMA = """
+        CREATE TABLE IF NOT EXISTS interactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            request_id TEXT,
+            session_id TEXT,
+            user_email TEXT,
+            data TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_interactions_timestamp ON interactions (timestamp);
+        CREATE INDEX IF NOT EXISTS idx_interactions_session_id ON interactions (session_id);
+        CREATE INDEX IF NOT EXISTS idx_interactions_request_id ON interactions (request_id);
+        CREATE INDEX IF NOT EXISTS idx_interactions_user_email ON interactions (user_email);
+    """
+
+    def __init__(self, data_dir: str, read_only: bool = False, import_legacy: bool = True):
+        super().__init__(data_dir, read_only)
+        self.path = os.path.join(data_dir, SQLITE_FILE)
+        if read_only:
+            return
+        with closing(self._connect()) as db:
+            # WAL lets the dashboards read while an answer is being logged
+            db.execute("PRAGMA journal_mode=WAL")
+            db.executescript(self.SCHEMA)
+        if import_legacy:
+            imported = self.import_legacy_json()
+            if imported:
+                logger.info(f"Imported {imported} interactions from {JSON_FILE} into {SQLITE_FILE}")
+
+    def _connect(self) -> sqlite3.Connection:
+        # One connection per call: requests log from many threads, and sqlite3 connections can't be shared
+        if self.read_only:
+            return sqlite3.connect(f"file:{self.path}?mode=ro", uri=True, timeout=30)
+        return sqlite3.connect(self.path, timeout=30)
+
+    @staticmethod
+    def _row(interaction: Dict) -> tuple:
+        return (interaction.get("timestamp", ""), interaction.get("request_id"), interaction.get("session_id"),
+                interaction.get("user_email"), json.dumps(interaction, ensure_ascii=False))
+
+    def append(self, interaction: Dict):
+        self._check_writable()
+        with closing(self._connect()) as db, db:
+            db.execute("INSERT INTO interactions (timestamp, request_id, session_id, user_email, data) VALUES (?, ?, ?, ?, ?)",
+                       self._row(interaction))
+
+    def set_feedback(self, request_id: str, rating: Optional[str]) -> bool:
+        self._check_writable()
+        with closing(self._connect()) as db, db:
+            row = db.execute("SELECT id, data FROM interactions WHERE request_id = ? ORDER BY id DESC LIMIT 1",
+                             (request_id,)).fetchone()
+            if row is None:
+                return False
+            interaction = json.loads(row[1])
+            interaction["feedback"] = rating
+            db.execute("UPDATE interactions SET data = ? WHERE id = ?", (json.dumps(interaction, ensure_ascii=False), row[0]))
+        return True
+
+    def iter(self, start: Optional[str] = None, end: Optional[str] = None,
+             user_email: Optional[str] = None) -> Iterator[Dict]:
+        if self.read_only and not os.path.exists(self.path):
+            return
+        conditions, params = [], []
+        if start:
+            conditions.append("timestamp >= ?")
+            params.append(start)
+        if end:
+            conditions.append("timestamp <= ?")
+            params.append(_end_bound(end))
+        if user_email:
+            conditions.append("user_email = ?")
+            params.append(user_email)
+        where = f" WHERE {' AND '.join(conditions)}" if conditions else ""
+        with closing(self._connect()) as db:
+            # Rows are fetched as they're iterated, not all at once
+            for (data,) in db.execute(f"SELECT data FROM interactions{where} ORDER BY id", params):
+                yield json.loads(data)
+
+    def recent(self, limit: int) -> List[Dict]:
+        if limit <= 0 or (self.read_only and not os.path.exists(self.path)):
+            return []
+        with closing(self._connect()) as db:
+            rows = db.execute("SELECT data FROM interactions ORDER BY id DESC LIMIT ?", (limit,)).fetchall()
+        return [json.loads(data) for (data,) in reversed(rows)]
+
+    def count(self) -> int:
+        with closing(self._connect()) as db:
+            return db.execute("SELECT COUNT(*) FROM interactions").fetchone()[0]
+
+    def import_legacy_json(self) -> int:
+        """
+        Copy analytics.json into the table and rename it to analytics.json.migrated. Only into an
+        empty table, so an import that's already happened isn't repeated. Returns the number imported.
+        """
+        legacy = JsonAnalyticsStore(self.data_dir, read_only=True)
+        if not os.path.exists(legacy.path):
+            return 0
+        if self.count():
+            logger.warning(f"Not importing {JSON_FILE}, {SQLITE_FILE} already has interactions")
+            return 0
+        imported = 0
+        with closing(self._connect()) as db, db:
+            batch = []
+            for interaction in legacy.iter():
+                batch.append(self._row(interaction))
+                if len(batch) >= 1000:
+                    db.executemany("INSERT INTO interactions (timestamp, request_id, session_id, user_email, data) VALUES (?, ?, ?, ?, ?)", batch)
+                    imported += len(batch)
+                    batch = []
+            db.executemany("INSERT INTO interactions (timestamp, request_id, session_id, user_email, data) VALUES (?, ?, ?, ?, ?)", batch)
+            imported += len(batch)
+        os.replace(legacy.path, legacy.path + ".migrated")
+        return imported
+
+
+def open_store(backend: str, data_dir: str, read_only: bool = False) -> AnalyticsStore:
+    if backend not in ANALYTICS_BACKENDS:
+        raise ValueError(f"Unknown analytics backend {backend}; expected one of {', '.join(ANALYTICS_BACKENDS)}")
+    if backend == "sqlite":
+        return SqliteAnalyticsStore(data_dir, read_only=read_only)
+    return JsonAnalyticsStore(data_dir, read_only=read_only)
@@ -0,0 +1,104 @@
+"""
+Campus announcements for ArchieAI.
+Admin-written notices ("registration opens Monday") stored in data/announcements.json.
+Active ones are shown in the chat UI and, unless turned off per announcement, added to
+the system prompt so Archie knows about them without a re-crawl.
+"""
+import os
+import json
+import uuid
+import threading
+from datetime import datetime
+from typing import Dict, List, Optional
+
+ANNOUNCEMENTS_FILE = "announcements.json"
+# Keeps a pile of old notices from crowding out the rest of the prompt
+MAX_PROMPT_ANNOUNCEMENTS = 10
+
+
+class AnnouncementStore:
+    """CRUD for announcements plus the active ones for a point in time."""
+
+    def __init__(self, data_dir: str = "data"):
+        self.path = os.path.join(data_dir, ANNOUNCEMENTS_FILE)
+        self._lock = threading.Lock()
+
+    def _load(self) -> List[Dict]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return []
+
+    def _save(self, announcements: List[Dict]):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(announcements, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def list_announcements(self) -> List[Dict]:
+        """All announcements, newest first."""
+        return sorted(self._load(), key=lambda a: a["created_at"], reverse=True)
+
+    def get(self, announcement_id: str) -> Optional[Dict]:
+        return next((a for a in self._load() if a["announcement_id"] == announcement_id), None)
+
+    def create(self, title: str, body: str, starts_at: Optional[str] = None, ends_at: Optional[str] = None,
+               in_prompt: bool = True, created_by: Optional[str] = None) -> Dict:
+        now = datetime.now().isoformat()
+        announcement = {
+            "announcement_id": uuid.uuid4().hex,
+            "title": title,
+            "body": body,
+            "starts_at": starts_at or now,
+            "ends_at": ends_at,
+            "in_prompt": in_prompt,
+            "created_by": created_by,
+            "created_at": now,
+            "updated_at": now,
+        }
+        with self._lock:
+            announcements = self._load()
+            announcements.append(announcement)
+            self._save(announcements)
+        return announcement
+
+    def update(self, announcement_id: str, updates: Dict) -> Optional[Dict]:
+        """Update the given fields. Returns None if there's no such announcement."""
+        with self._lock:
+            announcements = self._load()
+            announcement = next((a for a in announcements if a["announcement_id"] == announcement_id), None)
+            if announcement is None:
+                return None
+            for key in ("title", "body", "starts_at", "ends_at", "in_prompt"):
+                if key in updates:
+                    announcement[key] = updates[key]
+            announcement["updated_at"] = datetime.now().isoformat()
+            self._save(announcements)
+        return announcement
+
+    def delete(self, announcement_id: str) -> bool:
+        with self._lock:
+            announcements = self._load()
+            remaining = [a for a in announcements if a["announcement_id"] != announcement_id]
+            if len(remaining) == len(announcements):
+                return False
+            self._save(remaining)
+        return True
+
+    def active(self, now: Optional[datetime] = None) -> List[Dict]:
+        """Announcements that have started and not yet ended, newest first."""
+        now = (now or datetime.now()).isoformat()
+        return [
+            a for a in self.list_announcements()
+            if a["starts_at"] <= now and (not a.get("ends_at") or a["ends_at"] > now)
+        ]
+
+    def prompt_section(self) -> str:
+        """Active announcements formatted for the system prompt (empty if there are none)."""
+        announcements = [a for a in self.active() if a.get("in_prompt", True)][:MAX_PROMPT_ANNOUNCEMENTS]
+        if not announcements:
+            return ""
+        lines = [f"- {a['title']} (posted {a['starts_at'][:10]}): {a['body']}" for a in announcements]
+        return "\n\nCurrent official campus announcements (mention them when relevant):\n" + "\n".join(lines)
@@ -0,0 +1,38 @@
+"""
+Answer length and style for ArchieAI.
+A kiosk display needs two sentences; someone drafting an email to the registrar wants the
+whole picture. A style picked per question or as a session's default adds a directive to
+the system prompt and caps how many tokens the model may generate (Ollama's num_predict).
+The cap is a backstop for the directive, so it's generous: thinking models spend part of
+it before the answer starts.
+"""
+from typing import Dict, Optional
+
+STYLES: Dict[str, Dict] = {
+    "concise": {
+        "directive": "Answer in at most two or three short sentences. Give only the key fact, date, or link; no background, no lists.",
+        "num_predict": 512,
+    },
+    "detailed": {
+        "directive": "Give a thorough answer: explain the relevant details, exceptions, and next steps, and mention where to find more information.",
+        "num_predict": 4096,
+    },
+    "bullet": {
+        "directive": "Answer as a short list of bullet points (one fact or step per bullet, at most seven), with no introduction or closing paragraph.",
+        "num_predict": 1024,
+    },
+}
+
+
+def style_directive(style: Optional[str]) -> str:
+    """The system prompt addition for a style ("" for none or an unknown one)."""
+    if style not in STYLES:
+        return ""
+    return f"\n\nAnswer style: {STYLES[style]['directive']}"
+
+
+def style_options(style: Optional[str]) -> Dict:
+    """Ollama options for a style ({} for none or an unknown one)."""
+    if style not in STYLES:
+        return {}
+    return {"num_predict": STYLES[style]["num_predict"]}
@@ -0,0 +1,49 @@
+"""
+Ollama reachability for ArchieAI.
+Answers check the backend before generating; while it's down, app.py falls back to curated
+FAQ answers or a "temporarily limited" message instead of streaming a connection error.
+The result is cached so a healthy backend costs one request every check_interval seconds.
+"""
+import logging
+import time
+import threading
+from typing import Optional
+import requests
+
+logger = logging.getLogger(__name__)
+
+
+class BackendHealth:
+    """Cached up/down state of the Ollama backend."""
+
+    def __init__(self, host: str, check_interval: int = 30, timeout: float = 2.0):
+        self.url = f"{host.rstrip('/')}/api/version"
+        self.check_interval = check_interval
+        self.timeout = timeout
+        self._lock = threading.Lock()
+        self._up: Optional[bool] = None
+        self._checked_at = 0.0
+
+    def check(self) -> bool:
+        """Ask the backend now and cache the answer."""
+        try:
+            up = requests.get(self.url, timeout=self.timeout).status_code < 500
+        except requests.RequestException:
+            up = False
+        with self._lock:
+            if up != self._up and self._up is not None:
+                if up:
+                    logger.info(f"Ollama backend is back up ({self.url})")
+                else:
+                    logger.warning(f"Ollama backend is unreachable ({self.url})")
+            self._up = up
+            self._checked_at = time.monotonic()
+        return up
+
+    def is_up(self) -> bool:
+        """The cached state, re-checked once it's older than check_interval."""
+        with self._lock:
+            fresh = self._up is not None and time.monotonic() - self._checked_at < self.check_interval
+            if fresh:
+                return self._up
+        return self.check()
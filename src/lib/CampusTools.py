@@ -0,0 +1,188 @@
+"""
+Pluggable tools for live campus data (dining hours, shuttle schedule, library hours, IT status,
+academic calendar).
+Each tool describes itself for Ollama function calling and lists keywords that trigger it
+directly, so Archie can look things up instead of guessing. New data sources subclass
+CampusTool and get registered in build_registry().
+"""
+import logging
+import os
+import json
+import time
+import re
+import threading
+from typing import Dict, List, Optional
+import requests
+from bs4 import BeautifulSoup
+
+logger = logging.getLogger(__name__)
+
+# Tool output is trimmed to this many characters before it goes into the prompt
+MAX_RESULT_CHARS = 4000
+
+
+class CampusTool:
+    """Base class for a campus data source the model can call."""
+    name = ""
+    description = ""
+    # JSON schema for the arguments; most campus tools don't take any
+    parameters: Dict = {"type": "object", "properties": {}, "required": []}
+    # Lowercase words/phrases that trigger the tool without the model asking for it
+    keywords: List[str] = []
+
+    def run(self, **kwargs) -> str:
+        raise NotImplementedError
+
+    def schema(self) -> Dict:
+        """Tool definition in the format Ollama's chat API expects."""
+        return {
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            },
+        }
+
+    def matches(self, question: str) -> bool:
+        text = question.lower()
+        return any(re.search(rf"\b{re.escape(keyword)}\b", text) for keyword in self.keywords)
+
+
+class WebPageTool(CampusTool):
+    """A tool that answers with the text of a campus web page, cached for a few minutes."""
+
+    def __init__(self, url: str, cache_seconds: int = 300, timeout: int = 10):
+        self.url = url
+        self.cache_seconds = cache_seconds
+        self.timeout = timeout
+        self._cache = None
+        self._lock = threading.Lock()
+
+    def fetch(self) -> str:
+        resp = requests.get(self.url, timeout=self.timeout, headers={"User-Agent": "ArchieAI campus assistant"})
+        resp.raise_for_status()
+        soup = BeautifulSoup(resp.text, "html.parser")
+        return " ".join(soup.get_text().split())
+
+    def _format(self, fetched_at: float, text: str) -> str:
+        """The page text, labeled with how old it is so cached answers aren't passed off as fresh."""
+        minutes = int((time.time() - fetched_at) // 60)
+        age = "just now" if minutes < 1 else f"{minutes} minute{'s' if minutes != 1 else ''} ago"
+        return f"From {self.url} (fetched {age}):\n{text}"
+
+    def run(self, **kwargs) -> str:
+        with self._lock:
+            if self._cache is not None and time.time() - self._cache[0] < self.cache_seconds:
+                return self._format(*self._cache)
+        try:
+            text = self.fetch()[:MAX_RESULT_CHARS]
+        except requests.RequestException as e:
+            logger.warning(f"{self.name} could not fetch {self.url}: {e}")
+            return f"{self.name} is unavailable right now. Point the user to {self.url}"
+        fetched_at = time.time()
+        with self._lock:
+            self._cache = (fetched_at, text)
+        return self._format(fetched_at, text)
+
+
+class DiningHoursTool(WebPageTool):
+    name = "dining_hours"
+    description = "Get current dining hall and cafe hours and menus on campus."
+    keywords = ["dining", "dining hall", "cafeteria", "cafe", "meal", "breakfast", "lunch", "dinner", "food"]
+
+
+class LibraryHoursTool(WebPageTool):
+    name = "library_hours"
+    description = "Get the Landman Library's opening hours and services."
+    keywords = ["library", "landman", "study room"]
+
+
+class ITStatusTool(WebPageTool):
+    name = "it_status"
+    description = "Get IT service status and help desk information (wifi, email, Canvas, accounts)."
+    keywords = ["wifi", "wi-fi", "internet", "help desk", "canvas", "password reset", "it status", "outage"]
+
+
+class ShuttleScheduleTool(CampusTool):
+    """Answers from a JSON schedule file that staff keep in the data directory."""
+    name = "shuttle_schedule"
+    description = "Get the campus shuttle schedule (routes, stops, and departure times)."
+    keywords = ["shuttle", "bus", "septa", "train station", "glenside station"]
+
+    def __init__(self, path: str):
+        self.path = path
+
+    def run(self, **kwargs) -> str:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                schedule = json.load(f)
+        except FileNotFoundError:
+            return "No shuttle schedule has been published to Archie yet."
+        except json.JSONDecodeError as e:
+            logger.warning(f"{self.path} is not valid JSON: {e}")
+            return "The shuttle schedule is unavailable right now."
+        return json.dumps(schedule, ensure_ascii=False)[:MAX_RESULT_CHARS]
+
+
+class ToolRegistry:
+    """The set of enabled campus tools, looked up by name."""
+
+    def __init__(self, tools: Optional[List[CampusTool]] = None, keyword_triggers: bool = True):
+        self.tools: Dict[str, CampusTool] = {}
+        self.keyword_triggers = keyword_triggers
+        for tool in tools or []:
+            self.register(tool)
+
+    def register(self, tool: CampusTool):
+        if not tool.name:
+            raise ValueError("Campus tools need a name")
+        self.tools[tool.name] = tool
+
+    def get(self, name: str) -> Optional[CampusTool]:
+        return self.tools.get(name)
+
+    def schemas(self) -> List[Dict]:
+        """Tool definitions to pass to the chat API."""
+        return [tool.schema() for tool in self.tools.values()]
+
+    def callables(self) -> Dict:
+        """name -> function, in the shape the function-calling loop uses for available_tools."""
+        return {name: tool.run for name, tool in self.tools.items()}
+
+    def triggered_by(self, question: str) -> List[CampusTool]:
+        """Tools whose keywords appear in the question (empty if keyword triggers are off)."""
+        if not self.keyword_triggers:
+            return []
+        return [tool for tool in self.tools.values() if tool.matches(question)]
+
+
+def build_registry(config=None, data_dir: str = "data") -> ToolRegistry:
+    """Create the registry of built-in tools, limited to the ones enabled in config."""
+    if config is None:
+        from lib.Config import ToolsConfig
+        tools_config = ToolsConfig()
+    else:
+        tools_config = config.tools
+        data_dir = config.data_dir
+
+    # Imported here because AcademicCalendar builds on CampusTool
+    from lib.AcademicCalendar import AcademicCalendar, AcademicCalendarTool
+
+    available = {
+        "dining_hours": lambda: DiningHoursTool(tools_config.dining_url, tools_config.cache_seconds),
+        "library_hours": lambda: LibraryHoursTool(tools_config.library_url, tools_config.cache_seconds),
+        "it_status": lambda: ITStatusTool(tools_config.it_status_url, tools_config.cache_seconds),
+        "shuttle_schedule": lambda: ShuttleScheduleTool(os.path.join(data_dir, tools_config.shuttle_schedule_file)),
+        "academic_calendar": lambda: AcademicCalendarTool(AcademicCalendar(
+            data_dir, tools_config.academic_calendar_url, tools_config.academic_calendar_refresh_hours)),
+    }
+
+    registry = ToolRegistry(keyword_triggers=tools_config.keyword_triggers)
+    for name in tools_config.enabled:
+        factory = available.get(name)
+        if factory is None:
+            logger.warning(f"Unknown campus tool in config: {name}")
+            continue
+        registry.register(factory())
+    return registry
@@ -0,0 +1,210 @@
+"""
+Canary rollouts of chat models and system prompts for ArchieAI.
+A new model or prompt used to go to everyone at once, and whether it was better was
+guesswork. A canary sends a percentage of sessions to a candidate model and/or prompt
+(a file in src/prompts/) while the rest stay on the baseline; like a feature flag, a
+session always lands in the same group (lib/FeatureFlags.py). Streamed answers are
+tagged with the canary ID and their arm in the analytics log, so the feedback,
+latency, and unanswered rate of the two groups can be compared (compare_arms) and the
+candidate promoted or rolled back from /api/admin/canary. A session only takes part
+while it's on the server default model; someone who picked a model keeps it.
+State, including what was promoted and past canaries, is in data/canary.json.
+"""
+import os
+import re
+import json
+import uuid
+import threading
+from datetime import datetime
+from typing import Dict, Iterable, List, Optional
+
+from lib.FeatureFlags import rollout_bucket
+
+CANARY_FILE = "canary.json"
+ARMS = ("baseline", "canary")
+# Past canaries kept in the history
+MAX_HISTORY = 50
+
+PROMPT_NAME = re.compile(r"^[A-Za-z0-9_-]+$")
+
+
+def _arm_stats(interactions: List[Dict]) -> Dict:
+    times = sorted(i.get("generation_time_seconds") or 0 for i in interactions)
+    up = sum(1 for i in interactions if i.get("feedback") == "up")
+    down = sum(1 for i in interactions if i.get("feedback") == "down")
+    count = len(interactions)
+    return {
+        "interactions": count,
+        "sessions": len({i.get("session_id") for i in interactions}),
+        "avg_generation_time_seconds": round(sum(times) / count, 2) if count else None,
+        "p95_generation_time_seconds": times[min(count - 1, int(count * 0.95))] if count else None,
+        "unanswered_rate": round(sum(1 for i in interactions if i.get("unanswered")) / count, 3) if count else None,
+        "interrupted_rate": round(sum(1 for i in interactions if i.get("interrupted")) / count, 3) if count else None,
+        "feedback": {"up": up, "down": down},
+        # Share of rated answers rated up
+        "satisfaction": round(up / (up + down), 3) if up + down else None,
+    }
+
+
+def compare_arms(interactions: Iterable[Dict], canary_id: str, min_interactions: int = 30,
+                 max_latency_increase: float = 0.25, max_satisfaction_drop: float = 0.1) -> Dict:
+    """
+    Compare the canary's arms from the analytics log.
+
+    Args:
+        interactions: Logged interactions (only the ones tagged with canary_id count)
+        canary_id: The canary to compare
+        min_interactions: Fewer answers than this in either arm is "insufficient_data"
+        max_latency_increase: Average answer time may grow by this fraction before the candidate is "worse"
+        max_satisfaction_drop: Share of thumbs up may drop by this much before the candidate is "worse"
+
+    Returns:
+        {"baseline": stats, "canary": stats, "latency_change", "satisfaction_change",
+         "verdict": "insufficient_data" | "worse" | "ok", "reasons": [why it's worse]}
+    """
+    by_arm = {arm: [] for arm in ARMS}
+    for interaction in interactions:
+        experiment = interaction.get("experiment") or {}
+        if experiment.get("id") == canary_id and experiment.get("arm") in by_arm:
+            by_arm[experiment["arm"]].append(interaction)
+    baseline, candidate = _arm_stats(by_arm["baseline"]), _arm_stats(by_arm["canary"])
+
+    latency_change = None
+    if baseline["avg_generation_time_seconds"] and candidate["avg_generation_time_seconds"] is not None:
+        latency_change = round(candidate["avg_generation_time_seconds"] / baseline["avg_generation_time_seconds"] - 1, 3)
+    satisfaction_change = None
+    if baseline["satisfaction"] is not None and candidate["satisfaction"] is not None:
+        satisfaction_change = round(candidate["satisfaction"] - baseline["satisfaction"], 3)
+
+    reasons = []
+    if latency_change is not None and latency_change > max_latency_increase:
+        reasons.append(f"answers take {latency_change:.0%} longer")
+    if satisfaction_change is not None and -satisfaction_change > max_satisfaction_drop:
+        reasons.append(f"{-satisfaction_change:.0%} fewer thumbs up")
+    if min(baseline["interactions"], candidate["interactions"]) < min_interactions:
+        verdict = "insufficient_data"
+    else:
+        verdict = "worse" if reasons else "ok"
+    return {
+        "baseline": baseline,
+        "canary": candidate,
+        "latency_change": latency_change,
+        "satisfaction_change": satisfaction_change,
+        "verdict": verdict,
+        "reasons": reasons,
+    }
+
+
+class CanaryRollout:
+    """The running canary (at most one), what earlier ones promoted, and their history."""
+
+    def __init__(self, data_dir: str = "data", prompts_dir: str = "prompts"):
+        self.path = os.path.join(data_dir, CANARY_FILE)
+        self.prompts_dir = prompts_dir
+        self._lock = threading.Lock()
+
+    def _load(self) -> Dict:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                state = json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            state = {}
+        state.setdefault("active", None)
+        state.setdefault("promoted", {"model": None, "prompt": None})
+        state.setdefault("history", [])
+        return state
+
+    def _save(self, state: Dict):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(state, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def prompt_exists(self, name: str) -> bool:
+        return bool(PROMPT_NAME.match(name)) and os.path.exists(os.path.join(self.prompts_dir, f"{name}.txt"))
+
+    def state(self) -> Dict:
+        """{"active": the running canary or None, "promoted": {"model", "prompt"}, "history": [...]}."""
+        with self._lock:
+            return self._load()
+
+    def active(self) -> Optional[Dict]:
+        return self.state()["active"]
+
+    def promoted(self) -> Dict:
+        """The model and prompt promoted by earlier canaries ({"model", "prompt"}, None where nothing was)."""
+        return self.state()["promoted"]
+
+    def start(self, model: Optional[str], prompt: Optional[str], percent: int, baseline_model: str,
+              started_by: Optional[str] = None) -> Optional[Dict]:
+        """Start a canary; returns None if one is already running."""
+        with self._lock:
+            state = self._load()
+            if state["active"] is not None:
+                return None
+            canary = {
+                "id": uuid.uuid4().hex[:12],
+                "model": model,
+                "prompt": prompt,
+                "percent": percent,
+                # What the other sessions get, for the record
+                "baseline": {"model": baseline_model, "prompt": state["promoted"]["prompt"]},
+                "started_at": datetime.now().isoformat(),
+                "started_by": started_by,
+            }
+            state["active"] = canary
+            self._save(state)
+            return canary
+
+    def set_percent(self, percent: int) -> Optional[Dict]:
+        """Change the share of sessions in the running canary; None if there isn't one."""
+        with self._lock:
+            state = self._load()
+            if state["active"] is None:
+                return None
+            state["active"]["percent"] = percent
+            self._save(state)
+            return state["active"]
+
+    def finish(self, outcome: str, finished_by: Optional[str] = None, comparison: Optional[Dict] = None) -> Optional[Dict]:
+        """
+        End the running canary: "promoted" makes the candidate the new default for everyone,
+        "rolled_back" sends everyone back to the baseline. Returns it, or None if none was running.
+        """
+        with self._lock:
+            state = self._load()
+            canary = state["active"]
+            if canary is None:
+                return None
+            canary.update(outcome=outcome, finished_at=datetime.now().isoformat(), finished_by=finished_by,
+                          comparison=comparison)
+            if outcome == "promoted":
+                for key in ("model", "prompt"):
+                    if canary[key]:
+                        state["promoted"][key] = canary[key]
+            state["active"] = None
+            state["history"] = ([canary] + state["history"])[:MAX_HISTORY]
+            self._save(state)
+            return canary
+
+    def assign(self, session_id: Optional[str], model: str, default_model: str) -> Optional[Dict]:
+        """
+        The canary arm a streamed answer belongs to.
+
+        Args:
+            session_id: The session asking (sessionless questions don't take part)
+            model: The model resolve_model() picked
+            default_model: The server default it picks when nothing else was chosen
+
+        Returns:
+            {"id", "arm", "model", "prompt"} with the model and prompt (None for the default one) to
+            answer with, or None if no canary is running or the session picked another model
+        """
+        canary = self.active()
+        if canary is None or not session_id or model != default_model:
+            return None
+        if rollout_bucket(f"canary:{canary['id']}", session_id) < canary["percent"]:
+            return {"id": canary["id"], "arm": "canary", "model": canary["model"] or model,
+                    "prompt": canary["prompt"] or canary["baseline"]["prompt"]}
+        return {"id": canary["id"], "arm": "baseline", "model": model, "prompt": canary["baseline"]["prompt"]}
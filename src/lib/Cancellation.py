@@ -0,0 +1,75 @@
+"""
+Cancelling answers for ArchieAI.
+Once a question was sent, the only way to stop a long generation was to close the tab, and
+with resumable streams (lib/StreamBuffer.py) even that keeps the model going for the grace
+period. Every streamed answer now registers a token under its session; POST
+/api/archie/cancel fires it, and the model stream is aborted at the next chunk Ollama sends.
+What was generated so far is saved with an interrupted flag, like a disconnect.
+"""
+import threading
+from typing import Dict, Optional
+
+
+class GenerationCancelled(Exception):
+    """Raised inside a generation whose token fired."""
+
+
+class CancelToken:
+    """Set once, when the user asks to stop the answer."""
+
+    __slots__ = ("_event",)
+
+    def __init__(self):
+        self._event = threading.Event()
+
+    def cancel(self):
+        self._event.set()
+
+    @property
+    def cancelled(self) -> bool:
+        return self._event.is_set()
+
+    def check(self):
+        """Raise GenerationCancelled if the token fired."""
+        if self._event.is_set():
+            raise GenerationCancelled()
+
+
+class CancellationRegistry:
+    """The cancel token of the answer each session is getting, one at a time."""
+
+    def __init__(self):
+        self._lock = threading.Lock()
+        self._tokens: Dict[str, CancelToken] = {}
+
+    def register(self, session_id: Optional[str]) -> CancelToken:
+        """
+        A token for a new answer in the session. It replaces the previous answer's, so a cancel
+        stops the newest one; an answer without a session gets a token nothing can fire.
+        """
+        token = CancelToken()
+        if session_id:
+            with self._lock:
+                self._tokens[session_id] = token
+        return token
+
+    def release(self, session_id: Optional[str], token: CancelToken):
+        """Forget the token once its answer is done, unless a newer answer replaced it."""
+        if not session_id:
+            return
+        with self._lock:
+            if self._tokens.get(session_id) is token:
+                del self._tokens[session_id]
+
+    def cancel(self, session_id: str) -> bool:
+        """Fire the session's token; False if it has no answer generating."""
+        with self._lock:
+            token = self._tokens.pop(session_id, None)
+        if token is None:
+            return False
+        token.cancel()
+        return True
+
+    def active(self) -> int:
+        with self._lock:
+            return len(self._tokens)
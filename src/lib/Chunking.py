@@ -0,0 +1,162 @@
+"""
+Chunking strategies for knowledge base documents.
+  - fixed: every `chunk_size` words, each chunk overlapping the previous one by `chunk_overlap` words
+  - sentence: whole sentences packed up to `chunk_size` words; the overlap carries trailing sentences over
+  - heading: one chunk per heading section (the "#" lines lib/DocumentText.py keeps), prefixed with
+    its heading path ("Dining > Weekend hours: ..."); long sections are split by sentence
+The right strategy depends on the page: the academic calendar is a long run of short dated lines,
+the dining page is a handful of headed sections. So the strategy and sizes can be set per source
+type and per source name ([retrieval.chunking] in archie.toml).
+"""
+import logging
+import re
+from typing import Dict, List, Optional, Tuple
+
+from lib.DocumentText import HEADING_RE
+
+logger = logging.getLogger(__name__)
+
+STRATEGIES = ("fixed", "sentence", "heading")
+
+# Sentence ends: ., ! or ? followed by whitespace and something that can start a sentence
+_SENTENCE_END_RE = re.compile(r"(?<=[.!?])\s+(?=[A-Z0-9\"'(\[])")
+
+
+def chunk_fixed(text: str, size: int = 200, overlap: int = 40) -> List[str]:
+    """Split text into chunks of about `size` words, each sharing `overlap` words with the previous one."""
+    words = text.split()
+    if not words:
+        return []
+    step = max(1, size - overlap)
+    chunks = []
+    for start in range(0, len(words), step):
+        chunks.append(" ".join(words[start:start + size]))
+        if start + size >= len(words):
+            break
+    return chunks
+
+
+def split_sentences(text: str) -> List[str]:
+    """Split text into sentences on terminal punctuation; good enough for web copy."""
+    return [sentence for sentence in _SENTENCE_END_RE.split(" ".join(text.split())) if sentence]
+
+
+def chunk_sentences(text: str, size: int = 200, overlap: int = 40) -> List[str]:
+    """
+    Pack whole sentences into chunks of at most `size` words.
+    The trailing sentences of a chunk (up to `overlap` words) start the next one.
+    A single sentence longer than `size` is split with chunk_fixed.
+    """
+    chunks: List[str] = []
+    current: List[str] = []
+    count = 0
+    for sentence in split_sentences(text):
+        words = len(sentence.split())
+        if words > size:
+            if current:
+                chunks.append(" ".join(current))
+            chunks.extend(chunk_fixed(sentence, size, overlap))
+            current, count = [], 0
+            continue
+        if current and count + words > size:
+            chunks.append(" ".join(current))
+            carried, carried_count = [], 0
+            for previous in reversed(current):
+                previous_count = len(previous.split())
+                if carried_count + previous_count > overlap:
+                    break
+                carried.insert(0, previous)
+                carried_count += previous_count
+            current, count = carried, carried_count
+        current.append(sentence)
+        count += words
+    if current:
+        chunks.append(" ".join(current))
+    return chunks
+
+
+def split_sections(text: str) -> List[Tuple[List[str], str]]:
+    """
+    Split text on "#" heading lines.
+
+    Returns:
+        (heading path, body) pairs in document order; text before the first heading has an empty path
+    """
+    sections: List[Tuple[List[str], str]] = []
+    path: List[Tuple[int, str]] = []
+    body: List[str] = []
+
+    def flush():
+        if body:
+            sections.append(([title for _, title in path], " ".join(body)))
+
+    for line in text.splitlines():
+        match = HEADING_RE.match(line.strip())
+        if match is None:
+            if line.strip():
+                body.append(line.strip())
+            continue
+        flush()
+        body = []
+        level = len(match.group(1))
+        # A heading closes every open heading at its level or deeper
+        path = [(open_level, title) for open_level, title in path if open_level < level]
+        path.append((level, match.group(2).strip()))
+    flush()
+    return sections
+
+
+def chunk_headings(text: str, size: int = 200, overlap: int = 40) -> List[str]:
+    """One chunk per heading section, prefixed with its heading path; long sections are split by sentence."""
+    chunks = []
+    for path, body in split_sections(text):
+        prefix = " > ".join(path)
+        # Leave room for the prefix so chunks stay close to `size` words
+        body_size = max(1, size - len(prefix.split()))
+        for piece in chunk_sentences(body, body_size, min(overlap, body_size - 1)):
+            chunks.append(f"{prefix}: {piece}" if prefix else piece)
+    return chunks
+
+
+def chunk_document(text: str, strategy: str = "fixed", size: int = 200, overlap: int = 40) -> List[str]:
+    """
+    Chunk a document with one of STRATEGIES.
+
+    Raises:
+        ValueError: For an unknown strategy
+    """
+    if strategy == "fixed":
+        return chunk_fixed(text, size, overlap)
+    if strategy == "sentence":
+        return chunk_sentences(text, size, overlap)
+    if strategy == "heading":
+        return chunk_headings(text, size, overlap)
+    raise ValueError(f"Unknown chunking strategy {strategy!r}, expected one of {', '.join(STRATEGIES)}")
+
+
+def chunking_options(source: str, source_type: str, defaults: Dict,
+                     overrides: Optional[Dict[str, Dict]] = None) -> Dict:
+    """
+    The chunking options for one document: the defaults, then the source type's
+    overrides, then the source name's (the most specific wins).
+
+    Args:
+        source: Source name ("events", "Academic Calendar", ...)
+        source_type: Kind of document ("html", "markdown", "text", ...)
+        defaults: {"strategy", "chunk_size", "chunk_overlap"}
+        overrides: Source type or name -> any of those keys
+
+    Returns:
+        {"strategy", "chunk_size", "chunk_overlap"}
+    """
+    options = dict(defaults)
+    for key in (source_type, source):
+        override = (overrides or {}).get(key)
+        if isinstance(override, dict):
+            options.update({name: value for name, value in override.items() if name in defaults})
+    if options["strategy"] not in STRATEGIES:
+        logger.warning(f"Unknown chunking strategy {options['strategy']!r} for {source}, using fixed")
+        options["strategy"] = "fixed"
+    options["chunk_size"] = max(1, int(options["chunk_size"]))
+    options["chunk_overlap"] = min(max(0, int(options["chunk_overlap"])), options["chunk_size"] - 1)
+    return options
@@ -0,0 +1,142 @@
+"""
+Circuit breaker for the Ollama backend.
+When Ollama crashes or hangs, every question used to wait for its own connection error or
+timeout, and all of them retried against the backend the moment it came back. The breaker
+counts consecutive failures; after failure_threshold of them it opens and generations are
+refused right away (app.py answers with the offline fallback instead) until cooldown_seconds
+have passed. Then a single trial request is let through: if it works the breaker closes,
+if not it stays open for another cooldown. State and counters are exported on /metrics.
+"""
+import logging
+import time
+import threading
+from contextlib import asynccontextmanager
+from typing import Dict
+
+logger = logging.getLogger(__name__)
+
+CLOSED, OPEN, HALF_OPEN = "closed", "open", "half_open"
+# Numeric values for the /metrics gauge
+STATE_VALUES = {CLOSED: 0, HALF_OPEN: 1, OPEN: 2}
+
+
+class CircuitOpen(Exception):
+    """The backend has been failing; generations are short-circuited until the cooldown is over."""
+
+    def __init__(self, retry_after: float):
+        super().__init__(f"Model backend circuit is open, retry in {retry_after:.0f} seconds")
+        self.retry_after = retry_after
+
+
+class CircuitBreaker:
+    """Closed, open, or half-open state of one backend, with counters."""
+
+    def __init__(self, failure_threshold: int = 5, cooldown_seconds: float = 30.0):
+        """
+        Args:
+            failure_threshold: Consecutive failures that open the breaker; 0 means it never opens
+            cooldown_seconds: How long it stays open before a trial request is let through
+        """
+        self.failure_threshold = failure_threshold
+        self.cooldown_seconds = cooldown_seconds
+        self._lock = threading.Lock()
+        self._state = CLOSED
+        self._failures = 0
+        self._opened_at = 0.0
+        self._trial_in_flight = False
+        self._opened_total = 0
+        self._short_circuited = 0
+        self._failures_total = 0
+
+    def _retry_after(self) -> float:
+        return max(0.0, self._opened_at + self.cooldown_seconds - time.monotonic())
+
+    def _would_allow(self) -> bool:
+        if self._state == CLOSED:
+            return True
+        if self._state == OPEN:
+            return self._retry_after() <= 0
+        return not self._trial_in_flight
+
+    def is_open(self) -> bool:
+        """True while requests would be refused (open and cooling down, or a trial is already running)."""
+        with self._lock:
+            return not self._would_allow()
+
+    def check(self):
+        """Raise CircuitOpen if a request would be refused right now, without using up the trial."""
+        with self._lock:
+            if not self._would_allow():
+                self._short_circuited += 1
+                raise CircuitOpen(self._retry_after())
+
+    def _admit(self):
+        with self._lock:
+            if not self._would_allow():
+                self._short_circuited += 1
+                raise CircuitOpen(self._retry_after())
+            if self._state != CLOSED:
+                self._state = HALF_OPEN
+                self._trial_in_flight = True
+
+    def record_success(self):
+        with self._lock:
+            if self._state != CLOSED:
+                logger.info("Model backend circuit closed, requests are going through again")
+            self._state = CLOSED
+            self._failures = 0
+            self._trial_in_flight = False
+
+    def record_failure(self):
+        with self._lock:
+            self._failures += 1
+            self._failures_total += 1
+            self._trial_in_flight = False
+            if self.failure_threshold <= 0:
+                return
+            if self._state == HALF_OPEN or (self._state == CLOSED and self._failures >= self.failure_threshold):
+                if self._state == CLOSED:
+                    logger.warning(f"Model backend failed {self._failures} times in a row, opening the circuit "
+                                   f"for {self.cooldown_seconds:g} seconds")
+                self._state = OPEN
+                self._opened_at = time.monotonic()
+                self._opened_total += 1
+
+    def _abandon(self):
+        """A request that neither worked nor failed (the client left); let another one be the trial."""
+        with self._lock:
+            self._trial_in_flight = False
+
+    @asynccontextmanager
+    async def guard(self):
+        """
+        Run a backend call through the breaker: refused with CircuitOpen while it's open,
+        and the outcome of the block counts as a success or a failure.
+        """
+        self._admit()
+        try:
+            yield
+        except Exception:
+            self.record_failure()
+            raise
+        except BaseException:
+            # Cancelled or closed early, which says nothing about the backend
+            self._abandon()
+            raise
+        else:
+            self.record_success()
+
+    def metrics(self) -> Dict:
+        with self._lock:
+            state = self._state
+            if state == OPEN and self._retry_after() <= 0:
+                # Cooldown is over; the next request will be the trial
+                state = HALF_OPEN
+            return {
+                "state": state,
+                "consecutive_failures": self._failures,
+                "failures": self._failures_total,
+                "opened": self._opened_total,
+                "short_circuited": self._short_circuited,
+                "retry_after_seconds": round(self._retry_after(), 1) if self._state == OPEN else 0,
+            }
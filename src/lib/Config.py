@@ -0,0 +1,709 @@
+"""
+Configuration for ArchieAI.
+Settings are loaded from a TOML file (archie.toml by default) and can be overridden
+with environment variables, so deployments don't need source edits.
+"""
+import logging
+import os
+import tomllib
+from dataclasses import dataclass, field
+from typing import Dict, List, Optional
+from dotenv import load_dotenv
+from lib.DataLayout import DataLayout, PROJECT_ROOT, config_file_candidates
+
+logger = logging.getLogger(__name__)
+
+
+@dataclass
+class ServerConfig:
+    host: str = "0.0.0.0"
+    port: int = 5000
+    debug: bool = True
+    dev_mode: bool = False
+    # Sub-path the app is served under behind a reverse proxy, e.g. "/archie" ("" means the root)
+    base_path: str = ""
+    # Reverse proxies in front of the app that append to X-Forwarded-For. With 0 the client is the
+    # connecting address; behind a proxy that's the proxy, so set this or every caller looks the same
+    trusted_proxies: int = 0
+    # On SIGTERM/SIGINT, how long running requests get to finish before they're stopped (see lib/Shutdown.py)
+    drain_seconds: float = 30
+
+
+@dataclass
+class LoggingConfig:
+    # "pretty" for reading in a console, "json" for one object per line (see lib/Logging.py)
+    format: str = "pretty"
+    level: str = "INFO"
+
+
+@dataclass
+class PathsConfig:
+    # Defaults for the directories left empty: "project" (data/ in the checkout) or "xdg"
+    # (XDG base directories, or systemd's StateDirectory=/CacheDirectory=); see lib/DataLayout.py
+    layout: str = "project"
+    data_dir: str = ""
+    # Rebuildable files (the embedding cache); empty is the data directory in the project layout
+    cache_dir: str = ""
+    # Empty reads the files bundled with the code
+    templates_dir: str = ""
+    static_dir: str = ""
+    prompts_dir: str = ""
+    # YAML question suites for the evaluation harness
+    evals_dir: str = ""
+
+
+@dataclass
+class InstanceConfig:
+    # "primary" writes the data directory and holds its lock; "replica" only reads it (see lib/InstanceLock.py)
+    role: str = "primary"
+    # Refuse to start while another server holds the data directory's lock
+    lock: bool = True
+
+
+@dataclass
+class StaticConfig:
+    max_age: int = 3600
+    fingerprint: bool = True
+
+
+@dataclass
+class CookieConfig:
+    samesite: str = "Strict"
+    secure: bool = False
+    httponly: bool = True
+    # Key that signs session tokens; empty keeps a generated one in data_dir/session_secret
+    secret: str = ""
+    # Sign-ins older than this need the password again
+    session_max_age_days: int = 30
+
+
+@dataclass
+class OllamaConfig:
+    host: str = "http://localhost:11434"
+    # Bearer token sent to Ollama; normally left to OLLAMA_API_KEY in .env rather than the file
+    api_key: str = ""
+    model: str = "llama2"
+    stream_model: str = "qwen3"
+    # Other chat models users may pick per question, per session, or as their default;
+    # model and stream_model are always allowed
+    models: List[str] = field(default_factory=list)
+    embedding_model: str = "nomic-embed-text"
+    # Embeddings cached by content hash in data/embedding_cache.jsonl (see lib/EmbeddingCache.py); 0 turns it off
+    embedding_cache_size: int = 5000
+
+
+@dataclass
+class QueueConfig:
+    # Model generations running at once; more wait in line (see lib/GenerationQueue.py). 0 = no limit
+    max_concurrent: int = 4
+    # Requests waiting for a slot before new ones get a 503
+    max_waiting: int = 50
+    wait_timeout_seconds: float = 60.0
+    # Share of the turns each class gets while generations are queued
+    weights: Dict[str, float] = field(default_factory=lambda: {"user": 6, "guest": 3, "batch": 1})
+    # Identical questions asked at the same time (with no history) share one generation (see lib/Inflight.py)
+    coalesce_identical: bool = True
+
+
+@dataclass
+class BreakerConfig:
+    # Short-circuit Ollama calls after repeated failures (see lib/CircuitBreaker.py)
+    enabled: bool = True
+    # Consecutive failed calls that open the breaker
+    failure_threshold: int = 5
+    # Seconds it stays open (answering with the offline fallback) before one trial request is let through
+    cooldown_seconds: float = 30.0
+    # Seconds without a response from Ollama before a call counts as failed; 0 = no timeout
+    request_timeout_seconds: float = 120.0
+
+
+@dataclass
+class StreamsConfig:
+    # SSE answers are generated in the background so a client can reconnect with Last-Event-ID (see lib/StreamBuffer.py)
+    resumable: bool = True
+    # How long an answer keeps generating with no client attached before it's stopped and saved as interrupted
+    resume_grace_seconds: float = 30.0
+    # How long a finished answer's events stay available to a late reconnect
+    resume_ttl_seconds: float = 120.0
+
+
+
+@dataclass
+class DraftsConfig:
+    # How long an answer asked with "ephemeral": true can still be committed to a session (see lib/Drafts.py)
+    ttl_seconds: float = 900.0
+    # Uncommitted drafts held in memory at once; the oldest are dropped beyond this
+    max_pending: int = 1000
+
+
+@dataclass
+class LimitsConfig:
+    max_question_length: int = 4000
+    max_body_bytes: int = 64 * 1024
+    # Previous messages of the session sent to the model with each question
+    history_messages: int = 10
+    # Older messages are folded into a rolling summary once this many have left the window (0 = never)
+    summary_batch_messages: int = 6
+
+
+@dataclass
+class AdminConfig:
+    emails: List[str] = field(default_factory=list)
+
+
+@dataclass
+class AbuseConfig:
+    # Spam and jailbreak heuristics run on every question (see lib/AbuseDetector.py)
+    enabled: bool = True
+    max_questions_per_minute: int = 20
+    # The same question this many times within repeat_window_seconds gets throttled
+    repeat_threshold: int = 5
+    repeat_window_seconds: int = 120
+    # Questions at least this fraction of limits.max_question_length are flagged
+    long_question_ratio: float = 0.9
+    # Extra regexes treated as jailbreak attempts, on top of the built-in ones
+    jailbreak_patterns: List[str] = field(default_factory=list)
+    # Suspend an account (pending admin review) after this many incidents within suspend_window_seconds
+    auto_suspend: bool = False
+    suspend_after_incidents: int = 5
+    suspend_window_seconds: int = 3600
+
+
+@dataclass
+class RateLimitConfig:
+    # Token buckets on every /api/ request, per session and per IP (see lib/RateLimiter.py)
+    enabled: bool = True
+    # A bucket holds up to burst requests and refills at per_minute
+    session_burst: int = 30
+    session_per_minute: float = 60
+    # Larger, since many students can share one NAT address
+    ip_burst: int = 120
+    ip_per_minute: float = 300
+    # Path prefixes that aren't limited (admins' dashboards poll several endpoints at once)
+    exempt_paths: List[str] = field(default_factory=lambda: ["/api/admin/"])
+
+
+@dataclass
+class ProfanityConfig:
+    # Wordlist filter on questions, before the model sees them (see lib/ProfanityFilter.py)
+    enabled: bool = True
+    # "mask" replaces the words with asterisks and answers; "reject" refuses the question
+    action: str = "mask"
+    # Use the built-in list, plus these words/phrases and the ones in wordlist_file (one per line)
+    default_words: bool = True
+    words: List[str] = field(default_factory=list)
+    wordlist_file: str = ""
+
+
+@dataclass
+class FrustrationConfig:
+    # Flag sessions where the user is stuck for staff to follow up on (see lib/Frustration.py)
+    enabled: bool = True
+    # Score (negative questions + rephrasings + 1.5 per thumbs down) over the last `window` questions
+    threshold: float = 3.0
+    window: int = 6
+    # Email [admin] emails about each flagged session (needs [mail]); the webhook event is frustrated_session
+    email_alerts: bool = True
+
+
+@dataclass
+class SloConfig:
+    # Log requests slower than their route's threshold and count them in /metrics (see lib/SloTracker.py)
+    enabled: bool = True
+    default_seconds: float = 2.0
+    # URL rule -> seconds; streamed answers count until the last token was sent
+    routes: Dict[str, float] = field(default_factory=lambda: {
+        "/api/archie": 30.0,
+        "/api/archie/stream": 30.0,
+        "/api/sessions/<session_id>/messages/<message_id>/resubmit": 30.0,
+        "/api/transcribe": 15.0,
+        "/api/admin/compare": 120.0,
+    })
+    # Not tracked at all: connections that stay open on purpose
+    exclude: List[str] = field(default_factory=lambda: ["/api/sessions/stream", "/ws/admin/tail"])
+
+
+@dataclass
+class CanaryConfig:
+    # When GET /api/admin/canary calls a candidate model or prompt worse than the baseline (see lib/Canary.py)
+    min_interactions: int = 30
+    # Fraction the average answer time may grow by
+    max_latency_increase: float = 0.25
+    # How much the share of thumbs up may drop
+    max_satisfaction_drop: float = 0.1
+
+
+@dataclass
+class WebhookConfig:
+    url: str = ""
+    events: List[str] = field(default_factory=lambda: ["user_registered", "generation_failures", "quota_exhausted", "account_suspended",
+                                                       "frustrated_session"])
+    failure_threshold: int = 3
+    failure_window_seconds: int = 300
+    cooldown_seconds: int = 900
+
+
+@dataclass
+class ToolsConfig:
+    # Campus data tools the model can call (see lib/CampusTools.py)
+    enabled: List[str] = field(default_factory=lambda: ["dining_hours", "shuttle_schedule", "library_hours", "it_status", "academic_calendar"])
+    # Also run a tool up front when the question mentions its keywords, for models without tool calling
+    keyword_triggers: bool = True
+    cache_seconds: int = 300
+    dining_url: str = "https://www.arcadia.edu/life-arcadia/living-commuting/dining/"
+    library_url: str = "https://library.arcadia.edu/"
+    it_status_url: str = "https://www.arcadia.edu/life-arcadia/campus-life-resources/information-technology/"
+    # JSON file in the data directory maintained by staff
+    shuttle_schedule_file: str = "shuttle_schedule.json"
+    # ICS feed or calendar web page; ingested into data/academic_calendar.json
+    academic_calendar_url: str = "https://www.arcadia.edu/academics/resources/academic-calendars/2025-26/"
+    academic_calendar_refresh_hours: int = 24
+
+
+@dataclass
+class CrawlConfig:
+    # Crawl the site from its sitemaps into the knowledge base, on top of the fixed sources (see lib/Crawler.py)
+    enabled: bool = False
+    start_urls: List[str] = field(default_factory=lambda: ["https://www.arcadia.edu/"])
+    # Domains to stay on, subdomains included; empty uses the start URLs' hosts
+    allowed_domains: List[str] = field(default_factory=lambda: ["arcadia.edu"])
+    # Regexes on the URL: a page must match one include pattern (if any) and no exclude pattern
+    include_patterns: List[str] = field(default_factory=list)
+    exclude_patterns: List[str] = field(default_factory=list)
+    max_pages: int = 300
+    # Requests in flight to one host at a time
+    per_domain_concurrency: int = 2
+    # Seconds between requests to a host when robots.txt has no Crawl-delay
+    default_delay: float = 1.0
+    user_agent: str = "ArchieAI"
+    timeout: int = 15
+
+
+@dataclass
+class RetrievalConfig:
+    # Add the knowledge base chunks that best match the question to the prompt (see lib/Retrieval.py)
+    enabled: bool = True
+    top_k: int = 5
+    # Default chunking: strategy (fixed, sentence, heading; see lib/Chunking.py), length and overlap in words
+    chunk_strategy: str = "fixed"
+    chunk_size: int = 200
+    chunk_overlap: int = 40
+    # Source type (html, pdf, docx, markdown, text) or source name -> strategy/chunk_size/chunk_overlap overrides
+    chunking: Dict[str, Dict] = field(default_factory=lambda: {
+        "html": {"strategy": "heading"},
+        "docx": {"strategy": "heading"},
+        "pdf": {"strategy": "sentence"},
+    })
+    # Results each retriever (BM25, vectors) contributes before reciprocal rank fusion
+    candidates: int = 20
+    rrf_k: int = 60
+    # Rescore the top fused results with a model before picking top_k (slower, better on ambiguous questions)
+    rerank: bool = False
+    # Ollama model that scores the chunks; empty uses ollama.model
+    rerank_model: str = ""
+    rerank_candidates: int = 15
+
+
+@dataclass
+class VerificationConfig:
+    # Check answers against the retrieved pages with a second model (see lib/FactCheck.py); about doubles answer time
+    enabled: bool = False
+    # Ollama model that checks; a small one keeps the cost down, empty uses ollama.model
+    model: str = ""
+    # "note" streams the answer and notes what couldn't be confirmed; "regenerate" holds the
+    # answer until it's checked and writes it again if it fails
+    action: str = "note"
+    # Rewrites per answer with "regenerate"; an answer still failing after them gets the note
+    max_regenerations: int = 1
+
+
+@dataclass
+class FaqConfig:
+    # Check curated FAQ entries (data/faq_entries.json) before asking the model
+    enabled: bool = True
+    # Also match paraphrases by embedding similarity, not just the exact question
+    semantic_matching: bool = True
+    similarity_threshold: float = 0.9
+
+
+@dataclass
+class MemoryConfig:
+    # Long-term memory for users who opt in (see lib/UserMemory.py); false turns it off for everyone
+    enabled: bool = True
+    max_per_user: int = 50
+    # How many remembered facts go into the prompt per question
+    prompt_limit: int = 5
+
+
+@dataclass
+class AnalyticsConfig:
+    # Where interactions are logged: "json" (analytics.json) or "sqlite" (analytics.db, see lib/AnalyticsStore.py)
+    backend: str = "json"
+    # Score each question's sentiment (see lib/Sentiment.py) so the dashboard can show frustration over time
+    sentiment: bool = True
+    # Tag each interaction with a country and region from this offline MaxMind database (.mmdb);
+    # needs the maxminddb package (see lib/GeoIp.py)
+    geoip_db: str = ""
+    # CIDRs of the campus networks, so interactions can be told apart as on- or off-campus
+    campus_networks: List[str] = field(default_factory=list)
+    # Store only the network part of IPs in the analytics (the location is looked up first)
+    anonymize_ips: bool = False
+
+
+@dataclass
+class FallbackConfig:
+    # Check that Ollama is reachable before answering and, if it isn't, answer from the FAQ
+    # entries or with a "temporarily limited" message instead of an error
+    enabled: bool = True
+    health_check_seconds: int = 30
+    # Pages suggested in the limited-mode message
+    links: List[Dict[str, str]] = field(default_factory=lambda: [
+        {"title": "Arcadia University", "url": "https://www.arcadia.edu/"},
+        {"title": "Academic calendars", "url": "https://www.arcadia.edu/academics/resources/academic-calendars/"},
+        {"title": "IT help desk", "url": "https://www.arcadia.edu/life-arcadia/campus-life-resources/information-technology/"},
+    ])
+
+
+@dataclass
+class DiscordConfig:
+    # The bot only starts when this is on (python src/discord_bot.py)
+    enabled: bool = False
+    token: str = ""
+    # Channel IDs Archie answers in without being mentioned; DMs and mentions work anywhere
+    channels: List[str] = field(default_factory=list)
+    # "user": one conversation per Discord user, "channel": one shared conversation per channel
+    session_scope: str = "user"
+    # Minimum time between edits of the streaming answer message (Discord rate limits edits)
+    edit_interval_seconds: float = 1.0
+
+
+
+@dataclass
+class KioskConfig:
+    # Touchscreen mode (the library lobby): GET /kiosk starts a throwaway session, see lib/Kiosk.py
+    enabled: bool = False
+    # The whole server is a kiosk: every page goes to /kiosk and sign-in/sign-up are off
+    exclusive: bool = False
+    # Stricter than the abuse limits, counted per kiosk device (IP) since sessions are thrown away
+    max_questions_per_minute: int = 6
+    repeat_threshold: int = 3
+    max_question_length: int = 500
+    # Default answer style on the kiosk (lib/AnswerStyle.py); empty for none
+    style: str = "concise"
+    # The screen starts over with a new session after this long without a touch (0 = never)
+    idle_reset_seconds: int = 120
+
+
+@dataclass
+class TermsConfig:
+    # Version of the terms of use / AI disclaimer users must accept before chatting; empty turns
+    # acceptance off. Changing it asks everyone to accept again
+    version: str = ""
+    # Page with the full terms, linked from the prompt
+    url: str = ""
+    # Shown when asking for acceptance
+    text: str = ("Archie is an AI assistant and can make mistakes. Don't share sensitive personal information, "
+                 "and check important answers with the university.")
+
+
+@dataclass
+class SttConfig:
+    # Transcription server for POST /api/transcribe; empty disables voice input
+    url: str = ""
+    # "whisper_cpp" (whisper.cpp server) or "openai" (OpenAI-compatible /v1/audio/transcriptions)
+    backend: str = "whisper_cpp"
+    # Model name sent to OpenAI-compatible servers
+    model: str = ""
+    # Language hint like "en"; empty lets the server detect it
+    language: str = ""
+    max_audio_bytes: int = 10 * 1024 * 1024
+    timeout_seconds: int = 60
+
+
+@dataclass
+class MailConfig:
+    # SMTP server for outgoing mail (admin digests, transcripts); empty host disables sending
+    host: str = ""
+    port: int = 587
+    username: str = ""
+    password: str = ""
+    from_address: str = "archie@arcadia.edu"
+    # STARTTLS on a plain connection; set ssl = true instead for implicit TLS (port 465)
+    starttls: bool = True
+    ssl: bool = False
+    timeout_seconds: int = 30
+    # Conversation transcripts a user can email themselves per hour
+    transcripts_per_hour: int = 5
+
+
+@dataclass
+class IntegrityConfig:
+    # Cross-check account and session files on startup and print a summary (see lib/DataIntegrity.py)
+    check_on_startup: bool = True
+    # Also fix what can be fixed safely; bad session files are quarantined, not deleted
+    repair: bool = False
+
+
+@dataclass
+class ReportsConfig:
+    # Materialize daily/weekly analytics rollups into data/reports/ (see lib/AnalyticsReports.py)
+    enabled: bool = True
+    # Also write daily.csv / weekly.csv next to the JSON reports
+    csv: bool = True
+
+
+@dataclass
+class DigestConfig:
+    # Weekly usage summary emailed to [admin] emails
+    enabled: bool = False
+    # Day of the week (0 = Monday) and hour (server local time) the digest goes out
+    weekday: int = 0
+    hour: int = 8
+    top_questions: int = 10
+
+
+@dataclass
+class Config:
+    """Typed application configuration."""
+    server: ServerConfig = field(default_factory=ServerConfig)
+    logging: LoggingConfig = field(default_factory=LoggingConfig)
+    paths: PathsConfig = field(default_factory=PathsConfig)
+    instance: InstanceConfig = field(default_factory=InstanceConfig)
+    static: StaticConfig = field(default_factory=StaticConfig)
+    cookies: CookieConfig = field(default_factory=CookieConfig)
+    ollama: OllamaConfig = field(default_factory=OllamaConfig)
+    queue: QueueConfig = field(default_factory=QueueConfig)
+    breaker: BreakerConfig = field(default_factory=BreakerConfig)
+    streams: StreamsConfig = field(default_factory=StreamsConfig)
+    drafts: DraftsConfig = field(default_factory=DraftsConfig)
+    limits: LimitsConfig = field(default_factory=LimitsConfig)
+    admin: AdminConfig = field(default_factory=AdminConfig)
+    abuse: AbuseConfig = field(default_factory=AbuseConfig)
+    rate_limit: RateLimitConfig = field(default_factory=RateLimitConfig)
+    webhook: WebhookConfig = field(default_factory=WebhookConfig)
+    tools: ToolsConfig = field(default_factory=ToolsConfig)
+    crawl: CrawlConfig = field(default_factory=CrawlConfig)
+    retrieval: RetrievalConfig = field(default_factory=RetrievalConfig)
+    verification: VerificationConfig = field(default_factory=VerificationConfig)
+    faq: FaqConfig = field(default_factory=FaqConfig)
+    fallback: FallbackConfig = field(default_factory=FallbackConfig)
+    memory: MemoryConfig = field(default_factory=MemoryConfig)
+    analytics: AnalyticsConfig = field(default_factory=AnalyticsConfig)
+    discord: DiscordConfig = field(default_factory=DiscordConfig)
+    kiosk: KioskConfig = field(default_factory=KioskConfig)
+    profanity: ProfanityConfig = field(default_factory=ProfanityConfig)
+    frustration: FrustrationConfig = field(default_factory=FrustrationConfig)
+    canary: CanaryConfig = field(default_factory=CanaryConfig)
+    slo: SloConfig = field(default_factory=SloConfig)
+    terms: TermsConfig = field(default_factory=TermsConfig)
+    stt: SttConfig = field(default_factory=SttConfig)
+    mail: MailConfig = field(default_factory=MailConfig)
+    digest: DigestConfig = field(default_factory=DigestConfig)
+    reports: ReportsConfig = field(default_factory=ReportsConfig)
+    integrity: IntegrityConfig = field(default_factory=IntegrityConfig)
+    # Feature flag defaults, name -> {"enabled", "rollout_percent"} (see lib/FeatureFlags.py)
+    flags: Dict[str, Dict] = field(default_factory=dict)
+    config_file: Optional[str] = None
+
+    def resolve_path(self, path: str) -> str:
+        """Resolve a configured path relative to the project root."""
+        return DataLayout.resolve(path)
+
+    @property
+    def layout(self) -> DataLayout:
+        return DataLayout(self.paths)
+
+    @property
+    def data_dir(self) -> str:
+        return self.layout.data_dir
+
+    @property
+    def cache_dir(self) -> str:
+        return self.layout.cache_dir
+
+    @property
+    def templates_dir(self) -> str:
+        return self.layout.templates_dir
+
+    @property
+    def static_dir(self) -> str:
+        return self.layout.static_dir
+
+    @property
+    def prompts_dir(self) -> str:
+        return self.layout.prompts_dir
+
+    @property
+    def evals_dir(self) -> str:
+        return self.layout.evals_dir
+
+
+def _parse_bool(value: str) -> bool:
+    return value.strip().lower() in ("1", "true", "yes", "on")
+
+
+def _parse_list(value: str) -> List[str]:
+    return [item.strip() for item in value.split(",") if item.strip()]
+
+
+# Environment variable -> (section, key, parser)
+ENV_OVERRIDES = {
+    "ARCHIE_HOST": ("server", "host", str),
+    "ARCHIE_PORT": ("server", "port", int),
+    "ARCHIE_DEBUG": ("server", "debug", _parse_bool),
+    "ARCHIE_DEV_MODE": ("server", "dev_mode", _parse_bool),
+    "ARCHIE_BASE_PATH": ("server", "base_path", str),
+    "ARCHIE_TRUSTED_PROXIES": ("server", "trusted_proxies", int),
+    "ARCHIE_DRAIN_SECONDS": ("server", "drain_seconds", float),
+    "ARCHIE_LOG_FORMAT": ("logging", "format", str),
+    "ARCHIE_LOG_LEVEL": ("logging", "level", str),
+    "ARCHIE_LAYOUT": ("paths", "layout", str),
+    "ARCHIE_DATA_DIR": ("paths", "data_dir", str),
+    "ARCHIE_CACHE_DIR": ("paths", "cache_dir", str),
+    "ARCHIE_INSTANCE_ROLE": ("instance", "role", str),
+    "ARCHIE_INSTANCE_LOCK": ("instance", "lock", _parse_bool),
+    "ARCHIE_TEMPLATES_DIR": ("paths", "templates_dir", str),
+    "ARCHIE_STATIC_DIR": ("paths", "static_dir", str),
+    "ARCHIE_PROMPTS_DIR": ("paths", "prompts_dir", str),
+    "ARCHIE_EVALS_DIR": ("paths", "evals_dir", str),
+    "ARCHIE_STATIC_MAX_AGE": ("static", "max_age", int),
+    "ARCHIE_STATIC_FINGERPRINT": ("static", "fingerprint", _parse_bool),
+    "ARCHIE_COOKIE_SAMESITE": ("cookies", "samesite", str),
+    "ARCHIE_COOKIE_SECURE": ("cookies", "secure", _parse_bool),
+    "ARCHIE_SECRET_KEY": ("cookies", "secret", str),
+    "ARCHIE_SESSION_MAX_AGE_DAYS": ("cookies", "session_max_age_days", int),
+    "OLLAMA_HOST": ("ollama", "host", str),
+    # Either name works; OLLAMA_API_KEY wins when both are set
+    "OLLAMA_TOKEN": ("ollama", "api_key", str),
+    "OLLAMA_API_KEY": ("ollama", "api_key", str),
+    "MODEL": ("ollama", "model", str),
+    "OLLAMA_MODEL": ("ollama", "stream_model", str),
+    "ARCHIE_MODELS": ("ollama", "models", _parse_list),
+    "EMBEDDING_MODEL": ("ollama", "embedding_model", str),
+    "ARCHIE_EMBEDDING_CACHE_SIZE": ("ollama", "embedding_cache_size", int),
+    "ARCHIE_MAX_CONCURRENT_GENERATIONS": ("queue", "max_concurrent", int),
+    "ARCHIE_MAX_WAITING_GENERATIONS": ("queue", "max_waiting", int),
+    "ARCHIE_COALESCE_QUESTIONS": ("queue", "coalesce_identical", _parse_bool),
+    "ARCHIE_BREAKER_ENABLED": ("breaker", "enabled", _parse_bool),
+    "ARCHIE_BREAKER_FAILURES": ("breaker", "failure_threshold", int),
+    "ARCHIE_BREAKER_COOLDOWN": ("breaker", "cooldown_seconds", float),
+    "ARCHIE_OLLAMA_TIMEOUT": ("breaker", "request_timeout_seconds", float),
+    "ARCHIE_RESUMABLE_STREAMS": ("streams", "resumable", _parse_bool),
+    "ARCHIE_STREAM_RESUME_GRACE": ("streams", "resume_grace_seconds", float),
+    "ARCHIE_DRAFT_TTL": ("drafts", "ttl_seconds", float),
+    "ARCHIE_KIOSK": ("kiosk", "enabled", _parse_bool),
+    "ARCHIE_KIOSK_EXCLUSIVE": ("kiosk", "exclusive", _parse_bool),
+    "ARCHIE_TERMS_VERSION": ("terms", "version", str),
+    "ARCHIE_TERMS_URL": ("terms", "url", str),
+    "ARCHIE_MAX_QUESTION_LENGTH": ("limits", "max_question_length", int),
+    "ARCHIE_MAX_BODY_BYTES": ("limits", "max_body_bytes", int),
+    "ARCHIE_HISTORY_MESSAGES": ("limits", "history_messages", int),
+    "ARCHIE_SUMMARY_BATCH_MESSAGES": ("limits", "summary_batch_messages", int),
+    "ADMIN_EMAILS": ("admin", "emails", _parse_list),
+    "ARCHIE_ABUSE_DETECTION": ("abuse", "enabled", _parse_bool),
+    "ARCHIE_ABUSE_AUTO_SUSPEND": ("abuse", "auto_suspend", _parse_bool),
+    "ARCHIE_RATE_LIMIT": ("rate_limit", "enabled", _parse_bool),
+    "ARCHIE_RATE_LIMIT_SESSION_PER_MINUTE": ("rate_limit", "session_per_minute", float),
+    "ARCHIE_RATE_LIMIT_IP_PER_MINUTE": ("rate_limit", "ip_per_minute", float),
+    "ARCHIE_PROFANITY_FILTER": ("profanity", "enabled", _parse_bool),
+    "ARCHIE_PROFANITY_ACTION": ("profanity", "action", str),
+    "ARCHIE_FRUSTRATION_DETECTION": ("frustration", "enabled", _parse_bool),
+    "ARCHIE_FRUSTRATION_THRESHOLD": ("frustration", "threshold", float),
+    "ARCHIE_SLO_TRACKING": ("slo", "enabled", _parse_bool),
+    "ARCHIE_SLO_DEFAULT_SECONDS": ("slo", "default_seconds", float),
+    "ARCHIE_WEBHOOK_URL": ("webhook", "url", str),
+    "ARCHIE_WEBHOOK_EVENTS": ("webhook", "events", _parse_list),
+    "ARCHIE_TOOLS": ("tools", "enabled", _parse_list),
+    "ARCHIE_TOOL_KEYWORD_TRIGGERS": ("tools", "keyword_triggers", _parse_bool),
+    "ARCHIE_ACADEMIC_CALENDAR_URL": ("tools", "academic_calendar_url", str),
+    "ARCHIE_CRAWL_ENABLED": ("crawl", "enabled", _parse_bool),
+    "ARCHIE_CRAWL_MAX_PAGES": ("crawl", "max_pages", int),
+    "ARCHIE_RETRIEVAL_ENABLED": ("retrieval", "enabled", _parse_bool),
+    "ARCHIE_RETRIEVAL_TOP_K": ("retrieval", "top_k", int),
+    "ARCHIE_RERANK": ("retrieval", "rerank", _parse_bool),
+    "ARCHIE_RERANK_MODEL": ("retrieval", "rerank_model", str),
+    "ARCHIE_VERIFICATION": ("verification", "enabled", _parse_bool),
+    "ARCHIE_VERIFICATION_MODEL": ("verification", "model", str),
+    "ARCHIE_VERIFICATION_ACTION": ("verification", "action", str),
+    "ARCHIE_FAQ_ENABLED": ("faq", "enabled", _parse_bool),
+    "ARCHIE_FAQ_SEMANTIC_MATCHING": ("faq", "semantic_matching", _parse_bool),
+    "ARCHIE_MEMORY_ENABLED": ("memory", "enabled", _parse_bool),
+    "ARCHIE_FALLBACK_ENABLED": ("fallback", "enabled", _parse_bool),
+    "ARCHIE_SENTIMENT": ("analytics", "sentiment", _parse_bool),
+    "ARCHIE_GEOIP_DB": ("analytics", "geoip_db", str),
+    "ARCHIE_CAMPUS_NETWORKS": ("analytics", "campus_networks", _parse_list),
+    "ARCHIE_ANONYMIZE_IPS": ("analytics", "anonymize_ips", _parse_bool),
+    "ARCHIE_ANALYTICS_BACKEND": ("analytics", "backend", str),
+    "ARCHIE_DISCORD_ENABLED": ("discord", "enabled", _parse_bool),
+    "DISCORD_BOT_TOKEN": ("discord", "token", str),
+    "ARCHIE_DISCORD_CHANNELS": ("discord", "channels", _parse_list),
+    "ARCHIE_STT_URL": ("stt", "url", str),
+    "ARCHIE_STT_BACKEND": ("stt", "backend", str),
+    "ARCHIE_SMTP_HOST": ("mail", "host", str),
+    "ARCHIE_SMTP_PORT": ("mail", "port", int),
+    "ARCHIE_SMTP_USERNAME": ("mail", "username", str),
+    "ARCHIE_SMTP_PASSWORD": ("mail", "password", str),
+    "ARCHIE_MAIL_FROM": ("mail", "from_address", str),
+    "ARCHIE_DIGEST_ENABLED": ("digest", "enabled", _parse_bool),
+    "ARCHIE_REPORTS_ENABLED": ("reports", "enabled", _parse_bool),
+    "ARCHIE_INTEGRITY_CHECK": ("integrity", "check_on_startup", _parse_bool),
+    "ARCHIE_INTEGRITY_REPAIR": ("integrity", "repair", _parse_bool),
+}
+
+
+def load_config(path: Optional[str] = None) -> Config:
+    """
+    Load configuration from a TOML file and apply environment variable overrides.
+
+    Args:
+        path: Path to the TOML file. Defaults to $ARCHIE_CONFIG, then the first archie.toml found in the
+              project root, systemd's $CONFIGURATION_DIRECTORY, $XDG_CONFIG_HOME/archieai, and /etc/archieai.
+              A missing file is fine; defaults and environment variables are used instead.
+
+    Returns:
+        The populated Config
+    """
+    # Load the variables from the .env file into the environment
+    load_dotenv()
+
+    config = Config()
+
+    if path is None:
+        path = os.getenv("ARCHIE_CONFIG") or next(
+            (candidate for candidate in config_file_candidates() if os.path.exists(candidate)),
+            os.path.join(PROJECT_ROOT, "archie.toml"))
+
+    if os.path.exists(path):
+        with open(path, "rb") as f:
+            data = tomllib.load(f)
+        config.config_file = path
+
+        for section_name, values in data.items():
+            if section_name == "flags" and isinstance(values, dict):
+                # Flag names are free-form, so this table is taken as is
+                config.flags = {name: dict(state) for name, state in values.items() if isinstance(state, dict)}
+                continue
+            section = getattr(config, section_name, None)
+            if section is None or not isinstance(values, dict):
+                logger.warning(f"Unknown config section [{section_name}] in {path}")
+                continue
+            for key, value in values.items():
+                if not hasattr(section, key):
+                    logger.warning(f"Unknown config key {section_name}.{key} in {path}")
+                    continue
+                setattr(section, key, value)
+
+    for env_name, (section_name, key, parser) in ENV_OVERRIDES.items():
+        value = os.getenv(env_name)
+        if value is None or value == "":
+            continue
+        try:
+            setattr(getattr(config, section_name), key, parser(value))
+        except ValueError:
+            logger.warning(f"Ignoring invalid value for {env_name}: {value}")
+
+    # "/archie/", "archie" and "/archie" all mean the same thing; "/" is the root
+    base_path = config.server.base_path.strip().strip("/")
+    config.server.base_path = f"/{base_path}" if base_path else ""
+
+    return config
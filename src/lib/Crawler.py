@@ -0,0 +1,387 @@
+"""
+Site crawler for the knowledge base.
+Pages are discovered from sitemap.xml (the Sitemap: lines in robots.txt, or /sitemap.xml),
+filtered by robots.txt and the configured domains and patterns, and fetched with a
+per-domain concurrency limit and the Crawl-delay from robots.txt (or a default delay),
+so covering the whole site doesn't hammer it.
+Crawls are incremental: the ETag, Last-Modified, and content hash of every page are kept in
+data/crawl_state.json, pages are requested conditionally, and a page whose content hasn't
+changed keeps its previous text instead of being extracted again.
+"""
+import logging
+import os
+import re
+import gzip
+import json
+import time
+import hashlib
+import threading
+import xml.etree.ElementTree as ET
+from collections import Counter
+from concurrent.futures import ThreadPoolExecutor
+from datetime import datetime
+from typing import Callable, Dict, List, Optional, Tuple
+from urllib.parse import urldefrag, urljoin, urlparse
+from urllib.robotparser import RobotFileParser
+
+from lib.DocumentText import DocumentError, extract_text
+from lib.KnowledgeBase import source_type
+
+logger = logging.getLogger(__name__)
+
+CRAWL_STATE_FILE = "crawl_state.json"
+# Nested sitemap indexes followed at most this deep
+MAX_SITEMAP_DEPTH = 3
+# Redirects followed per request before giving up
+MAX_REDIRECTS = 5
+# Content types worth indexing, mapped to the document type extract_text() expects
+_CONTENT_TYPES = {
+    "text/html": "html",
+    "application/xhtml+xml": "html",
+    "application/pdf": "pdf",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document": "docx",
+    "text/plain": "text",
+    "text/markdown": "markdown",
+}
+
+# (url, timeout, request headers) -> (status code, response headers, body); redirects are returned, not followed,
+# so the crawler can check where they lead
+Fetcher = Callable[[str, int, Dict[str, str]], Tuple[int, Dict[str, str], bytes]]
+
+
+def _header(headers: Dict[str, str], name: str) -> str:
+    """Case-insensitive header lookup ("" if missing)."""
+    name = name.lower()
+    return next((value for key, value in headers.items() if key.lower() == name), "")
+
+
+def parse_sitemap(data: bytes) -> Tuple[List[str], List[str]]:
+    """
+    Read a sitemap (plain or gzipped).
+
+    Returns:
+        (page URLs, nested sitemap URLs); both empty if it isn't valid XML
+    """
+    if data[:2] == b"\x1f\x8b":
+        try:
+            data = gzip.decompress(data)
+        except OSError:
+            return [], []
+    try:
+        root = ET.fromstring(data)
+    except ET.ParseError:
+        return [], []
+    pages, sitemaps = [], []
+    for element in root:
+        tag = element.tag.rsplit("}", 1)[-1]
+        loc = next((child.text for child in element if child.tag.rsplit("}", 1)[-1] == "loc"), None)
+        if not loc or not loc.strip():
+            continue
+        if tag == "url":
+            pages.append(loc.strip())
+        elif tag == "sitemap":
+            sitemaps.append(loc.strip())
+    return pages, sitemaps
+
+
+def _default_fetch(user_agent: str) -> Fetcher:
+    """A fetcher over requests, with one session per worker thread."""
+    local = threading.local()
+
+    def fetch(url: str, timeout: int, headers: Dict[str, str]) -> Tuple[int, Dict[str, str], bytes]:
+        if not hasattr(local, "session"):
+            from helpers.scraper import make_session
+            local.session = make_session()
+            local.session.headers["User-Agent"] = user_agent
+        response = local.session.get(url, timeout=timeout, headers=headers, allow_redirects=False)
+        return response.status_code, dict(response.headers), response.content
+
+    return fetch
+
+
+class Crawler:
+    """Sitemap-driven crawler that follows robots.txt."""
+
+    def __init__(self, start_urls: List[str], allowed_domains: Optional[List[str]] = None,
+                 include_patterns: Optional[List[str]] = None, exclude_patterns: Optional[List[str]] = None,
+                 max_pages: int = 300, per_domain_concurrency: int = 2, default_delay: float = 1.0,
+                 user_agent: str = "ArchieAI", timeout: int = 15, fetch: Optional[Fetcher] = None,
+                 state_path: Optional[str] = None):
+        """
+        Args:
+            start_urls: Pages to start from; their sites' sitemaps are read too
+            allowed_domains: Domains to stay on (subdomains included); defaults to the start URLs' hosts
+            include_patterns: Regexes a URL must match one of (empty: any URL)
+            exclude_patterns: Regexes that rule a URL out
+            max_pages: Most pages fetched per crawl
+            per_domain_concurrency: Requests in flight to one host at a time
+            default_delay: Seconds between requests to a host when robots.txt has no Crawl-delay
+            user_agent: Sent with requests and matched against robots.txt rules
+            timeout: Seconds per request
+            fetch: (url, timeout, headers) -> (status, headers, body); defaults to requests
+            state_path: Where ETags and content hashes are kept between crawls (None: every crawl is full)
+        """
+        self.start_urls = list(start_urls)
+        self.allowed_domains = [domain.lower().lstrip(".") for domain in (allowed_domains or [])] or [
+            urlparse(url).hostname or "" for url in self.start_urls
+        ]
+        self.include_patterns = [re.compile(pattern) for pattern in include_patterns or []]
+        self.exclude_patterns = [re.compile(pattern) for pattern in exclude_patterns or []]
+        self.max_pages = max_pages
+        self.per_domain_concurrency = max(1, per_domain_concurrency)
+        self.default_delay = default_delay
+        self.user_agent = user_agent
+        self.timeout = timeout
+        self.fetch = fetch or _default_fetch(user_agent)
+        self.state_path = state_path
+        # Page counts by outcome for the last crawl: changed, unchanged, not_modified, failed
+        self.last_stats: Dict[str, int] = {}
+        # URL -> {"etag", "last_modified", "hash", "type", "checked_at"} for the last crawl's pages
+        self.pages: Dict[str, Dict] = {}
+        self._lock = threading.Lock()
+        self._robots: Dict[str, RobotFileParser] = {}
+        self._semaphores: Dict[str, threading.Semaphore] = {}
+        self._next_request: Dict[str, float] = {}
+
+    @classmethod
+    def from_config(cls, crawl, data_dir: str = "data") -> Optional["Crawler"]:
+        """A crawler for the [crawl] config section, or None when crawling is off."""
+        if not crawl.enabled or not crawl.start_urls:
+            return None
+        return cls(
+            start_urls=crawl.start_urls,
+            allowed_domains=crawl.allowed_domains,
+            include_patterns=crawl.include_patterns,
+            exclude_patterns=crawl.exclude_patterns,
+            max_pages=crawl.max_pages,
+            per_domain_concurrency=crawl.per_domain_concurrency,
+            default_delay=crawl.default_delay,
+            user_agent=crawl.user_agent,
+            timeout=crawl.timeout,
+            state_path=os.path.join(data_dir, CRAWL_STATE_FILE),
+        )
+
+    def _get(self, url: str, headers: Optional[Dict[str, str]] = None) -> Tuple[int, Dict[str, str], bytes]:
+        """Fetch a URL within its host's concurrency limit and crawl delay."""
+        host = (urlparse(url).hostname or "").lower()
+        robots = self.robots(url)
+        delay = robots.crawl_delay(self.user_agent)
+        rate = robots.request_rate(self.user_agent)
+        if delay is None and rate is not None and rate.requests:
+            delay = rate.seconds / rate.requests
+        if delay is None:
+            delay = self.default_delay
+
+        with self._lock:
+            semaphore = self._semaphores.setdefault(host, threading.Semaphore(self.per_domain_concurrency))
+            # Reserve the next slot for this host, then wait for it outside the lock
+            now = time.monotonic()
+            slot = max(now, self._next_request.get(host, now))
+            self._next_request[host] = slot + float(delay)
+        if slot > now:
+            time.sleep(slot - now)
+        with semaphore:
+            return self.fetch(url, self.timeout, headers or {})
+
+    def _follow(self, url: str, check: Callable[[str], bool], get: Callable,
+                headers: Optional[Dict[str, str]] = None) -> Tuple[int, Dict[str, str], bytes]:
+        """
+        Fetch a URL with get(url, headers), following redirects only to URLs that pass check.
+
+        Raises:
+            ValueError: A redirect leads somewhere check refuses, has no Location, or there are too many
+        """
+        for _ in range(MAX_REDIRECTS + 1):
+            status, response_headers, body = get(url, headers)
+            if status not in (301, 302, 303, 307, 308):
+                return status, response_headers, body
+            location = _header(response_headers, "Location")
+            if not location:
+                raise ValueError(f"HTTP {status} without a Location")
+            target = urldefrag(urljoin(url, location))[0]
+            if not check(target):
+                raise ValueError(f"redirected to {target}, which isn't allowed")
+            url = target
+        raise ValueError(f"more than {MAX_REDIRECTS} redirects")
+
+    def robots(self, url: str) -> RobotFileParser:
+        """The parsed robots.txt for a URL's site (fetched once per crawler; missing means allow all)."""
+        parts = urlparse(url)
+        origin = f"{parts.scheme}://{parts.netloc}"
+        with self._lock:
+            if origin in self._robots:
+                return self._robots[origin]
+        parser = RobotFileParser(origin + "/robots.txt")
+        try:
+            # Redirects (http to https, say) are followed as long as they stay on the allowed domains
+            status, _, body = self._follow(origin + "/robots.txt", self.on_allowed_domain,
+                                           lambda url, headers: self.fetch(url, self.timeout, headers or {}))
+        except Exception as e:
+            logger.warning(f"Could not fetch {origin}/robots.txt, assuming no restrictions: {e}")
+            status, body = 404, b""
+        if status in (401, 403):
+            # Same as urllib.robotparser: an access-controlled robots.txt means stay out
+            parser.disallow_all = True
+        elif status < 400:
+            parser.parse(body.decode("utf-8", errors="replace").splitlines())
+        else:
+            parser.allow_all = True
+        with self._lock:
+            return self._robots.setdefault(origin, parser)
+
+    def on_allowed_domain(self, url: str) -> bool:
+        """Whether a URL is http(s) on one of the allowed domains."""
+        parts = urlparse(url)
+        host = (parts.hostname or "").lower()
+        if parts.scheme not in ("http", "https"):
+            return False
+        return any(host == domain or host.endswith("." + domain) for domain in self.allowed_domains)
+
+    def allowed(self, url: str) -> bool:
+        """Whether a URL is on an allowed domain, passes the patterns, and isn't disallowed by robots.txt."""
+        if not self.on_allowed_domain(url):
+            return False
+        if self.include_patterns and not any(pattern.search(url) for pattern in self.include_patterns):
+            return False
+        if any(pattern.search(url) for pattern in self.exclude_patterns):
+            return False
+        return self.robots(url).can_fetch(self.user_agent, url)
+
+    def discover(self) -> List[str]:
+        """
+        Every allowed page URL from the start URLs and their sites' sitemaps, up to max_pages.
+        """
+        urls: List[str] = []
+        seen = set()
+
+        def add(url: str):
+            url = urldefrag(url.strip())[0]
+            if url not in seen and len(urls) < self.max_pages and self.allowed(url):
+                seen.add(url)
+                urls.append(url)
+
+        for url in self.start_urls:
+            add(url)
+
+        pending: List[Tuple[str, int]] = []
+        for origin in dict.fromkeys(f"{urlparse(url).scheme}://{urlparse(url).netloc}" for url in self.start_urls):
+            sitemaps = self.robots(origin).site_maps() or [urljoin(origin, "/sitemap.xml")]
+            pending.extend((sitemap, 0) for sitemap in sitemaps)
+
+        visited = set()
+        while pending and len(urls) < self.max_pages:
+            sitemap, depth = pending.pop(0)
+            # robots.txt and sitemap indexes can point anywhere; only read sitemaps on the allowed domains
+            if sitemap in visited or depth > MAX_SITEMAP_DEPTH or not self.on_allowed_domain(sitemap):
+                continue
+            visited.add(sitemap)
+            try:
+                status, _, body = self._follow(sitemap, self.on_allowed_domain, self._get)
+            except Exception as e:
+                logger.warning(f"Could not fetch sitemap {sitemap}: {e}")
+                continue
+            if status >= 400:
+                continue
+            pages, nested = parse_sitemap(body)
+            for page in pages:
+                add(page)
+            pending.extend((url, depth + 1) for url in nested)
+        return urls
+
+    def _load_state(self) -> Dict[str, Dict]:
+        if not self.state_path:
+            return {}
+        try:
+            with open(self.state_path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {}
+
+    def _save_state(self, state: Dict[str, Dict]):
+        if not self.state_path:
+            return
+        os.makedirs(os.path.dirname(self.state_path) or ".", exist_ok=True)
+        tmp_path = self.state_path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(state, f, indent=2)
+        os.replace(tmp_path, self.state_path)
+
+    def _visit(self, url: str, entry: Dict, previous_text: Optional[str]) -> Tuple[Optional[str], Dict, str]:
+        """
+        Fetch one page, conditionally when there's text from the last crawl to fall back on.
+
+        Returns:
+            (text, new state entry, outcome: changed, unchanged, not_modified, or failed)
+        """
+        headers = {}
+        if previous_text is not None:
+            if entry.get("etag"):
+                headers["If-None-Match"] = entry["etag"]
+            if entry.get("last_modified"):
+                headers["If-Modified-Since"] = entry["last_modified"]
+        try:
+            # A redirect has to pass the same domain, pattern, and robots.txt checks as the page itself
+            status, response_headers, body = self._follow(url, self.allowed, self._get, headers)
+        except Exception as e:
+            logger.warning(f"Could not crawl {url}: {e}")
+            # Keep what we had rather than dropping the page over a blip
+            return previous_text, entry, "failed"
+        if status == 304 and previous_text is not None:
+            return previous_text, {**entry, "checked_at": datetime.now().isoformat(timespec="seconds")}, "not_modified"
+        if status >= 300:
+            logger.warning(f"Could not crawl {url}: HTTP {status}")
+            return previous_text, entry, "failed"
+
+        new_entry = {
+            "etag": _header(response_headers, "ETag"),
+            "last_modified": _header(response_headers, "Last-Modified"),
+            "hash": hashlib.sha256(body).hexdigest(),
+            "type": entry.get("type"),
+            "checked_at": datetime.now().isoformat(timespec="seconds"),
+        }
+        if previous_text is not None and new_entry["hash"] == entry.get("hash"):
+            return previous_text, new_entry, "unchanged"
+
+        content_type = _header(response_headers, "Content-Type")
+        document_type = _CONTENT_TYPES.get(content_type.split(";", 1)[0].strip().lower())
+        if document_type is None:
+            # Unknown or missing content type: go by the URL, but skip anything that isn't a document
+            if content_type:
+                return None, new_entry, "changed"
+            document_type = source_type(url)
+        new_entry["type"] = document_type
+        try:
+            return extract_text(body, document_type), new_entry, "changed"
+        except DocumentError as e:
+            logger.warning(f"Skipping {url}: {e}")
+            return None, new_entry, "failed"
+
+    def crawl(self, previous: Optional[Dict[str, str]] = None, full: bool = False) -> Dict[str, str]:
+        """
+        Discover and fetch the site.
+
+        Args:
+            previous: URL -> text from the last crawl; pages that haven't changed keep it
+            full: Ignore the saved ETags and hashes and extract every page again
+
+        Returns:
+            URL -> extracted text, for the pages that had any
+        """
+        previous = {} if full else (previous or {})
+        state = {} if full else self._load_state()
+        urls = self.discover()
+        hosts = {urlparse(url).hostname for url in urls}
+        workers = max(1, min(len(urls), self.per_domain_concurrency * len(hosts)))
+        logger.info(f"Crawling {len(urls)} pages on {len(hosts)} host(s)")
+
+        def visit(url):
+            return self._visit(url, state.get(url, {}), previous.get(url))
+
+        with ThreadPoolExecutor(max_workers=workers) as pool:
+            visits = list(pool.map(visit, urls))
+
+        # Pages no longer in the sitemap drop out of the state with the crawl results
+        self.pages = {url: entry for url, (_, entry, _) in zip(urls, visits) if entry}
+        self._save_state(self.pages)
+        self.last_stats = dict(Counter(outcome for _, _, outcome in visits))
+        return {url: text for url, (text, _, _) in zip(urls, visits) if text}
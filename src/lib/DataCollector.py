@@ -1,32 +1,137 @@
 """
 Data collection module for ArchieAI analytics.
-Collects interaction data and saves to JSON for later analysis.
+Collects interaction data and saves it for later analysis, to analytics.json or SQLite
+(see lib/AnalyticsStore.py).
 """
+import logging
 import os
 import json
-from datetime import datetime
-from typing import Optional
+import re
+import threading
+import csv
+import io
+from datetime import datetime, timedelta
+from typing import Callable, Optional, Dict, List, Iterator
+from collections import Counter, defaultdict
+from statistics import median
+from lib.QuestionCategorizer import categorize_question
+from lib.Sentiment import score_sentiment, sentiment_label
+from lib.GeoIp import GeoLocator, anonymize_ip
+from lib.AnalyticsStore import open_store
+
+logger = logging.getLogger(__name__)
+
 "For the data science class I will probably remove this when the semester ends but for now it will help me collect data on how people are using ArchieAI "
 "and i will manipulate the data to find trends for my project"
 
+# Requests turned away by the rate limiter (lib/RateLimiter.py), one line each
+THROTTLE_FILE = "throttle_events.jsonl"
+
+# Phrases that mean the model didn't actually answer the question
+UNANSWERED_PATTERNS = [
+    r"\bi don'?t know\b",
+    r"\bi do not know\b",
+    r"\bi'?m not sure\b",
+    r"\bi am not sure\b",
+    r"\bi don'?t have (any )?(information|details|data)\b",
+    r"\bi do not have (any )?(information|details|data)\b",
+    r"\b(couldn'?t|could not|was unable to|am unable to|can'?t|cannot) find\b",
+    r"\bunable to (answer|help with that)\b",
+    r"\bno information (is )?available\b",
+    r"\bcheck with the (university|relevant office)\b",
+]
+
+
+def normalize_question(question: str) -> str:
+    """Normalize a question for grouping (lowercase, no punctuation, single spaces)."""
+    cleaned = re.sub(r"[^\w\s]", " ", question.lower())
+    return " ".join(cleaned.split())
+
+
+def is_unanswered(answer: str) -> bool:
+    """Return True if an answer amounts to "I don't know", is empty, or is an error."""
+    if not answer or not answer.strip():
+        return True
+    text = answer.strip().lower()
+    if text.startswith("error") or text.startswith("an error occurred"):
+        return True
+    return any(re.search(pattern, text) for pattern in UNANSWERED_PATTERNS)
+
+
+# Export format -> MIME type
+EXPORT_FORMATS = {
+    "json": "application/json",
+    "jsonl": "application/x-ndjson",
+    "csv": "text/csv",
+}
+
+EXPORT_CSV_FIELDS = [
+    "timestamp", "request_id", "session_id", "user_email", "ip_address", "device_info",
+    "question", "question_length", "answer", "answer_length", "category",
+    "sentiment", "unanswered", "feedback", "generation_time_seconds", "answer_source", "faq_entry_id",
+    "interrupted", "model", "country", "region", "on_campus", "experiment_id", "experiment_arm"
+]
+
+# Chart series: metric -> what's counted (or averaged) per bucket
+TIMESERIES_METRICS = {
+    "questions": "questions asked",
+    "unanswered": "questions Archie couldn't answer",
+    "faq_answers": "questions answered by a curated FAQ entry",
+    "interrupted": "answers cut off by a disconnect",
+    "users": "distinct signed-in users",
+    "sessions": "distinct sessions",
+    "generation_time": "average generation time in seconds",
+    "sentiment": "average question sentiment",
+}
+
+# Bucket interval -> (bucket length, buckets shown when no range is given)
+TIMESERIES_INTERVALS = {
+    "hour": (timedelta(hours=1), 48),
+    "day": (timedelta(days=1), 30),
+    "week": (timedelta(weeks=1), 12),
+}
+
+MAX_TIMESERIES_BUCKETS = 2000
+
+
+def bucket_start(timestamp: datetime, interval: str) -> datetime:
+    """Start of the hour, day, or ISO week (Monday) a timestamp falls in."""
+    if interval == "hour":
+        return timestamp.replace(minute=0, second=0, microsecond=0)
+    day = timestamp.replace(hour=0, minute=0, second=0, microsecond=0)
+    return day - timedelta(days=day.weekday()) if interval == "week" else day
+
+
 class DataCollector:
-    """Collects and logs interaction data to JSON file."""
+    """Collects and logs interaction data to the configured analytics store."""
     
-    def __init__(self, data_dir: str = "data"):
+    def __init__(self, data_dir: str = "data", config=None):
+        if config is not None:
+            data_dir = config.data_dir
+        
         self.data_dir = data_dir
-        self.json_file = os.path.join(data_dir, "analytics.json")
+        # Sentiment scoring can be turned off with [analytics] sentiment = false
+        self.sentiment_enabled = config.analytics.sentiment if config is not None else True
+        # Coarse location of each question (None when no GeoIP database or campus networks are configured)
+        self.geo = GeoLocator.from_config(config) if config is not None else None
+        self.anonymize_ips = config.analytics.anonymize_ips if config is not None else False
+        # A read-only replica reads the primary's log and never writes it (lib/InstanceLock.py)
+        self.read_only = config.instance.role == "replica" if config is not None else False
+        # Called with each interaction once it's logged (the admin live feed, see lib/InteractionTail.py)
+        self._listeners: List[Callable[[Dict], None]] = []
         
         # Ensure data directory exists
-        os.makedirs(self.data_dir, exist_ok=True)
+        if not self.read_only:
+            os.makedirs(self.data_dir, exist_ok=True)
         
-        # Initialize JSON file with empty array if it doesn't exist
-        if not os.path.exists(self.json_file):
-            self._create_json_file()
+        # analytics.json or analytics.db; switching to SQLite imports the JSON log
+        self.store = open_store(config.analytics.backend if config is not None else "json", data_dir, read_only=self.read_only)
+        self.throttle_path = os.path.join(self.data_dir, THROTTLE_FILE)
+        self._throttle_lock = threading.Lock()
     
-    def _create_json_file(self):
-        """Create JSON file with empty array."""
-        with open(self.json_file, "w", encoding="utf-8") as f:
-            json.dump([], f, ensure_ascii=False, indent=2)
+    def add_listener(self, listener: Callable[[Dict], None]):
+        """Register a callback for every interaction logged from now on."""
+        self._listeners.append(listener)
     
     def log_interaction(
         self,
@@ -36,26 +141,46 @@ class DataCollector:
         device_info: str,
         question: str,
         answer: str,
-        generation_time_seconds: float
+        generation_time_seconds: float,
+        request_id: Optional[str] = None,
+        answer_source: str = "model",
+        faq_entry_id: Optional[str] = None,
+        interrupted: bool = False,
+        model: Optional[str] = None,
+        experiment: Optional[Dict] = None
     ):
         """
-        Log a user interaction to the JSON file.
+        Log a user interaction to the analytics store.
         
         Args:
             session_id: Unique session identifier
             user_email: User's email (None for guests)
-            ip_address: User's IP address
+            ip_address: User's IP address (its location is logged too, and it's stored anonymized
+                        with [analytics] anonymize_ips)
             device_info: User agent string
             question: User's question
             answer: AI's answer
             generation_time_seconds: Time taken to generate the answer
+            request_id: ID of the HTTP request, matches the X-Request-Id header and server logs
+            answer_source: "model", "faq" when a curated FAQ entry answered instead, or "fallback" when
+                           the model was unreachable and the limited-mode message was sent (counts as unanswered)
+            faq_entry_id: The FAQ entry that answered, if any
+            interrupted: The client disconnected mid-stream, so answer is only what was generated by then
+            model: The chat model that wrote the answer (None when it didn't come from a model)
+            experiment: {"id", "arm"} of the canary rollout the answer was part of (lib/Canary.py), if any
         """
         timestamp = datetime.now().isoformat()
         question_length = len(question)
         answer_length = len(answer)
+        category = categorize_question(question)
+        sentiment = score_sentiment(question) if self.sentiment_enabled else None
+        location = self.geo.locate(ip_address) if self.geo is not None else None
+        if self.anonymize_ips:
+            ip_address = anonymize_ip(ip_address)
         
         interaction = {
             "timestamp": timestamp,
+            "request_id": request_id,
             "session_id": session_id,
             "user_email": user_email if user_email else "guest",
             "ip_address": ip_address,
@@ -64,20 +189,505 @@ class DataCollector:
             "question_length": question_length,
             "answer": answer,
             "answer_length": answer_length,
-            "generation_time_seconds": round(generation_time_seconds, 2)
+            "category": category,
+            "sentiment": sentiment,
+            "unanswered": answer_source == "fallback" or is_unanswered(answer),
+            "generation_time_seconds": round(generation_time_seconds, 2),
+            "answer_source": answer_source,
+            "faq_entry_id": faq_entry_id,
+            "interrupted": interrupted,
+            "model": model,
+            "location": location,
+            "experiment": experiment
         }
         
-        # Read existing data
+        self.store.append(interaction)
+        
+        for listener in list(self._listeners):
+            try:
+                listener(interaction)
+            except Exception as e:
+                # A broken listener shouldn't break logging
+                logger.warning(f"Analytics listener failed: {e}")
+
+    def log_throttle(self, scope: str, session_id: Optional[str], user_email: Optional[str], ip_address: Optional[str],
+                     path: str, retry_after_seconds: float, request_id: Optional[str] = None) -> Dict:
+        """
+        Log a caller being rate limited.
+
+        Args:
+            scope: The bucket that ran dry, "session" or "ip"
+            path: The request path that was refused
+            retry_after_seconds: How long until the caller may send again
+        """
+        event = {
+            "timestamp": datetime.now().isoformat(),
+            "request_id": request_id,
+            "scope": scope,
+            "session_id": session_id,
+            "user_email": user_email if user_email else "guest",
+            "ip_address": anonymize_ip(ip_address) if self.anonymize_ips and ip_address else ip_address,
+            "path": path,
+            "retry_after_seconds": round(retry_after_seconds, 1),
+        }
+        if self.read_only:
+            # Replicas don't write the data directory; the server log still shows it
+            return event
+        with self._throttle_lock, open(self.throttle_path, "a", encoding="utf-8") as f:
+            f.write(json.dumps(event, ensure_ascii=False) + "\n")
+        return event
+
+    def close(self):
+        """Let writes in progress finish and drop any after this; called on shutdown (lib/Shutdown.py)."""
+        with self._throttle_lock:
+            self.read_only = True
+        self.store.close()
+
+    def throttle_events(self, limit: int = 100, since: Optional[str] = None) -> List[Dict]:
+        """Logged throttle events, newest first, optionally since an ISO time."""
+        events = []
         try:
-            with open(self.json_file, "r", encoding="utf-8") as f:
-                data = json.load(f)
-        except (FileNotFoundError, json.JSONDecodeError):
-            data = []
+            with open(self.throttle_path, "r", encoding="utf-8") as f:
+                for line in f:
+                    try:
+                        event = json.loads(line)
+                    except json.JSONDecodeError:
+                        continue
+                    if since and event.get("timestamp", "") < since:
+                        continue
+                    events.append(event)
+        except FileNotFoundError:
+            return []
+        events.reverse()
+        return events[:limit] if limit > 0 else events
+
+    def record_feedback(self, request_id: str, rating: Optional[str]) -> bool:
+        """
+        Set (or with None, clear) the user's rating on the interaction logged for a request.
+
+        Returns:
+            False if no interaction was logged with that request ID
+        """
+        return self.store.set_feedback(request_id, rating)
+
+    def load_interactions(self) -> List[Dict]:
+        """Load all logged interactions."""
+        return self.store.load()
+
+    def iter_interactions(
+        self,
+        start: Optional[str] = None,
+        end: Optional[str] = None,
+        user_email: Optional[str] = None
+    ) -> Iterator[Dict]:
+        """
+        Stream interactions one at a time, optionally filtered, so large logs never sit fully in memory.
+        
+        Args:
+            start: Only include interactions at or after this ISO date/datetime
+            end: Only include interactions at or before this ISO date/datetime (dates are inclusive)
+            user_email: Only include interactions from this user
+        """
+        return self.store.iter(start=start, end=end, user_email=user_email)
+
+    def export_interactions(
+        self,
+        export_format: str = "json",
+        start: Optional[str] = None,
+        end: Optional[str] = None,
+        user_email: Optional[str] = None
+    ) -> Iterator[str]:
+        """
+        Serialize a filtered set of interactions as text chunks, without loading everything at once.
+        
+        Args:
+            export_format: One of EXPORT_FORMATS ("json", "jsonl", "csv")
+            start, end, user_email: Filters, same as iter_interactions
+        """
+        if export_format not in EXPORT_FORMATS:
+            raise ValueError(f"Unknown export format: {export_format}")
+        
+        interactions = self.iter_interactions(start=start, end=end, user_email=user_email)
+        
+        if export_format == "json":
+            yield "["
+            first = True
+            for interaction in interactions:
+                yield ("" if first else ",") + json.dumps(interaction, ensure_ascii=False)
+                first = False
+            yield "]"
+        elif export_format == "jsonl":
+            for interaction in interactions:
+                yield json.dumps(interaction, ensure_ascii=False) + "\n"
+        else:
+            buffer = io.StringIO()
+            writer = csv.DictWriter(buffer, fieldnames=EXPORT_CSV_FIELDS, extrasaction="ignore")
+            writer.writeheader()
+            for interaction in interactions:
+                # The location and experiment are flattened into their own columns
+                experiment = interaction.get("experiment") or {}
+                writer.writerow({**interaction, **(interaction.get("location") or {}),
+                                 "experiment_id": experiment.get("id"), "experiment_arm": experiment.get("arm")})
+                yield buffer.getvalue()
+                buffer.seek(0)
+                buffer.truncate(0)
+            yield buffer.getvalue()
+
+    def get_aggregate_stats(self, interactions: Optional[List[Dict]] = None) -> Dict:
+        """
+        Compute aggregate statistics over all logged interactions (or the given ones, e.g. one
+        day's for the rollups in lib/AnalyticsReports.py).
+        
+        Returns:
+            Dict with total question count, average generation time, unanswered
+            count, curated FAQ answer count, per-category and per-day breakdowns, on/off-campus and
+            per-country counts, engagement metrics, and sentiment metrics (None when sentiment scoring is off).
+        """
+        if interactions is None:
+            interactions = self.load_interactions()
+        total= len(interactions)
+        
+        # Older records were logged before categorization existed, so tag them on the fly
+        categories = Counter(
+            i.get("category") or categorize_question(i.get("question", ""))
+            for i in interactions
+        )
         
-        # Append new interaction
-        data.append(interaction)
+        unanswered = sum(
+            1 for i in interactions
+            if i.get("unanswered", is_unanswered(i.get("answer", "")))
+        )
         
-        # Write back to file
-        with open(self.json_file, "w", encoding="utf-8") as f:
-            json.dump(data, f, ensure_ascii=False, indent=2)
+        questions_per_day = Counter(i.get("timestamp", "")[:10] for i in interactions if i.get("timestamp"))
+        
+        faq_answers = sum(1 for i in interactions if i.get("answer_source") == "faq")
+        fallback_answers = sum(1 for i in interactions if i.get("answer_source") == "fallback")
+        interrupted_answers = sum(1 for i in interactions if i.get("interrupted"))
+        models = Counter(i["model"] for i in interactions if i.get("model"))
+        located = [i["location"] for i in interactions if i.get("location")]
+        countries = Counter(location["country"] for location in located if location.get("country"))
+        on_campus = sum(1 for location in located if location.get("on_campus"))
+        
+        generation_times = [i.get("generation_time_seconds", 0) for i in interactions]
+        avg_generation_time = round(sum(generation_times) / total, 2) if total else 0
+        
+        return {
+            "total_questions": total,
+            "avg_generation_time_seconds": avg_generation_time,
+            "unanswered_questions": unanswered,
+            "faq_answers": faq_answers,
+            "fallback_answers": fallback_answers,
+            "interrupted_answers": interrupted_answers,
+            "models": dict(models.most_common()),
+            "categories": dict(categories.most_common()),
+            "questions_per_day": dict(sorted(questions_per_day.items())),
+            "locations": {
+                "on_campus": on_campus,
+                "off_campus": len(located) - on_campus,
+                "countries": dict(countries.most_common()),
+            },
+            "engagement": self.get_engagement_metrics(interactions),
+            "sentiment": self.get_sentiment_metrics(interactions) if self.sentiment_enabled else None
+        }
+
+    def get_summary(self, start: Optional[str] = None, end: Optional[str] = None,
+                    user_email: Optional[str] = None, top_users: int = 10) -> Dict:
+        """
+        Headline numbers for a date range in one pass over the log (nothing is held in memory):
+        question count, average generation time, unanswered count, distinct users and sessions,
+        questions per day, and the signed-in users who asked the most (guests aren't ranked).
+        Filters are as for iter_interactions().
+        """
+        total, unanswered, generation_time = 0, 0, 0.0
+        per_day, per_user, sessions = Counter(), Counter(), set()
+        first = last = None
+        for i in self.iter_interactions(start=start, end=end, user_email=user_email):
+            total += 1
+            generation_time += i.get("generation_time_seconds", 0) or 0
+            if i.get("unanswered", is_unanswered(i.get("answer", ""))):
+                unanswered += 1
+            timestamp = i.get("timestamp", "")
+            if timestamp:
+                per_day[timestamp[:10]] += 1
+                first = timestamp if first is None else min(first, timestamp)
+                last = timestamp if last is None else max(last, timestamp)
+            if i.get("user_email") and i["user_email"] != "guest":
+                per_user[i["user_email"]] += 1
+            if i.get("session_id"):
+                sessions.add(i["session_id"])
+        return {
+            "total_questions": total,
+            "avg_generation_time_seconds": round(generation_time / total, 2) if total else 0,
+            "unanswered_questions": unanswered,
+            "distinct_users": len(per_user),
+            "distinct_sessions": len(sessions),
+            "first_question_at": first,
+            "last_question_at": last,
+            "questions_per_day": dict(sorted(per_day.items())),
+            "top_users": [{"user_email": email, "questions": count} for email, count in per_user.most_common(top_users)],
+        }
+
+    def query_interactions(self, start: Optional[str] = None, end: Optional[str] = None,
+                           user_email: Optional[str] = None, limit: int = 50, offset: int = 0) -> Dict:
+        """A page of the interactions matching the filters, oldest first, and how many match in all."""
+        page, total = [], 0
+        for interaction in self.iter_interactions(start=start, end=end, user_email=user_email):
+            if offset <= total < offset + limit:
+                page.append(interaction)
+            total += 1
+        return {"interactions": page, "total": total, "offset": offset, "limit": limit,
+                "has_more": offset + len(page) < total}
 
+    def get_recent_interactions(self, limit: int = 50) -> List[Dict]:
+        """Return the most recent interactions, newest first."""
+        return list(reversed(self.store.recent(limit)))
+
+    def get_engagement_metrics(self, interactions: Optional[List[Dict]] = None) -> Dict:
+        """
+        Compute session duration, gaps between turns, and return visits from interaction timestamps.
+        
+        A session's duration is the wall-clock time between its first and last question.
+        A return visit is any session after a user's first one.
+        
+        Args:
+            interactions: Interactions to analyze (defaults to everything logged)
+        """
+        if interactions is None:
+            interactions = self.load_interactions()
+        
+        session_times = defaultdict(list)
+        user_sessions = defaultdict(set)
+        for interaction in interactions:
+            try:
+                timestamp = datetime.fromisoformat(interaction["timestamp"])
+            except (KeyError, ValueError):
+                continue
+            session_id = interaction.get("session_id", "no_session")
+            if session_id == "no_session":
+                continue
+            session_times[session_id].append(timestamp)
+            user = interaction.get("user_email", "guest")
+            if user != "guest":
+                user_sessions[user].add(session_id)
+        
+        durations = []
+        gaps = []
+        for times in session_times.values():
+            times.sort()
+            durations.append((times[-1] - times[0]).total_seconds())
+            gaps.extend((b - a).total_seconds() for a, b in zip(times, times[1:]))
+        
+        return_visits = [len(sessions) - 1 for sessions in user_sessions.values()]
+        returning_users = sum(1 for visits in return_visits if visits > 0)
+        
+        def avg(values):
+            return round(sum(values) / len(values), 2) if values else 0
+        
+        return {
+            "sessions": len(session_times),
+            "avg_session_duration_seconds": avg(durations),
+            "median_session_duration_seconds": round(median(durations), 2) if durations else 0,
+            "avg_turns_per_session": avg([len(t) for t in session_times.values()]),
+            "avg_gap_between_turns_seconds": avg(gaps),
+            "median_gap_between_turns_seconds": round(median(gaps), 2) if gaps else 0,
+            "users": len(user_sessions),
+            "returning_users": returning_users,
+            "return_rate": round(returning_users / len(user_sessions), 3) if user_sessions else 0,
+            "avg_return_visits_per_user": avg(return_visits)
+        }
+
+    def get_sentiment_metrics(self, interactions: Optional[List[Dict]] = None) -> Dict:
+        """
+        Summarize question sentiment: the average score, label counts, the average per day,
+        and how many sessions had two or more negative questions (a sign of frustration).
+        
+        Args:
+            interactions: Interactions to analyze (defaults to everything logged)
+        """
+        if interactions is None:
+            interactions = self.load_interactions()
+        
+        labels = Counter({"positive": 0, "neutral": 0, "negative": 0})
+        scores = []
+        day_scores = defaultdict(list)
+        negative_per_session = Counter()
+        for interaction in interactions:
+            score = interaction.get("sentiment")
+            if score is None:
+                # Logged before sentiment scoring or while it was off
+                score = score_sentiment(interaction.get("question", ""))
+            label = sentiment_label(score)
+            labels[label] += 1
+            scores.append(score)
+            if interaction.get("timestamp"):
+                day_scores[interaction["timestamp"][:10]].append(score)
+            session_id = interaction.get("session_id", "no_session")
+            if label == "negative" and session_id != "no_session":
+                negative_per_session[session_id] += 1
+        
+        def avg(values):
+            return round(sum(values) / len(values), 3) if values else 0
+        
+        return {
+            "average": avg(scores),
+            "labels": dict(labels),
+            "per_day": {day: avg(values) for day, values in sorted(day_scores.items())},
+            "negative_rate": round(labels["negative"] / len(scores), 3) if scores else 0,
+            "frustrated_sessions": sum(1 for count in negative_per_session.values() if count >= 2)
+        }
+
+    def get_top_questions(self, start: Optional[str] = None, limit: int = 20) -> Dict:
+        """
+        The most asked questions (grouped after normalization) with answer-quality signals,
+        ranked by how many asks the curated FAQ could have answered instead of the model.
+        
+        Args:
+            start: Only count interactions at or after this ISO datetime
+            limit: Maximum number of questions to return
+        
+        Returns:
+            Dict with the total question count and, per question: count, share of all questions,
+            distinct users, unanswered count and rate, thumbs up/down, asks answered by the FAQ,
+            and cache_hit_potential (the share of all questions a FAQ entry for it would have answered)
+        """
+        total = 0
+        groups: Dict[str, Dict] = {}
+        for interaction in self.iter_interactions(start=start):
+            total += 1
+            question = interaction.get("question", "")
+            key = normalize_question(question)
+            if not key:
+                continue
+            
+            group = groups.setdefault(key, {
+                "question": question,
+                "normalized": key,
+                "count": 0,
+                "users": set(),
+                "unanswered": 0,
+                "feedback": {"up": 0, "down": 0},
+                "faq_answers": 0,
+                "category": interaction.get("category") or categorize_question(question),
+                "last_asked": None
+            })
+            group["count"] += 1
+            group["users"].add(interaction.get("user_email", "guest"))
+            flagged = interaction.get("unanswered")
+            if flagged is None:
+                flagged = is_unanswered(interaction.get("answer", ""))
+            group["unanswered"] += bool(flagged)
+            if interaction.get("feedback") in group["feedback"]:
+                group["feedback"][interaction["feedback"]] += 1
+            if interaction.get("answer_source") == "faq":
+                group["faq_answers"] += 1
+            if interaction.get("timestamp", "") > (group["last_asked"] or ""):
+                group["last_asked"] = interaction.get("timestamp")
+        
+        report = []
+        for group in groups.values():
+            # Asks after the first one that weren't already answered from the FAQ
+            cacheable = max(0, group["count"] - group["faq_answers"] - 1)
+            group["unique_users"] = len(group.pop("users"))
+            group["share"] = round(group["count"] / total, 3)
+            group["unanswered_rate"] = round(group["unanswered"] / group["count"], 3)
+            group["cache_hit_potential"] = round(cacheable / total, 3)
+            group["cacheable_asks"] = cacheable
+            report.append(group)
+        
+        report.sort(key=lambda g: (g["cacheable_asks"], g["count"], g["unanswered"]), reverse=True)
+        return {"total_questions": total, "questions": report[:limit]}
+
+    def get_timeseries(self, metric: str, interval: str, start: datetime, end: datetime) -> List[Dict]:
+        """
+        Bucket the interactions between start and end into a chart series, so the dashboard
+        doesn't have to download them.
+        
+        Args:
+            metric: One of TIMESERIES_METRICS
+            interval: One of TIMESERIES_INTERVALS
+            start, end: The range covered (end inclusive)
+        
+        Returns:
+            One {"start", "value"} per bucket, oldest first, including empty buckets (0 for
+            counts, None for averages with nothing to average)
+        """
+        step, _ = TIMESERIES_INTERVALS[interval]
+        values = defaultdict(list)
+        for interaction in self.iter_interactions(start=start.isoformat(), end=end.isoformat()):
+            try:
+                timestamp = datetime.fromisoformat(interaction["timestamp"])
+            except (KeyError, ValueError):
+                continue
+            key = bucket_start(timestamp, interval)
+            if metric == "questions":
+                values[key].append(1)
+            elif metric == "unanswered":
+                flagged = interaction.get("unanswered")
+                if flagged is None:
+                    flagged = is_unanswered(interaction.get("answer", ""))
+                values[key].append(1 if flagged else 0)
+            elif metric == "faq_answers":
+                values[key].append(1 if interaction.get("answer_source") == "faq" else 0)
+            elif metric == "interrupted":
+                values[key].append(1 if interaction.get("interrupted") else 0)
+            elif metric == "users":
+                if interaction.get("user_email", "guest") != "guest":
+                    values[key].append(interaction["user_email"])
+            elif metric == "sessions":
+                if interaction.get("session_id", "no_session") != "no_session":
+                    values[key].append(interaction["session_id"])
+            elif metric == "generation_time":
+                values[key].append(interaction.get("generation_time_seconds", 0))
+            elif metric == "sentiment":
+                if interaction.get("sentiment") is not None:
+                    values[key].append(interaction["sentiment"])
+        
+        def value(bucket: list):
+            if metric in ("users", "sessions"):
+                return len(set(bucket))
+            if metric in ("generation_time", "sentiment"):
+                return round(sum(bucket) / len(bucket), 3) if bucket else None
+            return sum(bucket)
+        
+        series = []
+        bucket = bucket_start(start, interval)
+        while bucket <= end:
+            series.append({"start": bucket.isoformat(), "value": value(values.get(bucket, []))})
+            bucket += step
+        return series
+
+    def get_unanswered_report(self, limit: int = 20) -> List[Dict]:
+        """
+        List the most common questions that Archie failed to answer.
+        
+        Args:
+            limit: Maximum number of questions to return
+        
+        Returns:
+            List of dicts with the question, how often it went unanswered, and when it was last asked
+        """
+        groups: Dict[str, Dict] = {}
+        for interaction in self.load_interactions():
+            flagged = interaction.get("unanswered")
+            if flagged is None:
+                flagged = is_unanswered(interaction.get("answer", ""))
+            if not flagged:
+                continue
+            
+            question = interaction.get("question", "")
+            key = normalize_question(question)
+            if not key:
+                continue
+            
+            group = groups.setdefault(key, {
+                "question": question,
+                "count": 0,
+                "category": interaction.get("category") or categorize_question(question),
+                "last_asked": interaction.get("timestamp")
+            })
+            group["count"] += 1
+            if interaction.get("timestamp", "") > (group["last_asked"] or ""):
+                group["last_asked"] = interaction.get("timestamp")
+        
+        report = sorted(groups.values(), key=lambda g: g["count"], reverse=True)
+        return report[:limit]
@@ -0,0 +1,268 @@
+"""
+Startup consistency checks for ArchieAI's data directory.
+Account records and session files are written separately, so they can drift apart: a
+session listed on an account whose file is gone, a session file its owner's account
+doesn't list, an account file missing from the index. Nothing noticed until a student
+found chats missing. On boot every account and session file is checked against the
+expected shape and against each other, and a summary is printed. With repair on, each
+problem that has a safe fix is fixed; files that can't be trusted are moved to
+data/quarantine/ like corrupt sessions (see lib/SessionRecovery.py), never deleted.
+"""
+import os
+import json
+from collections import Counter
+from typing import Dict, List, Optional
+from lib.UserStore import INDEX_FILE, user_filename
+
+# Issue kinds, with what repair does about them
+ISSUE_KINDS = {
+    "corrupt_user": "account file can't be read (left alone; fix it by hand)",
+    "invalid_user": "account record is missing fields (sessions list reset if it isn't a list)",
+    "index_drift": "users/index.json doesn't match the account files (index rebuilt)",
+    "missing_session": "account lists a session with no file (removed from the list)",
+    "duplicate_session": "session listed twice, or on two accounts (kept only on its owner)",
+    "unlinked_session": "session file its owner's account doesn't list (added to the list)",
+    "orphaned_session": "session belongs to an account that doesn't exist (quarantined)",
+    "corrupt_session": "session file isn't valid JSON (quarantined, messages salvaged)",
+    "invalid_session": "session file doesn't match the schema (quarantined, messages salvaged)",
+}
+
+
+def _issue(kind: str, detail: str, email: Optional[str] = None, session_id: Optional[str] = None) -> Dict:
+    return {"kind": kind, "detail": detail, "email": email, "session_id": session_id, "repaired": False}
+
+
+def session_problems(session_id: str, data) -> List[str]:
+    """What's wrong with a parsed session file; empty if it's fine."""
+    if not isinstance(data, dict):
+        return ["not a JSON object"]
+    problems = []
+    if data.get("session_id") != session_id:
+        problems.append("session_id doesn't match the file name")
+    if data.get("user_email") is not None and not isinstance(data.get("user_email"), str):
+        problems.append("user_email isn't a string")
+    if not isinstance(data.get("created_at"), str):
+        problems.append("created_at is missing")
+    messages = data.get("messages")
+    if not isinstance(messages, list):
+        problems.append("messages isn't a list")
+    else:
+        bad = sum(1 for m in messages
+                  if not isinstance(m, dict) or not isinstance(m.get("role"), str) or not isinstance(m.get("content"), str))
+        if bad:
+            problems.append(f"{bad} messages without a role and content")
+    return problems
+
+
+def user_problems(email: str, record) -> List[str]:
+    """What's wrong with a parsed account record; empty if it's fine."""
+    if not isinstance(record, dict):
+        return ["not a JSON object"]
+    problems = []
+    if record.get("email") != email:
+        problems.append("email doesn't match the index")
+    if not isinstance(record.get("password_hash"), str):
+        problems.append("password_hash is missing")
+    if not isinstance(record.get("sessions", []), list):
+        problems.append("sessions isn't a list")
+    return problems
+
+
+class DataIntegrityChecker:
+    """Cross-checks account records and session files, and optionally repairs them."""
+
+    def __init__(self, session_manager):
+        self.session_manager = session_manager
+        self.users = session_manager.users
+        self.sessions_dir = session_manager.sessions_dir
+
+    def _read_users(self, issues: List[Dict]) -> Dict[str, Dict]:
+        """Every readable account, keyed by email; reports unreadable files and index drift."""
+        directory = self.users.directory
+        on_disk = {}
+        for name in sorted(os.listdir(directory)):
+            if name == INDEX_FILE or not name.endswith(".json"):
+                continue
+            try:
+                with open(os.path.join(directory, name), "r", encoding="utf-8") as f:
+                    on_disk[name] = json.load(f)
+            except (OSError, UnicodeDecodeError, json.JSONDecodeError) as e:
+                issues.append(_issue("corrupt_user", f"users/{name}: {e}"))
+
+        index = self.users._load_index()
+        by_file = {name: email for email, name in index.items()}
+        drift = [f"{email} points at a missing file" for email, name in index.items() if name not in on_disk]
+        drift += [f"users/{name} isn't in the index" for name in on_disk if name not in by_file]
+        if drift:
+            issues.append(_issue("index_drift", "; ".join(drift)))
+
+        users = {}
+        for name, record in on_disk.items():
+            email = by_file.get(name) or (record.get("email") if isinstance(record, dict) else None)
+            if not email or user_filename(email) != name:
+                issues.append(_issue("invalid_user", f"users/{name} has no email that matches its file name"))
+                continue
+            problems = user_problems(email, record)
+            if problems:
+                issues.append(_issue("invalid_user", ", ".join(problems), email=email))
+            if isinstance(record, dict):
+                users[email] = record
+        return users
+
+    def _read_sessions(self, issues: List[Dict]) -> Dict[str, Optional[Dict]]:
+        """Every session file, keyed by ID (None if it's unreadable or invalid); reports the bad ones."""
+        sessions = {}
+        for name in sorted(os.listdir(self.sessions_dir)):
+            if not name.endswith(".json"):
+                continue
+            session_id = name[:-len(".json")]
+            try:
+                with open(os.path.join(self.sessions_dir, name), "r", encoding="utf-8") as f:
+                    data = json.load(f)
+            except (UnicodeDecodeError, json.JSONDecodeError) as e:
+                issues.append(_issue("corrupt_session", str(e), session_id=session_id))
+                sessions[session_id] = None
+                continue
+            except OSError:
+                continue
+            problems = session_problems(session_id, data)
+            if problems:
+                owner = data.get("user_email") if isinstance(data, dict) else None
+                issues.append(_issue("invalid_session", ", ".join(problems), session_id=session_id,
+                                     email=owner if isinstance(owner, str) else None))
+                sessions[session_id] = None
+            else:
+                sessions[session_id] = data
+        return sessions
+
+    def check(self, repair: bool = False) -> Dict:
+        """
+        Validate every account and session file and cross-check them.
+
+        Args:
+            repair: Fix what can be fixed safely (see ISSUE_KINDS)
+
+        Returns:
+            {"users", "sessions", "issues": [{"kind", "detail", "email", "session_id", "repaired"}],
+             "counts": issues per kind, "repaired": how many were fixed}
+        """
+        issues: List[Dict] = []
+        users = self._read_users(issues)
+        sessions = self._read_sessions(issues)
+
+        # Which account each session is listed on, to find duplicates and missing files
+        listed_on: Dict[str, List[str]] = {}
+        for email, record in users.items():
+            session_list = record.get("sessions", [])
+            if not isinstance(session_list, list):
+                continue
+            for session_id, count in Counter(session_list).items():
+                if count > 1:
+                    issues.append(_issue("duplicate_session", f"listed {count} times", email=email, session_id=session_id))
+                listed_on.setdefault(session_id, []).append(email)
+                if session_id not in sessions:
+                    issues.append(_issue("missing_session", "no session file", email=email, session_id=session_id))
+
+        for session_id, data in sessions.items():
+            if data is None:
+                continue
+            owner = data.get("user_email")
+            accounts = listed_on.get(session_id, [])
+            for email in accounts:
+                if email != owner:
+                    issues.append(_issue("duplicate_session", f"listed on {email}, but the file belongs to {owner or 'a guest'}",
+                                         email=email, session_id=session_id))
+            if owner and owner not in users:
+                issues.append(_issue("orphaned_session", f"owner {owner} has no account", email=owner, session_id=session_id))
+            elif owner and owner not in accounts:
+                issues.append(_issue("unlinked_session", "not in the owner's session list", email=owner, session_id=session_id))
+
+        if repair:
+            self._repair(issues, sessions)
+
+        return {
+            "users": len(users),
+            "sessions": len(sessions),
+            "issues": issues,
+            "counts": dict(Counter(issue["kind"] for issue in issues)),
+            "repaired": sum(1 for issue in issues if issue["repaired"]),
+        }
+
+    def _repair(self, issues: List[Dict], sessions: Dict[str, Optional[Dict]]):
+        if any(issue["kind"] == "index_drift" for issue in issues):
+            self.users.rebuild_index()
+            for issue in issues:
+                if issue["kind"] == "index_drift":
+                    issue["repaired"] = True
+
+        # Session list fixes, gathered per account so each record is rewritten once
+        drop: Dict[str, set] = {}
+        add: Dict[str, List[str]] = {}
+        for issue in issues:
+            kind, email, session_id = issue["kind"], issue["email"], issue["session_id"]
+            if kind == "missing_session":
+                drop.setdefault(email, set()).add(session_id)
+            elif kind == "duplicate_session":
+                owner = (sessions.get(session_id) or {}).get("user_email")
+                if owner != email:
+                    drop.setdefault(email, set()).add(session_id)
+                else:
+                    # Listed twice on the owner: drop every copy, then add one back
+                    drop.setdefault(email, set()).add(session_id)
+                    add.setdefault(email, []).append(session_id)
+            elif kind == "unlinked_session":
+                add.setdefault(email, []).append(session_id)
+            elif kind == "invalid_user" and email and "sessions isn't a list" in issue["detail"]:
+                drop.setdefault(email, set())
+
+        for email in set(drop) | set(add):
+            def fix(user, email=email):
+                if not isinstance(user.get("sessions"), list):
+                    user["sessions"] = []
+                removed = drop.get(email, set())
+                user["sessions"] = [s for s in user["sessions"] if s not in removed]
+                for session_id in add.get(email, []):
+                    if session_id not in user["sessions"]:
+                        user["sessions"].append(session_id)
+            if self.users.update(email, fix) is None:
+                continue
+            for issue in issues:
+                if issue["email"] != email:
+                    continue
+                if issue["kind"] in ("missing_session", "duplicate_session", "unlinked_session"):
+                    issue["repaired"] = True
+                elif issue["kind"] == "invalid_user":
+                    # Only the session list is ours to fix; a missing password hash needs a reset
+                    issue["repaired"] = issue["detail"] == "sessions isn't a list"
+
+        for issue in issues:
+            session_id = issue["session_id"]
+            path = os.path.join(self.sessions_dir, f"{session_id}.json")
+            if issue["kind"] in ("corrupt_session", "invalid_session"):
+                # Same path as a corrupt file found at request time: quarantine, salvage, put back
+                recovered = self.session_manager._recover_session(session_id, path, issue["detail"],
+                                                                  force=issue["kind"] == "invalid_session")
+                issue["repaired"] = recovered is not None
+            elif issue["kind"] == "orphaned_session":
+                # Nobody can open it anymore, but it isn't ours to delete
+                issue["repaired"] = self.session_manager.quarantine.quarantine(
+                    path, session_id, issue["detail"], force=True) is not None
+
+
+def format_report(report: Dict, repair: bool = False) -> str:
+    """One summary line, plus a line per issue."""
+    issues = report["issues"]
+    if not issues:
+        return f"Data check: {report['users']} accounts and {report['sessions']} sessions, no problems found"
+    counts = ", ".join(f"{count} {kind}" for kind, count in sorted(report["counts"].items()))
+    summary = f"Data check: {report['users']} accounts and {report['sessions']} sessions, {len(issues)} problems ({counts})"
+    if repair:
+        summary += f", {report['repaired']} repaired"
+    else:
+        summary += "; run with repair on to fix them"
+    lines = [summary]
+    for issue in issues:
+        subject = " ".join(part for part in (issue["email"], issue["session_id"]) if part)
+        status = " [repaired]" if issue["repaired"] else ""
+        lines.append(f"  {issue['kind']}: {subject + ': ' if subject else ''}{issue['detail']}{status}")
+    return "\n".join(lines)
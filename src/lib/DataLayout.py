@@ -0,0 +1,122 @@
+"""
+Where ArchieAI keeps its files.
+Paths used to default to directories of the checkout (data/, src/templates, ...), which is
+fine when running from a clone but not for a packaged install or a systemd unit, where the
+code directory is read-only and nobody wants state next to it. paths.layout picks the
+defaults for anything not set explicitly in [paths]:
+
+- "project" (the default): data/ in the project root, as before
+- "xdg": data in $XDG_DATA_HOME/archieai (~/.local/share/archieai), the embedding cache in
+  $XDG_CACHE_HOME/archieai (~/.cache/archieai). Under systemd, StateDirectory= and
+  CacheDirectory= ($STATE_DIRECTORY, $CACHE_DIRECTORY) win over the XDG variables
+
+Templates, static files, prompts, and evaluation suites ship with the code, so by default
+they're read from next to it in either layout, whatever the working directory is. Relative
+paths set in [paths] are taken relative to the project root.
+"""
+import os
+from typing import Dict, List, Mapping, Optional
+
+APP_NAME = "archieai"
+LAYOUTS = ("project", "xdg")
+
+# The directory containing src/, and src/ itself (where the bundled assets are)
+PROJECT_ROOT = os.path.dirname(os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+SOURCE_DIR = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+
+
+def _first_dir(value: Optional[str]) -> Optional[str]:
+    """systemd separates several directories with ":"; the first is the unit's own."""
+    return value.split(":")[0] if value else None
+
+
+def config_file_candidates(env: Optional[Mapping[str, str]] = None) -> List[str]:
+    """Where archie.toml is looked for when $ARCHIE_CONFIG isn't set, in order."""
+    env = os.environ if env is None else env
+    config_home = env.get("XDG_CONFIG_HOME") or os.path.join(os.path.expanduser("~"), ".config")
+    candidates = [os.path.join(PROJECT_ROOT, "archie.toml")]
+    if _first_dir(env.get("CONFIGURATION_DIRECTORY")):
+        candidates.append(os.path.join(_first_dir(env["CONFIGURATION_DIRECTORY"]), "archie.toml"))
+    candidates += [os.path.join(config_home, APP_NAME, "archie.toml"), os.path.join("/etc", APP_NAME, "archie.toml")]
+    return candidates
+
+
+class DataLayout:
+    """Resolves the directories ArchieAI reads and writes from the [paths] config."""
+
+    def __init__(self, paths, env: Optional[Mapping[str, str]] = None):
+        """
+        Args:
+            paths: The PathsConfig; empty directories get the layout's default
+            env: Environment to read XDG/systemd variables from (defaults to os.environ)
+        """
+        self.paths = paths
+        self.env = os.environ if env is None else env
+        self.layout = paths.layout if paths.layout in LAYOUTS else "project"
+
+    @staticmethod
+    def resolve(path: str) -> str:
+        """A configured path, relative ones taken from the project root."""
+        path = os.path.expanduser(path)
+        if os.path.isabs(path):
+            return path
+        return os.path.join(PROJECT_ROOT, path)
+
+    def _xdg(self, systemd_var: str, xdg_var: str, fallback: str) -> str:
+        explicit = _first_dir(self.env.get(systemd_var))
+        if explicit:
+            return explicit
+        base = self.env.get(xdg_var) or os.path.join(os.path.expanduser("~"), fallback)
+        return os.path.join(base, APP_NAME)
+
+    @property
+    def data_dir(self) -> str:
+        if self.paths.data_dir:
+            return self.resolve(self.paths.data_dir)
+        if self.layout == "xdg":
+            return self._xdg("STATE_DIRECTORY", "XDG_DATA_HOME", os.path.join(".local", "share"))
+        return os.path.join(PROJECT_ROOT, "data")
+
+    @property
+    def cache_dir(self) -> str:
+        """Files that can be rebuilt (the embedding cache); in the project layout, the data directory."""
+        if self.paths.cache_dir:
+            return self.resolve(self.paths.cache_dir)
+        if self.layout == "xdg":
+            return self._xdg("CACHE_DIRECTORY", "XDG_CACHE_HOME", ".cache")
+        return self.data_dir
+
+    def _asset_dir(self, configured: str, name: str) -> str:
+        return self.resolve(configured) if configured else os.path.join(SOURCE_DIR, name)
+
+    @property
+    def templates_dir(self) -> str:
+        return self._asset_dir(self.paths.templates_dir, "templates")
+
+    @property
+    def static_dir(self) -> str:
+        return self._asset_dir(self.paths.static_dir, "static")
+
+    @property
+    def prompts_dir(self) -> str:
+        return self._asset_dir(self.paths.prompts_dir, "prompts")
+
+    @property
+    def evals_dir(self) -> str:
+        return self._asset_dir(self.paths.evals_dir, "evals")
+
+    def ensure(self):
+        """Create the writable directories (XDG ones usually don't exist on a fresh install)."""
+        os.makedirs(self.data_dir, exist_ok=True)
+        os.makedirs(self.cache_dir, exist_ok=True)
+
+    def describe(self) -> Dict[str, str]:
+        return {
+            "layout": self.layout,
+            "data_dir": self.data_dir,
+            "cache_dir": self.cache_dir,
+            "templates_dir": self.templates_dir,
+            "static_dir": self.static_dir,
+            "prompts_dir": self.prompts_dir,
+            "evals_dir": self.evals_dir,
+        }
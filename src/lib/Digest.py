@@ -0,0 +1,167 @@
+"""
+Weekly admin digest for ArchieAI.
+Summarizes the past week of analytics (questions, top questions, failure rate, latency,
+new users) and emails it to the [admin] emails through lib/Mailer.py. The time of the
+last send is kept in data/digest_state.json so restarts don't send it twice.
+"""
+import os
+import json
+from collections import Counter
+from datetime import datetime, timedelta
+from typing import Dict, List, Optional
+
+from lib.DataCollector import is_unanswered, normalize_question
+from lib.Mailer import MailError
+
+STATE_FILE = "digest_state.json"
+
+
+def build_digest(data_collector, session_manager, end: Optional[datetime] = None, days: int = 7,
+                 top_questions: int = 10) -> Dict:
+    """
+    Aggregate the interactions and sign-ups in the `days` before `end`.
+
+    Returns:
+        Dict with the period, question/session/user counts, failure rate, latency, and top questions
+    """
+    end = end or datetime.now()
+    start = end - timedelta(days=days)
+    start_iso, end_iso = start.isoformat(), end.isoformat()
+
+    total = 0
+    failures = 0
+    faq_answers = 0
+    sessions = set()
+    users = set()
+    latencies = []
+    questions = Counter()
+    examples = {}
+    for interaction in data_collector.iter_interactions(start=start_iso, end=end_iso):
+        total += 1
+        flagged = interaction.get("unanswered")
+        if flagged is None:
+            flagged = is_unanswered(interaction.get("answer", ""))
+        failures += bool(flagged)
+        if interaction.get("answer_source") == "faq":
+            faq_answers += 1
+        sessions.add(interaction.get("session_id"))
+        if interaction.get("user_email", "guest") != "guest":
+            users.add(interaction["user_email"])
+        latencies.append(interaction.get("generation_time_seconds", 0))
+        key = normalize_question(interaction.get("question", ""))
+        if key:
+            questions[key] += 1
+            examples.setdefault(key, interaction["question"])
+
+    new_users = [
+        u["email"] for u in session_manager.list_users()
+        if u.get("created_at") and start_iso <= u["created_at"] <= end_iso
+    ]
+    latencies.sort()
+    return {
+        "start": start_iso,
+        "end": end_iso,
+        "total_questions": total,
+        "sessions": len(sessions),
+        "active_users": len(users),
+        "new_users": len(new_users),
+        "failure_rate": round(failures / total, 3) if total else 0,
+        "faq_answers": faq_answers,
+        "avg_generation_time_seconds": round(sum(latencies) / total, 2) if total else 0,
+        "p95_generation_time_seconds": latencies[int(0.95 * (len(latencies) - 1))] if latencies else 0,
+        "top_questions": [
+            {"question": examples[key], "count": count}
+            for key, count in questions.most_common(top_questions)
+        ],
+    }
+
+
+def render_digest(digest: Dict) -> str:
+    """Plain-text email body for a digest."""
+    lines = [
+        f"ArchieAI weekly digest, {digest['start'][:10]} to {digest['end'][:10]}",
+        "",
+        f"Questions asked:     {digest['total_questions']}",
+        f"Sessions:            {digest['sessions']}",
+        f"Active users:        {digest['active_users']}",
+        f"New users:           {digest['new_users']}",
+        f"Failure rate:        {digest['failure_rate'] * 100:.1f}%",
+        f"Answered from FAQ:   {digest['faq_answers']}",
+        f"Avg response time:   {digest['avg_generation_time_seconds']}s "
+        f"(p95 {digest['p95_generation_time_seconds']}s)",
+        "",
+        "Top questions:",
+    ]
+    if digest["top_questions"]:
+        lines += [f"  {q['count']:>4}  {q['question']}" for q in digest["top_questions"]]
+    else:
+        lines.append("  (none this week)")
+    return "\n".join(lines) + "\n"
+
+
+class DigestScheduler:
+    """Decides when the weekly digest is due and sends it."""
+
+    def __init__(self, data_collector, session_manager, mailer, recipients: List[str],
+                 data_dir: str = "data", weekday: int = 0, hour: int = 8, top_questions: int = 10):
+        self.data_collector = data_collector
+        self.session_manager = session_manager
+        self.mailer = mailer
+        self.recipients = recipients
+        self.path = os.path.join(data_dir, STATE_FILE)
+        self.weekday = weekday
+        self.hour = hour
+        self.top_questions = top_questions
+
+    def last_sent(self) -> Optional[datetime]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return datetime.fromisoformat(json.load(f)["last_sent_at"])
+        except (FileNotFoundError, json.JSONDecodeError, KeyError, ValueError):
+            return None
+
+    def _record_sent(self, when: datetime):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump({"last_sent_at": when.isoformat()}, f, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def is_due(self, now: Optional[datetime] = None) -> bool:
+        """True once this week's send time has passed and the digest hasn't gone out since."""
+        now = now or datetime.now()
+        scheduled = (now - timedelta(days=(now.weekday() - self.weekday) % 7)).replace(
+            hour=self.hour, minute=0, second=0, microsecond=0)
+        if scheduled > now:
+            scheduled -= timedelta(days=7)
+        last = self.last_sent()
+        return last is None or last < scheduled
+
+    def build(self, now: Optional[datetime] = None) -> Dict:
+        return build_digest(self.data_collector, self.session_manager, end=now, top_questions=self.top_questions)
+
+    def send(self, now: Optional[datetime] = None) -> Dict:
+        """
+        Build and email the digest, then record the send.
+
+        Raises:
+            MailError: If mail isn't configured or sending fails
+        """
+        if self.mailer is None:
+            raise MailError("Mail isn't configured; set [mail] host")
+        now = now or datetime.now()
+        digest = self.build(now)
+        self.mailer.send(
+            self.recipients,
+            f"ArchieAI weekly digest ({digest['start'][:10]} to {digest['end'][:10]})",
+            render_digest(digest),
+        )
+        self._record_sent(now)
+        return digest
+
+    def run_if_due(self, now: Optional[datetime] = None) -> bool:
+        """Send the digest if it's due. Returns whether it was sent."""
+        if not self.is_due(now):
+            return False
+        self.send(now)
+        return True
@@ -0,0 +1,160 @@
+"""
+Discord bridge for ArchieAI.
+Maps Discord users (or channels) to ArchieAI sessions and answers questions through the
+same pipeline as the web chat, streaming the answer into a Discord message that gets
+edited as tokens arrive. Interactions are logged through DataCollector like any other.
+Nothing here imports discord.py; src/discord_bot.py wires this up to the Discord client.
+"""
+import os
+import json
+import time
+import threading
+from typing import Awaitable, Callable, List, Optional
+
+MAPPING_FILE = "discord_sessions.json"
+# Discord rejects messages longer than this
+MESSAGE_LIMIT = 2000
+SESSION_SCOPES = ("user", "channel")
+
+
+def discord_user_label(user_id) -> str:
+    """What Discord users are recorded as in analytics (they don't have Archie accounts)."""
+    return f"discord:{user_id}"
+
+
+def split_message(text: str, limit: int = MESSAGE_LIMIT) -> List[str]:
+    """Split text into Discord-sized pieces, preferring paragraph, line, then word breaks."""
+    pieces = []
+    while len(text) > limit:
+        cut = -1
+        for separator in ("\n\n", "\n", " "):
+            cut = text.rfind(separator, 0, limit)
+            if cut > limit // 2:
+                break
+        if cut <= 0:
+            cut = limit
+        pieces.append(text[:cut].rstrip())
+        text = text[cut:].lstrip()
+    if text or not pieces:
+        pieces.append(text)
+    return pieces
+
+
+class DiscordSessionMap:
+    """Persistent Discord user/channel -> ArchieAI session ID mapping."""
+
+    def __init__(self, session_manager, data_dir: str = "data", scope: str = "user"):
+        if scope not in SESSION_SCOPES:
+            raise ValueError(f"Discord session scope must be one of {', '.join(SESSION_SCOPES)}")
+        self.session_manager = session_manager
+        self.path = os.path.join(data_dir, MAPPING_FILE)
+        self.scope = scope
+        self._lock = threading.Lock()
+
+    def _load(self) -> dict:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {}
+
+    def _save(self, mapping: dict):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(mapping, f, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def key_for(self, user_id, channel_id) -> str:
+        return f"channel:{channel_id}" if self.scope == "channel" else f"user:{user_id}"
+
+    def session_for(self, user_id, channel_id) -> str:
+        """The session for this user/channel, starting a new one if there isn't one (or it was purged)."""
+        key = self.key_for(user_id, channel_id)
+        with self._lock:
+            mapping = self._load()
+            session_id = mapping.get(key)
+            if session_id is None or self.session_manager.get_session(session_id) is None:
+                session_id = self.session_manager.create_session(user_email=None)
+                mapping[key] = session_id
+                self._save(mapping)
+        return session_id
+
+    def reset(self, user_id, channel_id) -> bool:
+        """Forget the session so the next question starts a fresh conversation."""
+        key = self.key_for(user_id, channel_id)
+        with self._lock:
+            mapping = self._load()
+            if key not in mapping:
+                return False
+            del mapping[key]
+            self._save(mapping)
+        return True
+
+
+class DiscordBridge:
+    """Answers Discord questions with Archie and records them like web chats."""
+
+    def __init__(self, ai_interface, session_manager, data_collector, session_map: DiscordSessionMap,
+                 faq_store=None, edit_interval_seconds: float = 1.0):
+        self.ai = ai_interface
+        self.session_manager = session_manager
+        self.data_collector = data_collector
+        self.session_map = session_map
+        self.faq_store = faq_store
+        # Discord rate limits message edits, so partial answers are pushed at most this often
+        self.edit_interval_seconds = edit_interval_seconds
+
+    async def answer(self, question: str, user_id, channel_id,
+                     on_update: Callable[[str], Awaitable[None]]) -> str:
+        """
+        Answer a question, calling on_update with the answer so far as it streams.
+
+        Args:
+            question: The question text (mention already stripped)
+            user_id, channel_id: Discord IDs, used to pick the session
+            on_update: Coroutine called with the partial answer (throttled) and once with the full answer
+
+        Returns:
+            The full answer
+        """
+        start_time = time.time()
+        session_id = self.session_map.session_for(user_id, channel_id)
+        faq_match = None
+        citations = None
+        if self.faq_store is not None and self.ai.flags.is_enabled("faq_answers", session_id):
+            faq_match = self.faq_store.match(question)
+
+        if faq_match:
+            answer = faq_match["entry"]["answer"]
+        else:
+            history_summary, history = self.session_manager.get_conversation_context(session_id)
+            answer = ""
+            last_update = time.monotonic()
+            async for chunk in self.ai.Archie_streaming(question, conversation_history=history, session_id=session_id,
+                                                        priority="guest", history_summary=history_summary):
+                if isinstance(chunk, dict) and chunk.get("citations") is not None:
+                    citations = chunk["citations"]
+                if not isinstance(chunk, str):
+                    continue
+                answer += chunk
+                if answer.strip() and time.monotonic() - last_update >= self.edit_interval_seconds:
+                    await on_update(answer)
+                    last_update = time.monotonic()
+
+        await on_update(answer)
+
+        self.session_manager.add_message(session_id, "user", question)
+        self.session_manager.add_message(session_id, "assistant", answer, citations=citations)
+        self.data_collector.log_interaction(
+            session_id=session_id,
+            user_email=discord_user_label(user_id),
+            ip_address="discord",
+            device_info=f"Discord channel {channel_id}",
+            question=question,
+            answer=answer,
+            generation_time_seconds=time.time() - start_time,
+            answer_source="faq" if faq_match else "model",
+            faq_entry_id=faq_match["entry"]["entry_id"] if faq_match else None,
+        )
+        return answer
@@ -0,0 +1,172 @@
+"""
+Text extraction for knowledge base documents: web pages, PDFs, DOCX, markdown, and plain text.
+Documents keep their headings as markdown "#" lines so heading-aware chunking (lib/Chunking.py)
+can split on them; everything between two headings is collapsed into one paragraph.
+Tables are flattened to one line per row ("Course: CS 242; Credits: 3."), so a row
+stays together in a chunk and still reads as text to the model.
+"""
+import io
+import re
+import zipfile
+import xml.etree.ElementTree as ET
+from typing import List
+
+HEADING_RE = re.compile(r"^(#{1,6})\s+(.+)$")
+# Two or more gaps of 3+ spaces: a table row in pypdf's layout-preserving output
+_PDF_CELL_GAP_RE = re.compile(r"\s{3,}")
+_WORD_NS = "{http://schemas.openxmlformats.org/wordprocessingml/2006/main}"
+
+
+class DocumentError(Exception):
+    """A document couldn't be read (corrupt, encrypted, or a missing parser)."""
+
+
+def normalize_text(text: str) -> str:
+    """
+    Collapse whitespace while keeping heading lines on their own lines.
+
+    Text without headings comes back as a single line, the same as " ".join(text.split()).
+    """
+    lines, paragraph = [], []
+    for line in text.splitlines():
+        line = " ".join(line.split())
+        if HEADING_RE.match(line):
+            if paragraph:
+                lines.append(" ".join(paragraph))
+                paragraph = []
+            lines.append(line)
+        elif line:
+            paragraph.append(line)
+    if paragraph:
+        lines.append(" ".join(paragraph))
+    return "\n".join(lines)
+
+
+def html_to_text(html: str) -> str:
+    """Text of an HTML page, with <h1>-<h6> turned into "#" heading lines and scripts and styles dropped."""
+    from bs4 import BeautifulSoup
+
+    soup = BeautifulSoup(html, "html.parser")
+    for tag in soup(["script", "style", "noscript", "template"]):
+        tag.decompose()
+    for level in range(1, 7):
+        for heading in soup.find_all(f"h{level}"):
+            title = " ".join(heading.get_text(" ").split())
+            heading.replace_with(f"\n{'#' * level} {title}\n" if title else " ")
+    return normalize_text(soup.get_text(" "))
+
+
+def flatten_table(rows: List[List[str]]) -> List[str]:
+    """
+    One line per table row. With a header row of distinct non-empty cells, each cell is
+    labeled with its column ("Course: CS 242; Credits: 3."); otherwise cells are joined with " | ".
+    """
+    rows = [[" ".join(cell.split()) for cell in row] for row in rows]
+    rows = [row for row in rows if any(row)]
+    if not rows:
+        return []
+    header = rows[0]
+    if len(rows) > 1 and all(header) and len(set(header)) == len(header):
+        lines = []
+        for row in rows[1:]:
+            cells = [f"{name}: {cell}" for name, cell in zip(header, row) if cell]
+            if cells:
+                lines.append("; ".join(cells) + ".")
+        return lines
+    return [" | ".join(cell for cell in row if cell) + "." for row in rows]
+
+
+def pdf_to_text(data: bytes) -> str:
+    """
+    Text of a PDF, using pypdf's layout mode so table columns stay apart.
+    Runs of lines with three or more columns are flattened as tables.
+    Scanned PDFs without a text layer come back empty.
+    """
+    try:
+        from pypdf import PdfReader
+        from pypdf.errors import PdfReadError
+    except ImportError:
+        raise DocumentError("PDF support needs the pypdf package (pip install pypdf)")
+
+    try:
+        reader = PdfReader(io.BytesIO(data))
+        if reader.is_encrypted and not reader.decrypt(""):
+            raise DocumentError("the PDF is password protected")
+        pages = [page.extract_text(extraction_mode="layout") or "" for page in reader.pages]
+    except PdfReadError as e:
+        raise DocumentError(f"could not read the PDF: {e}")
+
+    lines: List[str] = []
+    table: List[List[str]] = []
+    for line in "\n".join(pages).splitlines():
+        cells = [cell for cell in _PDF_CELL_GAP_RE.split(line.strip()) if cell]
+        if len(cells) >= 3:
+            table.append(cells)
+            continue
+        if table:
+            lines.extend(flatten_table(table))
+            table = []
+        lines.append(line)
+    lines.extend(flatten_table(table))
+    return normalize_text("\n".join(lines))
+
+
+def _docx_text(element) -> str:
+    parts = []
+    for node in element.iter():
+        if node.tag == f"{_WORD_NS}t" and node.text:
+            parts.append(node.text)
+        elif node.tag in (f"{_WORD_NS}tab", f"{_WORD_NS}br"):
+            parts.append(" ")
+    return " ".join("".join(parts).split())
+
+
+def docx_to_text(data: bytes) -> str:
+    """Text of a DOCX file: heading styles become "#" lines, tables are flattened row by row."""
+    try:
+        with zipfile.ZipFile(io.BytesIO(data)) as archive:
+            body = ET.fromstring(archive.read("word/document.xml")).find(f"{_WORD_NS}body")
+    except (zipfile.BadZipFile, KeyError, ET.ParseError) as e:
+        raise DocumentError(f"could not read the DOCX file: {e}")
+    if body is None:
+        return ""
+
+    lines = []
+    for element in body:
+        if element.tag == f"{_WORD_NS}p":
+            text = _docx_text(element)
+            if not text:
+                continue
+            style = element.find(f"{_WORD_NS}pPr/{_WORD_NS}pStyle")
+            style_name = (style.get(f"{_WORD_NS}val") or "") if style is not None else ""
+            match = re.fullmatch(r"Heading([1-6])", style_name)
+            if style_name == "Title":
+                lines.append(f"# {text}")
+            elif match:
+                lines.append(f"{'#' * int(match.group(1))} {text}")
+            else:
+                lines.append(text)
+        elif element.tag == f"{_WORD_NS}tbl":
+            rows = [
+                [_docx_text(cell) for cell in row.findall(f"{_WORD_NS}tc")]
+                for row in element.findall(f"{_WORD_NS}tr")
+            ]
+            lines.extend(flatten_table(rows))
+    return normalize_text("\n".join(lines))
+
+
+def extract_text(data: bytes, document_type: str) -> str:
+    """
+    Text of a document of a given type (see lib.KnowledgeBase.source_type).
+
+    Raises:
+        DocumentError: If the document can't be read
+    """
+    if document_type == "pdf":
+        return pdf_to_text(data)
+    if document_type == "docx":
+        return docx_to_text(data)
+    text = data.decode("utf-8", errors="replace")
+    if document_type == "html":
+        return html_to_text(text)
+    return normalize_text(text)
@@ -0,0 +1,88 @@
+"""
+Draft (ephemeral) answers for ArchieAI.
+The "try Archie" embed on the public site shouldn't fill sessions and the analytics log
+with visitors kicking the tires. A question asked with "ephemeral": true is answered as
+usual, but the exchange is held here instead of being saved; only the counters below
+(exported on /metrics) see it. POST /api/sessions/<id>/commit with the draft's ID saves
+it into a session and the analytics after all. Drafts live in memory and expire, so a
+restart or a visitor who walks away leaves nothing behind.
+"""
+import time
+import secrets
+import threading
+from collections import OrderedDict
+from typing import Dict, Optional
+
+
+class DraftStore:
+    """Uncommitted exchanges, keyed by draft ID, oldest first."""
+
+    def __init__(self, ttl_seconds: float = 900.0, max_drafts: int = 1000):
+        """
+        Args:
+            ttl_seconds: How long a draft can still be committed
+            max_drafts: Drafts kept at once; the oldest are dropped beyond this
+        """
+        self.ttl_seconds = ttl_seconds
+        self.max_drafts = max_drafts
+        self._lock = threading.Lock()
+        self._drafts: "OrderedDict[str, Dict]" = OrderedDict()
+        self._created = 0
+        self._committed = 0
+        self._discarded = 0
+
+    def _prune(self):
+        now = time.monotonic()
+        while self._drafts:
+            draft_id, draft = next(iter(self._drafts.items()))
+            if now < draft["expires"] and len(self._drafts) <= self.max_drafts:
+                break
+            del self._drafts[draft_id]
+            self._discarded += 1
+
+    def add(self, session_id: Optional[str], exchange: Dict) -> Dict:
+        """
+        Hold an exchange until it's committed or expires.
+
+        Args:
+            session_id: Session the question was asked in (None for a visitor without one)
+            exchange: Keyword arguments for app.persist_exchange()
+
+        Returns:
+            {"draft_id", "expires_in_seconds"} for the client
+        """
+        draft_id = secrets.token_urlsafe(16)
+        with self._lock:
+            self._drafts[draft_id] = {
+                "session_id": session_id,
+                "exchange": exchange,
+                "expires": time.monotonic() + self.ttl_seconds,
+            }
+            self._created += 1
+            self._prune()
+        return {"draft_id": draft_id, "expires_in_seconds": round(self.ttl_seconds)}
+
+    def take(self, draft_id: str, session_id: str) -> Optional[Dict]:
+        """
+        Remove and return a draft's exchange for committing into session_id, or None if it
+        doesn't exist, expired, or was asked in a different session. Drafts asked without a
+        session can go into any session (a visitor who signs up after trying the embed).
+        """
+        with self._lock:
+            self._prune()
+            draft = self._drafts.get(draft_id)
+            if draft is None or draft["session_id"] not in (None, session_id):
+                return None
+            del self._drafts[draft_id]
+            self._committed += 1
+            return draft["exchange"]
+
+    def metrics(self) -> Dict:
+        with self._lock:
+            self._prune()
+            return {
+                "pending": len(self._drafts),
+                "created": self._created,
+                "committed": self._committed,
+                "discarded": self._discarded,
+            }
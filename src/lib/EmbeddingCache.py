@@ -0,0 +1,110 @@
+"""
+Embedding cache for ArchieAI.
+Vectors are keyed by a hash of the embedding model and the exact text, so re-embedding an
+unchanged chunk or a question that's been asked before doesn't call the model again.
+Entries are appended to data/embedding_cache.jsonl as they're added (one small write each,
+rather than rewriting a big file) and the log is compacted when it grows well past the cap.
+The least recently used entries are evicted once there are more than max_entries.
+"""
+import logging
+import os
+import json
+import hashlib
+import threading
+from collections import OrderedDict
+from typing import Dict, List, Optional
+
+logger = logging.getLogger(__name__)
+
+CACHE_FILE = "embedding_cache.jsonl"
+
+
+def cache_key(model: str, text: str) -> str:
+    """Content hash for a text embedded with a model."""
+    return hashlib.sha256(f"{model}\0{text}".encode("utf-8")).hexdigest()
+
+
+class EmbeddingCache:
+    """Size-capped LRU cache of embeddings, persisted as an append-only log."""
+
+    def __init__(self, data_dir: str = "data", max_entries: int = 5000):
+        self.path = os.path.join(data_dir, CACHE_FILE)
+        self.max_entries = max_entries
+        self.hits = 0
+        self.misses = 0
+        self._lock = threading.Lock()
+        self._entries: "OrderedDict[str, List[float]]" = OrderedDict()
+        self._log_lines = 0
+        self._load()
+
+    def _load(self):
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                for line in f:
+                    self._log_lines += 1
+                    try:
+                        entry = json.loads(line)
+                        key, vector = entry["key"], entry["embedding"]
+                    except (json.JSONDecodeError, KeyError, TypeError):
+                        # A torn last line from a crash; compaction drops it
+                        continue
+                    self._entries[key] = vector
+                    self._entries.move_to_end(key)
+        except FileNotFoundError:
+            return
+        while len(self._entries) > self.max_entries:
+            self._entries.popitem(last=False)
+
+    def get_many(self, model: str, texts: List[str]) -> List[Optional[List[float]]]:
+        """Cached vectors for the texts, None where there isn't one."""
+        vectors = []
+        with self._lock:
+            for text in texts:
+                key = cache_key(model, text)
+                vector = self._entries.get(key)
+                if vector is None:
+                    self.misses += 1
+                else:
+                    self.hits += 1
+                    self._entries.move_to_end(key)
+                vectors.append(vector)
+        return vectors
+
+    def put_many(self, model: str, vectors: Dict[str, List[float]]):
+        """Add text -> vector pairs, evicting the least recently used entries past the cap."""
+        if not vectors:
+            return
+        with self._lock:
+            lines = []
+            for text, vector in vectors.items():
+                key = cache_key(model, text)
+                self._entries[key] = vector
+                self._entries.move_to_end(key)
+                lines.append(json.dumps({"key": key, "embedding": vector}) + "\n")
+            while len(self._entries) > self.max_entries:
+                self._entries.popitem(last=False)
+            try:
+                os.makedirs(os.path.dirname(self.path) or ".", exist_ok=True)
+                if self._log_lines + len(lines) > 2 * self.max_entries:
+                    self._compact()
+                else:
+                    with open(self.path, "a", encoding="utf-8") as f:
+                        f.writelines(lines)
+                    self._log_lines += len(lines)
+            except OSError as e:
+                # Still cached in memory; the file catches up at the next compaction
+                logger.warning(f"Could not write {self.path}: {e}")
+
+    def _compact(self):
+        """Rewrite the log with just the live entries, oldest first (caller holds the lock)."""
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            for key, vector in self._entries.items():
+                f.write(json.dumps({"key": key, "embedding": vector}) + "\n")
+        os.replace(tmp_path, self.path)
+        self._log_lines = len(self._entries)
+
+    def stats(self) -> Dict:
+        """Entry count, cap, and hit/miss counts since startup, for the admin API."""
+        with self._lock:
+            return {"entries": len(self._entries), "max_entries": self.max_entries, "hits": self.hits, "misses": self.misses}
@@ -0,0 +1,131 @@
+"""
+Typed errors for ArchieAI request handlers.
+Handlers raise these instead of building error responses by hand; app.py turns them
+into consistent JSON bodies ({"error": ..., "code": ...}) with the right status code.
+"""
+from typing import Optional
+
+
+class AppError(Exception):
+    """Base class for errors that map to an HTTP response."""
+    status_code = 500
+    code = "internal"
+    default_message = "Internal server error"
+
+    def __init__(self, message: Optional[str] = None, params: Optional[dict] = None, **details):
+        # message is the English text (and the translation key); params fill its {placeholders}
+        self.message = message or self.default_message
+        self.params = params or {}
+        self.details = details
+        super().__init__(self.message.format(**self.params))
+
+    def to_dict(self, translate=None) -> dict:
+        """
+        Build the JSON error body.
+
+        Args:
+            translate: Optional callable(message, **params) used to localize the message
+        """
+        if translate is not None:
+            error = translate(self.message, **self.params)
+        else:
+            error = self.message.format(**self.params)
+        body = {"error": error, "code": self.code}
+        body.update(self.details)
+        return body
+
+
+class ValidationError(AppError):
+    """The request body or parameters are invalid (400)."""
+    status_code = 400
+    code = "validation"
+    default_message = "Invalid request"
+
+    def __init__(self, message: Optional[str] = None, field: Optional[str] = None,
+                 params: Optional[dict] = None, **details):
+        if field:
+            details["field"] = field
+        super().__init__(message, params=params, **details)
+        self.field = field
+
+
+class Unauthorized(AppError):
+    """No valid login or session (401)."""
+    status_code = 401
+    code = "unauthorized"
+    default_message = "Not logged in"
+
+
+class Forbidden(AppError):
+    """Logged in, but not allowed to touch this resource (403)."""
+    status_code = 403
+    code = "forbidden"
+    default_message = "Unauthorized"
+
+
+class AccountSuspended(Forbidden):
+    """The account is suspended or banned, so it can't use Archie (403)."""
+    code = "account_suspended"
+    default_message = "Your account is suspended pending review"
+
+
+class TermsNotAccepted(Forbidden):
+    """The current terms of use haven't been accepted yet, so chat is blocked (403)."""
+    code = "terms_required"
+    default_message = "Please accept the terms of use before chatting with Archie"
+
+
+class NotFound(AppError):
+    """The requested resource doesn't exist (404)."""
+    status_code = 404
+    code = "not_found"
+    default_message = "Not found"
+
+
+class Conflict(AppError):
+    """The request conflicts with the current state, e.g. a job already running (409)."""
+    status_code = 409
+    code = "conflict"
+    default_message = "Conflict"
+
+
+class PayloadTooLarge(AppError):
+    """The request body is over the configured size limit (413)."""
+    status_code = 413
+    code = "payload_too_large"
+    default_message = "Request body too large"
+
+
+class TooManyRequests(AppError):
+    """The caller is sending questions too fast or repeating itself (429)."""
+    status_code = 429
+    code = "rate_limited"
+    default_message = "Too many requests, please slow down"
+
+
+class Upstream(AppError):
+    """The model backend (Ollama) failed or is unreachable (502)."""
+    status_code = 502
+    code = "upstream"
+    default_message = "The AI backend is unavailable"
+
+
+class Unavailable(AppError):
+    """Archie is too busy to take the request right now (503)."""
+    status_code = 503
+    code = "unavailable"
+    default_message = "Archie is busy right now, please try again in a moment"
+
+
+class ReadOnly(AppError):
+    """The request would write, and this server is a read-only replica (503)."""
+    status_code = 503
+    code = "read_only"
+    default_message = "This server is read-only, please try again in a moment"
+
+
+class Internal(AppError):
+    """Anything unexpected (500)."""
+    status_code = 500
+    code = "internal"
+    default_message = "Internal server error"
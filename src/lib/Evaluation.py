@@ -0,0 +1,232 @@
+"""
+Evaluation harness for ArchieAI.
+Runs a YAML suite of test questions through the same answer pipeline the chat uses
+(knowledge base + campus tools + system prompt + model), scores each answer against
+expected facts and/or an LLM judge, and writes a report to data/eval_reports/ so prompt
+and model changes can be compared run to run.
+
+Suite format:
+    name: campus-basics
+    judge: false                  # judge every question that has a reference answer
+    questions:
+      - id: fall-break
+        question: When is fall break?
+        expected_facts:            # all must appear (case-insensitive); a list means "any of these"
+          - October
+          - ["13", "thirteenth"]
+        forbidden: [Georgia]       # must not appear
+        reference: Fall break is October 13-14, 2025.   # for the LLM judge
+"""
+import os
+import re
+import json
+import time
+import asyncio
+import hashlib
+from datetime import datetime
+from typing import Callable, Dict, List, Optional, Tuple
+import yaml
+
+REPORTS_DIR = "eval_reports"
+# Judge scores at or above this count as a pass
+JUDGE_PASS_SCORE = 0.7
+
+
+class SuiteError(ValueError):
+    """The suite file is missing or malformed."""
+
+
+def load_suite(path: str) -> Dict:
+    """Load and check a YAML suite file."""
+    try:
+        with open(path, "r", encoding="utf-8") as f:
+            suite = yaml.safe_load(f)
+    except FileNotFoundError:
+        raise SuiteError(f"No such suite: {path}")
+    except yaml.YAMLError as e:
+        raise SuiteError(f"{path} is not valid YAML: {e}")
+
+    if not isinstance(suite, dict) or not isinstance(suite.get("questions"), list) or not suite["questions"]:
+        raise SuiteError(f"{path} needs a non-empty questions list")
+    for index, case in enumerate(suite["questions"]):
+        if not isinstance(case, dict) or not isinstance(case.get("question"), str):
+            raise SuiteError(f"{path}: question #{index + 1} needs a question string")
+        case.setdefault("id", f"q{index + 1}")
+    suite.setdefault("name", os.path.splitext(os.path.basename(path))[0])
+    return suite
+
+
+def _normalize(text: str) -> str:
+    return " ".join(text.lower().split())
+
+
+def score_facts(answer: str, expected_facts: List) -> Tuple[float, List[str]]:
+    """Fraction of expected facts found in the answer, and the ones that were missing."""
+    if not expected_facts:
+        return 1.0, []
+    text = _normalize(answer)
+    missing = []
+    for fact in expected_facts:
+        alternatives = fact if isinstance(fact, list) else [fact]
+        if not any(_normalize(str(alt)) in text for alt in alternatives):
+            missing.append(" | ".join(str(alt) for alt in alternatives))
+    return (len(expected_facts) - len(missing)) / len(expected_facts), missing
+
+
+def find_forbidden(answer: str, forbidden: List[str]) -> List[str]:
+    text = _normalize(answer)
+    return [phrase for phrase in forbidden or [] if _normalize(phrase) in text]
+
+
+class Evaluator:
+    """Runs suites against an AiInterface and saves the reports."""
+
+    def __init__(self, ai_interface, data_dir: str = "data"):
+        self.ai = ai_interface
+        self.reports_dir = os.path.join(data_dir, REPORTS_DIR)
+
+    async def _answer(self, question: str) -> Tuple[str, List[str]]:
+        """Answer like the chat stream does, returning the text and the tools that ran."""
+        answer = ""
+        tools = []
+        async for chunk in self.ai.Archie_streaming(question):
+            if isinstance(chunk, str):
+                answer += chunk
+            elif isinstance(chunk, dict) and chunk.get("tool_name"):
+                tools.append(chunk["tool_name"])
+        return answer, tools
+
+    async def _judge(self, question: str, reference: str, answer: str) -> Dict:
+        """Ask the model to grade the answer against the reference. Returns {"score", "reason"}."""
+        prompt = self.ai.load_prompt("eval_judge").format(question=question, reference=reference, answer=answer)
+        output = ""
+        async for token in self.ai.generate_text_streaming(prompt):
+            output += token
+        # Models like to wrap JSON in prose or code fences, so take the first object in the output
+        match = re.search(r"\{.*\}", output, re.DOTALL)
+        try:
+            verdict = json.loads(match.group(0)) if match else {}
+            score = max(0.0, min(1.0, float(verdict.get("score"))))
+        except (ValueError, TypeError):
+            return {"score": None, "reason": f"Unreadable judge output: {output[:200]}"}
+        return {"score": score, "reason": str(verdict.get("reason", ""))}
+
+    def run_case(self, case: Dict, use_judge: bool) -> Dict:
+        start = time.time()
+        result = {"id": case["id"], "question": case["question"]}
+        try:
+            answer, tools = asyncio.run(self._answer(case["question"]))
+        except Exception as e:
+            result.update({"passed": False, "error": str(e), "seconds": round(time.time() - start, 2)})
+            return result
+
+        fact_score, missing = score_facts(answer, case.get("expected_facts", []))
+        forbidden = find_forbidden(answer, case.get("forbidden", []))
+        result.update({
+            "answer": answer,
+            "tools": tools,
+            "fact_score": round(fact_score, 3),
+            "missing_facts": missing,
+            "forbidden_found": forbidden,
+        })
+        passed = fact_score == 1.0 and not forbidden
+
+        if case.get("judge", use_judge) and case.get("reference"):
+            try:
+                verdict = asyncio.run(self._judge(case["question"], case["reference"], answer))
+            except Exception as e:
+                verdict = {"score": None, "reason": f"Judge failed: {e}"}
+            result["judge"] = verdict
+            passed = passed and verdict["score"] is not None and verdict["score"] >= JUDGE_PASS_SCORE
+
+        result["passed"] = passed
+        result["seconds"] = round(time.time() - start, 2)
+        return result
+
+    def run(self, suite_path: str, use_judge: Optional[bool] = None,
+            progress: Optional[Callable[[Dict], None]] = None) -> Dict:
+        """
+        Run every question in a suite and save the report.
+
+        Args:
+            suite_path: YAML suite file
+            use_judge: Override the suite's judge setting
+            progress: Called with each question's result as it finishes
+
+        Returns:
+            The report dict that was written
+        """
+        suite = load_suite(suite_path)
+        use_judge = suite.get("judge", False) if use_judge is None else use_judge
+
+        results = []
+        for case in suite["questions"]:
+            result = self.run_case(case, use_judge)
+            results.append(result)
+            if progress:
+                progress(result)
+
+        judged = [r["judge"]["score"] for r in results if r.get("judge", {}).get("score") is not None]
+        scored = [r["fact_score"] for r in results if "fact_score" in r]
+        passed = sum(1 for r in results if r["passed"])
+        report = {
+            "suite": suite["name"],
+            "suite_file": suite_path,
+            "generated_at": datetime.now().isoformat(),
+            # What was under test, so runs can be compared
+            "model": self.ai.stream_model or self.ai.model,
+            "prompt_sha256": hashlib.sha256(self.ai.load_prompt("archie_system").encode("utf-8")).hexdigest()[:12],
+            "summary": {
+                "total": len(results),
+                "passed": passed,
+                "failed": len(results) - passed,
+                "errors": sum(1 for r in results if "error" in r),
+                "pass_rate": round(passed / len(results), 3),
+                "avg_fact_score": round(sum(scored) / len(scored), 3) if scored else None,
+                "avg_judge_score": round(sum(judged) / len(judged), 3) if judged else None,
+            },
+            "results": results,
+        }
+
+        os.makedirs(self.reports_dir, exist_ok=True)
+        filename = f"{datetime.now().strftime('%Y%m%d-%H%M%S')}-{re.sub(r'[^A-Za-z0-9_-]', '_', suite['name'])}.json"
+        report["report_file"] = filename
+        with open(os.path.join(self.reports_dir, filename), "w", encoding="utf-8") as f:
+            json.dump(report, f, ensure_ascii=False, indent=2)
+        return report
+
+    def list_reports(self) -> List[str]:
+        """Report file names, newest first."""
+        try:
+            return sorted((name for name in os.listdir(self.reports_dir) if name.endswith(".json")), reverse=True)
+        except FileNotFoundError:
+            return []
+
+    def load_report(self, name: Optional[str] = None) -> Optional[Dict]:
+        """Load a report by file name, or the newest one."""
+        reports = self.list_reports()
+        if name is None:
+            name = reports[0] if reports else None
+        if name not in reports:
+            return None
+        with open(os.path.join(self.reports_dir, name), "r", encoding="utf-8") as f:
+            return json.load(f)
+
+
+def list_suites(evals_dir: str) -> List[str]:
+    """Names of the suites in the evals directory."""
+    try:
+        return sorted(os.path.splitext(name)[0] for name in os.listdir(evals_dir) if name.endswith((".yaml", ".yml")))
+    except FileNotFoundError:
+        return []
+
+
+def resolve_suite(evals_dir: str, name: str) -> str:
+    """Path of a suite given by name (looked up in the evals directory) or as a file path."""
+    if os.path.sep in name or name.endswith((".yaml", ".yml")):
+        return name
+    for extension in (".yaml", ".yml"):
+        path = os.path.join(evals_dir, name + extension)
+        if os.path.exists(path):
+            return path
+    raise SuiteError(f"No suite named {name} in {evals_dir}")
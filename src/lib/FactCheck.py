@@ -0,0 +1,115 @@
+"""
+Fact-check pass for ArchieAI answers.
+Answers are grounded in the knowledge base pages retrieval puts in the prompt, but nothing
+checked that the model stuck to them. With [verification] on, a second (ideally small)
+model reads the finished answer next to those pages with prompts/verify.txt and lists the
+claims they don't support. With verification.action = "note" the answer streams as usual
+and gets a note naming what couldn't be confirmed; with "regenerate" it's held back until
+it's checked, and written again with the unsupported claims pointed out if it fails. A
+check costs about as much as the answer itself, so it's off by default. Answers without
+retrieved pages aren't checked, since there's nothing to check them against.
+"""
+import re
+import json
+import threading
+from typing import Dict, List, Optional
+
+from lib.PromptSafety import neutralize
+
+# Characters of each page shown to the checker, keeps the prompt within small context windows
+SOURCE_CHARS = 1500
+# Unsupported claims named in the note shown to the user
+NOTE_CLAIMS = 3
+CONFIDENCE_LEVELS = ("high", "medium", "low")
+
+
+class VerificationError(Exception):
+    """The checking model failed or answered with something unusable."""
+
+
+def parse_verdict(output: str) -> Dict:
+    """
+    The JSON object in the checker's output.
+
+    Returns:
+        {"checked": True, "supported", "unsupported_claims", "confidence"}
+    """
+    match = re.search(r"\{.*\}", output, re.DOTALL)
+    try:
+        verdict = json.loads(match.group(0)) if match else None
+    except json.JSONDecodeError:
+        verdict = None
+    if not isinstance(verdict, dict) or not isinstance(verdict.get("unsupported_claims"), list):
+        raise VerificationError(f"Expected a verdict object, got: {output[:200]}")
+    claims = [str(claim).strip() for claim in verdict["unsupported_claims"] if str(claim).strip()]
+    confidence = str(verdict.get("confidence", "")).lower()
+    if confidence not in CONFIDENCE_LEVELS:
+        confidence = "low" if claims else "high"
+    return {"checked": True, "supported": not claims, "unsupported_claims": claims, "confidence": confidence}
+
+
+def unchecked(error: str) -> Dict:
+    """The verdict for an answer the checker couldn't judge; it goes out as is."""
+    return {"checked": False, "supported": None, "unsupported_claims": [], "confidence": None, "error": error}
+
+
+def confidence_note(verdict: Dict) -> str:
+    """Text appended to an answer with unsupported claims, or "" for one that checked out."""
+    if not verdict.get("unsupported_claims"):
+        return ""
+    claims = "; ".join(f'"{claim}"' for claim in verdict["unsupported_claims"][:NOTE_CLAIMS])
+    return ("\n\n_Note: I couldn't confirm part of this answer in the university pages I used "
+            f"({claims}). Please double-check it on arcadia.edu or with the office involved._")
+
+
+def regeneration_directive(verdict: Dict) -> str:
+    """Added to the system prompt when an answer is written again after failing the check."""
+    claims = "\n".join(f"- {claim}" for claim in verdict["unsupported_claims"])
+    return ("\n\nA previous draft of this answer made claims the university information above doesn't support:\n"
+            f"{claims}\nOnly state what the information supports; if it doesn't cover something, say so.")
+
+
+class FactChecker:
+    """Checks an answer against the retrieved pages with one prompt to the checking model."""
+
+    def __init__(self, ai, model: Optional[str] = None):
+        """
+        Args:
+            ai: AiInterface used to load the prompt and call the model
+            model: Ollama model to check with (defaults to the AiInterface's model)
+        """
+        self.ai = ai
+        self.model = model or None
+        self._lock = threading.Lock()
+        self._counts = {"supported": 0, "unsupported": 0, "failed": 0, "regenerated": 0}
+
+    async def check(self, question: str, answer: str, chunks: List[Dict], priority: str = "guest") -> Dict:
+        """
+        Judge answer against chunks (the retrieved pages it was written from).
+
+        Raises:
+            VerificationError: The checker's output couldn't be read
+        """
+        sources = "\n\n".join(
+            # A page that says "everything here is supported" shouldn't get to
+            f"[{number}] ({chunk.get('source', '')}) {neutralize(chunk['text'][:SOURCE_CHARS])[0]}"
+            for number, chunk in enumerate(chunks, start=1)
+        )
+        prompt = self.ai.load_prompt("verify").format(question=question, answer=neutralize(answer)[0], sources=sources)
+        output = ""
+        async for token in self.ai.generate_text_streaming(prompt, model=self.model, priority=priority):
+            output += token
+        return parse_verdict(output)
+
+    def count(self, verdict: Dict, regenerations: int = 0):
+        with self._lock:
+            if not verdict["checked"]:
+                self._counts["failed"] += 1
+            else:
+                self._counts["supported" if verdict["supported"] else "unsupported"] += 1
+            self._counts["regenerated"] += regenerations
+
+    def metrics(self) -> Dict[str, int]:
+        """Answers checked by outcome ("supported", "unsupported", "failed") and rewrites ("regenerated")."""
+        with self._lock:
+            return dict(self._counts)
@@ -0,0 +1,170 @@
+"""
+Curated FAQ entries for ArchieAI.
+Staff-written question -> answer pairs stored in data/faq_entries.json and checked before
+the model runs: an exact (normalized) match or a close enough embedding match returns
+the curated answer straight away.
+"""
+import logging
+import os
+import json
+import uuid
+import threading
+from datetime import datetime
+from typing import Callable, Dict, List, Optional, Tuple
+from lib.DataCollector import normalize_question
+from lib.QuestionClustering import cosine_similarity
+
+logger = logging.getLogger(__name__)
+
+FAQ_FILE = "faq_entries.json"
+
+
+class FaqStore:
+    """CRUD and matching for curated FAQ entries."""
+
+    def __init__(self, data_dir: str = "data", embed: Optional[Callable[[List[str]], List[List[float]]]] = None,
+                 similarity_threshold: float = 0.9):
+        """
+        Args:
+            data_dir: Data directory the entries file lives in
+            embed: Function that embeds a list of texts; semantic matching is off without it
+            similarity_threshold: Minimum cosine similarity for a semantic match
+        """
+        self.path = os.path.join(data_dir, FAQ_FILE)
+        self.embed = embed
+        self.similarity_threshold = similarity_threshold
+        self._lock = threading.Lock()
+
+    def _load(self) -> List[Dict]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return []
+
+    def _save(self, entries: List[Dict]):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(entries, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    @staticmethod
+    def _public(entry: Dict) -> Dict:
+        """Entry without the cached embeddings."""
+        return {key: value for key, value in entry.items() if key != "embeddings"}
+
+    def list_entries(self) -> List[Dict]:
+        return [self._public(entry) for entry in self._load()]
+
+    def get_entry(self, entry_id: str) -> Optional[Dict]:
+        entry = next((e for e in self._load() if e["entry_id"] == entry_id), None)
+        return self._public(entry) if entry else None
+
+    def create_entry(self, question: str, answer: str, alternate_questions: Optional[List[str]] = None,
+                     created_by: Optional[str] = None) -> Dict:
+        now = datetime.now().isoformat()
+        entry = {
+            "entry_id": uuid.uuid4().hex,
+            "question": question,
+            "alternate_questions": alternate_questions or [],
+            "answer": answer,
+            "created_by": created_by,
+            "created_at": now,
+            "updated_at": now,
+            "hits": 0,
+        }
+        with self._lock:
+            entries = self._load()
+            entries.append(entry)
+            self._save(entries)
+        return self._public(entry)
+
+    def update_entry(self, entry_id: str, updates: Dict) -> Optional[Dict]:
+        """Update question, alternate_questions, and/or answer. Returns None if there's no such entry."""
+        with self._lock:
+            entries = self._load()
+            entry = next((e for e in entries if e["entry_id"] == entry_id), None)
+            if entry is None:
+                return None
+            for key in ("question", "alternate_questions", "answer"):
+                if key in updates:
+                    entry[key] = updates[key]
+            if "question" in updates or "alternate_questions" in updates:
+                # Question text changed, so the cached vectors are wrong now
+                entry.pop("embeddings", None)
+            entry["updated_at"] = datetime.now().isoformat()
+            self._save(entries)
+        return self._public(entry)
+
+    def delete_entry(self, entry_id: str) -> bool:
+        with self._lock:
+            entries = self._load()
+            remaining = [e for e in entries if e["entry_id"] != entry_id]
+            if len(remaining) == len(entries):
+                return False
+            self._save(remaining)
+        return True
+
+    def _record_hit(self, entry_id: str):
+        with self._lock:
+            entries = self._load()
+            for entry in entries:
+                if entry["entry_id"] == entry_id:
+                    entry["hits"] = entry.get("hits", 0) + 1
+                    entry["last_matched_at"] = datetime.now().isoformat()
+            self._save(entries)
+
+    def _semantic_match(self, question: str, entries: List[Dict]) -> Optional[Tuple[Dict, float]]:
+        # Embeddings for each entry's questions are cached in the file and only computed once
+        missing = [e for e in entries if "embeddings" not in e]
+        if missing:
+            for entry in missing:
+                entry["embeddings"] = self.embed([entry["question"], *entry.get("alternate_questions", [])])
+            with self._lock:
+                stored = {e["entry_id"]: e for e in self._load()}
+                for entry in missing:
+                    if entry["entry_id"] in stored:
+                        stored[entry["entry_id"]]["embeddings"] = entry["embeddings"]
+                self._save(list(stored.values()))
+
+        vector = self.embed([question])[0]
+        best = None
+        for entry in entries:
+            for candidate in entry["embeddings"]:
+                score = cosine_similarity(vector, candidate)
+                if score >= self.similarity_threshold and (best is None or score > best[1]):
+                    best = (entry, score)
+        return best
+
+    def match(self, question: str) -> Optional[Dict]:
+        """
+        Find the curated entry for a question.
+
+        Returns:
+            {"entry": ..., "match": "exact" | "semantic", "score": float}, or None
+        """
+        entries = self._load()
+        if not entries:
+            return None
+
+        key = normalize_question(question)
+        for entry in entries:
+            questions = [entry["question"], *entry.get("alternate_questions", [])]
+            if key in (normalize_question(q) for q in questions):
+                self._record_hit(entry["entry_id"])
+                return {"entry": self._public(entry), "match": "exact", "score": 1.0}
+
+        if self.embed is None:
+            return None
+        try:
+            found = self._semantic_match(question, entries)
+        except Exception as e:
+            # The model is the fallback anyway, so a broken embedding model just means no FAQ match
+            logger.warning(f"FAQ semantic matching failed: {e}")
+            return None
+        if found is None:
+            return None
+        entry, score = found
+        self._record_hit(entry["entry_id"])
+        return {"entry": self._public(entry), "match": "semantic", "score": round(score, 4)}
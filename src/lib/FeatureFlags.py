@@ -0,0 +1,158 @@
+"""
+Feature flags for ArchieAI.
+Risky features can be switched on for a percentage of sessions before everyone gets them.
+Defaults come from the [flags] table in archie.toml; admins can change a flag at runtime
+through /api/admin/flags, which saves an override to data/feature_flags.json (picked up by
+every process without a restart). A session always lands in the same rollout bucket, so
+a conversation doesn't flip between behaviours halfway through.
+"""
+import logging
+import os
+import json
+import hashlib
+import threading
+from datetime import datetime
+from typing import Dict, List, Optional
+
+logger = logging.getLogger(__name__)
+
+FLAGS_FILE = "feature_flags.json"
+
+# Known flags -> (description, default state)
+FLAG_DEFINITIONS = {
+    "faq_answers": (
+        "Answer from curated FAQ entries before asking the model",
+        {"enabled": True, "rollout_percent": 100},
+    ),
+    "campus_tool_prefetch": (
+        "Run campus tools up front when a question mentions their keywords",
+        {"enabled": True, "rollout_percent": 100},
+    ),
+    "announcements_in_prompt": (
+        "Add active campus announcements to the system prompt",
+        {"enabled": True, "rollout_percent": 100},
+    ),
+    "session_titles": (
+        "Name each chat with a short model-written title after its first exchange",
+        {"enabled": True, "rollout_percent": 100},
+    ),
+    "history_summaries": (
+        "Summarize messages older than the history window instead of dropping them from the prompt",
+        {"enabled": True, "rollout_percent": 100},
+    ),
+    "experimental_prompt": (
+        "Use src/prompts/archie_system_experimental.txt instead of archie_system.txt",
+        {"enabled": False, "rollout_percent": 0},
+    ),
+}
+
+
+def rollout_bucket(name: str, key: str) -> int:
+    """Stable 0-99 bucket for a flag and a session (or user)."""
+    digest = hashlib.sha256(f"{name}:{key}".encode("utf-8")).hexdigest()
+    return int(digest[:8], 16) % 100
+
+
+class FeatureFlags:
+    """Flag state from config plus runtime overrides."""
+
+    def __init__(self, data_dir: str = "data", defaults: Optional[Dict[str, Dict]] = None):
+        """
+        Args:
+            data_dir: Data directory the runtime overrides are saved in
+            defaults: The [flags] table from the config, name -> {"enabled", "rollout_percent"}
+        """
+        self.path = os.path.join(data_dir, FLAGS_FILE)
+        self.defaults = defaults or {}
+        self._lock = threading.Lock()
+        self._overrides: Dict[str, Dict] = {}
+        self._overrides_mtime = None
+
+    def _load_overrides(self) -> Dict[str, Dict]:
+        """Overrides from disk, re-read only when the file changes."""
+        try:
+            mtime = os.path.getmtime(self.path)
+        except OSError:
+            return {}
+        if mtime != self._overrides_mtime:
+            try:
+                with open(self.path, "r", encoding="utf-8") as f:
+                    self._overrides = json.load(f)
+            except (OSError, json.JSONDecodeError) as e:
+                logger.warning(f"Could not read {self.path}: {e}")
+                self._overrides = {}
+            self._overrides_mtime = mtime
+        return self._overrides
+
+    def _save_overrides(self, overrides: Dict[str, Dict]):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(overrides, f, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def get(self, name: str) -> Dict:
+        """Effective state of a flag: built-in default, then config, then runtime override."""
+        state = dict(FLAG_DEFINITIONS[name][1]) if name in FLAG_DEFINITIONS else {"enabled": False, "rollout_percent": 0}
+        state.update(self.defaults.get(name, {}))
+        override = self._load_overrides().get(name)
+        if override:
+            state.update({key: override[key] for key in ("enabled", "rollout_percent") if key in override})
+        return state
+
+    def is_enabled(self, name: str, key: Optional[str] = None) -> bool:
+        """
+        Whether a flag is on for a session.
+
+        Args:
+            name: Flag name
+            key: Session ID (or other stable ID) used for percentage rollouts; without one,
+                 only fully rolled out flags count as on
+        """
+        state = self.get(name)
+        if not state.get("enabled"):
+            return False
+        percent = state.get("rollout_percent", 100)
+        if percent >= 100:
+            return True
+        if not key or percent <= 0:
+            return False
+        return rollout_bucket(name, key) < percent
+
+    def list_flags(self) -> List[Dict]:
+        """Every known or configured flag with its effective state, for the admin API."""
+        overrides = self._load_overrides()
+        names = sorted(set(FLAG_DEFINITIONS) | set(self.defaults) | set(overrides))
+        return [
+            dict(
+                self.get(name),
+                name=name,
+                description=FLAG_DEFINITIONS.get(name, ("",))[0],
+                overridden=name in overrides,
+                updated_at=overrides.get(name, {}).get("updated_at"),
+                updated_by=overrides.get(name, {}).get("updated_by"),
+            )
+            for name in names
+        ]
+
+    def set_override(self, name: str, updates: Dict, updated_by: Optional[str] = None) -> Dict:
+        """Save a runtime override ({"enabled", "rollout_percent"}) and return the new effective state."""
+        with self._lock:
+            overrides = dict(self._load_overrides())
+            override = dict(overrides.get(name, {}))
+            override.update(updates)
+            override["updated_at"] = datetime.now().isoformat()
+            override["updated_by"] = updated_by
+            overrides[name] = override
+            self._save_overrides(overrides)
+        return self.get(name)
+
+    def clear_override(self, name: str) -> bool:
+        """Drop a runtime override so the config value applies again. False if there wasn't one."""
+        with self._lock:
+            overrides = dict(self._load_overrides())
+            if name not in overrides:
+                return False
+            del overrides[name]
+            self._save_overrides(overrides)
+        return True
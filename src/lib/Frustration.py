@@ -0,0 +1,137 @@
+"""
+Frustration detection for ArchieAI conversations.
+A student who asks the same thing three ways, gets more annoyed each time, and thumbs
+down the answers is stuck, and a person should reach out. After each answer (and each
+rating) the last few turns of a session are scored on three signals:
+  - negative_sentiment: questions scoring negative (lib/Sentiment.py, which also catches
+                        "I already asked", "that's not what I meant", ...)
+  - rephrasing:         a question that mostly repeats the one before it
+  - negative_feedback:  answers rated thumbs down
+Past the threshold the session goes into the review queue (data/session_reviews.json)
+and app.py alerts staff once per session, by webhook and email. Admins resolve reviews
+after following up.
+"""
+import os
+import json
+import re
+import threading
+from datetime import datetime
+from typing import Dict, List, Optional
+
+from lib.Sentiment import score_sentiment, NEGATIVE_THRESHOLD
+
+REVIEWS_FILE = "session_reviews.json"
+
+# How much each occurrence of a signal adds to the score
+SIGNAL_WEIGHTS = {
+    "negative_sentiment": 1.0,
+    "rephrasing": 1.0,
+    "negative_feedback": 1.5,
+}
+
+# Share of words two questions have in common to count as a rephrasing
+REPHRASE_SIMILARITY = 0.5
+
+STOPWORDS = {"the", "a", "an", "is", "are", "to", "of", "for", "in", "on", "at", "do", "does", "i", "you", "my",
+             "what", "when", "where", "how", "can", "and", "or", "it", "me", "please"}
+
+
+def _words(text: str) -> set:
+    return {w for w in re.findall(r"[a-z0-9']+", text.lower()) if w not in STOPWORDS}
+
+
+def is_rephrasing(question: str, previous: str) -> bool:
+    """True if question shares most of its words with previous (or is the same question again)."""
+    a, b = _words(question), _words(previous)
+    if not a or not b:
+        return False
+    return len(a & b) / len(a | b) >= REPHRASE_SIMILARITY
+
+
+def frustration_signals(messages: List[Dict], window: int = 6) -> Dict:
+    """
+    Score the last window questions of a conversation (and the answers to them).
+
+    Returns:
+        {"score", "signals": {signal: count}}
+    """
+    user_indices = [i for i, m in enumerate(messages) if m.get("role") == "user"][-window:]
+    if not user_indices:
+        return {"score": 0.0, "signals": {name: 0 for name in SIGNAL_WEIGHTS}}
+    recent = messages[user_indices[0]:]
+    questions = [m.get("content", "") for m in recent if m.get("role") == "user"]
+
+    signals = {
+        "negative_sentiment": sum(1 for q in questions if score_sentiment(q) <= NEGATIVE_THRESHOLD),
+        "rephrasing": sum(1 for previous, q in zip(questions, questions[1:]) if is_rephrasing(q, previous)),
+        "negative_feedback": sum(1 for m in recent if m.get("role") == "assistant"
+                                 and (m.get("feedback") or {}).get("rating") == "down"),
+    }
+    score = sum(SIGNAL_WEIGHTS[name] * count for name, count in signals.items())
+    return {"score": round(score, 2), "signals": signals}
+
+
+class ReviewQueue:
+    """Sessions flagged for a human to follow up on, keyed by session ID."""
+
+    def __init__(self, data_dir: str = "data"):
+        self.path = os.path.join(data_dir, REVIEWS_FILE)
+        self._lock = threading.Lock()
+
+    def _load(self) -> Dict[str, Dict]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {}
+
+    def _save(self, reviews: Dict[str, Dict]):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(reviews, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def flag(self, session_id: str, user_email: Optional[str], assessment: Dict) -> Optional[Dict]:
+        """
+        Queue a session for review. Returns the new review, or None if it's already queued
+        (so staff get one alert per session, not one per answer).
+        """
+        with self._lock:
+            reviews = self._load()
+            if session_id in reviews and not reviews[session_id].get("resolved_at"):
+                return None
+            review = {
+                "session_id": session_id,
+                "user_email": user_email,
+                "reason": "frustration",
+                "score": assessment["score"],
+                "signals": assessment["signals"],
+                "flagged_at": datetime.now().isoformat(),
+                "resolved_at": None,
+                "resolved_by": None,
+                "note": None,
+            }
+            reviews[session_id] = review
+            self._save(reviews)
+            return review
+
+    def resolve(self, session_id: str, resolved_by: str, note: Optional[str] = None) -> Optional[Dict]:
+        """Mark a review as followed up. Returns it, or None if the session isn't queued."""
+        with self._lock:
+            reviews = self._load()
+            review = reviews.get(session_id)
+            if review is None:
+                return None
+            review.update(resolved_at=datetime.now().isoformat(), resolved_by=resolved_by, note=note)
+            self._save(reviews)
+            return review
+
+    def list(self, include_resolved: bool = False) -> List[Dict]:
+        """Reviews, newest first; open ones only unless include_resolved."""
+        with self._lock:
+            reviews = list(self._load().values())
+        if not include_resolved:
+            reviews = [r for r in reviews if not r.get("resolved_at")]
+        reviews.sort(key=lambda r: r.get("flagged_at") or "", reverse=True)
+        return reviews
@@ -1,15 +1,33 @@
+import logging
 import os
+import time
 import asyncio
-from dotenv import load_dotenv
 import requests
 from requests.adapters import HTTPAdapter
 from urllib3.util.retry import Retry
 from typing import Any,  AsyncIterator
-import json
 import sys
 from ollama import AsyncClient, web_fetch, web_search
 import inspect
 import datetime
+from contextlib import asynccontextmanager
+from lib.CampusTools import build_registry
+from lib.FeatureFlags import FeatureFlags
+from lib.Announcements import AnnouncementStore
+from lib.Retrieval import KnowledgeIndex, citation
+from lib.EmbeddingCache import EmbeddingCache
+from lib.Reranker import PromptReranker
+from lib.Config import load_config
+from lib.FactCheck import FactChecker, confidence_note, regeneration_directive, unchecked
+from lib.PromptSafety import UNTRUSTED_CONTEXT_NOTICE, wrap_untrusted
+from lib.GenerationQueue import GenerationQueue
+from lib.CircuitBreaker import CircuitBreaker
+from lib.AnswerStyle import style_directive, style_options
+from lib.Cancellation import CancelToken
+
+logger = logging.getLogger(__name__)
+
+
 class AiInterface:
     """
     AI Interface using Ollama for local LLM inference with streaming support.
@@ -31,13 +49,80 @@ class AiInterface:
         scraper_max_retries: int = 3,
         scraper_backoff_factor: float = 1.0,
         scraper_timeout: int = 15,
-        available_tools = {'web_search': web_search, 'web_fetch': web_fetch}
+        available_tools = {'web_search': web_search, 'web_fetch': web_fetch},
+        config = None,
+        flags = None
     ):
-        # Load the variables from the .env file into the environment
-        load_dotenv()
+        # Without one (a script using the class directly), archie.toml and the environment as the server reads them
+        if config is None:
+            config = load_config()
+        self.model = config.ollama.model
+        self.stream_model = config.ollama.stream_model
+        self.embedding_model = config.ollama.embedding_model
+        # Chat models a question may ask for: the two defaults plus ollama.models
+        self.allowed_models = list(dict.fromkeys([self.stream_model, self.model, *config.ollama.models]))
+        # (when it was fetched, names) of the models the Ollama daemon has pulled, see list_local_models()
+        self._local_models = None
+        embedding_cache_size = config.ollama.embedding_cache_size
+        self.ollama_host = config.ollama.host
+        self.api_key = config.ollama.api_key
+        # Previous messages of the session sent along with each question
+        self.history_messages = config.limits.history_messages
+        self.data_dir = config.data_dir
+        self.cache_dir = config.cache_dir
+        self.prompts_dir = config.prompts_dir
+        self.dev_mode = config.server.dev_mode
+
+        # Prompt templates are read once and cached, except in dev mode where they're re-read every time
+        self._prompt_cache = {}
+
+        # Caps how many generations run at once; queued ones go logged-in users first, batch jobs last
+        self.queue = GenerationQueue(config.queue.max_concurrent, config.queue.max_waiting,
+                                     config.queue.wait_timeout_seconds, config.queue.weights)
+
+        # Stops sending requests to Ollama for a while after it fails repeatedly (see lib/CircuitBreaker.py)
+        breaker = config.breaker
+        if breaker.enabled:
+            self.breaker = CircuitBreaker(breaker.failure_threshold, breaker.cooldown_seconds)
+        else:
+            self.breaker = CircuitBreaker(failure_threshold=0)
+        # Seconds without a response from Ollama before a request counts as failed (None = wait forever)
+        self.request_timeout = breaker.request_timeout_seconds or None
+
+        # Embeddings of texts we've seen before (unchanged chunks, repeated questions) come from here
+        self.embedding_cache = EmbeddingCache(self.cache_dir, embedding_cache_size) if embedding_cache_size > 0 else None
+
+        # Campus data tools (dining hours, shuttle, library, IT status) offered to the model
+        self.tools = build_registry(config, data_dir=self.data_dir)
+
+        # Admin-posted campus announcements, added to the system prompt while they're active
+        self.announcements = AnnouncementStore(self.data_dir)
+
+        # Hybrid keyword + vector search over the scraped knowledge base
+        retrieval = config.retrieval
+        self.retrieval_enabled = retrieval.enabled
+        self.retrieval_top_k = retrieval.top_k
+        self.knowledge = KnowledgeIndex(
+            self.data_dir,
+            # Called from worker threads (asyncio.to_thread), so it can run its own event loop
+            embed=lambda texts: asyncio.run(self.embed(texts)),
+            chunk_size=retrieval.chunk_size,
+            chunk_overlap=retrieval.chunk_overlap,
+            chunk_strategy=retrieval.chunk_strategy,
+            chunking=retrieval.chunking,
+            embed_model=self.embedding_model,
+            candidates=retrieval.candidates,
+            rrf_k=retrieval.rrf_k,
+            reranker=PromptReranker(self, model=retrieval.rerank_model) if retrieval.rerank else None,
+            rerank_candidates=retrieval.rerank_candidates,
+        )
+
+        # Second-model check of answers against the pages they were written from (off by default)
+        self.verification = config.verification
+        self.fact_checker = FactChecker(self, model=self.verification.model) if self.verification.enabled else None
 
-        # Retrieve the model name from environment (defaults to llama2 if not set)
-        self.model = os.getenv("MODEL", "llama2")
+        # Feature flags for features being rolled out gradually
+        self.flags = flags if flags is not None else FeatureFlags(self.data_dir, config.flags)
 
         # Debug flag
         self.debug = debug
@@ -72,16 +157,115 @@ class AiInterface:
 
     def _log(self, *args):
         if self.debug:
-            print("[AiInterface DEBUG]", *args)
+            logger.debug(" ".join(str(arg) for arg in args))
 
+    def _untrusted(self, text: str, source: str = "", as_of: str = "") -> str:
+        """Neutralize scraped/fetched text for the prompt (lib/PromptSafety.py), logging any injection attempt."""
+        block, found = wrap_untrusted(text, source=source, as_of=as_of)
+        if found:
+            logger.warning(f"Removed {len(found)} prompt-injection phrase(s) from {source or 'untrusted content'}: {found[:3]}")
+        return block
 
+    def load_prompt(self, name: str) -> str:
+        """Load a prompt template from the prompts directory (cached unless in dev mode)."""
+        if not self.dev_mode and name in self._prompt_cache:
+            return self._prompt_cache[name]
+        with open(os.path.join(self.prompts_dir, f"{name}.txt"), "r", encoding="utf-8") as f:
+            prompt = f.read().strip()
+        self._prompt_cache[name] = prompt
+        return prompt
+
+
+
+    @asynccontextmanager
+    async def _generation_slot(self, priority: str):
+        """
+        A generation queue slot, taken through the circuit breaker. Raises CircuitOpen straight
+        away while Ollama is failing, instead of waiting in line for a backend that's down.
+        """
+        self.breaker.check()
+        async with self.queue.slot(priority):
+            async with self.breaker.guard():
+                yield
+
+    async def embed(self, texts: list) -> list:
+        """
+        Embed a list of texts with the Ollama embeddings API.
+        Returns one vector (list of floats) per input text, in the same order.
+        Texts in the embedding cache aren't sent to the model.
+        """
+        if not texts:
+            return []
+        if self.embedding_cache is not None:
+            vectors = self.embedding_cache.get_many(self.embedding_model, texts)
+        else:
+            vectors = [None] * len(texts)
+        # Each distinct uncached text is embedded once
+        missing = list(dict.fromkeys(text for text, vector in zip(texts, vectors) if vector is None))
+        if missing:
+            async_client = AsyncClient(host=self.ollama_host, timeout=self.request_timeout)
+            async with self.breaker.guard():
+                response = await async_client.embed(model=self.embedding_model, input=missing)
+            embedded = {text: list(vector) for text, vector in zip(missing, response['embeddings'])}
+            if self.embedding_cache is not None:
+                self.embedding_cache.put_many(self.embedding_model, embedded)
+            vectors = [vector if vector is not None else embedded[text] for text, vector in zip(texts, vectors)]
+        return vectors
 
     #I dont think this is used anywhere but im keeping it just in case
 
-    async def generate_text_streaming(self, prompt: str, system_prompt: str = "") -> AsyncIterator[str]:
+    def check_model(self, model: str):
+        """Raise ValueError unless model is one of the allowed chat models."""
+        if model not in self.allowed_models:
+            raise ValueError(f"Model {model} isn't allowed; expected one of {', '.join(self.allowed_models)}")
+
+    async def list_local_models(self, max_age_seconds: float = 60) -> list:
+        """
+        Names of the models the Ollama daemon has pulled (e.g. "llama3:latest"), cached for
+        max_age_seconds so a page load doesn't hit Ollama each time. Raises if it can't be reached.
+        """
+        if self._local_models is not None and time.monotonic() - self._local_models[0] < max_age_seconds:
+            return self._local_models[1]
+        async_client = AsyncClient(host=self.ollama_host, timeout=self.request_timeout)
+        response = await async_client.list()
+        names = [entry.get("model") or entry.get("name") for entry in response.get("models", [])]
+        self._local_models = (time.monotonic(), [name for name in names if name])
+        return self._local_models[1]
+
+    async def health_check(self, timeout: float = 2.0) -> dict:
+        """
+        Ask the Ollama daemon for its models right now, for readiness probes; never raises.
+
+        Returns:
+            {"status": "ok", "latency_ms", "models", "default_model_installed"} or
+            {"status": "fail", "latency_ms", "error"}
+        """
+        started = time.monotonic()
+        try:
+            async_client = AsyncClient(host=self.ollama_host, timeout=timeout)
+            response = await asyncio.wait_for(async_client.list(), timeout)
+        except Exception as e:
+            return {"status": "fail", "latency_ms": round((time.monotonic() - started) * 1000),
+                    "error": str(e) or type(e).__name__}
+        names = [entry.get("model") or entry.get("name") for entry in response.get("models", [])]
+        names = [name for name in names if name]
+        # Fresh anyway, so the model picker gets it for free
+        self._local_models = (time.monotonic(), names)
+        return {
+            "status": "ok",
+            "latency_ms": round((time.monotonic() - started) * 1000),
+            "models": len(names),
+            # Untagged pulls are listed as "<name>:latest"
+            "default_model_installed": self.stream_model in names or f"{self.stream_model}:latest" in names,
+        }
+
+    async def generate_text_streaming(self, prompt: str, system_prompt: str = "", model: str = None,
+                                      priority: str = "batch") -> AsyncIterator[str]:
         """
         Async streaming generator that yields tokens as they are generated by Ollama.
         This allows for real-time display of the AI's thinking process.
+        model overrides the configured model (e.g. a small one for reranking). priority is the
+        generation queue class ("user", "guest", or "batch"); raises QueueFull if no slot comes up.
         
         Usage:
             async for token in ai.generate_text_streaming(prompt, system):
@@ -99,68 +283,50 @@ class AiInterface:
         })
         
         
-        # Create a new AsyncClient for each streaming request to avoid event loop conflicts
-        async_client = AsyncClient()
-        stream = await async_client.chat(
-            model=self.model,
-            messages=messages,
-            stream=True,
-
-        
-        )
+        async with self._generation_slot(priority):
+            # Create a new AsyncClient for each streaming request to avoid event loop conflicts
+            async_client = AsyncClient(host=self.ollama_host, timeout=self.request_timeout)
+            stream = await async_client.chat(
+                model=model or self.model,
+                messages=messages,
+                stream=True,
+            )
 
-        async for chunk in stream:
-            if 'message' in chunk and 'content' in chunk['message']:
-                yield chunk['message']['content']
+            async for chunk in stream:
+                if 'message' in chunk and 'content' in chunk['message']:
+                    yield chunk['message']['content']
        
     
     #I dont think this is used anywhere but im keeping it just in case
     
-    async def Archie(self, query: str, conversation_history: list = None) -> str:
+    @staticmethod
+    def _pinned_outside(pinned: list, history: list) -> list:
+        """Pinned messages that aren't already in the history being sent."""
+        in_history = {msg.get("message_id") for msg in history or []}
+        return [msg for msg in pinned or [] if msg.get("message_id") not in in_history]
+
+    async def Archie(self, query: str, conversation_history: list = None, model: str = None,
+                     pinned: list = None, style: str = None, session_id: str = None,
+                     priority: str = "batch", history_summary: str = None) -> str:
         """
-        Main async entry point for the Archie AI assistant.
-        Uses scraped data from JSON file to provide context for answering queries.
-        Uses Ollama tool calling to enable web search when needed.
+        Main async entry point for the Archie AI assistant: the whole answer as one string.
+        It's Archie_streaming() collected, so it gets the same prompt, including the knowledge
+        base chunks retrieved for the question (lib/Retrieval.py), campus tools, and web search.
+        model overrides ollama.model for this question. pinned are messages the user pinned,
+        sent even when they're older than the history window. style is an answer style from
+        lib/AnswerStyle.py ("concise", "detailed", "bullet"), or None for the default.
+        session_id, priority, and history_summary are as for Archie_streaming().
         """
-        with open("data/scrape_results.json", "r", encoding="utf-8") as f:
-            results = json.load(f)
-        
-        # Build messages list with system prompt and conversation history
-        messages = []
-        
-        system_content = f"""You are ArchieAI, an AI assistant for Arcadia University. You are here to help students, faculty, and staff with any questions they may have about the university.
-
-You are made by students for a final project. You must be factual and accurate based on the information provided.
-
-Respond based on your knowledge up to 2025.
-
-Use the following university data to answer questions:
-{json.dumps(results, indent=2)}
-
-If the university data doesn't contain the information needed, or if the query requires current/real-time information, you can use the search_web tool to find additional information."""
-        
-        messages.append({
-            'role': 'system',
-            'content': system_content
-        })
-        
-        # Add conversation history
-        if conversation_history:
-            for msg in conversation_history[-5:]:  # Last 5 messages for context
-                messages.append({
-                    'role': msg.get('role', 'user'),
-                    'content': msg.get('content', '')
-                })
-        
-        # Add current query
-        messages.append({
-            'role': 'user',
-            'content': query
-        })
-        
-        # Call with tools - run in executor since it's synchronous
-
-    async def async_WebSearch(self, prompt: str, system_prompt: str = "", available_tools = {'web_search': web_search, 'web_fetch': web_fetch}) -> AsyncIterator[Any]:
+        answer = ""
+        async for chunk in self.Archie_streaming(query, conversation_history=conversation_history, session_id=session_id,
+                                                 model=model or self.model, priority=priority, pinned=pinned, style=style,
+                                                 history_summary=history_summary):
+            if isinstance(chunk, str):
+                answer += chunk
+        return answer
+
+    async def async_WebSearch(self, prompt: str, system_prompt: str = "", available_tools = {'web_search': web_search, 'web_fetch': web_fetch},
+                              model: str = None, options: dict = None, cancel: CancelToken = None) -> AsyncIterator[Any]:
         
             
         """
@@ -168,28 +334,39 @@ If the university data doesn't contain the information needed, or if the query r
         Yields:
         - str: incremental content chunks from the assistant
         - dict: tool call results in the form {'tool_name': ..., 'tool_result': ...}
-        - dict: final message when done: {'final': True, 'message': final_response_message}
+        - dict: final message when done: {'final': True, 'message': final_response_message, 'usage': ...}
+          where usage is the tokens Ollama reported over every round of the tool loop:
+          {'prompt_tokens', 'completion_tokens', 'generation_seconds'}
+        model overrides ollama.stream_model for this call. options are passed to Ollama as is
+        (e.g. num_predict from an answer style). If cancel fires, the Ollama stream is closed
+        at its next chunk and GenerationCancelled is raised (lib/Cancellation.py).
         """
-        OLLAMA_API_KEY = os.getenv('OLLAMA_API_KEY') or os.getenv('OLLAMA_TOKEN')
+        OLLAMA_API_KEY = self.api_key
         if not OLLAMA_API_KEY:
-            print("Error: OLLAMA_API_KEY (or OLLAMA_TOKEN) not found in environment; add it to your .env or export it before running.")
+            logger.error("No Ollama API key; set ollama.api_key, or OLLAMA_API_KEY (or OLLAMA_TOKEN) in your .env or environment.")
             sys.exit(1)
-        MODEL = os.getenv('OLLAMA_MODEL')
+        MODEL = model or self.stream_model
 
         # Normalize to OLLAMA_API_KEY for the Ollama client if the token was provided under OLLAMA_TOKEN.
         # This took me way too long to figure out Headers are of the devil and there is no documentation on this.
         custom_headers = {
             "Authorization": f"Bearer {OLLAMA_API_KEY}"
         }
-        client = AsyncClient(headers=custom_headers)
+        client = AsyncClient(host=self.ollama_host, headers=custom_headers, timeout=self.request_timeout)
         messages = [{'role': 'user', 'content': prompt}, {'role': 'system', 'content': system_prompt}]
+        # Campus tools are called through the same loop as web search
+        available_tools = {**available_tools, **self.tools.callables()}
+        usage = {'prompt_tokens': 0, 'completion_tokens': 0, 'generation_seconds': 0.0}
         while True:
+            if cancel is not None:
+                cancel.check()
             response_stream = await client.chat(
                 model=MODEL,
                 messages=messages,
-                tools=[client.web_search, client.web_fetch],
+                tools=[client.web_search, client.web_fetch, *self.tools.schemas()],
                 think=True,
-                stream=True
+                stream=True,
+                options=options or None
             )
 
             final_response_message = {
@@ -201,6 +378,11 @@ If the university data doesn't contain the information needed, or if the query r
 
             # Iterate asynchronously through streamed chunks and yield content as it arrives
             async for response_chunk in response_stream:
+                if cancel is not None and cancel.cancelled:
+                    # Closing the stream drops the connection, which stops Ollama generating
+                    if hasattr(response_stream, "aclose"):
+                        await response_stream.aclose()
+                    cancel.check()
                 chunk_message = response_chunk.message
 
                 if chunk_message.thinking:
@@ -215,6 +397,12 @@ If the university data doesn't contain the information needed, or if the query r
                 if chunk_message.tool_calls:
                     final_response_message['tool_calls'] = chunk_message.tool_calls
 
+                # The last chunk of a round carries Ollama's token counts
+                if getattr(response_chunk, 'done', False):
+                    usage['prompt_tokens'] += getattr(response_chunk, 'prompt_eval_count', None) or 0
+                    usage['completion_tokens'] += getattr(response_chunk, 'eval_count', None) or 0
+                    usage['generation_seconds'] += (getattr(response_chunk, 'eval_duration', None) or 0) / 1e9
+
             # Add the assistant's final streamed message into the conversation history
             messages.append(final_response_message)
 
@@ -238,10 +426,11 @@ If the university data doesn't contain the information needed, or if the query r
                             else:
                                 result = maybe_result
 
-                        # Append tool result to messages so the model can continue the conversation
+                        # Append tool result to messages so the model can continue the conversation.
+                        # Search results and fetched pages are untrusted, like any scraped text
                         messages.append({
                             'role': 'tool',
-                            'content': str(result)[:2000 * 4],
+                            'content': self._untrusted(str(result)[:2000 * 4], source=tool_name),
                             'tool_name': tool_name
                         })
 
@@ -257,38 +446,153 @@ If the university data doesn't contain the information needed, or if the query r
                 # continue to next iteration so the model can respond to tool results
             else:
                 # No tool calls: streaming finished; yield final assembled message and exit
-                yield {'final': True, 'message': final_response_message}
+                yield {'final': True, 'message': final_response_message, 'usage': usage}
                 break
     
-    async def Archie_streaming(self, query: str, conversation_history: list = None) -> AsyncIterator[str]:
+    async def Archie_streaming(self, query: str, conversation_history: list = None, session_id: str = None,
+                               memories: list = None, personalization: bool = False, model: str = None,
+                               priority: str = "batch", pinned: list = None, style: str = None,
+                               prompt: str = None, history_summary: str = None,
+                               cancel: CancelToken = None) -> AsyncIterator[str]:
         """
         Streaming version of Archie that yields tokens as they are generated.
-        Note: Tool calling with streaming is complex, so this version uses the standard approach.
-        For full tool calling support, use the non-streaming Archie() method.
         
+        session_id picks the rollout bucket for feature flags. memories are facts remembered
+        about the user (lib/UserMemory.py); they only go into the system prompt when
+        personalization is True, i.e. the user has memory and personalization turned on.
+        model picks another configured chat model than ollama.stream_model (see resolve_model in app.py).
+        priority is the generation queue class: "user" or "guest" for people waiting on the answer,
+        "batch" (the default) for jobs like evaluations. pinned are messages the user pinned in the
+        session (SessionManager.set_message_pinned); they go into the prompt even once they've
+        scrolled out of conversation_history. style ("concise", "detailed", "bullet", see
+        lib/AnswerStyle.py) adds a length/format directive and caps the tokens generated.
+        prompt is a system prompt file in the prompts directory to use instead of archie_system
+        (a canary's candidate or a promoted one, see lib/Canary.py).
+        history_summary is the session's rolling summary of the messages older than
+        conversation_history (SessionManager.get_conversation_context), sent ahead of them.
+        cancel is the answer's token from lib/Cancellation.py; when it fires the model stream is
+        aborted and GenerationCancelled is raised.
+        Besides tokens it yields dicts: {'citations': [...]}, {'tool_name', 'tool_result'}, the final
+        message from async_WebSearch, and {'timing': {'retrieval_seconds'}} for the request's SLO log.
+        With [verification] on, an answer written from retrieved pages is fact-checked once it's done
+        (lib/FactCheck.py): a confidence note is streamed after it if it failed, and the verdict follows
+        as {'verification': {...}}.
+
         Usage:
             async for token in ai.Archie_streaming("When is fall break?"):
                 print(token, end='', flush=True)
         """
         
+        if model is not None:
+            # Callers validate the user's pick; this keeps anything else from reaching Ollama
+            self.check_model(model)
+
         # Build context with conversation history
         history_context = ""
+        if history_summary:
+            # Model-written from what the user said, so it gets the same treatment as fetched text
+            history_context = "\n\nSummary of the earlier conversation:\n" + self._untrusted(history_summary, source="conversation summary") + "\n"
         if conversation_history:
-            history_context = "\n\nConversation History:\n"
+            history_context += "\n\nConversation History:\n"
             for msg in conversation_history: 
                 role = msg.get("role", "user")
                 content = msg.get("content", "")
                 history_context += f"{role.upper()}: {content}\n"
 
-        system_prompt = f"""You are ArchieAI, an AI assistant for Arcadia University IN glenside pennsylvania. Do not mention Georgia or the arcadia university in georgia. You are here to help students, faculty, and staff with any questions they may have about the university.
-
-You are made by students for a final project. You must be factual and concise based on the information provided however if a user specifies a length requirement or a word count you must adhere to it. All responses should be professional yet to the point.
-Markdown IS NOT SUPPORTED OR RENDERED in the final output. DO NOT RESPOND WITH MARKDOWN FORMATTING OR HYPERLINKS so no [links](url) formatting or bolding. however you can provide full URLs.
-You are not associated with Arcadia University officially as you are a student project.
-History:
-{history_context}
-The Time is {datetime.datetime.now().strftime("%Y-%m-%d %H:%M:%S")}"""
-
-        async for token in self.async_WebSearch(query, system_prompt=system_prompt):
-            yield token
+        prompt_name = "archie_system"
+        if prompt and os.path.exists(os.path.join(self.prompts_dir, f"{prompt}.txt")):
+            prompt_name = prompt
+        elif self.flags.is_enabled("experimental_prompt", session_id) and os.path.exists(
+                os.path.join(self.prompts_dir, "archie_system_experimental.txt")):
+            prompt_name = "archie_system_experimental"
+
+        system_prompt = self.load_prompt(prompt_name).format(
+            history_context=history_context,
+            now=datetime.datetime.now().strftime("%Y-%m-%d %H:%M:%S")
+        ) + UNTRUSTED_CONTEXT_NOTICE
+
+        pinned = self._pinned_outside(pinned, conversation_history)
+        if pinned:
+            system_prompt += "\n\nMessages the user pinned earlier in this conversation; keep them in mind for every answer:\n"
+            system_prompt += "\n".join(f"{msg.get('role', 'user').upper()}: {msg.get('content', '')}" for msg in pinned)
+
+        chunks = []
+        if self.retrieval_enabled:
+            retrieval_start = time.monotonic()
+            chunks = await asyncio.to_thread(self.knowledge.search, query, self.retrieval_top_k)
+            yield {'timing': {'retrieval_seconds': round(time.monotonic() - retrieval_start, 3)}}
+            if chunks:
+                system_prompt += (
+                    "\n\nUniversity information related to the question (from arcadia.edu), with the date each page was fetched. "
+                    "If the answer depends on a deadline, price, or schedule from a page that isn't recent, "
+                    "say what date the information is from and suggest checking the page:\n"
+                )
+                cited = [citation(chunk) for chunk in chunks]
+                system_prompt += "\n".join(
+                    self._untrusted(chunk['text'], source=chunk['source'], as_of=cite['as_of'] or "")
+                    for chunk, cite in zip(chunks, cited)
+                )
+                yield {'citations': cited}
+
+        # Campus tools whose keywords show up in the question run up front, so their data is
+        # in the prompt even if the model never calls them
+        live_data = ""
+        prefetched = self.tools.triggered_by(query) if self.flags.is_enabled("campus_tool_prefetch", session_id) else []
+        for tool in prefetched:
+            result = await asyncio.to_thread(tool.run, query=query)
+            yield {'tool_name': tool.name, 'tool_result': result}
+            live_data += "\n" + self._untrusted(str(result), source=tool.name) + "\n"
+        if live_data:
+            system_prompt += "\n\nLive campus data (prefer this over anything older):" + live_data
+
+        if self.flags.is_enabled("announcements_in_prompt", session_id):
+            system_prompt += self.announcements.prompt_section()
+
+        if memories and personalization is True:
+            system_prompt += "\n\nWhat you remember about this user from earlier conversations (use it when it helps, don't recite it):\n"
+            system_prompt += "\n".join(f"- {fact}" for fact in memories)
+
+        # Last, so it's what the model reads right before the question
+        system_prompt += style_directive(style)
+
+        if self.fact_checker is None or not chunks:
+            # The slot is held for the whole answer, tool calls included
+            async with self._generation_slot(priority):
+                async for token in self.async_WebSearch(query, system_prompt=system_prompt, model=model,
+                                                        options=style_options(style), cancel=cancel):
+                    yield token
+            return
+
+        # "regenerate" can't take back streamed tokens, so the answer is held until it passes
+        hold = self.verification.action == "regenerate"
+        retries = max(0, self.verification.max_regenerations) if hold else 0
+        directive = ""
+        for attempt in range(retries + 1):
+            answer, held = "", []
+            async with self._generation_slot(priority):
+                async for chunk in self.async_WebSearch(query, system_prompt=system_prompt + directive, model=model,
+                                                        options=style_options(style), cancel=cancel):
+                    if isinstance(chunk, str):
+                        answer += chunk
+                    if hold:
+                        held.append(chunk)
+                    else:
+                        yield chunk
+            # Checked outside the slot, the checker takes its own
+            try:
+                verdict = await self.fact_checker.check(query, answer, chunks, priority=priority)
+            except Exception as e:
+                logger.warning(f"Fact check failed, the answer goes out unchecked: {e}")
+                verdict = unchecked(str(e) or type(e).__name__)
+            if verdict["supported"] is not False or attempt == retries:
+                break
+            directive = regeneration_directive(verdict)
+
+        for chunk in held:
+            yield chunk
+        self.fact_checker.count(verdict, regenerations=attempt)
+        note = confidence_note(verdict)
+        if note:
+            yield note
+        yield {'verification': dict(verdict, regenerations=attempt)}
     
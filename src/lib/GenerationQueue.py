@@ -0,0 +1,144 @@
+"""
+Priority queueing for model generations.
+Ollama can only run a few generations at once; past that, every extra request just slows
+down the ones already running. The queue caps how many generations are in flight and,
+when it's saturated, decides who goes next by weighted fair queueing over three classes:
+logged-in users, guests, and batch jobs (evaluations, summaries, memory extraction).
+Each class gets turns in proportion to its weight, so students asking questions go first
+without background jobs starving forever. Counters are exported on /metrics.
+"""
+import time
+import asyncio
+import threading
+from collections import deque
+from contextlib import asynccontextmanager
+from typing import Deque, Dict, Optional
+
+# Interactive logged-in users, interactive guests, background work
+PRIORITIES = ("user", "guest", "batch")
+DEFAULT_WEIGHTS = {"user": 6, "guest": 3, "batch": 1}
+
+
+class QueueFull(Exception):
+    """Too many requests are already waiting, or this one waited too long for a slot."""
+
+
+class GenerationQueue:
+    """Caps concurrent generations and hands out free slots by weighted priority."""
+
+    def __init__(self, max_concurrent: int = 4, max_waiting: int = 50, wait_timeout: float = 60.0,
+                 weights: Optional[Dict[str, float]] = None):
+        """
+        Args:
+            max_concurrent: Generations allowed at once; 0 means no limit (nothing ever waits)
+            max_waiting: Requests allowed to wait for a slot before new ones are turned away
+            wait_timeout: Seconds a request waits for a slot before giving up
+            weights: Share of the turns each priority class gets while the queue is saturated
+        """
+        self.max_concurrent = max_concurrent
+        self.max_waiting = max_waiting
+        self.wait_timeout = wait_timeout
+        self.weights = {p: max(float((weights or {}).get(p, DEFAULT_WEIGHTS[p])), 0.01) for p in PRIORITIES}
+        self._cond = threading.Condition()
+        self._active = 0
+        self._waiting: Dict[str, Deque[object]] = {p: deque() for p in PRIORITIES}
+        # Stride scheduling: each turn advances a class's pass by 1 / weight; the lowest pass goes next
+        self._pass = {p: 0.0 for p in PRIORITIES}
+        self._clock = 0.0
+        self._served = {p: 0 for p in PRIORITIES}
+        self._rejected = {p: 0 for p in PRIORITIES}
+        self._wait_seconds = {p: 0.0 for p in PRIORITIES}
+
+    def _next_waiter(self) -> Optional[object]:
+        candidates = [p for p in PRIORITIES if self._waiting[p]]
+        if not candidates:
+            return None
+        # Ties go to the higher priority (PRIORITIES is in priority order)
+        best = min(candidates, key=lambda p: (self._pass[p], PRIORITIES.index(p)))
+        return self._waiting[best][0]
+
+    def _take(self, priority: str, started: float) -> float:
+        self._clock = self._pass[priority]
+        self._pass[priority] += 1 / self.weights[priority]
+        self._active += 1
+        self._served[priority] += 1
+        waited = time.monotonic() - started
+        self._wait_seconds[priority] += waited
+        return waited
+
+    def acquire(self, priority: str = "batch") -> float:
+        """
+        Block until a generation slot is free for this priority class.
+
+        Returns:
+            Seconds spent waiting
+
+        Raises:
+            QueueFull: If the waiting line is full or no slot came up within wait_timeout
+        """
+        if priority not in PRIORITIES:
+            priority = "batch"
+        started = time.monotonic()
+        with self._cond:
+            if self.max_concurrent <= 0:
+                self._active += 1
+                self._served[priority] += 1
+                return 0.0
+            waiting = sum(len(q) for q in self._waiting.values())
+            if self._active < self.max_concurrent and not waiting:
+                return self._take(priority, started)
+            if waiting >= self.max_waiting:
+                self._rejected[priority] += 1
+                raise QueueFull("Too many requests are waiting for the model")
+
+            line = self._waiting[priority]
+            if not line:
+                # A class that was idle rejoins at the current time instead of cashing in turns it didn't use
+                self._pass[priority] = max(self._pass[priority], self._clock)
+            me = object()
+            line.append(me)
+            deadline = started + self.wait_timeout
+            while not (self._active < self.max_concurrent and self._next_waiter() is me):
+                remaining = deadline - time.monotonic()
+                if remaining <= 0:
+                    line.remove(me)
+                    self._rejected[priority] += 1
+                    # Whoever was behind us may be next now
+                    self._cond.notify_all()
+                    raise QueueFull(f"No generation slot within {self.wait_timeout:g} seconds")
+                self._cond.wait(remaining)
+            line.popleft()
+            waited = self._take(priority, started)
+            # More than one slot may be free
+            self._cond.notify_all()
+            return waited
+
+    def release(self):
+        """Give a slot back."""
+        with self._cond:
+            self._active = max(0, self._active - 1)
+            self._cond.notify_all()
+
+    @asynccontextmanager
+    async def slot(self, priority: str = "batch"):
+        """
+        Hold a generation slot for the duration of an async block. The wait happens in a
+        worker thread so the event loop (e.g. the Discord bot's) keeps running meanwhile.
+        """
+        await asyncio.to_thread(self.acquire, priority)
+        try:
+            yield
+        finally:
+            self.release()
+
+    def metrics(self) -> Dict:
+        """Current load and lifetime counters per priority class."""
+        with self._cond:
+            return {
+                "active": self._active,
+                "max_concurrent": self.max_concurrent,
+                "waiting": {p: len(self._waiting[p]) for p in PRIORITIES},
+                "served": dict(self._served),
+                "rejected": dict(self._rejected),
+                "wait_seconds": {p: round(self._wait_seconds[p], 3) for p in PRIORITIES},
+            }
@@ -0,0 +1,91 @@
+"""
+Coarse location of questions for ArchieAI analytics.
+For the usage analysis it's more useful to know whether a question came from the dorms
+or from a parent at home in another state than which IP it came from. Each interaction
+is tagged with a country and region from an offline MaxMind database (GeoLite2-City or
+GeoLite2-Country, path in [analytics] geoip_db; nothing is sent anywhere) and with
+whether the IP is in one of the campus networks ([analytics] campus_networks). Both
+work on their own: without the database (or the maxminddb package) only the campus
+flag is set. anonymize_ip() is applied to the IP stored next to them, after the lookup.
+"""
+import logging
+import ipaddress
+from typing import Dict, List, Optional
+
+try:
+    import maxminddb
+except ImportError:  # Optional dependency: pip install maxminddb
+    maxminddb = None
+
+logger = logging.getLogger(__name__)
+
+
+def anonymize_ip(ip_address: Optional[str]) -> Optional[str]:
+    """Zero the host part of an IP: the last octet of IPv4 (/24), all but the first 48 bits of IPv6."""
+    try:
+        ip = ipaddress.ip_address(ip_address)
+    except (TypeError, ValueError):
+        return ip_address
+    prefix = 24 if ip.version == 4 else 48
+    return str(ipaddress.ip_network(f"{ip}/{prefix}", strict=False).network_address)
+
+
+class GeoLocator:
+    """Looks up the coarse location of an IP; one instance is shared by the analytics logger."""
+
+    def __init__(self, db_path: str = "", campus_networks: Optional[List[str]] = None):
+        """
+        Args:
+            db_path: MaxMind database (.mmdb) file, or "" for campus detection only
+            campus_networks: CIDRs of the university's networks, e.g. ["10.0.0.0/8"]
+        """
+        self.campus_networks = []
+        for cidr in campus_networks or []:
+            try:
+                self.campus_networks.append(ipaddress.ip_network(cidr, strict=False))
+            except ValueError:
+                logger.warning(f"Ignoring invalid campus network {cidr!r}")
+        self._reader = None
+        if db_path:
+            if maxminddb is None:
+                logger.warning("analytics.geoip_db is set but the maxminddb package isn't installed")
+            else:
+                try:
+                    self._reader = maxminddb.open_database(db_path)
+                except (OSError, ValueError) as e:
+                    logger.warning(f"Couldn't open GeoIP database {db_path}: {e}")
+
+    @classmethod
+    def from_config(cls, config) -> Optional["GeoLocator"]:
+        """A locator for the [analytics] settings, or None when neither lookup is configured."""
+        analytics = config.analytics
+        if not analytics.geoip_db and not analytics.campus_networks:
+            return None
+        return cls(analytics.geoip_db, analytics.campus_networks)
+
+    def locate(self, ip_address: Optional[str]) -> Optional[Dict]:
+        """
+        Returns:
+            {"country": ISO code, "region": subdivision name, "on_campus": bool}, with None for
+            what isn't known, or None if ip_address isn't an IP
+        """
+        try:
+            ip = ipaddress.ip_address(ip_address)
+        except (TypeError, ValueError):
+            return None
+        location = {
+            "country": None,
+            "region": None,
+            "on_campus": any(ip in network for network in self.campus_networks if network.version == ip.version),
+        }
+        if self._reader is not None and ip.is_global:
+            try:
+                record = self._reader.get(str(ip)) or {}
+            except ValueError:
+                record = {}
+            location["country"] = (record.get("country") or {}).get("iso_code")
+            subdivisions = record.get("subdivisions") or []
+            if subdivisions:
+                names = subdivisions[0].get("names") or {}
+                location["region"] = names.get("en") or subdivisions[0].get("iso_code")
+        return location
@@ -0,0 +1,330 @@
+"""
+GraphQL API for sessions, messages, and (admin-only) analytics.
+Lets the frontend ask for exactly the fields it needs in one round trip instead of
+chaining REST calls. Built on graphql-core; fields are camelCase and resolve to the
+snake_case keys used everywhere else.
+"""
+import re
+from dataclasses import dataclass
+from functools import cached_property
+from typing import Callable, Optional
+from graphql import build_schema, graphql_sync, GraphQLError
+from lib.Errors import AppError, Unauthorized, Forbidden
+
+SCHEMA_SDL = """
+type Query {
+  "The logged-in user, or null for guests"
+  me: User
+  "The logged-in user's sessions"
+  sessions: [Session!]!
+  "A session the caller owns or is currently using"
+  session(id: ID!): Session
+  "Aggregate analytics (admins only)"
+  analytics: Analytics
+}
+
+type User {
+  email: String!
+  createdAt: String
+  isAdmin: Boolean!
+  locale: String
+  sessionCount: Int!
+}
+
+type Session {
+  id: ID!
+  createdAt: String
+  "Generated after the first exchange, or set by the user; null until then"
+  title: String
+  preview: String!
+  messageCount: Int!
+  "Newest page of messages first; pass the oldest id you have as before to page back"
+  messages(before: ID, limit: Int = 10): MessagePage!
+}
+
+type MessagePage {
+  messages: [Message!]!
+  hasMore: Boolean!
+}
+
+type Message {
+  id: ID!
+  role: String!
+  content: String!
+  timestamp: String
+  pinned: Boolean!
+  partial: Boolean!
+}
+
+type Analytics {
+  totalQuestions: Int!
+  avgGenerationTimeSeconds: Float!
+  unansweredQuestions: Int!
+  faqAnswers: Int!
+  fallbackAnswers: Int!
+  interruptedAnswers: Int!
+  categories: [CategoryCount!]!
+  questionsPerDay: [DayCount!]!
+  engagement: Engagement!
+  "Null when sentiment scoring is off"
+  sentiment: Sentiment
+  recentInteractions(limit: Int = 20): [Interaction!]!
+  unanswered(limit: Int = 20): [UnansweredQuestion!]!
+}
+
+type CategoryCount {
+  category: String!
+  count: Int!
+}
+
+type DayCount {
+  day: String!
+  count: Int!
+}
+
+type Engagement {
+  sessions: Int!
+  avgSessionDurationSeconds: Float!
+  medianSessionDurationSeconds: Float!
+  avgTurnsPerSession: Float!
+  avgGapBetweenTurnsSeconds: Float!
+  medianGapBetweenTurnsSeconds: Float!
+  users: Int!
+  returningUsers: Int!
+  returnRate: Float!
+  avgReturnVisitsPerUser: Float!
+}
+
+type Sentiment {
+  "-1 (frustrated) to 1 (happy)"
+  average: Float!
+  positive: Int!
+  neutral: Int!
+  negative: Int!
+  negativeRate: Float!
+  "Sessions with two or more negative questions"
+  frustratedSessions: Int!
+  perDay: [DaySentiment!]!
+}
+
+type DaySentiment {
+  day: String!
+  average: Float!
+}
+
+type Interaction {
+  timestamp: String
+  requestId: String
+  sessionId: String
+  userEmail: String
+  question: String!
+  answer: String!
+  category: String
+  sentiment: Float
+  unanswered: Boolean
+  generationTimeSeconds: Float
+  "model, faq when a curated FAQ entry answered, or fallback while the model was unreachable"
+  answerSource: String
+}
+
+type UnansweredQuestion {
+  question: String!
+  count: Int!
+  category: String
+  lastAsked: String
+}
+"""
+
+SCHEMA = build_schema(SCHEMA_SDL)
+
+MAX_PAGE_SIZE = 100
+
+
+@dataclass
+class GraphQLContext:
+    """Per-request state the resolvers need."""
+    session_manager: object
+    data_collector: object
+    user_email: Optional[str]
+    is_admin: bool
+    # load_session(session_id) -> session dict, raising NotFound/Forbidden like the REST routes
+    load_session: Callable[[str], dict]
+
+
+def _snake_case(name: str) -> str:
+    return re.sub(r"(?<!^)(?=[A-Z])", "_", name).lower()
+
+
+def _resolve_field(source, info, **args):
+    """
+    Default resolver: look up the snake_case version of the field on dicts and objects.
+    Callables are resolved lazily with the field arguments, so work only happens for selected fields.
+    """
+    key = _snake_case(info.field_name)
+    if isinstance(source, dict):
+        value = source.get(key)
+    else:
+        value = getattr(source, key, None)
+    if callable(value):
+        return value(info, **args)
+    return value
+
+
+def _clamp_limit(limit: Optional[int], default: int) -> int:
+    if limit is None:
+        return default
+    return max(1, min(limit, MAX_PAGE_SIZE))
+
+
+def _message(message: dict) -> dict:
+    return {
+        "id": message.get("message_id"),
+        "role": message.get("role"),
+        "content": message.get("content", ""),
+        "timestamp": message.get("timestamp"),
+        "pinned": bool(message.get("pinned")),
+        # Older messages only have interrupted
+        "partial": bool(message.get("partial") or message.get("interrupted")),
+    }
+
+
+def _session(session_data: dict, session_manager) -> dict:
+    session_id = session_data["session_id"]
+    messages = session_data.get("messages", [])
+    preview = next((m.get("content", "")[:100] for m in messages if m.get("role") == "user"), "")
+
+    def resolve_messages(info, before=None, limit=10):
+        page = session_manager.get_messages_page(session_id, before=before, limit=_clamp_limit(limit, 10))
+        return {"messages": [_message(m) for m in page["messages"]], "has_more": page["has_more"]}
+
+    return {
+        "id": session_id,
+        "created_at": session_data.get("created_at"),
+        "title": session_data.get("title"),
+        "preview": preview,
+        "message_count": len(messages),
+        "messages": resolve_messages,
+    }
+
+
+class Analytics:
+    """Analytics root; aggregates are computed once, and only if one of them is selected."""
+
+    def __init__(self, data_collector):
+        self.data_collector = data_collector
+
+    @cached_property
+    def _stats(self) -> dict:
+        return self.data_collector.get_aggregate_stats()
+
+    def total_questions(self, info):
+        return self._stats["total_questions"]
+
+    def avg_generation_time_seconds(self, info):
+        return self._stats["avg_generation_time_seconds"]
+
+    def unanswered_questions(self, info):
+        return self._stats["unanswered_questions"]
+
+    def faq_answers(self, info):
+        return self._stats["faq_answers"]
+
+    def fallback_answers(self, info):
+        return self._stats["fallback_answers"]
+
+    def interrupted_answers(self, info):
+        return self._stats["interrupted_answers"]
+
+    def categories(self, info):
+        return [{"category": name, "count": count} for name, count in self._stats["categories"].items()]
+
+    def questions_per_day(self, info):
+        return [{"day": day, "count": count} for day, count in self._stats["questions_per_day"].items()]
+
+    def engagement(self, info):
+        return self._stats["engagement"]
+
+    def sentiment(self, info):
+        sentiment = self._stats["sentiment"]
+        if sentiment is None:
+            return None
+        return dict(
+            sentiment["labels"],
+            average=sentiment["average"],
+            negative_rate=sentiment["negative_rate"],
+            frustrated_sessions=sentiment["frustrated_sessions"],
+            per_day=[{"day": day, "average": average} for day, average in sentiment["per_day"].items()],
+        )
+
+    def recent_interactions(self, info, limit=20):
+        return self.data_collector.get_recent_interactions(limit=_clamp_limit(limit, 20))
+
+    def unanswered(self, info, limit=20):
+        return self.data_collector.get_unanswered_report(limit=_clamp_limit(limit, 20))
+
+
+class Query:
+    """Root resolvers."""
+
+    def me(self, info):
+        ctx: GraphQLContext = info.context
+        user = ctx.session_manager.get_user(ctx.user_email)
+        if user is None:
+            return None
+        return {
+            "email": ctx.user_email,
+            "created_at": user.get("created_at"),
+            "is_admin": ctx.is_admin,
+            "locale": user.get("preferences", {}).get("locale"),
+            "session_count": len(user.get("sessions", [])),
+        }
+
+    def sessions(self, info):
+        ctx: GraphQLContext = info.context
+        if ctx.session_manager.get_user(ctx.user_email) is None:
+            raise Unauthorized("Not logged in")
+        sessions = []
+        for session_id in ctx.session_manager.get_user_sessions(ctx.user_email):
+            session_data = ctx.session_manager.get_session(session_id)
+            if session_data:
+                sessions.append(_session(session_data, ctx.session_manager))
+        return sessions
+
+    def session(self, info, id):
+        ctx: GraphQLContext = info.context
+        return _session(ctx.load_session(id), ctx.session_manager)
+
+    def analytics(self, info):
+        ctx: GraphQLContext = info.context
+        if not ctx.is_admin:
+            raise Forbidden("Admin access required")
+        return Analytics(ctx.data_collector)
+
+
+def execute(query: str, context: GraphQLContext, variables: Optional[dict] = None,
+            operation_name: Optional[str] = None):
+    """Run a GraphQL query and return the graphql-core ExecutionResult."""
+    return graphql_sync(
+        SCHEMA,
+        query,
+        root_value=Query(),
+        context_value=context,
+        variable_values=variables,
+        operation_name=operation_name,
+        field_resolver=_resolve_field,
+    )
+
+
+def format_error(error: GraphQLError, translate=None) -> dict:
+    """Format an error like the spec wants, adding our error code (and translated message) for AppErrors."""
+    formatted = error.formatted
+    original = error.original_error
+    if isinstance(original, AppError):
+        body = original.to_dict(translate=translate)
+        formatted["message"] = body["error"]
+        formatted["extensions"] = {"code": original.code}
+    elif original is not None:
+        # Don't leak internals from unexpected exceptions
+        formatted["message"] = "Internal server error" if translate is None else translate("Internal server error")
+        formatted["extensions"] = {"code": "internal"}
+    return formatted
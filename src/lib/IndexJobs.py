@@ -0,0 +1,99 @@
+"""
+Background jobs that refresh the knowledge base and rebuild its search index.
+Admins queue them from the dashboard (POST /api/admin/kb/reindex); one worker thread runs
+them in order, so two rebuilds never write data/kb_index.json at the same time.
+Jobs are kept in memory, with the most recent finished ones kept for the status endpoint.
+"""
+import logging
+import uuid
+import threading
+from collections import deque
+from datetime import datetime
+from typing import Callable, Dict, List, Optional
+
+logger = logging.getLogger(__name__)
+
+
+class IndexJobQueue:
+    """Queue of reindex jobs run one at a time by a background thread."""
+
+    def __init__(self, run: Callable[[Dict], Dict], history: int = 20):
+        """
+        Args:
+            run: Does the work for a job dict (full, sources, refresh) and returns a result dict
+            history: Finished jobs to remember
+        """
+        self.run = run
+        self._lock = threading.Lock()
+        self._pending: deque = deque()
+        self._running: Optional[Dict] = None
+        self._finished: deque = deque(maxlen=history)
+        self._worker: Optional[threading.Thread] = None
+
+    def submit(self, full: bool = False, sources: Optional[List[str]] = None, refresh: bool = False,
+               requested_by: Optional[str] = None) -> Dict:
+        """
+        Queue a job. An identical job that hasn't started yet is returned instead of queuing another.
+
+        Args:
+            full: Re-chunk and re-embed every source
+            sources: Re-chunk and re-embed just these sources
+            refresh: Re-scrape (and crawl) the sources first
+
+        Returns:
+            The job
+        """
+        sources = sorted(set(sources)) if sources else None
+        with self._lock:
+            for job in self._pending:
+                if (job["full"], job["sources"], job["refresh"]) == (full, sources, refresh):
+                    return dict(job)
+            job = {
+                "job_id": uuid.uuid4().hex,
+                "status": "pending",
+                "full": full,
+                "sources": sources,
+                "refresh": refresh,
+                "requested_by": requested_by,
+                "created_at": datetime.now().isoformat(),
+                "started_at": None,
+                "finished_at": None,
+                "result": None,
+                "error": None,
+            }
+            self._pending.append(job)
+            if self._worker is None or not self._worker.is_alive():
+                self._worker = threading.Thread(target=self._work, daemon=True)
+                self._worker.start()
+            return dict(job)
+
+    def _work(self):
+        while True:
+            with self._lock:
+                if not self._pending:
+                    # Let the thread end; the next submit starts a new one
+                    self._worker = None
+                    return
+                job = self._pending.popleft()
+                job["status"] = "running"
+                job["started_at"] = datetime.now().isoformat()
+                self._running = job
+            try:
+                result = self.run(dict(job))
+                status, error = "done", None
+            except Exception as e:
+                logger.error(f"Error during knowledge base job {job['job_id']}: {e}")
+                result, status, error = None, "failed", str(e)
+            with self._lock:
+                job.update(status=status, result=result, error=error, finished_at=datetime.now().isoformat())
+                self._running = None
+                self._finished.appendleft(job)
+
+    def list_jobs(self) -> Dict[str, List[Dict]]:
+        """{"pending": [...], "running": [...], "finished": [...]} with the newest finished first."""
+        with self._lock:
+            return {
+                "pending": [dict(job) for job in self._pending],
+                "running": [dict(self._running)] if self._running else [],
+                "finished": [dict(job) for job in self._finished],
+            }
@@ -0,0 +1,152 @@
+"""
+In-flight deduplication of identical questions for ArchieAI.
+Right after an announcement, dozens of students ask "when does registration open?" within
+seconds, and each question used to take its own generation slot for the same answer. A
+question whose answer can't depend on who asked it (no history, pins, or remembered facts,
+and the same model, style, and feature flags) now joins a generation that is already running
+for it, if any. The generation runs in its own thread and its chunks are fanned out to every
+request on it; one that joins late first gets the chunks it missed. Each request still saves
+and logs its own exchange. The generation stops early only once every request on it has gone.
+"""
+import logging
+import asyncio
+import threading
+from typing import AsyncIterator, Callable, Dict, Hashable, Iterator, Tuple
+
+logger = logging.getLogger(__name__)
+
+
+def close_async_generator(loop: asyncio.AbstractEventLoop, agen):
+    """Stop a model stream on its event loop, including the nested streams down to the Ollama client's."""
+    loop.run_until_complete(agen.aclose())
+    # The nested streams are closed by tasks the loop schedules
+    loop.run_until_complete(loop.shutdown_asyncgens())
+    pending = asyncio.all_tasks(loop)
+    if pending:
+        loop.run_until_complete(asyncio.gather(*pending, return_exceptions=True))
+
+
+class SharedGeneration:
+    """One model generation whose chunks go to every request asking the same question."""
+
+    def __init__(self, key: Hashable, factory: Callable[[], AsyncIterator], on_finish: Callable):
+        self.key = key
+        self._factory = factory
+        self._on_finish = on_finish
+        self._cond = threading.Condition()
+        self._chunks = []
+        self._error = None
+        # The request that started it counts as attached from the start
+        self._readers = 1
+        self._closed = False
+        self.done = False
+        self._thread = threading.Thread(target=self._run, daemon=True)
+
+    def start(self):
+        self._thread.start()
+
+    def attach(self) -> bool:
+        """Join as another reader; False if the generation is already finishing."""
+        with self._cond:
+            if self._closed:
+                return False
+            self._readers += 1
+            return True
+
+    def _run(self):
+        loop = asyncio.new_event_loop()
+        agen = None
+        try:
+            agen = self._factory()
+            while True:
+                with self._cond:
+                    if self._readers == 0:
+                        # Everyone waiting on it left
+                        break
+                try:
+                    chunk = loop.run_until_complete(agen.__anext__())
+                except StopAsyncIteration:
+                    break
+                with self._cond:
+                    self._chunks.append(chunk)
+                    self._cond.notify_all()
+        except Exception as e:
+            with self._cond:
+                self._error = e
+        finally:
+            # No one may join once the chunks stop coming
+            with self._cond:
+                self._closed = True
+            self._on_finish(self)
+            try:
+                if agen is not None:
+                    close_async_generator(loop, agen)
+            except Exception as e:
+                logger.warning(f"Error closing a shared model stream: {e}")
+            finally:
+                loop.close()
+                with self._cond:
+                    self.done = True
+                    self._cond.notify_all()
+
+    def read(self) -> Iterator:
+        """
+        Yield every chunk from the first one, waiting for new ones until the generation ends, then
+        raise its error if it failed. Closing the iterator detaches this reader.
+        """
+        position = 0
+        try:
+            while True:
+                with self._cond:
+                    while position >= len(self._chunks) and not self.done:
+                        self._cond.wait(timeout=1.0)
+                    pending = self._chunks[position:]
+                    finished, error = self.done, self._error
+                for chunk in pending:
+                    position += 1
+                    yield chunk
+                if finished and not pending:
+                    if error is not None:
+                        raise error
+                    return
+        finally:
+            with self._cond:
+                self._readers -= 1
+
+
+class InflightGenerations:
+    """The shared generations still running, keyed by everything the answer depends on."""
+
+    def __init__(self):
+        self._lock = threading.Lock()
+        self._active: Dict[Hashable, SharedGeneration] = {}
+        self._started = 0
+        self._coalesced = 0
+
+    def join(self, key: Hashable, factory: Callable[[], AsyncIterator]) -> Tuple[SharedGeneration, bool]:
+        """
+        Join the running generation for key, or start one with factory() (which creates the model's
+        async generator, on the generation's own event loop).
+
+        Returns:
+            (the generation, True if this call started it)
+        """
+        with self._lock:
+            shared = self._active.get(key)
+            if shared is not None and shared.attach():
+                self._coalesced += 1
+                return shared, False
+            shared = SharedGeneration(key, factory, self._finished)
+            self._active[key] = shared
+            self._started += 1
+        shared.start()
+        return shared, True
+
+    def _finished(self, shared: SharedGeneration):
+        with self._lock:
+            if self._active.get(shared.key) is shared:
+                del self._active[shared.key]
+
+    def metrics(self) -> Dict:
+        with self._lock:
+            return {"active": len(self._active), "started": self._started, "coalesced": self._coalesced}
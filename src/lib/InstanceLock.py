@@ -0,0 +1,136 @@
+"""
+One writer per data directory for ArchieAI.
+Everything is stored in JSON files that are read, changed, and written back whole, which is
+only safe within one process: two servers started on the same data directory (a second
+container on the same volume, a forgotten instance on a shared mount) overwrite each other's
+accounts and analytics without any error. The server now takes an exclusive lock on
+archie.lock in the data directory before it serves anything, and a second one refuses to
+start, saying who holds it. The lock is an OS file lock, so it goes away with the process
+even if it crashes; a lock file left behind doesn't block anything.
+
+To scale out, extra instances run with [instance] role = "replica": they don't take the lock
+and refuse every write (lib/SessionManager.py, lib/UserStore.py, lib/DataCollector.py raise
+ReadOnlyStorage), so they can serve dashboards, history, and exports off the same files
+while the one primary handles everything that writes. Several writers would need a storage
+backend that coordinates writes between processes, which the JSON files can't.
+"""
+import os
+import json
+import socket
+from datetime import datetime
+from typing import Dict, Optional
+
+try:
+    import fcntl
+except ImportError:  # Windows
+    fcntl = None
+    import msvcrt
+
+LOCK_FILE = "archie.lock"
+ROLES = ("primary", "replica")
+
+
+class InstanceLockHeld(RuntimeError):
+    """Another process holds the data directory's lock."""
+
+    def __init__(self, path: str, owner: Optional[Dict]):
+        self.path = path
+        self.owner = owner or {}
+        who = f"pid {self.owner.get('pid', '?')} on {self.owner.get('host', '?')}, since {self.owner.get('started_at', '?')}"
+        super().__init__(
+            f"Another ArchieAI server is already using this data directory ({who}; lock file {path}). "
+            "Stop it first, point this one at another data directory, or start it as a read-only "
+            "replica with [instance] role = \"replica\"."
+        )
+
+
+class ReadOnlyStorage(RuntimeError):
+    """A write was attempted on a read-only replica."""
+
+    def __init__(self, what: str = "data"):
+        super().__init__(f"This instance is a read-only replica and can't write {what}")
+
+
+def _lock(f):
+    """Non-blocking exclusive lock on an open file; OSError if someone else has it."""
+    if fcntl is not None:
+        fcntl.flock(f.fileno(), fcntl.LOCK_EX | fcntl.LOCK_NB)
+    else:
+        f.seek(0)
+        msvcrt.locking(f.fileno(), msvcrt.LK_NBLCK, 1)
+
+
+def _unlock(f):
+    try:
+        if fcntl is not None:
+            fcntl.flock(f.fileno(), fcntl.LOCK_UN)
+        else:
+            f.seek(0)
+            msvcrt.locking(f.fileno(), msvcrt.LK_UNLCK, 1)
+    except OSError:
+        pass
+
+
+class InstanceLock:
+    """Exclusive lock on a data directory, held for the life of the process."""
+
+    def __init__(self, data_dir: str):
+        self.path = os.path.join(data_dir, LOCK_FILE)
+        self._file = None
+
+    @property
+    def held(self) -> bool:
+        return self._file is not None
+
+    def owner(self) -> Optional[Dict]:
+        """Who wrote the lock file last: {"pid", "host", "started_at"}, or None."""
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (OSError, ValueError):
+            return None
+
+    def acquire(self):
+        """
+        Take the lock and record this process in the lock file.
+
+        Raises:
+            InstanceLockHeld: Another process has it
+        """
+        if self._file is not None:
+            return
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        f = open(self.path, "a+", encoding="utf-8")
+        try:
+            _lock(f)
+        except OSError:
+            f.close()
+            raise InstanceLockHeld(self.path, self.owner())
+        f.seek(0)
+        f.truncate()
+        json.dump({"pid": os.getpid(), "host": socket.gethostname(), "started_at": datetime.now().isoformat()}, f)
+        f.flush()
+        self._file = f
+
+    def release(self):
+        if self._file is None:
+            return
+        _unlock(self._file)
+        self._file.close()
+        self._file = None
+
+    def is_locked(self) -> bool:
+        """Whether some process (possibly this one) holds the lock right now."""
+        if self._file is not None:
+            return True
+        try:
+            f = open(self.path, "r", encoding="utf-8")
+        except OSError:
+            return False
+        with f:
+            try:
+                _lock(f)
+            except OSError:
+                return True
+            _unlock(f)
+            return False
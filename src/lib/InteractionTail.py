@@ -0,0 +1,93 @@
+"""
+Live feed of logged interactions for admins (GET /ws/admin/tail).
+During peak events like orientation day, operators want to watch what the system is doing
+as it happens, not a dashboard that refreshes every few minutes. DataCollector hands every
+interaction it logs to publish(), which redacts it and fans it out to each connected admin.
+The feed only says how an answer went (category, model, latency, where the answer came
+from, and whether it failed), never the question, the answer, or who asked.
+"""
+import queue
+import threading
+from typing import Dict, List
+
+
+def redact(interaction: Dict) -> Dict:
+    """The fields of an interaction that are safe to show on an operator's screen."""
+    location = interaction.get("location") or {}
+    return {
+        "timestamp": interaction.get("timestamp"),
+        "request_id": interaction.get("request_id"),
+        "category": interaction.get("category"),
+        "model": interaction.get("model"),
+        "answer_source": interaction.get("answer_source"),
+        "generation_time_seconds": interaction.get("generation_time_seconds"),
+        "question_length": interaction.get("question_length"),
+        "answer_length": interaction.get("answer_length"),
+        "sentiment": interaction.get("sentiment"),
+        "guest": interaction.get("user_email") in (None, "guest"),
+        "on_campus": location.get("on_campus"),
+        # Something went wrong
+        "fallback": interaction.get("answer_source") == "fallback",
+        "unanswered": bool(interaction.get("unanswered")),
+        "interrupted": bool(interaction.get("interrupted")),
+        "experiment": interaction.get("experiment"),
+    }
+
+
+def _put_last(q: queue.Queue):
+    # Make room if the feed fell behind, the end of the feed matters more than one interaction
+    try:
+        q.put_nowait(None)
+    except queue.Full:
+        try:
+            q.get_nowait()
+        except queue.Empty:
+            pass
+        q.put_nowait(None)
+
+
+class InteractionTail:
+    """Delivers redacted interactions to every open admin feed."""
+
+    def __init__(self, max_queue_size: int = 500):
+        self.max_queue_size = max_queue_size
+        self._lock = threading.Lock()
+        self._subscribers: List[queue.Queue] = []
+        self._dropped = 0
+
+    def subscribe(self) -> queue.Queue:
+        q = queue.Queue(maxsize=self.max_queue_size)
+        with self._lock:
+            self._subscribers.append(q)
+        return q
+
+    def unsubscribe(self, q: queue.Queue):
+        with self._lock:
+            if q in self._subscribers:
+                self._subscribers.remove(q)
+
+    def publish(self, interaction: Dict):
+        """DataCollector listener: send a logged interaction to every feed."""
+        with self._lock:
+            queues = list(self._subscribers)
+        if not queues:
+            return
+        event = redact(interaction)
+        for q in queues:
+            try:
+                q.put_nowait(event)
+            except queue.Full:
+                # That admin's connection can't keep up; it misses this one
+                with self._lock:
+                    self._dropped += 1
+
+    def close_all(self):
+        """End every open feed (the server is shutting down); each queue gets None as its last item."""
+        with self._lock:
+            queues = list(self._subscribers)
+        for q in queues:
+            _put_last(q)
+
+    def metrics(self) -> Dict:
+        with self._lock:
+            return {"subscribers": len(self._subscribers), "dropped": self._dropped}
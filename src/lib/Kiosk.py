@@ -0,0 +1,21 @@
+"""
+Kiosk mode for ArchieAI (the library lobby touchscreen).
+Anyone can walk up to a kiosk, so nothing one visitor asks should be there for the next.
+GET /kiosk hands out a throwaway session ID: it starts with KIOSK_PREFIX and has no session
+file, so questions asked with it are answered without history and never saved to a session
+(the analytics still count them). Kiosk callers can't sign in or create sessions, and their
+questions go through a stricter quota, counted per device since the sessions don't last.
+"""
+import secrets
+from typing import Optional
+
+KIOSK_PREFIX = "kiosk-"
+
+
+def new_kiosk_session_id() -> str:
+    """A throwaway session ID; within the 64 characters SessionManager accepts."""
+    return KIOSK_PREFIX + secrets.token_urlsafe(24)
+
+
+def is_kiosk_session(session_id: Optional[str]) -> bool:
+    return bool(session_id) and session_id.startswith(KIOSK_PREFIX)
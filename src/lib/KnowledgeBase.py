@@ -0,0 +1,162 @@
+"""
+The scraped university knowledge base (data/scrape_results.json).
+Holds the list of source pages and rebuilds the file from them, so the web app, the
+admin CLI, and the standalone scraper all refresh it the same way.
+Sources can be web pages or PDF/DOCX links, and any supported file dropped into
+data/documents/ (catalogs, policies) is added as a source named after the file.
+With a crawler (lib/Crawler.py), every page it finds is added too, named by its URL.
+Where each source came from and when it was fetched goes to data/scrape_sources.json,
+so retrieved context can say how old it is.
+"""
+import logging
+import os
+import json
+from datetime import datetime
+from typing import Callable, Dict, Optional
+
+from lib.DocumentText import DocumentError, extract_text, normalize_text
+
+logger = logging.getLogger(__name__)
+
+# Source name -> page that gets scraped for it
+KB_SOURCES = {
+    "website": "https://www.arcadia.edu/",
+    "events": "https://www.arcadia.edu/events/?mode=month",
+    "about": "https://www.arcadia.edu/about-arcadia/",
+    "weather": "https://weather.com/weather/today/l/b0f4fc1167769407f55347d55f492a46e194ccaed63281d2fa3db2e515020994",
+    "diningHours": "https://www.arcadia.edu/life-arcadia/living-commuting/dining/",
+    "ITresources": "https://www.arcadia.edu/life-arcadia/campus-life-resources/information-technology/",
+    "Academic Calendar": "https://www.arcadia.edu/academics/resources/academic-calendars/2025-26/",
+}
+
+SCRAPE_RESULTS_FILE = "scrape_results.json"
+# Source name -> {"url", "type", "fetched_at"}
+SOURCES_FILE = "scrape_sources.json"
+# Local files to ingest alongside the scraped pages
+DOCUMENTS_DIR = "documents"
+# Types that are downloaded and parsed rather than scraped as a page
+FILE_TYPES = ("pdf", "docx")
+DOCUMENT_EXTENSIONS = (".pdf", ".docx", ".md", ".markdown", ".txt", ".html", ".htm")
+
+
+def source_type(location: str) -> str:
+    """Kind of document a source is, used to parse it and pick its chunking strategy: "pdf", "docx", "markdown", "text", or "html"."""
+    path = location.lower().split("?", 1)[0].split("#", 1)[0]
+    if path.endswith(".pdf"):
+        return "pdf"
+    if path.endswith(".docx"):
+        return "docx"
+    if path.endswith((".md", ".markdown")):
+        return "markdown"
+    if path.endswith(".txt"):
+        return "text"
+    return "html"
+
+
+def load_scrape_results(data_dir: str) -> Dict[str, str]:
+    """The source name -> text mapping from the last refresh (empty if there isn't one)."""
+    try:
+        with open(os.path.join(data_dir, SCRAPE_RESULTS_FILE), "r", encoding="utf-8") as f:
+            results = json.load(f)
+    except (FileNotFoundError, json.JSONDecodeError):
+        return {}
+    return {name: text for name, text in results.items() if isinstance(text, str)}
+
+
+def load_source_info(data_dir: str) -> Dict[str, Dict]:
+    """Source name -> {"url", "type", "fetched_at"} from the last refresh (empty if there isn't one)."""
+    try:
+        with open(os.path.join(data_dir, SOURCES_FILE), "r", encoding="utf-8") as f:
+            return json.load(f)
+    except (FileNotFoundError, json.JSONDecodeError):
+        return {}
+
+
+def load_local_documents(data_dir: str) -> Dict[str, str]:
+    """
+    Text of every supported file in data/documents/, keyed by file name.
+    Files that can't be read are skipped with a warning.
+    """
+    documents_dir = os.path.join(data_dir, DOCUMENTS_DIR)
+    if not os.path.isdir(documents_dir):
+        return {}
+    documents = {}
+    for name in sorted(os.listdir(documents_dir)):
+        path = os.path.join(documents_dir, name)
+        if not os.path.isfile(path) or not name.lower().endswith(DOCUMENT_EXTENSIONS):
+            continue
+        try:
+            with open(path, "rb") as f:
+                documents[name] = extract_text(f.read(), source_type(name))
+        except (OSError, DocumentError) as e:
+            logger.warning(f"Skipping {path}: {e}")
+    return documents
+
+
+def refresh_knowledge_base(data_dir: str, scrape: Optional[Callable[[str], str]] = None,
+                           sources: Optional[Dict[str, str]] = None,
+                           fetch: Optional[Callable[[str], bytes]] = None, crawler=None,
+                           full: bool = False) -> Dict[str, str]:
+    """
+    Scrape every source, add the files in data/documents/, and rewrite scrape_results.json.
+
+    Args:
+        data_dir: Data directory to write into
+        scrape: Function that takes a URL and returns the page text (defaults to helpers.scraper,
+                keeping headings as "#" lines for heading-aware chunking)
+        sources: Sources to scrape instead of KB_SOURCES
+        fetch: Function that takes a URL and returns the file's bytes, for PDF/DOCX sources
+               (defaults to helpers.scraper.fetch_document)
+        crawler: Optional lib.Crawler.Crawler whose pages are added to the configured sources
+        full: Have the crawler fetch and extract every page, not just the ones that changed
+
+    Returns:
+        The source name -> text mapping that was written (scrape_sources.json is written alongside)
+    """
+    if scrape is None:
+        from helpers.scraper import scrape_website
+
+        def scrape(url):
+            return scrape_website(url, keep_headings=True)
+    if fetch is None:
+        from helpers.scraper import fetch_document
+        fetch = fetch_document
+
+    results, info = {}, {}
+    for name, url in (sources or KB_SOURCES).items():
+        document_type = source_type(url)
+        if document_type in FILE_TYPES:
+            try:
+                results[name] = extract_text(fetch(url), document_type)
+            except Exception as e:
+                # One broken link shouldn't throw away the rest of the refresh
+                logger.warning(f"Could not ingest {name} ({url}): {e}")
+                continue
+        else:
+            # Collapse runs of whitespace; only heading lines keep their own line
+            results[name] = normalize_text(scrape(url))
+        info[name] = {"url": url, "type": document_type, "fetched_at": datetime.now().isoformat(timespec="seconds")}
+    if crawler is not None:
+        configured = set((sources or KB_SOURCES).values())
+        for url, text in crawler.crawl(previous=load_scrape_results(data_dir), full=full).items():
+            # A configured source keeps its own name rather than appearing twice
+            if url not in configured:
+                results[url] = text
+                page = crawler.pages.get(url, {})
+                # An unchanged page was still confirmed current when it was checked
+                info[url] = {"url": url, "type": page.get("type") or source_type(url),
+                             "fetched_at": page.get("checked_at") or datetime.now().isoformat(timespec="seconds")}
+    for name, text in load_local_documents(data_dir).items():
+        results[name] = text
+        modified = os.path.getmtime(os.path.join(data_dir, DOCUMENTS_DIR, name))
+        info[name] = {"url": None, "type": source_type(name),
+                      "fetched_at": datetime.fromtimestamp(modified).isoformat(timespec="seconds")}
+
+    os.makedirs(data_dir, exist_ok=True)
+    for filename, data, indent in ((SCRAPE_RESULTS_FILE, results, 4), (SOURCES_FILE, info, 2)):
+        path = os.path.join(data_dir, filename)
+        tmp_path = path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(data, f, ensure_ascii=False, indent=indent)
+        os.replace(tmp_path, path)
+    return results
@@ -0,0 +1,79 @@
+"""
+Localization for server-generated messages.
+Messages are written in English in the code and used as the lookup key into
+per-language catalogs in src/locales/<lang>.json. Anything missing from a catalog
+falls back to the English text, so adding a new message never breaks a locale.
+"""
+import logging
+import os
+import json
+from typing import Dict, Optional
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_LOCALE = "en"
+LOCALES_DIR = os.path.join(os.path.dirname(os.path.dirname(os.path.abspath(__file__))), "locales")
+
+
+def _load_catalogs() -> Dict[str, Dict[str, str]]:
+    """Load every <lang>.json catalog from the locales directory."""
+    catalogs = {DEFAULT_LOCALE: {}}
+    if not os.path.isdir(LOCALES_DIR):
+        return catalogs
+    for filename in sorted(os.listdir(LOCALES_DIR)):
+        if not filename.endswith(".json"):
+            continue
+        lang = filename[:-len(".json")]
+        try:
+            with open(os.path.join(LOCALES_DIR, filename), "r", encoding="utf-8") as f:
+                catalogs[lang] = json.load(f)
+        except (OSError, json.JSONDecodeError) as e:
+            logger.warning(f"Could not load locale catalog {filename}: {e}")
+    return catalogs
+
+
+CATALOGS = _load_catalogs()
+SUPPORTED_LOCALES = sorted(CATALOGS.keys())
+
+
+def translate(message: str, locale: Optional[str] = None, **params) -> str:
+    """
+    Translate an English message into the given locale and fill in {placeholders}.
+
+    Args:
+        message: The English message (also the catalog key)
+        locale: Target language code, e.g. "es". None or unknown means English.
+        **params: Values for any {placeholders} in the message
+    """
+    catalog = CATALOGS.get(locale or DEFAULT_LOCALE, {})
+    text = catalog.get(message, message)
+    if params:
+        try:
+            return text.format(**params)
+        except (KeyError, IndexError, ValueError):
+            # A bad translation shouldn't take the response down with it
+            return message.format(**params)
+    return text
+
+
+def negotiate_locale(accept_languages, preferred: Optional[str] = None) -> str:
+    """
+    Pick the locale for a request: the user's saved preference first, then the
+    browser's Accept-Language header, then English.
+
+    Args:
+        accept_languages: werkzeug LanguageAccept from request.accept_languages
+        preferred: The user's saved locale preference, if any
+    """
+    if preferred in CATALOGS:
+        return preferred
+    if accept_languages is not None:
+        match = accept_languages.best_match(SUPPORTED_LOCALES)
+        if match:
+            return match
+        # "es-MX" should still get Spanish
+        for value, _quality in accept_languages:
+            base = value.split("-")[0].lower()
+            if base in CATALOGS:
+                return base
+    return DEFAULT_LOCALE
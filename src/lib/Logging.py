@@ -0,0 +1,113 @@
+"""
+Structured logging for ArchieAI.
+Logs used to be print() calls, each formatting its own "[request id] ..." prefix, which made
+them hard to filter and impossible to feed into a log pipeline. Everything now goes through
+the logging module:
+
+- Every request binds its request_id, method, and path (and session_id once it's known), and
+  each log line written while handling it carries them, including lines from answers that
+  are generated in a background thread (lib/StreamBuffer.py copies the context along)
+- Lines can carry structured fields (generation time, token counts, ...) with extra=fields(...)
+- logging.format picks the output: "pretty" for a console, "json" for one object per line
+"""
+import contextvars
+import json
+import logging
+import re
+import sys
+from datetime import datetime
+from typing import Dict, Optional
+
+FORMATS = ("pretty", "json")
+
+# Werkzeug colors its request lines for terminals
+ANSI_ESCAPE = re.compile(r"\x1b\[[0-9;]*m")
+
+# The request being handled in this context: request_id, method, path, session_id
+_request_fields: contextvars.ContextVar = contextvars.ContextVar("archie_request_fields", default={})
+
+
+def bind_request(**values):
+    """Start the log context of a new request (None values are left out)."""
+    _request_fields.set({key: value for key, value in values.items() if value is not None})
+
+
+def update_request(**values):
+    """Add to the current request's log context, e.g. the session_id once it's been looked up."""
+    current = dict(_request_fields.get())
+    current.update({key: value for key, value in values.items() if value is not None})
+    _request_fields.set(current)
+
+
+def request_fields() -> Dict:
+    return dict(_request_fields.get())
+
+
+def fields(**values) -> Dict:
+    """Structured fields for a log line: logger.info("Answered", extra=fields(generation_seconds=1.2))."""
+    return {"fields": values}
+
+
+class RequestContextFilter(logging.Filter):
+    """Attach the current request's fields to every record."""
+
+    def filter(self, record: logging.LogRecord) -> bool:
+        record.request = request_fields()
+        return True
+
+
+class JsonFormatter(logging.Formatter):
+    """One JSON object per line: timestamp, level, logger, message, the request's fields, then the line's own."""
+
+    def format(self, record: logging.LogRecord) -> str:
+        entry = {
+            "timestamp": datetime.fromtimestamp(record.created).isoformat(),
+            "level": record.levelname.lower(),
+            "logger": record.name,
+            "message": ANSI_ESCAPE.sub("", record.getMessage()),
+            **getattr(record, "request", {}),
+            **getattr(record, "fields", {}),
+        }
+        if record.exc_info:
+            entry["exception"] = self.formatException(record.exc_info)
+        return json.dumps(entry, default=str)
+
+
+class ConsoleFormatter(logging.Formatter):
+    """Readable lines: time, level, [request id], message, then key=value fields."""
+
+    def format(self, record: logging.LogRecord) -> str:
+        request = getattr(record, "request", {})
+        line = datetime.fromtimestamp(record.created).strftime("%H:%M:%S")
+        line += f" {record.levelname:<7} "
+        if request.get("request_id"):
+            line += f"[{request['request_id']}] "
+        line += record.getMessage()
+        extra = getattr(record, "fields", {})
+        if extra:
+            line += " " + " ".join(f"{key}={value}" for key, value in extra.items())
+        if record.exc_info:
+            line += "\n" + self.formatException(record.exc_info)
+        return line
+
+
+def setup_logging(fmt: str = "pretty", level: str = "INFO", stream=None):
+    """
+    Send every logger's output (Werkzeug's request lines included) to stream, stderr by default,
+    in the given format. Safe to call again, e.g. when the config is reloaded.
+    """
+    handler = logging.StreamHandler(stream or sys.stderr)
+    handler.addFilter(RequestContextFilter())
+    handler.setFormatter(JsonFormatter() if fmt == "json" else ConsoleFormatter())
+    root = logging.getLogger()
+    for existing in list(root.handlers):
+        if getattr(existing, "_archie", False):
+            root.removeHandler(existing)
+    handler._archie = True
+    root.addHandler(handler)
+    resolved: Optional[int] = logging.getLevelName(str(level).upper())
+    root.setLevel(resolved if isinstance(resolved, int) else logging.INFO)
+    if fmt not in FORMATS:
+        logging.getLogger(__name__).warning(f"Unknown logging.format {fmt!r}, using pretty")
+    if not isinstance(resolved, int):
+        logging.getLogger(__name__).warning(f"Unknown logging.level {level!r}, using INFO")
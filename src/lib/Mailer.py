@@ -0,0 +1,82 @@
+"""
+Outgoing mail for ArchieAI.
+A thin smtplib wrapper configured by the [mail] section, used for admin digests and
+anything else that needs to email someone. Nothing is sent when no SMTP host is set.
+"""
+import smtplib
+import ssl
+from email.message import EmailMessage
+from typing import List, Optional
+
+
+class MailError(Exception):
+    """Mail isn't configured or the SMTP server refused the message."""
+
+
+class Mailer:
+    """Sends plain-text (optionally HTML) mail through the configured SMTP server."""
+
+    def __init__(self, host: str, port: int = 587, username: str = "", password: str = "",
+                 from_address: str = "archie@arcadia.edu", starttls: bool = True, use_ssl: bool = False,
+                 timeout: int = 30):
+        self.host = host
+        self.port = port
+        self.username = username
+        self.password = password
+        self.from_address = from_address
+        self.starttls = starttls
+        self.use_ssl = use_ssl
+        self.timeout = timeout
+
+    @classmethod
+    def from_config(cls, config) -> Optional["Mailer"]:
+        """The configured mailer, or None if no SMTP host is set."""
+        mail = config.mail
+        if not mail.host:
+            return None
+        return cls(mail.host, port=mail.port, username=mail.username, password=mail.password,
+                   from_address=mail.from_address, starttls=mail.starttls, use_ssl=mail.ssl,
+                   timeout=mail.timeout_seconds)
+
+    def send(self, to: List[str], subject: str, body: str, html: Optional[str] = None,
+             attachments: Optional[List[tuple]] = None):
+        """
+        Send a message.
+
+        Args:
+            to: Recipient addresses
+            subject: Subject line
+            body: Plain-text body
+            html: Optional HTML alternative
+            attachments: Optional (filename, bytes, mimetype) tuples
+
+        Raises:
+            MailError: If there are no recipients or the SMTP conversation fails
+        """
+        if not to:
+            raise MailError("No recipients")
+        message = EmailMessage()
+        message["From"] = self.from_address
+        message["To"] = ", ".join(to)
+        message["Subject"] = subject
+        message.set_content(body)
+        if html:
+            message.add_alternative(html, subtype="html")
+        for filename, data, mimetype in attachments or []:
+            maintype, _, subtype = mimetype.partition("/")
+            message.add_attachment(data, maintype=maintype, subtype=subtype or "octet-stream", filename=filename)
+
+        try:
+            if self.use_ssl:
+                server = smtplib.SMTP_SSL(self.host, self.port, timeout=self.timeout,
+                                          context=ssl.create_default_context())
+            else:
+                server = smtplib.SMTP(self.host, self.port, timeout=self.timeout)
+            with server:
+                if self.starttls and not self.use_ssl:
+                    server.starttls(context=ssl.create_default_context())
+                if self.username:
+                    server.login(self.username, self.password)
+                server.send_message(message)
+        except (smtplib.SMTPException, OSError) as e:
+            raise MailError(f"Could not send mail through {self.host}:{self.port}: {e}")
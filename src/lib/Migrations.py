@@ -0,0 +1,95 @@
+"""
+Data migrations for ArchieAI.
+Each migration upgrades files in the data directory in place and is safe to run
+more than once. Run them with `python src/app.py migrate`.
+"""
+import os
+import json
+from typing import Callable, List, Tuple
+from lib.QuestionCategorizer import categorize_question
+from lib.DataCollector import is_unanswered
+from lib.UserStore import UserStore, USERS_DIR
+from lib.AnalyticsStore import SqliteAnalyticsStore
+
+
+def ensure_data_layout(config) -> str:
+    """Create the data directory structure and empty data files."""
+    data_dir = config.data_dir
+    os.makedirs(os.path.join(data_dir, "sessions"), exist_ok=True)
+    os.makedirs(os.path.join(data_dir, USERS_DIR), exist_ok=True)
+
+    created = []
+    for name, empty in (("qna.json", {}), ("analytics.json", []), ("faq_entries.json", [])):
+        if name == "analytics.json" and config.analytics.backend == "sqlite":
+            continue
+        path = os.path.join(data_dir, name)
+        if not os.path.exists(path):
+            with open(path, "w", encoding="utf-8") as f:
+                json.dump(empty, f)
+            created.append(name)
+
+    return f"created {', '.join(created)}" if created else "already up to date"
+
+
+def backfill_analytics_fields(config) -> str:
+    """Add category and unanswered flags to interactions logged before they existed."""
+    path = os.path.join(config.data_dir, "analytics.json")
+    try:
+        with open(path, "r", encoding="utf-8") as f:
+            interactions = json.load(f)
+    except (FileNotFoundError, json.JSONDecodeError):
+        return "no analytics to migrate"
+
+    updated = 0
+    for interaction in interactions:
+        changed = False
+        if "category" not in interaction:
+            interaction["category"] = categorize_question(interaction.get("question", ""))
+            changed = True
+        if "unanswered" not in interaction:
+            interaction["unanswered"] = is_unanswered(interaction.get("answer", ""))
+            changed = True
+        if changed:
+            updated += 1
+
+    if updated:
+        with open(path, "w", encoding="utf-8") as f:
+            json.dump(interactions, f, ensure_ascii=False, indent=2)
+
+    return f"updated {updated} interactions"
+
+
+def shard_users_file(config) -> str:
+    """Move accounts from the old single users.json into one file per user."""
+    moved = UserStore(config.data_dir, migrate=False).migrate_legacy_file()
+    return f"moved {moved} accounts" if moved else "already up to date"
+
+
+def import_analytics_to_sqlite(config) -> str:
+    """With [analytics] backend = "sqlite", move the interactions in analytics.json into analytics.db."""
+    if config.analytics.backend != "sqlite":
+        return "skipped (analytics.backend is json)"
+    imported = SqliteAnalyticsStore(config.data_dir, import_legacy=False).import_legacy_json()
+    return f"imported {imported} interactions" if imported else "already up to date"
+
+
+# Migrations run in order; add new ones to the end
+MIGRATIONS: List[Tuple[str, Callable]] = [
+    ("ensure_data_layout", ensure_data_layout),
+    ("backfill_analytics_fields", backfill_analytics_fields),
+    ("shard_users_file", shard_users_file),
+    ("import_analytics_to_sqlite", import_analytics_to_sqlite),
+]
+
+
+def run_migrations(config) -> bool:
+    """Run every migration in order, printing the result of each. Returns False if any failed."""
+    ok = True
+    for name, migration in MIGRATIONS:
+        try:
+            result = migration(config)
+            print(f"[migrate] {name}: {result}")
+        except Exception as e:
+            print(f"[migrate] {name}: FAILED ({e})")
+            ok = False
+    return ok
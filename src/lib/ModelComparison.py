@@ -0,0 +1,85 @@
+"""
+Side-by-side model comparison for ArchieAI.
+Picking the chat model used to be done by asking a few questions in the chat and going
+with a gut feeling. POST /api/admin/compare runs one question through two or more of the
+configured models at the same time, through the same answer pipeline the chat uses
+(knowledge base, campus tools, system prompt, answer style), and returns each answer with
+how long it took, how long until the first token, and the tokens Ollama reported. The
+models share the generation queue with everyone else, so on a busy server they may not
+all start at once; time_to_first_token_seconds includes that wait.
+"""
+import time
+import asyncio
+from typing import Dict, List, Optional
+
+
+async def _run_model(ai, question: str, model: str, style: Optional[str]) -> Dict:
+    """One model's answer and stats; an error is reported in the result, not raised."""
+    start = time.monotonic()
+    first_token = None
+    answer = ""
+    chunks = 0
+    tools = []
+    usage = {}
+    result = {"model": model}
+    try:
+        async for chunk in ai.Archie_streaming(question, model=model, priority="user", style=style):
+            if isinstance(chunk, str):
+                if first_token is None and chunk:
+                    first_token = time.monotonic() - start
+                answer += chunk
+                chunks += 1
+            elif isinstance(chunk, dict) and chunk.get("tool_name"):
+                tools.append(chunk["tool_name"])
+            elif isinstance(chunk, dict) and chunk.get("final"):
+                usage = chunk.get("usage") or {}
+            elif isinstance(chunk, dict) and chunk.get("verification"):
+                result["verification"] = chunk["verification"]
+    except Exception as e:
+        result["error"] = str(e) or type(e).__name__
+
+    completion_tokens = usage.get("completion_tokens") or None
+    generation_seconds = usage.get("generation_seconds") or None
+    result.update({
+        "answer": answer,
+        "seconds": round(time.monotonic() - start, 3),
+        "time_to_first_token_seconds": round(first_token, 3) if first_token is not None else None,
+        "chunks": chunks,
+        "characters": len(answer),
+        "words": len(answer.split()),
+        "prompt_tokens": usage.get("prompt_tokens") or None,
+        "completion_tokens": completion_tokens,
+        "tokens_per_second": round(completion_tokens / generation_seconds, 1)
+        if completion_tokens and generation_seconds else None,
+        "tools": tools,
+    })
+    return result
+
+
+async def _run_all(ai, question: str, models: List[str], style: Optional[str]) -> List[Dict]:
+    return list(await asyncio.gather(*(_run_model(ai, question, model, style) for model in models)))
+
+
+def compare_models(ai, question: str, models: List[str], style: Optional[str] = None) -> Dict:
+    """
+    Answer question with each model concurrently.
+
+    Args:
+        ai: The AiInterface the chat uses
+        question: The question, already screened
+        models: The models to compare, in the order to report them
+        style: Answer style for every model, or None
+
+    Returns:
+        {"question", "style", "results": [one per model], "fastest", "seconds"}
+    """
+    start = time.monotonic()
+    results = asyncio.run(_run_all(ai, question, models, style))
+    answered = [r for r in results if "error" not in r and r["answer"]]
+    return {
+        "question": question,
+        "style": style,
+        "results": results,
+        "fastest": min(answered, key=lambda r: r["seconds"])["model"] if answered else None,
+        "seconds": round(time.monotonic() - start, 3),
+    }
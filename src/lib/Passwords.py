@@ -0,0 +1,86 @@
+"""
+Password hashing for ArchieAI accounts.
+New passwords are hashed with argon2id (argon2-cffi, its default cost parameters), which is
+built to be slow and memory-hard on the GPUs used to crack leaked hashes. Hashes from before
+keep working: Werkzeug's salted scrypt/pbkdf2 ones, and bare unsalted SHA-256 hex digests
+from accounts imported from older tools. verify_password() says when a stored hash is one of
+those, and the login rehashes the password with argon2id while it has it in hand, so
+accounts move over as people sign in and nobody has to reset a password. Without
+argon2-cffi installed, new hashes fall back to Werkzeug's scrypt (with a warning) and are
+upgraded on the first login after it's installed.
+"""
+import logging
+import re
+import hmac
+import hashlib
+from typing import Optional, Tuple
+
+from werkzeug.security import generate_password_hash, check_password_hash
+
+try:
+    from argon2 import PasswordHasher
+    from argon2.exceptions import InvalidHashError, VerificationError
+    # argon2id with the library's recommended time/memory cost
+    _argon2 = PasswordHasher()
+except ImportError:
+    _argon2 = None
+
+logger = logging.getLogger(__name__)
+
+_SHA256_HEX = re.compile(r"^[0-9a-f]{64}$")
+
+
+def argon2_available() -> bool:
+    return _argon2 is not None
+
+
+def hash_scheme(password_hash: Optional[str]) -> str:
+    """"argon2", "werkzeug" (scrypt or pbkdf2), "sha256" (unsalted hex digest), or "unknown"."""
+    if not isinstance(password_hash, str):
+        return "unknown"
+    if password_hash.startswith("$argon2"):
+        return "argon2"
+    if password_hash.startswith(("scrypt:", "pbkdf2:")):
+        return "werkzeug"
+    if _SHA256_HEX.match(password_hash.lower()):
+        return "sha256"
+    return "unknown"
+
+
+def hash_password(password: str) -> str:
+    if _argon2 is None:
+        return generate_password_hash(password)
+    return _argon2.hash(password)
+
+
+def verify_password(password_hash: Optional[str], password: str) -> Tuple[bool, bool]:
+    """
+    Check a password against a stored hash.
+
+    Returns:
+        (whether it matches, whether the hash should be replaced with hash_password(password),
+         i.e. it's an older scheme or argon2 parameters that have since been raised)
+    """
+    scheme = hash_scheme(password_hash)
+    if scheme == "argon2":
+        if _argon2 is None:
+            # Can't check it without the library; refusing is the only safe answer
+            logger.warning("An argon2 password hash can't be checked, argon2-cffi isn't installed")
+            return False, False
+        try:
+            _argon2.verify(password_hash, password)
+        except (VerificationError, InvalidHashError):
+            return False, False
+        return True, _argon2.check_needs_rehash(password_hash)
+    if scheme == "werkzeug":
+        try:
+            matches = check_password_hash(password_hash, password)
+        except ValueError:
+            return False, False
+        # Without argon2, scrypt is already the best there is
+        return matches, matches and _argon2 is not None
+    if scheme == "sha256":
+        digest = hashlib.sha256(password.encode("utf-8")).hexdigest()
+        matches = hmac.compare_digest(digest, password_hash.lower())
+        return matches, matches
+    return False, False
@@ -0,0 +1,116 @@
+"""
+Profanity and harassment filter for questions to ArchieAI.
+Runs before the model sees a question: words and phrases from the wordlist are either
+masked with asterisks (the question is still answered) or the question is rejected.
+Matching is on normalized words, so "Sh1t", "$hiiit", "s.h.i.t", and "s h i t" all count,
+but only whole words (plus a few suffixes) match, so "class" or "Scunthorpe" don't.
+app.py logs each hit as a "profanity" abuse incident, which counts toward auto-suspension
+like the other heuristics in lib/AbuseDetector.py.
+"""
+import logging
+import re
+import unicodedata
+from typing import Dict, List, Optional
+
+logger = logging.getLogger(__name__)
+
+# Kept short on purpose; deployments extend it with [profanity] words or wordlist_file
+DEFAULT_WORDS = [
+    "fuck", "motherfucker", "shit", "bullshit", "bitch", "bastard", "asshole", "dickhead",
+    "cunt", "piss", "pissed off", "slut", "whore", "wanker", "twat", "prick", "douchebag",
+    "kill yourself", "kys", "go die", "die in a fire", "you are worthless", "shut up idiot",
+]
+
+# Characters commonly swapped in for letters to get past filters
+LEET = str.maketrans({
+    "0": "o", "1": "i", "3": "e", "4": "a", "5": "s", "7": "t", "8": "b", "9": "g",
+    "@": "a", "$": "s", "!": "i", "|": "i", "+": "t", "€": "e",
+})
+
+# Endings that still count as the word ("fucking", "bitches")
+SUFFIXES = ("", "s", "es", "ed", "er", "ers", "ing", "in", "y")
+
+TOKEN = re.compile(r"[^\s]+")
+# Punctuation around a word, which would otherwise be read as leetspeak ("bullshit!!!")
+EDGE_PUNCTUATION = ".,;:?!'\"()[]{}<>"
+
+
+def normalize_word(word: str) -> str:
+    """Lowercase, strip accents, undo leetspeak, drop everything but letters, squeeze repeats ("Sh1iiit" -> "shit")."""
+    text = unicodedata.normalize("NFKD", word.lower().strip(EDGE_PUNCTUATION))
+    text = "".join(ch for ch in text if not unicodedata.combining(ch)).translate(LEET)
+    text = re.sub(r"[^a-z]", "", text)
+    return re.sub(r"(.)\1+", r"\1", text)
+
+
+class ProfanityFilter:
+    """Finds wordlist entries in text and masks them."""
+
+    def __init__(self, words: Optional[List[str]] = None, use_defaults: bool = True):
+        """
+        Args:
+            words: Extra words or phrases to filter
+            use_defaults: Include DEFAULT_WORDS
+        """
+        # Entry -> one set of accepted spellings per word, matched against normalized words
+        self.phrases = {}
+        for entry in (DEFAULT_WORDS if use_defaults else []) + list(words or []):
+            entry = " ".join(entry.lower().split())
+            words_in_entry = [w for w in entry.split() if normalize_word(w)]
+            if words_in_entry:
+                self.phrases[entry] = [frozenset(normalize_word(w + suffix) for suffix in SUFFIXES) for w in words_in_entry]
+
+    @classmethod
+    def from_config(cls, config) -> "ProfanityFilter":
+        words = list(config.profanity.words)
+        if config.profanity.wordlist_file:
+            try:
+                with open(config.resolve_path(config.profanity.wordlist_file), "r", encoding="utf-8") as f:
+                    words += [line.strip() for line in f if line.strip() and not line.startswith("#")]
+            except OSError as e:
+                logger.warning(f"Couldn't read profanity wordlist {config.profanity.wordlist_file}: {e}")
+        return cls(words, use_defaults=config.profanity.default_words)
+
+    def _tokens(self, text: str) -> List[tuple]:
+        """(start, end, normalized) per word; runs of single characters ("f u c k") are joined into one."""
+        tokens = [(m.start(), m.end(), normalize_word(m.group())) for m in TOKEN.finditer(text)]
+        tokens = [t for t in tokens if t[2]]
+        joined = []
+        i = 0
+        while i < len(tokens):
+            j = i
+            while j < len(tokens) and len(tokens[j][2]) == 1:
+                j += 1
+            if j - i >= 3:
+                joined.append((tokens[i][0], tokens[j - 1][1], normalize_word("".join(t[2] for t in tokens[i:j]))))
+                i = j
+            else:
+                joined.append(tokens[i])
+                i += 1
+        return joined
+
+    def check(self, text: str) -> Dict:
+        """
+        Returns:
+            {"matches": the wordlist entries found, "masked": text with them replaced by asterisks}
+        """
+        tokens = self._tokens(text)
+        spans, matches = [], []
+        i = 0
+        while i < len(tokens):
+            found, length = None, 0
+            for entry, phrase in self.phrases.items():
+                n = len(phrase)
+                if n > length and i + n <= len(tokens) and all(tokens[i + k][2] in phrase[k] for k in range(n)):
+                    found, length = entry, n
+            if found is None:
+                i += 1
+                continue
+            spans.append((tokens[i][0], tokens[i + length - 1][1]))
+            matches.append(found)
+            i += length
+
+        masked = text
+        for start, end in reversed(spans):
+            masked = masked[:start] + re.sub(r"\S", "*", masked[start:end]) + masked[end:]
+        return {"matches": matches, "masked": masked}
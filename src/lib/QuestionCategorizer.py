@@ -0,0 +1,85 @@
+"""
+Question categorization for ArchieAI analytics.
+Tags each question with a coarse category using simple keyword rules so the
+aggregate stats can show what students are actually asking about.
+"""
+import re
+from typing import Dict, List
+
+# Keyword rules per category. Order matters: the first category with the most
+# keyword hits wins, so put the more specific categories first.
+CATEGORY_KEYWORDS: Dict[str, List[str]] = {
+    "admissions": [
+        "admission", "admissions", "apply", "application", "applying", "accepted",
+        "acceptance", "transfer", "sat", "act", "deadline to apply", "visit campus", "tour",
+    ],
+    "financial-aid": [
+        "financial aid", "fafsa", "scholarship", "scholarships", "tuition", "loan", "loans",
+        "grant", "grants", "bill", "billing", "payment", "cost", "fees", "work study", "work-study",
+    ],
+    "dining": [
+        "dining", "dinner", "lunch", "breakfast", "food", "meal", "meal plan", "cafe",
+        "cafeteria", "chartwells", "eat", "menu",
+    ],
+    "it": [
+        "wifi", "wi-fi", "password", "email", "canvas", "self-service", "login", "log in",
+        "printer", "printing", "laptop", "computer", "it help", "help desk", "helpdesk", "vpn", "duo",
+    ],
+    "academics": [
+        "class", "classes", "course", "courses", "major", "minor", "professor", "registration",
+        "register", "drop", "add/drop", "grade", "grades", "gpa", "syllabus", "credit", "credits",
+        "semester", "finals", "exam", "advisor",
+    ],
+    "calendar": [
+        "fall break", "spring break", "break", "holiday", "calendar", "when does", "when is",
+        "first day", "last day", "commencement", "graduation",
+    ],
+    "housing": [
+        "housing", "dorm", "residence", "roommate", "knight hall", "heinz", "castle", "move in",
+        "move-in", "res life",
+    ],
+    "events": [
+        "event", "events", "club", "clubs", "concert", "game", "athletics", "activities",
+    ],
+    "campus": [
+        "parking", "shuttle", "library", "map", "building", "directions", "weather", "gym",
+        "health center", "counseling", "public safety",
+    ],
+}
+
+DEFAULT_CATEGORY = "general"
+
+
+def _normalize(text: str) -> str:
+    """Lowercase and collapse whitespace so keyword matching is predictable."""
+    return " ".join(text.lower().split())
+
+
+def categorize_question(question: str) -> str:
+    """
+    Assign a single category to a question based on keyword hits.
+
+    Args:
+        question: The raw user question
+
+    Returns:
+        The best matching category name, or "general" if nothing matches.
+    """
+    if not question:
+        return DEFAULT_CATEGORY
+
+    text = _normalize(question)
+    best_category = DEFAULT_CATEGORY
+    best_hits = 0
+
+    for category, keywords in CATEGORY_KEYWORDS.items():
+        hits = 0
+        for keyword in keywords:
+            # Word boundaries so "act" doesn't match "contact"
+            if re.search(r"\b" + re.escape(keyword) + r"\b", text):
+                hits += 1
+        if hits > best_hits:
+            best_category = category
+            best_hits = hits
+
+    return best_category
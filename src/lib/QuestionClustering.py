@@ -0,0 +1,157 @@
+"""
+Duplicate question clustering for ArchieAI analytics.
+Groups similar logged questions using embeddings so we get a data-driven FAQ list
+and can see which questions are worth caching or adding to the knowledge base.
+"""
+import logging
+import os
+import json
+import math
+import asyncio
+from datetime import datetime
+from typing import Dict, List, Optional
+from lib.DataCollector import normalize_question
+
+logger = logging.getLogger(__name__)
+
+# Questions per embedding request, so a large log isn't sent to the model in one call
+EMBED_BATCH_SIZE = 64
+
+
+def cosine_similarity(a: List[float], b: List[float]) -> float:
+    """Cosine similarity between two vectors (0 if either is all zeros)."""
+    dot = sum(x * y for x, y in zip(a, b))
+    norm_a = math.sqrt(sum(x * x for x in a))
+    norm_b = math.sqrt(sum(y * y for y in b))
+    if norm_a == 0 or norm_b == 0:
+        return 0.0
+    return dot / (norm_a * norm_b)
+
+
+class QuestionClusterer:
+    """Clusters logged questions by embedding similarity and writes a report to disk."""
+
+    def __init__(self, ai_interface, data_collector, data_dir: str = "data", similarity_threshold: float = 0.85):
+        self.ai = ai_interface
+        self.data_collector = data_collector
+        self.similarity_threshold = similarity_threshold
+        self.report_file = os.path.join(data_dir, "question_clusters.json")
+
+    def _unique_questions(self) -> Dict[str, Dict]:
+        """Collapse exact duplicates (after normalization) and count them."""
+        unique: Dict[str, Dict] = {}
+        for interaction in self.data_collector.load_interactions():
+            question = interaction.get("question", "")
+            key = normalize_question(question)
+            if not key:
+                continue
+            entry = unique.setdefault(key, {"question": question, "count": 0})
+            entry["count"] += 1
+        return unique
+
+    async def _embed(self, texts: List[str]) -> List[List[float]]:
+        """Embed the questions in batches of EMBED_BATCH_SIZE."""
+        vectors = []
+        for start in range(0, len(texts), EMBED_BATCH_SIZE):
+            vectors.extend(await self.ai.embed(texts[start:start + EMBED_BATCH_SIZE]))
+        return vectors
+
+    def _cluster(self, questions: List[Dict], vectors: List[List[float]]) -> List[Dict]:
+        """
+        Greedy single-pass clustering: each question joins the most similar existing
+        cluster if it's above the threshold, otherwise it starts a new one.
+        Most frequent questions go first so they become the cluster representatives.
+        """
+        order = sorted(range(len(questions)), key=lambda i: questions[i]["count"], reverse=True)
+        clusters = []
+
+        for i in order:
+            best_cluster = None
+            best_score = self.similarity_threshold
+            for cluster in clusters:
+                score = cosine_similarity(vectors[i], cluster["vector"])
+                if score >= best_score:
+                    best_cluster = cluster
+                    best_score = score
+
+            if best_cluster is None:
+                clusters.append({
+                    "representative": questions[i]["question"],
+                    "vector": vectors[i],
+                    "count": questions[i]["count"],
+                    "questions": [questions[i]]
+                })
+            else:
+                best_cluster["count"] += questions[i]["count"]
+                best_cluster["questions"].append(questions[i])
+
+        return clusters
+
+    def run(self, top_n: int = 25) -> Dict:
+        """
+        Cluster all logged questions and save the top clusters as a report.
+
+        Args:
+            top_n: Number of clusters to keep in the report
+
+        Returns:
+            The report dict that was written to question_clusters.json
+        """
+        unique = list(self._unique_questions().values())
+
+        try:
+            vectors = asyncio.run(self._embed([q["question"] for q in unique]))
+        except Exception as e:
+            # Without the embedding model we can still report exact duplicates
+            logger.warning(f"Embedding failed, falling back to exact matching: {e}")
+            vectors = None
+
+        if vectors is not None:
+            clusters = self._cluster(unique, vectors)
+            method = "embeddings"
+        else:
+            # _unique_questions() already grouped them by their normalized text
+            clusters = [{"representative": q["question"], "count": q["count"], "questions": [q]} for q in unique]
+            method = "exact"
+
+        clusters.sort(key=lambda c: c["count"], reverse=True)
+
+        report = {
+            "generated_at": datetime.now().isoformat(),
+            "method": method,
+            "total_unique_questions": len(unique),
+            "clusters": [
+                {
+                    "representative": c["representative"],
+                    "count": c["count"],
+                    "questions": [q["question"] for q in c["questions"]]
+                }
+                for c in clusters[:top_n]
+            ]
+        }
+
+        with open(self.report_file, "w", encoding="utf-8") as f:
+            json.dump(report, f, ensure_ascii=False, indent=2)
+
+        return report
+
+    def load_report(self) -> Optional[Dict]:
+        """Load the last generated clustering report, if any."""
+        try:
+            with open(self.report_file, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return None
+
+
+if __name__ == "__main__":
+    # Run the clustering job offline from the project root: PYTHONPATH=src python -m lib.QuestionClustering
+    from lib.Config import load_config
+    from lib.GemInterface import AiInterface
+    from lib.DataCollector import DataCollector
+
+    config = load_config()
+    clusterer = QuestionClusterer(AiInterface(config=config), DataCollector(config=config), data_dir=config.data_dir)
+    result = clusterer.run()
+    for cluster in result["clusters"]:
+        print(f"{cluster['count']:>5}  {cluster['representative']}")
@@ -0,0 +1,114 @@
+"""
+Request rate limiting for ArchieAI.
+The abuse heuristics (lib/AbuseDetector.py) only look at questions; nothing stopped a script
+from hammering the rest of the API (session lists, history, exports) as fast as it could.
+Every /api/ request now takes a token from two buckets, one for its session and one for its
+IP address. A bucket holds up to `burst` tokens and refills at `per_minute` tokens a minute,
+so short bursts are fine but a sustained flood gets a 429 with a Retry-After header. The IP
+bucket is the larger of the two, since a whole campus can sit behind one NAT address. Behind a
+reverse proxy the IP is only the client's with server.trusted_proxies set (see app.py's ProxyFix);
+otherwise every caller shares the proxy's bucket.
+Buckets live in memory, per process, and full ones are dropped so idle callers cost nothing.
+"""
+import time
+import threading
+from typing import Dict, Optional, Tuple
+
+# Drop idle buckets once there are this many
+PRUNE_THRESHOLD = 10000
+
+
+class TokenBucket:
+    """Up to capacity tokens, refilled continuously at rate tokens a second."""
+
+    __slots__ = ("capacity", "rate", "tokens", "updated", "throttled")
+
+    def __init__(self, capacity: float, rate: float, now: float):
+        self.capacity = capacity
+        self.rate = rate
+        self.tokens = capacity
+        self.updated = now
+        # Set while the caller is being turned away, so a flood logs one throttle event and not thousands
+        self.throttled = False
+
+    def refill(self, now: float):
+        self.tokens = min(self.capacity, self.tokens + (now - self.updated) * self.rate)
+        self.updated = now
+
+    def wait(self, now: float) -> Optional[float]:
+        """None if there's a token to take, else the seconds until there will be."""
+        self.refill(now)
+        if self.tokens >= 1:
+            return None
+        return (1 - self.tokens) / self.rate if self.rate > 0 else float("inf")
+
+    def take(self, now: float) -> Optional[float]:
+        """Take a token; returns None if there was one, else the seconds until there will be."""
+        retry_after = self.wait(now)
+        if retry_after is None:
+            self.tokens -= 1
+            self.throttled = False
+        return retry_after
+
+
+class RateLimiter:
+    """A token bucket per session and per IP address."""
+
+    def __init__(self, session_burst: int = 30, session_per_minute: float = 60,
+                 ip_burst: int = 120, ip_per_minute: float = 300):
+        # scope -> (capacity, tokens per second)
+        self.limits = {
+            "session": (session_burst, session_per_minute / 60),
+            "ip": (ip_burst, ip_per_minute / 60),
+        }
+        self._lock = threading.Lock()
+        self._buckets: Dict[Tuple[str, str], TokenBucket] = {}
+        self._throttled = {scope: 0 for scope in self.limits}
+
+    @classmethod
+    def from_config(cls, config) -> "RateLimiter":
+        limits = config.rate_limit
+        return cls(session_burst=limits.session_burst, session_per_minute=limits.session_per_minute,
+                   ip_burst=limits.ip_burst, ip_per_minute=limits.ip_per_minute)
+
+    def _prune(self, now: float):
+        for key in [key for key, bucket in self._buckets.items()
+                    if bucket.tokens + (now - bucket.updated) * bucket.rate >= bucket.capacity]:
+            del self._buckets[key]
+
+    def check(self, session_id: Optional[str], ip_address: Optional[str]) -> Optional[Dict]:
+        """
+        Count a request against its session's and IP's buckets. A request is only counted if both
+        have a token, so one refused by the IP bucket doesn't use up its session's.
+
+        Returns:
+            None if it's allowed, else {"scope", "retry_after", "first"}: which bucket ran dry, the
+            seconds until it has a token again, and whether this is the first refusal since the caller
+            was last let through
+        """
+        now = time.monotonic()
+        with self._lock:
+            if len(self._buckets) >= PRUNE_THRESHOLD:
+                self._prune(now)
+            buckets = []
+            for scope, key in (("session", session_id), ("ip", ip_address)):
+                if not key:
+                    continue
+                bucket = self._buckets.get((scope, key))
+                if bucket is None:
+                    capacity, rate = self.limits[scope]
+                    bucket = self._buckets[(scope, key)] = TokenBucket(capacity, rate, now)
+                retry_after = bucket.wait(now)
+                if retry_after is not None:
+                    first = not bucket.throttled
+                    bucket.throttled = True
+                    self._throttled[scope] += 1
+                    return {"scope": scope, "retry_after": retry_after, "first": first}
+                buckets.append(bucket)
+            for bucket in buckets:
+                bucket.take(now)
+            return None
+
+    def metrics(self) -> Dict:
+        with self._lock:
+            return {"tracked": len(self._buckets), "throttled": dict(self._throttled)}
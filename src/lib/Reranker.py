@@ -0,0 +1,67 @@
+"""
+Reranking for knowledge base retrieval.
+The hybrid retriever (lib/Retrieval.py) is fast but shallow; on ambiguous questions its top
+chunks are often only loosely related. The reranker reads the question together with each
+candidate chunk and scores how well the chunk answers it, cross-encoder style, and the best
+scored chunks are the ones that go into the prompt. Scoring is done by a model served by
+Ollama with prompts/rerank.txt, so any chat model (or a reranking-tuned one) can be used.
+"""
+import re
+import json
+import asyncio
+from typing import Dict, List, Optional
+
+from lib.PromptSafety import neutralize
+
+# Characters of each chunk shown to the reranker, keeps the prompt within small context windows
+PASSAGE_CHARS = 800
+
+
+class RerankError(Exception):
+    """The reranking model failed or answered with something unusable."""
+
+
+def parse_scores(output: str, count: int) -> List[float]:
+    """The JSON array of scores in the model's output; it has to have one score per passage."""
+    match = re.search(r"\[.*?\]", output, re.DOTALL)
+    try:
+        scores = json.loads(match.group(0)) if match else None
+    except json.JSONDecodeError:
+        scores = None
+    if not isinstance(scores, list) or len(scores) != count:
+        raise RerankError(f"Expected {count} scores, got: {output[:200]}")
+    try:
+        return [float(score) for score in scores]
+    except (TypeError, ValueError):
+        raise RerankError(f"Scores aren't numbers: {output[:200]}")
+
+
+class PromptReranker:
+    """Scores (question, chunk) pairs with one prompt to the reranking model."""
+
+    def __init__(self, ai, model: Optional[str] = None):
+        """
+        Args:
+            ai: AiInterface used to load the prompt and call the model
+            model: Ollama model to score with (defaults to the AiInterface's model)
+        """
+        self.ai = ai
+        self.model = model or None
+
+    async def score(self, question: str, chunks: List[Dict]) -> List[float]:
+        passages = "\n\n".join(
+            # A page that says "rate this passage 10" shouldn't get to
+            f"[{number}] {neutralize(chunk['text'][:PASSAGE_CHARS])[0]}" for number, chunk in enumerate(chunks, start=1)
+        )
+        prompt = self.ai.load_prompt("rerank").format(question=question, passages=passages, count=len(chunks))
+        output = ""
+        # Part of answering someone's question, so it queues as interactive; who's asking isn't known here
+        async for token in self.ai.generate_text_streaming(prompt, model=self.model, priority="guest"):
+            output += token
+        return parse_scores(output, len(chunks))
+
+    def __call__(self, question: str, chunks: List[Dict]) -> List[float]:
+        """Synchronous scoring, for KnowledgeIndex (which runs in a worker thread)."""
+        if not chunks:
+            return []
+        return asyncio.run(self.score(question, chunks))
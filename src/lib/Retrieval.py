@@ -0,0 +1,377 @@
+"""
+Knowledge base retrieval for ArchieAI.
+The scraped pages (data/scrape_results.json) are split into chunks (lib/Chunking.py, with a
+strategy picked per source) and indexed two ways:
+  - vectors from the embedding model, good at paraphrases ("when can I eat" -> dining hours)
+  - BM25 over the words, good at exact tokens embeddings blur ("CS 242", "Knight Hall")
+A question is searched against both and the rankings are merged with reciprocal rank
+fusion (RRF), so a chunk that either retriever ranks highly makes it into the prompt.
+With a reranker (lib/Reranker.py) the top fused candidates are rescored against the
+question before the best ones are picked.
+The chunks and their embeddings are saved to data/kb_index.json by build(); BM25 is
+rebuilt in memory whenever that file changes. Rebuilds are incremental: a source whose
+text and chunking settings haven't changed keeps its chunks and embeddings.
+Every chunk carries its page's URL and fetch time, so answers can cite it "as of" a date.
+"""
+import logging
+import os
+import re
+import json
+import math
+import hashlib
+import threading
+from collections import Counter
+from datetime import datetime
+from typing import Callable, Dict, List, Optional, Tuple
+
+from lib.QuestionClustering import cosine_similarity
+from lib.KnowledgeBase import KB_SOURCES, SCRAPE_RESULTS_FILE, load_source_info, source_type
+from lib.Chunking import chunk_document, chunking_options
+
+logger = logging.getLogger(__name__)
+
+INDEX_FILE = "kb_index.json"
+# Texts per embedding request while building
+EMBED_BATCH_SIZE = 32
+
+_TOKEN_RE = re.compile(r"[a-z0-9]+")
+
+
+def tokenize(text: str) -> List[str]:
+    """
+    Lowercase word tokens. A letters token followed by a number token is also joined,
+    so "CS 242", "CS242", and "cs-242" all share the token "cs242".
+    """
+    words = _TOKEN_RE.findall(text.lower())
+    tokens = list(words)
+    for first, second in zip(words, words[1:]):
+        if first.isalpha() and second.isdigit():
+            tokens.append(first + second)
+    return tokens
+
+
+class BM25Index:
+    """Okapi BM25 over a fixed list of documents."""
+
+    def __init__(self, documents: List[str], k1: float = 1.5, b: float = 0.75):
+        self.k1 = k1
+        self.b = b
+        self.term_counts = [Counter(tokenize(doc)) for doc in documents]
+        self.lengths = [sum(counts.values()) for counts in self.term_counts]
+        self.avg_length = (sum(self.lengths) / len(self.lengths)) if self.lengths else 0
+        document_frequency = Counter()
+        for counts in self.term_counts:
+            document_frequency.update(counts.keys())
+        n = len(documents)
+        self.idf = {
+            term: math.log(1 + (n - df + 0.5) / (df + 0.5))
+            for term, df in document_frequency.items()
+        }
+
+    def search(self, query: str, limit: int = 20) -> List[tuple]:
+        """(document index, score) pairs for documents sharing a term with the query, best first."""
+        terms = set(tokenize(query))
+        scores = []
+        for index, counts in enumerate(self.term_counts):
+            score = 0.0
+            norm = self.k1 * (1 - self.b + self.b * self.lengths[index] / (self.avg_length or 1))
+            for term in terms:
+                tf = counts.get(term)
+                if tf:
+                    score += self.idf[term] * tf * (self.k1 + 1) / (tf + norm)
+            if score > 0:
+                scores.append((index, score))
+        scores.sort(key=lambda pair: pair[1], reverse=True)
+        return scores[:limit]
+
+
+def citation(result: Dict) -> Dict:
+    """What an answer cites for a search result: the source, its link, and the date it was fetched."""
+    fetched_at = result.get("fetched_at")
+    return {
+        "source": result["source"],
+        "url": result.get("url"),
+        "chunk_id": result["chunk_id"],
+        "fetched_at": fetched_at,
+        "as_of": fetched_at[:10] if fetched_at else None,
+    }
+
+
+def reciprocal_rank_fusion(rankings: List[List[int]], k: int = 60) -> List[tuple]:
+    """
+    Merge ranked lists of document indexes: each list adds 1 / (k + rank) to a document's score.
+
+    Returns:
+        (document index, fused score) pairs, best first
+    """
+    scores: Dict[int, float] = {}
+    for ranking in rankings:
+        for rank, index in enumerate(ranking, start=1):
+            scores[index] = scores.get(index, 0.0) + 1.0 / (k + rank)
+    return sorted(scores.items(), key=lambda pair: pair[1], reverse=True)
+
+
+class KnowledgeIndex:
+    """Hybrid (BM25 + vector) search over the knowledge base chunks."""
+
+    def __init__(self, data_dir: str = "data", embed: Optional[Callable[[List[str]], List[List[float]]]] = None,
+                 chunk_size: int = 200, chunk_overlap: int = 40, candidates: int = 20, rrf_k: int = 60,
+                 reranker: Optional[Callable[[str, List[Dict]], List[float]]] = None, rerank_candidates: int = 15,
+                 chunk_strategy: str = "fixed", chunking: Optional[Dict[str, Dict]] = None, embed_model: str = ""):
+        """
+        Args:
+            data_dir: Data directory holding scrape_results.json and the index
+            embed: Function texts -> vectors; without it (or if it fails) only BM25 is used
+            chunk_size, chunk_overlap: Default chunk length and overlap in words
+            candidates: How many results each retriever contributes to the fusion
+            rrf_k: RRF constant; higher values flatten the difference between ranks
+            reranker: Optional function (query, chunks) -> relevance scores applied to the fused results
+            rerank_candidates: How many fused results the reranker gets to reorder
+            chunk_strategy: Default chunking strategy (see lib/Chunking.py)
+            chunking: Source type or source name -> strategy/chunk_size/chunk_overlap overrides
+            embed_model: Name of the embedding model; changing it re-embeds everything
+        """
+        self.data_dir = data_dir
+        self.path = os.path.join(data_dir, INDEX_FILE)
+        self.embed = embed
+        self.chunk_size = chunk_size
+        self.chunk_overlap = chunk_overlap
+        self.chunk_strategy = chunk_strategy
+        self.chunking = chunking or {}
+        self.embed_model = embed_model
+        self.candidates = candidates
+        self.rrf_k = rrf_k
+        self.reranker = reranker
+        self.rerank_candidates = rerank_candidates
+        self._lock = threading.Lock()
+        self._chunks: List[Dict] = []
+        self._bm25: Optional[BM25Index] = None
+        self._loaded_mtime = None
+
+    def _load_documents(self) -> List[Dict]:
+        try:
+            with open(os.path.join(self.data_dir, SCRAPE_RESULTS_FILE), "r", encoding="utf-8") as f:
+                results = json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return []
+        info = load_source_info(self.data_dir)
+        documents = []
+        for name, text in results.items():
+            if not isinstance(text, str):
+                continue
+            source = info.get(name, {})
+            documents.append({
+                "source": name,
+                # Without scrape_sources.json (older scrapes), go by the configured URL or the file name
+                "type": source.get("type") or source_type(KB_SOURCES.get(name, name)),
+                "url": source.get("url", KB_SOURCES.get(name)),
+                "fetched_at": source.get("fetched_at"),
+                "text": text,
+            })
+        return documents
+
+    def chunking_for(self, source: str, document_type: str) -> Dict:
+        """The strategy, chunk_size, and chunk_overlap used for a document."""
+        defaults = {"strategy": self.chunk_strategy, "chunk_size": self.chunk_size, "chunk_overlap": self.chunk_overlap}
+        return chunking_options(source, document_type, defaults, self.chunking)
+
+    def _chunk_document(self, document: Dict) -> Tuple[List[Dict], Dict]:
+        """(chunks, how the document was chunked, including a hash of its text)"""
+        source, document_type = document["source"], document.get("type", "text")
+        options = self.chunking_for(source, document_type)
+        pieces = chunk_document(document["text"], options["strategy"], options["chunk_size"], options["chunk_overlap"])
+        chunks = [
+            {"chunk_id": f"{source}:{position}", "source": source, "text": chunk,
+             "url": document.get("url"), "fetched_at": document.get("fetched_at")}
+            for position, chunk in enumerate(pieces)
+        ]
+        content_hash = hashlib.sha256(document["text"].encode("utf-8")).hexdigest()
+        return chunks, {"type": document_type, **options, "chunks": len(pieces), "hash": content_hash}
+
+    def _make_chunks(self, documents: List[Dict]) -> Tuple[List[Dict], Dict[str, Dict]]:
+        """(chunks, source name -> how it was chunked)"""
+        chunks, sources = [], {}
+        for document in documents:
+            document_chunks, sources[document["source"]] = self._chunk_document(document)
+            chunks.extend(document_chunks)
+        return chunks, sources
+
+    def _previous_build(self) -> Tuple[Dict[str, Dict], Dict[str, List[Dict]]]:
+        """(source name -> how it was chunked, source name -> chunks) from the saved index, if it can be reused."""
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                index = json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {}, {}
+        if index.get("embed_model", "") != self.embed_model:
+            # Vectors from another model aren't comparable with the new ones
+            return {}, {}
+        chunks_by_source: Dict[str, List[Dict]] = {}
+        for chunk in index.get("chunks", []):
+            chunks_by_source.setdefault(chunk["source"], []).append(chunk)
+        return index.get("sources", {}), chunks_by_source
+
+    def build(self, documents: Optional[List[Dict]] = None, full: bool = False,
+              rebuild_sources: Optional[List[str]] = None) -> Dict:
+        """
+        Chunk and embed the knowledge base and save the index.
+        Sources whose text and chunking settings match the saved index keep their chunks
+        and embeddings; only new or changed sources are chunked and embedded.
+
+        Args:
+            documents: Dicts with source, type, and text (defaults to scrape_results.json)
+            full: Re-chunk and re-embed everything
+            rebuild_sources: Re-chunk and re-embed these sources even if they haven't changed
+
+        Returns:
+            The index status (see status())
+        """
+        previous_sources, previous_chunks = ({}, {}) if full else self._previous_build()
+        chunks, sources = [], {}
+        changed = []
+        rebuild = set(rebuild_sources or [])
+        for document in documents if documents is not None else self._load_documents():
+            source = document["source"]
+            document_chunks, meta = self._chunk_document(document)
+            reusable = previous_chunks.get(source, [])
+            previous = previous_sources.get(source) if source not in rebuild else None
+            if (previous is not None and previous == meta and len(reusable) == meta["chunks"]
+                    and (self.embed is None or all("embedding" in chunk for chunk in reusable))):
+                document_chunks = reusable
+                for chunk in document_chunks:
+                    # Same content, fetched again: it's current as of this fetch
+                    chunk["url"], chunk["fetched_at"] = document.get("url"), document.get("fetched_at")
+            else:
+                changed.append(source)
+            chunks.extend(document_chunks)
+            sources[source] = meta
+
+        to_embed = [chunk for chunk in chunks if "embedding" not in chunk]
+        if self.embed is not None and to_embed:
+            try:
+                for start in range(0, len(to_embed), EMBED_BATCH_SIZE):
+                    batch = to_embed[start:start + EMBED_BATCH_SIZE]
+                    for chunk, vector in zip(batch, self.embed([chunk["text"] for chunk in batch])):
+                        chunk["embedding"] = vector
+            except Exception as e:
+                # Keyword search still works without vectors; the next build retries these
+                logger.warning(f"Could not embed the knowledge base, indexing keywords only: {e}")
+                for chunk in to_embed:
+                    chunk.pop("embedding", None)
+        embedded = self.embed is not None and bool(chunks) and all("embedding" in chunk for chunk in chunks)
+
+        index = {
+            "built_at": datetime.now().isoformat(),
+            "embedded": embedded,
+            "embed_model": self.embed_model,
+            "last_build": {"full": full, "changed_sources": len(changed), "reused_sources": len(sources) - len(changed),
+                           "rebuilt": sorted(changed)[:50]},
+            "sources": sources,
+            "chunks": chunks,
+        }
+        os.makedirs(self.data_dir, exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(index, f, ensure_ascii=False)
+        os.replace(tmp_path, self.path)
+        return self.status()
+
+    def _ensure_loaded(self) -> List[Dict]:
+        """Chunks from the saved index (re-read when it changes), or keyword-only chunks of the raw scrape."""
+        with self._lock:
+            try:
+                mtime = os.path.getmtime(self.path)
+            except OSError:
+                mtime = None
+            if self._bm25 is not None and mtime == self._loaded_mtime:
+                return self._chunks
+
+            chunks = []
+            if mtime is not None:
+                try:
+                    with open(self.path, "r", encoding="utf-8") as f:
+                        chunks = json.load(f).get("chunks", [])
+                except (OSError, json.JSONDecodeError) as e:
+                    logger.warning(f"Could not read {self.path}: {e}")
+            if not chunks:
+                # Not built yet: BM25 over the scraped pages still beats nothing
+                chunks, _ = self._make_chunks(self._load_documents())
+            self._chunks = chunks
+            self._bm25 = BM25Index([chunk["text"] for chunk in chunks])
+            self._loaded_mtime = mtime
+            return chunks
+
+    def search(self, query: str, limit: int = 5, rerank: bool = True) -> List[Dict]:
+        """
+        Find the chunks that best match a query.
+
+        Args:
+            rerank: Use the reranker if there is one
+
+        Returns:
+            Up to `limit` dicts with chunk_id, source, text, url, fetched_at, score (fused), matched_by
+            ("keyword", "vector", or both), and rerank_score when reranked
+        """
+        chunks = self._ensure_loaded()
+        if not chunks:
+            return []
+
+        keyword_ranking = [index for index, _ in self._bm25.search(query, self.candidates)]
+        vector_ranking = []
+        if self.embed is not None and any("embedding" in chunk for chunk in chunks):
+            try:
+                query_vector = self.embed([query])[0]
+                scored = [
+                    (index, cosine_similarity(query_vector, chunk["embedding"]))
+                    for index, chunk in enumerate(chunks) if "embedding" in chunk
+                ]
+                scored.sort(key=lambda pair: pair[1], reverse=True)
+                vector_ranking = [index for index, _ in scored[:self.candidates]]
+            except Exception as e:
+                logger.warning(f"Vector search failed, using keywords only: {e}")
+
+        keyword_hits, vector_hits = set(keyword_ranking), set(vector_ranking)
+        use_reranker = rerank and self.reranker is not None
+        fused = reciprocal_rank_fusion([keyword_ranking, vector_ranking], k=self.rrf_k)
+        results = []
+        for index, score in fused[:max(limit, self.rerank_candidates) if use_reranker else limit]:
+            chunk = chunks[index]
+            results.append({
+                "chunk_id": chunk["chunk_id"],
+                "source": chunk["source"],
+                "text": chunk["text"],
+                "url": chunk.get("url"),
+                "fetched_at": chunk.get("fetched_at"),
+                "score": round(score, 5),
+                "matched_by": [name for name, hits in (("keyword", keyword_hits), ("vector", vector_hits)) if index in hits],
+            })
+        if use_reranker and len(results) > 1:
+            try:
+                scores = self.reranker(query, results)
+            except Exception as e:
+                # The fused order is still a decent answer
+                logger.warning(f"Reranking failed, using the fused ranking: {e}")
+            else:
+                for result, rerank_score in zip(results, scores):
+                    result["rerank_score"] = rerank_score
+                # sorted() is stable, so ties keep their fused order
+                results = sorted(results, key=lambda result: result["rerank_score"], reverse=True)
+        return results[:limit]
+
+    def status(self) -> Dict:
+        """What's in the saved index, for the admin API."""
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                index = json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {"exists": False, "chunks": 0}
+        return {
+            "exists": True,
+            "built_at": index.get("built_at"),
+            "embedded": index.get("embedded", False),
+            "chunks": len(index.get("chunks", [])),
+            # How many sources the last build re-chunked vs. kept from the one before
+            "last_build": index.get("last_build"),
+            # How each source was chunked, to check [retrieval.chunking] took effect
+            "sources": index.get("sources", {}),
+        }
@@ -0,0 +1,91 @@
+"""
+Sentiment scoring for ArchieAI analytics.
+Gives each question a score from -1 (frustrated) to 1 (happy) using a small word list,
+so the dashboard can show whether students are getting frustrated over time.
+Rule-based on purpose: it runs on every question, needs no model call, and is easy to explain.
+"""
+import re
+from typing import Dict
+
+# Word -> weight; a negative weight is a negative word
+LEXICON: Dict[str, float] = {
+    "thanks": 2, "thank": 2, "thx": 1.5, "great": 2, "awesome": 2.5, "perfect": 2.5, "helpful": 2,
+    "love": 2.5, "nice": 1.5, "cool": 1, "good": 1.5, "amazing": 2.5, "appreciate": 2, "excellent": 2.5,
+    "glad": 1.5, "happy": 2, "worked": 1,
+    "wrong": -2, "bad": -2, "terrible": -3, "awful": -3, "useless": -3, "stupid": -3, "dumb": -2.5,
+    "annoying": -2.5, "annoyed": -2.5, "frustrated": -3, "frustrating": -3, "confused": -1.5,
+    "confusing": -1.5, "broken": -2, "hate": -3, "worst": -3, "ridiculous": -2.5, "incorrect": -2,
+    "unhelpful": -2.5, "pointless": -2.5, "waste": -2, "ugh": -2, "wtf": -3, "seriously": -1,
+}
+
+# Phrases that mean the user is repeating themselves or the last answer missed
+FRUSTRATION_PHRASES = [
+    r"\bi (already|just) (asked|told you|said)\b",
+    r"\bthat'?s not what i (asked|meant|said)\b",
+    r"\bnot what i (asked|meant)\b",
+    r"\byou (already|just) said\b",
+    r"\bdoes(n'?t| not) (answer|help)\b",
+    r"\bstill (doesn'?t|does not|not|no)\b",
+    r"\bagain\?",
+    r"\bnever ?mind\b",
+    r"\bwhy (can'?t|won'?t|don'?t) you\b",
+]
+
+NEGATIONS = {"not", "no", "never", "isn't", "wasn't", "don't", "dont", "aren't", "nothing"}
+INTENSIFIERS = {"very": 1.5, "really": 1.5, "so": 1.3, "super": 1.5, "extremely": 2, "totally": 1.5}
+
+NEGATIVE_THRESHOLD = -0.2
+POSITIVE_THRESHOLD = 0.2
+
+_WORD_RE = re.compile(r"[a-z']+")
+
+
+def score_sentiment(text: str) -> float:
+    """
+    Score a question from -1 (negative) to 1 (positive); 0 is neutral.
+
+    A negation flips the next sentiment word ("not helpful"), an intensifier
+    strengthens it ("really annoying"), frustration phrases ("I already asked")
+    count as a strong negative, and repeated "!"/"?" or shouting amplify the result.
+    """
+    if not text or not text.strip():
+        return 0.0
+
+    lowered = text.lower()
+    words = _WORD_RE.findall(lowered.replace("’", "'"))
+    total = 0.0
+    multiplier = 1.0
+    negate = False
+    for word in words:
+        if word in NEGATIONS:
+            negate = True
+            continue
+        if word in INTENSIFIERS:
+            multiplier = INTENSIFIERS[word]
+            continue
+        weight = LEXICON.get(word)
+        if weight is not None:
+            total += weight * multiplier * (-0.75 if negate else 1)
+            negate = False
+            multiplier = 1.0
+
+    total -= 3 * sum(1 for pattern in FRUSTRATION_PHRASES if re.search(pattern, lowered))
+
+    if total:
+        # Emphasis: "!!", "??", or a question typed mostly in capitals
+        letters = [c for c in text if c.isalpha()]
+        shouting = len(letters) >= 8 and sum(c.isupper() for c in letters) / len(letters) > 0.7
+        if re.search(r"[!?]{2,}", text) or shouting:
+            total *= 1.5
+
+    # Squash into [-1, 1]; a single strong word lands around +-0.6
+    return round(total / (abs(total) + 2), 3)
+
+
+def sentiment_label(score: float) -> str:
+    """"positive", "neutral", or "negative" for a score."""
+    if score <= NEGATIVE_THRESHOLD:
+        return "negative"
+    if score >= POSITIVE_THRESHOLD:
+        return "positive"
+    return "neutral"
@@ -0,0 +1,71 @@
+"""
+Fan-out of session lifecycle events to open browser tabs.
+SessionManager calls publish() through its listener hook; each /api/sessions/stream
+connection subscribes with the user's email and gets its own queue of events.
+"""
+import queue
+import threading
+from datetime import datetime
+from typing import Dict, List, Optional
+
+
+def _put_last(q: queue.Queue):
+    # Make room if the tab stopped reading, the end of the stream matters more than one event
+    try:
+        q.put_nowait(None)
+    except queue.Full:
+        try:
+            q.get_nowait()
+        except queue.Empty:
+            pass
+        q.put_nowait(None)
+
+
+class SessionEventBroker:
+    """Delivers session events to every open stream of the user who owns the session."""
+
+    def __init__(self, max_queue_size: int = 100):
+        self.max_queue_size = max_queue_size
+        self._lock = threading.Lock()
+        self._subscribers: Dict[str, List[queue.Queue]] = {}
+
+    def subscribe(self, user_email: str) -> queue.Queue:
+        """Open a new event queue for a user."""
+        q = queue.Queue(maxsize=self.max_queue_size)
+        with self._lock:
+            self._subscribers.setdefault(user_email, []).append(q)
+        return q
+
+    def unsubscribe(self, user_email: str, q: queue.Queue):
+        """Close a queue once its stream has ended."""
+        with self._lock:
+            queues = self._subscribers.get(user_email, [])
+            if q in queues:
+                queues.remove(q)
+            if not queues:
+                self._subscribers.pop(user_email, None)
+
+    def publish(self, event: str, session_id: str, user_email: Optional[str]):
+        """Send an event to all of the user's streams. Guest sessions have no listeners."""
+        if not user_email:
+            return
+        payload = {
+            "event": event,
+            "session_id": session_id,
+            "timestamp": datetime.now().isoformat(),
+        }
+        with self._lock:
+            queues = list(self._subscribers.get(user_email, []))
+        for q in queues:
+            try:
+                q.put_nowait(payload)
+            except queue.Full:
+                # The tab stopped reading; it'll resync when it reconnects
+                pass
+
+    def close_all(self):
+        """End every open stream (the server is shutting down); each queue gets None as its last item."""
+        with self._lock:
+            queues = [q for user_queues in self._subscribers.values() for q in user_queues]
+        for q in queues:
+            _put_last(q)
@@ -1,77 +1,256 @@
 """
 Session and user management for ArchieAI.
-Handles user accounts, session storage, and chat history.
+Handles user accounts (stored one file per user by lib/UserStore.py), session storage, and chat history.
 """
+import logging
 import os
 import json
 import secrets
 import re
+import uuid
+import copy
+import functools
+import threading
 from datetime import datetime
-from typing import Optional, Dict, List
-from werkzeug.security import generate_password_hash, check_password_hash
+from typing import Optional, Dict, List, Callable, Tuple
+from lib.Passwords import hash_password, verify_password
+from lib.SessionRecovery import SessionQuarantine, QUARANTINE_DIR
+from lib.SessionSummary import history_summary_coverage
+from lib.UserStore import UserStore
+from lib.InstanceLock import ReadOnlyStorage
+
+logger = logging.getLogger(__name__)
+
+# Checkpoints hold a full copy of the messages, so keep the number per session bounded
+MAX_CHECKPOINTS = 20
+# Pinned messages go into every prompt, so only a few
+MAX_PINNED = 5
+# Suspended accounts are pending review; banned ones are closed for good (both can't use Archie)
+USER_STATUSES = ("active", "suspended", "banned")
+
+
+def _write_json(path: str, data):
+    # Written to a temp file and swapped in, so a crash or shutdown mid-write never leaves half a session
+    tmp_path = f"{path}.{threading.get_ident()}.tmp"
+    with open(tmp_path, "w", encoding="utf-8") as f:
+        json.dump(data, f, indent=4, ensure_ascii=False)
+    os.replace(tmp_path, path)
+
+
+def _locked(method):
+    """Run a SessionManager method holding its session's lock, so concurrent changes to one session don't drop each other's."""
+    @functools.wraps(method)
+    def wrapper(self, session_id, *args, **kwargs):
+        with self._lock_for(session_id):
+            return method(self, session_id, *args, **kwargs)
+    return wrapper
+
+
+def _remove_sessions(user: Dict, session_ids: set):
+    """Drop session IDs from a user record's session list."""
+    if "sessions" in user:
+        user["sessions"] = [session_id for session_id in user["sessions"] if session_id not in session_ids]
 
 
 class SessionManager:
     """Manages user accounts and chat sessions with JSON file storage."""
     
-    def __init__(self, data_dir: str = "data"):
+    def __init__(self, data_dir: str = "data", admin_emails: Optional[List[str]] = None, config=None,
+                 history_messages: int = 10):
+        # Config (lib.Config.Config) takes precedence over the individual arguments
+        read_only = False
+        if config is not None:
+            data_dir = config.data_dir
+            admin_emails = config.admin.emails
+            read_only = config.instance.role == "replica"
+            history_messages = config.limits.history_messages
+        
         self.data_dir = data_dir
-        self.users_file = os.path.join(data_dir, "users.json")
+        # A read-only replica serves the primary's files and refuses to change them (lib/InstanceLock.py)
+        self.read_only = read_only
+        # How many earlier messages get_conversation_history() returns
+        self.history_messages = history_messages
+        self.admin_emails = set(e.strip().lower() for e in (admin_emails or []) if e.strip())
         self.sessions_dir = os.path.join(data_dir, "sessions")
+        # Corrupt session files are moved here and salvaged (see lib/SessionRecovery.py)
+        self.quarantine = SessionQuarantine(data_dir)
         
         # Ensure directories exist
-        os.makedirs(self.sessions_dir, exist_ok=True)
+        if not read_only:
+            os.makedirs(self.sessions_dir, exist_ok=True)
+        
+        # Also moves accounts out of an old users.json
+        self.users = UserStore(data_dir, read_only=read_only)
         
-        # Initialize users file if it doesn't exist
-        if not os.path.exists(self.users_file):
-            with open(self.users_file, "w", encoding="utf-8") as f:
-                json.dump({}, f)
+        # Every change to a session file loads, edits, and saves it whole; changes to the same session
+        # (two tabs, a title being generated while the next answer is saved) take turns on its lock
+        self._locks_guard = threading.Lock()
+        self._session_locks: Dict[str, threading.RLock] = {}
+        
+        # Lifecycle hooks, called as listener(event, session_id, user_email)
+        self._listeners: List[Callable[[str, str, Optional[str]], None]] = []
     
-    def _load_users(self) -> Dict:
-        """Load users from JSON file."""
-        try:
-            with open(self.users_file, "r", encoding="utf-8") as f:
-                return json.load(f)
-        except FileNotFoundError:
-            # File doesn't exist yet, return empty dict
-            return {}
-        except json.JSONDecodeError as e:
-            # File is corrupted, log error and return empty dict
-            print(f"Warning: users.json is corrupted: {e}")
-            return {}
+    def _check_writable(self):
+        if self.read_only:
+            raise ReadOnlyStorage("sessions")
+    
+    def _lock_for(self, session_id: str) -> threading.RLock:
+        with self._locks_guard:
+            return self._session_locks.setdefault(session_id, threading.RLock())
+    
+    def _forget_lock(self, session_id: str):
+        with self._locks_guard:
+            self._session_locks.pop(session_id, None)
+    
+    def add_listener(self, listener: Callable[[str, str, Optional[str]], None]):
+        """Register a callback for session lifecycle events ("created", "updated", "deleted")."""
+        self._listeners.append(listener)
+    
+    def _emit(self, event: str, session_id: str, user_email: Optional[str]):
+        for listener in list(self._listeners):
+            try:
+                listener(event, session_id, user_email)
+            except Exception as e:
+                # A broken listener shouldn't break saving the session
+                logger.warning(f"Session listener failed on {event}: {e}")
     
-    def _save_users(self, users: Dict):
-        """Save users to JSON file."""
-        with open(self.users_file, "w", encoding="utf-8") as f:
-            json.dump(users, f, indent=4, ensure_ascii=False)
-
     def create_user(self, email: str, password: str, ip_address: str, device_info: str) -> bool:
         """Create a new user account."""
-        users = self._load_users()
-        
-        if email in users:
-            return False
-        
-        users[email] = {
+        return self.users.create(email, {
             "email": email,
-            "password_hash": generate_password_hash(password),
+            "password_hash": hash_password(password),
             "created_at": datetime.now().isoformat(),
             "ip_address": ip_address,
             "device_info": device_info,
+            "status": "active",
             "sessions": []
-        }
-        
-        self._save_users(users)
-        return True
+        })
     
     def authenticate_user(self, email: str, password: str) -> bool:
-        """Authenticate a user with email and password."""
-        users = self._load_users()
+        """
+        Authenticate a user with email and password. A correct password stored under an older
+        hash (lib/Passwords.py) is rehashed with argon2id on the way.
+        """
+        user = self.users.get(email)
         
-        if email not in users:
+        if user is None:
             return False
         
-        return check_password_hash(users[email]["password_hash"], password)
+        matches, needs_rehash = verify_password(user.get("password_hash"), password)
+        if matches and needs_rehash and not self.read_only:
+            old_hash = user["password_hash"]
+            new_hash = hash_password(password)
+
+            def upgrade(record):
+                # Unless the password was changed in the meantime
+                if record.get("password_hash") == old_hash:
+                    record["password_hash"] = new_hash
+
+            try:
+                self.users.update(email, upgrade)
+            except OSError as e:
+                # The login still counts; it's tried again next time
+                logger.warning(f"Could not upgrade the password hash of {email}: {e}")
+        return matches
+    
+    def set_password(self, email: str, password: str) -> bool:
+        """
+        Replace a user's password, signing them out everywhere (their session tokens carry the
+        old token_version, lib/SessionTokens.py). Returns False if there's no such user.
+        """
+        password_hash = hash_password(password)
+
+        def change(user):
+            user["password_hash"] = password_hash
+            user["token_version"] = user.get("token_version", 0) + 1
+
+        return self.users.update(email, change) is not None
+
+    @staticmethod
+    def token_version(user: Optional[Dict]) -> int:
+        """Session tokens issued with a lower version than the account's have been revoked."""
+        return (user or {}).get("token_version", 0)
+    
+    @staticmethod
+    def user_status(user: Optional[Dict]) -> str:
+        """"active", "suspended", or "banned" (records from before statuses only have "suspended")."""
+        if not user:
+            return "active"
+        return user.get("status") or ("suspended" if user.get("suspended") else "active")
+
+    def set_status(self, email: str, status: str, reason: str = "", changed_by: Optional[str] = None) -> Optional[str]:
+        """
+        Change an account's status. Suspended and banned accounts keep why, when, and by whom
+        under "suspended"; going back to active clears it.
+
+        Returns:
+            The previous status, or None if there's no such user
+        """
+        if status not in USER_STATUSES:
+            raise ValueError(f"Unknown user status: {status}")
+        previous = []
+
+        def change(user):
+            previous.append(self.user_status(user))
+            if status == "active":
+                user.pop("suspended", None)
+            else:
+                user["suspended"] = {
+                    "reason": reason,
+                    "suspended_at": datetime.now().isoformat(),
+                    "suspended_by": changed_by
+                }
+            user["status"] = status
+
+        self.users.update(email, change)
+        return previous[0] if previous else None
+
+    def suspend_user(self, email: str, reason: str, suspended_by: Optional[str] = None) -> bool:
+        """Suspend an account pending admin review. Returns False if there's no such user."""
+        return self.set_status(email, "suspended", reason=reason, changed_by=suspended_by) is not None
+    
+    def unsuspend_user(self, email: str) -> bool:
+        """Lift a suspension or ban. Returns False if the user doesn't exist or was already active."""
+        return self.set_status(email, "active") not in (None, "active")
+    
+    def get_status(self, email: Optional[str]) -> str:
+        return self.user_status(self.get_user(email))
+
+    def is_suspended(self, email: Optional[str]) -> bool:
+        """True for suspended and banned accounts."""
+        return self.get_status(email) != "active"
+    
+    def get_user(self, email: Optional[str]) -> Optional[Dict]:
+        """Get a user's record, or None if there's no such user."""
+        return self.users.get(email)
+    
+    def get_preferences(self, email: Optional[str]) -> Dict:
+        """Get a user's saved preferences (empty for guests and unknown users)."""
+        user = self.get_user(email)
+        if not user:
+            return {}
+        return dict(user.get("preferences", {}))
+
+    def update_preferences(self, email: str, updates: Dict) -> Optional[Dict]:
+        """Merge updates into a user's preferences. A None value clears that preference."""
+        def merge(user):
+            preferences = user.setdefault("preferences", {})
+            for key, value in updates.items():
+                if value is None:
+                    preferences.pop(key, None)
+                else:
+                    preferences[key] = value
+
+        user = self.users.update(email, merge)
+        return dict(user["preferences"]) if user is not None else None
+
+    def is_admin(self, email: Optional[str]) -> bool:
+        """Check if a user is an admin (listed in ADMIN_EMAILS or has the admin role)."""
+        if not email:
+            return False
+        if email.lower() in self.admin_emails:
+            return True
+        return (self.users.get(email) or {}).get("role") == "admin"
     
     def _is_valid_session_id(self, session_id: str) -> bool:
         """Validate that session_id is safe to use in file paths."""
@@ -80,15 +259,20 @@ class SessionManager:
     
     def get_user_sessions(self, email: str) -> List[str]:
         """Get all session IDs for a user."""
-        users = self._load_users()
+        user = self.users.get(email)
         
-        if email not in users:
+        if user is None:
             return []
         
-        return users[email].get("sessions", [])
+        return user.get("sessions", [])
     
-    def create_session(self, user_email: Optional[str] = None) -> str:
-        """Create a new chat session with a unique ID."""
+    def create_session(self, user_email: Optional[str] = None, model: Optional[str] = None,
+                       style: Optional[str] = None) -> str:
+        """
+        Create a new chat session with a unique ID. model, if given, answers every question in it;
+        style is the session's default answer style (lib/AnswerStyle.py).
+        """
+        self._check_writable()
         session_id = secrets.token_urlsafe(32)
         
         session_data = {
@@ -97,26 +281,25 @@ class SessionManager:
             "created_at": datetime.now().isoformat(),
             "messages": []
         }
+        if model:
+            session_data["model"] = model
+        if style:
+            session_data["style"] = style
         
-        session_file = os.path.join(self.sessions_dir, f"{session_id}.json")
-        with open(session_file, "w", encoding="utf-8") as f:
-            json.dump(session_data, f, indent=4, ensure_ascii=False)
+        session_file= os.path.join(self.sessions_dir, f"{session_id}.json")
+        _write_json(session_file, session_data)
         
         # Add session to user's session list if user is logged in
         if user_email:
-            users = self._load_users()
-            if user_email in users:
-                if "sessions" not in users[user_email]:
-                    users[user_email]["sessions"] = []
-                users[user_email]["sessions"].append(session_id)
-                self._save_users(users)
+            self.users.update(user_email, lambda user: user.setdefault("sessions", []).append(session_id))
         
+        self._emit("created", session_id, user_email)
         return session_id
     
     def get_session(self, session_id: str) -> Optional[Dict]:
         """Load a session from file."""
         if not self._is_valid_session_id(session_id):
-            print(f"Warning: invalid session_id format: {session_id}")
+            logger.warning(f"Invalid session_id format: {session_id}")
             return None
         
         session_file = os.path.join(self.sessions_dir, f"{session_id}.json")
@@ -126,24 +309,85 @@ class SessionManager:
         
         try:
             with open(session_file, "r", encoding="utf-8") as f:
-                return json.load(f)
+                session_data = json.load(f)
         except FileNotFoundError:
             return None
-        except json.JSONDecodeError as e:
-            print(f"Warning: session {session_id} is corrupted: {e}")
+        except (json.JSONDecodeError, UnicodeDecodeError) as e:
+            session_data = self._recover_session(session_id, session_file, str(e))
+        else:
+            if not isinstance(session_data, dict):
+                session_data = self._recover_session(session_id, session_file, "not a JSON object")
+        if session_data is None:
             return None
+        
+        # Messages saved before message IDs existed get a stable ID based on their position
+        for index, message in enumerate(session_data.get("messages", [])):
+            if "message_id" not in message:
+                message["message_id"] = f"legacy-{index}"
+        
+        return session_data
+    
+    def _recover_session(self, session_id: str, session_file: str, error: str, force: bool = False) -> Optional[Dict]:
+        """
+        Quarantine a corrupt session file and put back a session with whatever messages could be salvaged.
+        force is for files that parse but don't pass validation (see lib/DataIntegrity.py).
+        """
+        if self.read_only:
+            # The primary recovers it the next time it reads the file
+            logger.warning(f"Session {session_id} is corrupted ({error}), leaving it to the primary")
+            return None
+        logger.warning(f"Session {session_id} is corrupted ({error}), quarantining it")
+        incident = self.quarantine.quarantine(session_file, session_id, error, force=force)
+        if incident is None:
+            # Another request got there first; use its repaired file if there is one
+            try:
+                with open(session_file, "r", encoding="utf-8") as f:
+                    session_data = json.load(f)
+                return session_data if isinstance(session_data, dict) else None
+            except (FileNotFoundError, json.JSONDecodeError, UnicodeDecodeError):
+                return None
+        
+        salvaged = incident["salvaged"]
+        # The account records are the authority on ownership; the file's own user_email may be what got damaged
+        owner = next(
+            (user["email"] for user in self.users.all() if session_id in user.get("sessions", [])),
+            salvaged["user_email"]
+        )
+        session_data = {
+            "session_id": session_id,
+            "user_email": owner,
+            "created_at": salvaged["created_at"] or datetime.now().isoformat(),
+            "messages": salvaged["messages"],
+            "recovered": {
+                "recovered_at": incident["timestamp"],
+                "quarantined_as": incident["quarantined_as"],
+                "messages_recovered": incident["messages_recovered"]
+            }
+        }
+        self.save_session(session_id, session_data)
+        logger.warning(f"Recovered {incident['messages_recovered']} messages of session {session_id}, "
+                       f"original moved to {QUARANTINE_DIR}/{incident['quarantined_as']}")
+        self._emit("updated", session_id, owner)
+        return session_data
     
     def save_session(self, session_id: str, session_data: Dict):
         """Save session data to file."""
+        self._check_writable()
         if not self._is_valid_session_id(session_id):
             raise ValueError(f"Invalid session_id format: {session_id}")
         
         session_file = os.path.join(self.sessions_dir, f"{session_id}.json")
-        with open(session_file, "w", encoding="utf-8") as f:
-            json.dump(session_data, f, indent=4, ensure_ascii=False)
+        _write_json(session_file, session_data)
     
-    def add_message(self, session_id: str, role: str, content: str):
-        """Add a message to a session."""
+    @_locked
+    def add_message(self, session_id: str, role: str, content: str, citations: Optional[List[Dict]] = None,
+                    request_id: Optional[str] = None, interrupted: bool = False, partial: bool = False):
+        """
+        Add a message to a session. citations are the knowledge base pages an answer was based on;
+        request_id ties an answer to its analytics record, so feedback on it can be counted there.
+        interrupted marks an answer cut short because the client disconnected mid-stream; partial
+        marks any answer that stopped before it was done (a disconnect or a failed generation).
+        """
         session_data = self.get_session(session_id)
         
         if session_data is None:
@@ -156,27 +400,360 @@ class SessionManager:
             }
         
         message = {
+            "message_id": uuid.uuid4().hex,
             "role": role,
             "content": content,
             "timestamp": datetime.now().isoformat()
         }
+        if citations:
+            message["citations"] = citations
+        if request_id:
+            message["request_id"] = request_id
+        if interrupted:
+            message["interrupted"] = True
+        if partial or interrupted:
+            message["partial"] = True
         
         session_data["messages"].append(message)
         self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
     
-    def get_conversation_history(self, session_id: str) -> List[Dict]:
-        """Get conversation history for a session."""
+    @_locked
+    def set_style(self, session_id: str, style: Optional[str]) -> bool:
+        """Set (or with None, clear) a session's default answer style. Returns False if the session is gone."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return False
+        if style:
+            session_data["style"] = style
+        else:
+            session_data.pop("style", None)
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+        return True
+
+    @_locked
+    def rename_session(self, session_id: str, title: Optional[str]) -> bool:
+        """
+        Set the title the user gave a session, or with None, clear it (the chat list goes back to
+        the first question, and a new title isn't generated). Returns False if the session is gone.
+        """
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return False
+        session_data["title"] = title or None
+        session_data["title_source"] = "user"
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+        return True
+
+    @_locked
+    def set_generated_title(self, session_id: str, title: str) -> bool:
+        """Store a generated title (lib/SessionSummary.py), unless the session already has a title or was renamed."""
+        session_data = self.get_session(session_id)
+        if session_data is None or session_data.get("title_source"):
+            return False
+        session_data["title"] = title
+        session_data["title_source"] = "generated"
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+        return True
+
+    @_locked
+    def set_review(self, session_id: str, review: Optional[Dict]) -> bool:
+        """Tag (or with None, untag) a session as needing a human to look at it. Returns False if the session is gone."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return False
+        if review:
+            session_data["review"] = review
+        else:
+            session_data.pop("review", None)
+        self.save_session(session_id, session_data)
+        return True
+
+    def accept_terms(self, version: str, user_email: Optional[str] = None, session_id: Optional[str] = None) -> Optional[Dict]:
+        """
+        Record that a user (or, for guests, a session) accepted a version of the terms.
+
+        Returns:
+            {"version", "accepted_at"}, or None if the user or session doesn't exist
+        """
+        accepted = {"version": version, "accepted_at": datetime.now().isoformat()}
+        if user_email:
+            if self.users.update(user_email, lambda user: user.update(terms_accepted=accepted)) is None:
+                return None
+            return accepted
+        if not session_id:
+            return None
+        with self._lock_for(session_id):
+            session_data = self.get_session(session_id)
+            if session_data is None:
+                return None
+            session_data["terms_accepted"] = accepted
+            self.save_session(session_id, session_data)
+        return accepted
+
+    def get_terms_acceptance(self, user_email: Optional[str] = None, session_id: Optional[str] = None) -> Optional[Dict]:
+        """The last terms acceptance of a user (or a guest's session), or None."""
+        if user_email:
+            return (self.get_user(user_email) or {}).get("terms_accepted")
+        session_data = self.get_session(session_id) if session_id else None
+        return (session_data or {}).get("terms_accepted")
+
+    @_locked
+    def set_summary(self, session_id: str, summary: Dict):
+        """Cache a conversation summary on a session (see lib/SessionSummary.py)."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return
+        session_data["summary"] = summary
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+
+    @_locked
+    def set_history_summary(self, session_id: str, summary: Dict) -> bool:
+        """Save a session's rolling history summary (see lib/SessionSummary.py). Returns False if the session is gone."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return False
+        session_data["history_summary"] = summary
+        self.save_session(session_id, session_data)
+        return True
+
+    @_locked
+    def set_message_feedback(self, session_id: str, message_id: str, feedback: Optional[Dict]) -> Optional[Dict]:
+        """Store (or with None, clear) the user's rating of an answer. Returns the message, or None if it's gone."""
         session_data = self.get_session(session_id)
+        if session_data is None:
+            return None
+        for message in session_data.get("messages", []):
+            if message.get("message_id") == message_id:
+                if feedback is None:
+                    message.pop("feedback", None)
+                else:
+                    message["feedback"] = feedback
+                self.save_session(session_id, session_data)
+                self._emit("updated", session_id, session_data.get("user_email"))
+                return message
+        return None
+
+    @_locked
+    def set_message_pinned(self, session_id: str, message_id: str, pinned: bool) -> Optional[Dict]:
+        """Pin a message so it's always in the prompt, or unpin it. Returns the message, or None if it's gone."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return None
+        for message in session_data.get("messages", []):
+            if message.get("message_id") == message_id:
+                if pinned:
+                    message["pinned"] = True
+                else:
+                    message.pop("pinned", None)
+                self.save_session(session_id, session_data)
+                self._emit("updated", session_id, session_data.get("user_email"))
+                return message
+        return None
+
+    def get_pinned_messages(self, session_id: str) -> List[Dict]:
+        """A session's pinned messages, oldest first."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return []
+        return [message for message in session_data.get("messages", []) if message.get("pinned")]
+
+    def get_message(self, session_id: str, message_id: str) -> Optional[Dict]:
+        """Find a single message in a session by its ID."""
+        session_data = self.get_session(session_id)
+        
+        if session_data is None:
+            return None
+        
+        for message in session_data.get("messages", []):
+            if message.get("message_id") == message_id:
+                return message
+        return None
+    
+    @_locked
+    def truncate_at_message(self, session_id: str, message_id: str) -> bool:
+        """
+        Remove a message and everything after it from a session.
+        The removed messages are kept in the session's "branches" list so edits don't lose history.
+        """
+        session_data = self.get_session(session_id)
+        
+        if session_data is None:
+            return False
         
+        messages = session_data.get("messages", [])
+        for index, message in enumerate(messages):
+            if message.get("message_id") == message_id:
+                session_data.setdefault("branches", []).append({
+                    "branched_at": datetime.now().isoformat(),
+                    "from_message_id": message_id,
+                    "messages": messages[index:]
+                })
+                session_data["messages"] = messages[:index]
+                # The cached summary covered the messages just removed
+                session_data.pop("summary", None)
+                self.save_session(session_id, session_data)
+                self._emit("updated", session_id, session_data.get("user_email"))
+                return True
+        return False
+    
+    @staticmethod
+    def _checkpoint_info(checkpoint: Dict) -> Dict:
+        """A checkpoint without its copy of the messages."""
+        return {key: value for key, value in checkpoint.items() if key != "messages"}
+
+    @_locked
+    def create_checkpoint(self, session_id: str, label: Optional[str] = None) -> Optional[Dict]:
+        """
+        Snapshot a session's messages so the conversation can be rolled back to this point later.
+
+        Returns:
+            The checkpoint (without the copied messages), or None if the session doesn't exist
+        """
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return None
+
+        messages = session_data.get("messages", [])
+        checkpoint = {
+            "checkpoint_id": uuid.uuid4().hex,
+            "label": label,
+            "created_at": datetime.now().isoformat(),
+            "message_count": len(messages),
+            "last_message_id": messages[-1].get("message_id") if messages else None,
+            "messages": copy.deepcopy(messages)
+        }
+        session_data.setdefault("checkpoints", []).append(checkpoint)
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+        return self._checkpoint_info(checkpoint)
+
+    def list_checkpoints(self, session_id: str) -> List[Dict]:
+        """A session's checkpoints, oldest first, without the copied messages."""
+        session_data = self.get_session(session_id)
         if session_data is None:
             return []
+        return [self._checkpoint_info(checkpoint) for checkpoint in session_data.get("checkpoints", [])]
+
+    @_locked
+    def restore_checkpoint(self, session_id: str, checkpoint_id: str) -> Optional[Dict]:
+        """
+        Put a session's messages back the way they were at a checkpoint. The checkpoint stays,
+        so it can be restored again, and the messages being replaced are kept in "branches"
+        like an edit's are.
+
+        Returns:
+            The restored checkpoint (without the copied messages), or None if there's no such checkpoint
+        """
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return None
+
+        for checkpoint in session_data.get("checkpoints", []):
+            if checkpoint.get("checkpoint_id") == checkpoint_id:
+                current = session_data.get("messages", [])
+                saved = checkpoint["messages"]
+                # Only the messages after the point where the two diverge are being replaced
+                index = 0
+                while index < min(len(current), len(saved)) and current[index] == saved[index]:
+                    index += 1
+                if index < len(current):
+                    session_data.setdefault("branches", []).append({
+                        "branched_at": datetime.now().isoformat(),
+                        "from_message_id": current[index].get("message_id"),
+                        "restored_checkpoint_id": checkpoint_id,
+                        "messages": current[index:]
+                    })
+                session_data["messages"] = copy.deepcopy(checkpoint["messages"])
+                # The cached summary described the conversation being rolled back
+                session_data.pop("summary", None)
+                self.save_session(session_id, session_data)
+                self._emit("updated", session_id, session_data.get("user_email"))
+                return self._checkpoint_info(checkpoint)
+        return None
+
+    @_locked
+    def delete_checkpoint(self, session_id: str, checkpoint_id: str) -> bool:
+        """Remove a checkpoint. Returns False if there's no such checkpoint."""
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return False
+
+        checkpoints = session_data.get("checkpoints", [])
+        remaining = [checkpoint for checkpoint in checkpoints if checkpoint.get("checkpoint_id") != checkpoint_id]
+        if len(remaining) == len(checkpoints):
+            return False
+        session_data["checkpoints"] = remaining
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, session_data.get("user_email"))
+        return True
+
+    def get_messages_page(self, session_id: str, before: Optional[str] = None, limit: int = 10) -> Dict:
+        """
+        Get a page of messages, newest page first, for lazy-loading history.
+        
+        Args:
+            session_id: Session to read from
+            before: Only return messages older than this message_id (None = start from the newest)
+            limit: Maximum number of messages to return
+        
+        Returns:
+            Dict with the messages (oldest first) and whether there are older ones left
+        """
+        session_data = self.get_session(session_id)
+        
+        if session_data is None:
+            return {"messages": [], "has_more": False}
+        
+        messages = session_data.get("messages", [])
+        end = len(messages)
+        if before is not None:
+            end = next(
+                (index for index, message in enumerate(messages) if message.get("message_id") == before),
+                None
+            )
+            if end is None:
+                return {"messages": [], "has_more": False}
         
-        return session_data.get("messages", [])[:10]
+        start = max(0, end - max(limit, 0))
+        return {"messages": messages[start:end], "has_more": start > 0}
+    
+    def get_conversation_history(self, session_id: str) -> List[Dict]:
+        """Get the most recent conversation history for a session (see get_conversation_context)."""
+        return self.get_conversation_context(session_id)[1]
+    
+    def get_conversation_context(self, session_id: str) -> Tuple[Optional[str], List[Dict]]:
+        """
+        What the model is told of a session before a question: its history summary, if it has one
+        (lib/SessionSummary.py), and the messages after what that covers, or without one, the last
+        limits.history_messages messages.
+        
+        Returns:
+            (summary text or None, messages oldest first)
+        """
+        session_data = self.get_session(session_id)
+        if session_data is None:
+            return None, []
+        messages = session_data.get("messages", [])
+        start = max(0, len(messages) - self.history_messages)
+        covered = history_summary_coverage(session_data)
+        if not covered:
+            return None, messages[start:]
+        # A summary that's fallen far behind (its updates keep failing) doesn't pull in everything since
+        if start - covered > self.history_messages:
+            return session_data["history_summary"]["text"], messages[start:]
+        return session_data["history_summary"]["text"], messages[min(start, covered):]
     
+    @_locked
     def delete_session(self, session_id: str, user_email: Optional[str] = None) -> bool:
         """Delete a chat session."""
+        self._check_writable()
         if not self._is_valid_session_id(session_id):
-            print(f"Warning: invalid session_id format: {session_id}")
+            logger.warning(f"Invalid session_id format: {session_id}")
             return False
         
         session_file = os.path.join(self.sessions_dir, f"{session_id}.json")
@@ -188,15 +765,129 @@ class SessionManager:
         #At the time i wrote this i wasnt sure if i would be allowing guest sessions or not
         #For the sake of time (and my sanity) i am keeping this in
         if user_email:
-            users = self._load_users()
-            if user_email in users and "sessions" in users[user_email]:
-                if session_id in users[user_email]["sessions"]:
-                    users[user_email]["sessions"].remove(session_id)
-                    self._save_users(users)
+            self.users.update(user_email, lambda user: _remove_sessions(user, {session_id}))
         
         # Delete the session file
         os.remove(session_file)
+        self._forget_lock(session_id)
+        self._emit("deleted", session_id, user_email)
         return True
+
+    @staticmethod
+    def _exchanges(messages: List[Dict]) -> List[List[Dict]]:
+        """Split messages into exchanges: a user message with the answers after it."""
+        exchanges = []
+        for message in messages:
+            if message.get("role") == "user" or not exchanges:
+                exchanges.append([])
+            exchanges[-1].append(message)
+        return exchanges
+
+    def merge_sessions(self, session_ids: List[str], user_email: Optional[str] = None) -> Optional[Dict]:
+        """
+        Combine sessions into a new one, exchanges interleaved by when they were asked. Each copied
+        message gets "merged_from" with the session it came from, and the new session lists its
+        sources under "merged_from". The originals are left alone; checkpoints, branches, and
+        summaries aren't carried over. The model and style come from the first session that has one.
+
+        Returns:
+            The new session, or None if one of the sessions doesn't exist
+        """
+        sources = [self.get_session(session_id) for session_id in session_ids]
+        if any(source is None for source in sources):
+            return None
+
+        exchanges = []
+        for position, source in enumerate(sources):
+            for exchange in self._exchanges(source.get("messages", [])):
+                copied = [dict(copy.deepcopy(message), merged_from=source["session_id"]) for message in exchange]
+                exchanges.append((exchange[0].get("timestamp") or source.get("created_at") or "", position, copied))
+        # Stable on ties, so an exchange from the first session goes first
+        exchanges.sort(key=lambda item: (item[0], item[1]))
+        messages = [message for _, _, exchange in exchanges for message in exchange]
+
+        # Keep the pin limit, favouring the most recent pins
+        pinned = [message for message in messages if message.get("pinned")]
+        for message in pinned[:-MAX_PINNED]:
+            message.pop("pinned")
+
+        model = next((source["model"] for source in sources if source.get("model")), None)
+        style = next((source["style"] for source in sources if source.get("style")), None)
+        session_id = self.create_session(user_email=user_email, model=model, style=style)
+        session_data = self.get_session(session_id)
+        session_data["messages"] = messages
+        session_data["merged_from"] = [
+            {"session_id": source["session_id"], "created_at": source.get("created_at"),
+             "message_count": len(source.get("messages", []))}
+            for source in sources
+        ]
+        self.save_session(session_id, session_data)
+        self._emit("updated", session_id, user_email)
+        return session_data
+    
+    def purge_sessions(
+        self,
+        older_than: Optional[datetime] = None,
+        user_email: Optional[str] = None,
+        guests_only: bool = False,
+        dry_run: bool = False
+    ) -> List[str]:
+        """
+        Delete sessions in bulk.
+        
+        Args:
+            older_than: Only sessions whose last activity is before this time
+            user_email: Only this user's sessions
+            guests_only: Only sessions that don't belong to an account
+            dry_run: Report what would be deleted without deleting anything
+        
+        Returns:
+            IDs of the purged (or, with dry_run, matching) sessions
+        """
+        matched = []
+        for filename in sorted(os.listdir(self.sessions_dir)):
+            if not filename.endswith(".json"):
+                continue
+            session_data = self.get_session(filename[:-len(".json")])
+            if session_data is None:
+                continue
+            
+            owner = session_data.get("user_email")
+            if guests_only and owner:
+                continue
+            if user_email is not None and owner != user_email:
+                continue
+            if older_than is not None:
+                messages = session_data.get("messages", [])
+                last_active = messages[-1].get("timestamp") if messages else session_data.get("created_at")
+                try:
+                    if last_active and datetime.fromisoformat(last_active) >= older_than:
+                        continue
+                except ValueError:
+                    pass
+            matched.append((session_data["session_id"], owner))
+        
+        if dry_run or not matched:
+            return [session_id for session_id, _ in matched]
+        
+        self._check_writable()
+        by_owner: Dict[str, set] = {}
+        for session_id, owner in matched:
+            with self._lock_for(session_id):
+                try:
+                    os.remove(os.path.join(self.sessions_dir, f"{session_id}.json"))
+                except FileNotFoundError:
+                    # Deleted by a request while this was running
+                    pass
+            self._forget_lock(session_id)
+            if owner:
+                by_owner.setdefault(owner, set()).add(session_id)
+        for owner, session_ids in by_owner.items():
+            self.users.update(owner, lambda user: _remove_sessions(user, session_ids))
+        
+        for session_id, owner in matched:
+            self._emit("deleted", session_id, owner)
+        return [session_id for session_id, _ in matched]
     
     def get_all_user_sessions_with_preview(self, email: str) -> List[Dict]:
         """Get all sessions for a user with message preview."""
@@ -215,11 +906,106 @@ class SessionManager:
                             preview = msg["content"][:100]
                             break
                 
+                summary = session_data.get("summary") or {}
                 sessions.append({
                     "session_id": session_id,
                     "created_at": session_data.get("created_at"),
+                    "title": session_data.get("title"),
                     "preview": preview,
+                    # The last generated summary, even if messages were added since
+                    "summary": summary.get("text"),
                     "message_count": len(messages)
                 })
         
         return sessions
+
+    def _user_summary(self, user: Dict) -> Dict:
+        """The account fields admins see (password hashes are never included)."""
+        email = user["email"]
+        return {
+            "email": email,
+            "created_at": user.get("created_at"),
+            "role": user.get("role", "user"),
+            "is_admin": email.lower() in self.admin_emails or user.get("role") == "admin",
+            "status": self.user_status(user),
+            "suspended": user.get("suspended"),
+            "session_count": len(user.get("sessions", []))
+        }
+
+    def list_users(self) -> List[Dict]:
+        """List all users with their session counts, newest first."""
+        result = [self._user_summary(user) for user in self.users.all()]
+        result.sort(key=lambda u: u["created_at"] or "", reverse=True)
+        return result
+
+    def _session_activity(self, session_id: str) -> Optional[Dict]:
+        """Message counts and last activity (the newest message, or creation if it has none) of a session."""
+        session_data = self.get_session(session_id)
+        if not session_data:
+            return None
+        messages = session_data.get("messages", [])
+        timestamps = [m["timestamp"] for m in messages if m.get("timestamp")]
+        return {
+            "session_id": session_id,
+            "created_at": session_data.get("created_at"),
+            "message_count": len(messages),
+            "question_count": sum(1 for m in messages if m.get("role") == "user"),
+            "last_active_at": max(timestamps) if timestamps else session_data.get("created_at")
+        }
+
+    def _last_active(self, activity: List[Dict]) -> Optional[str]:
+        return max((a["last_active_at"] for a in activity if a["last_active_at"]), default=None)
+
+    def search_users(self, query: Optional[str] = None, offset: int = 0, limit: int = 50) -> Dict:
+        """
+        One page of users, newest first, optionally only those whose email contains query
+        (case-insensitive). Last activity is looked up for the users on the page only, since
+        it means opening their session files.
+
+        Returns:
+            {"total": users matching, "users": the page, each with last_active_at}
+        """
+        needle = (query or "").strip().lower()
+        matched = [user for user in self.users.all() if needle in user["email"].lower()]
+        matched.sort(key=lambda u: u.get("created_at") or "", reverse=True)
+        page = []
+        for user in matched[offset:offset + limit]:
+            activity = [a for a in map(self._session_activity, user.get("sessions", [])) if a]
+            page.append({**self._user_summary(user), "last_active_at": self._last_active(activity)})
+        return {"total": len(matched), "users": page}
+
+    def get_user_detail(self, email: str) -> Optional[Dict]:
+        """
+        An account with its sessions (newest activity first), message and question totals, and
+        last activity, or None if there's no such user.
+        """
+        user = self.users.get(email)
+        if user is None:
+            return None
+        activity = [a for a in map(self._session_activity, user.get("sessions", [])) if a]
+        activity.sort(key=lambda a: a["last_active_at"] or "", reverse=True)
+        return {
+            **self._user_summary(user),
+            "preferences": dict(user.get("preferences", {})),
+            "last_active_at": self._last_active(activity),
+            "message_count": sum(a["message_count"] for a in activity),
+            "question_count": sum(a["question_count"] for a in activity),
+            "sessions": activity
+        }
+    
+    def delete_user(self, email: str) -> bool:
+        """Delete a user account and all of their sessions."""
+        self._check_writable()
+        user = self.users.get(email)
+        
+        if user is None:
+            return False
+        
+        for session_id in user.get("sessions", []):
+            if self._is_valid_session_id(session_id):
+                session_file = os.path.join(self.sessions_dir, f"{session_id}.json")
+                if os.path.exists(session_file):
+                    os.remove(session_file)
+                    self._emit("deleted", session_id, email)
+        
+        return self.users.delete(email)
@@ -0,0 +1,122 @@
+"""
+Recovery for corrupted session files.
+A session file that isn't valid JSON (usually one cut off by a crash or a full disk in the
+middle of a save) used to make the chat history silently disappear. Instead, SessionManager
+moves the file to data/quarantine/ so nothing is lost, salvages every complete message it
+can from it, and logs an incident to data/quarantine/incidents.jsonl for the admin API.
+"""
+import os
+import re
+import json
+import threading
+from datetime import datetime
+from typing import Dict, List, Optional
+
+QUARANTINE_DIR = "quarantine"
+INCIDENTS_FILE = "incidents.jsonl"
+
+_MESSAGES_RE = re.compile(r'"messages"\s*:\s*\[')
+_HEADER_RE = re.compile(r'"(session_id|user_email|created_at)"\s*:\s*("(?:[^"\\]|\\.)*"|null)')
+
+_lock = threading.Lock()
+
+
+def salvage_session(text: str) -> Dict:
+    """
+    Pull what can be read out of a broken session file: the header fields and every
+    message up to the first one that's cut off or malformed.
+
+    Returns:
+        {"session_id", "user_email", "created_at", "messages"}; fields that weren't found are None
+    """
+    salvaged: Dict = {"session_id": None, "user_email": None, "created_at": None, "messages": []}
+    for match in _HEADER_RE.finditer(text):
+        key = match.group(1)
+        if salvaged[key] is None:
+            try:
+                salvaged[key] = json.loads(match.group(2))
+            except json.JSONDecodeError:
+                pass
+
+    start = _MESSAGES_RE.search(text)
+    if start is None:
+        return salvaged
+    decoder = json.JSONDecoder()
+    position = start.end()
+    while True:
+        while position < len(text) and text[position] in " \t\r\n,":
+            position += 1
+        if position >= len(text) or text[position] == "]":
+            break
+        try:
+            message, position = decoder.raw_decode(text, position)
+        except json.JSONDecodeError:
+            break
+        if isinstance(message, dict) and isinstance(message.get("role"), str) and isinstance(message.get("content"), str):
+            salvaged["messages"].append(message)
+    return salvaged
+
+
+class SessionQuarantine:
+    """Moves corrupt session files aside and keeps a log of what happened to them."""
+
+    def __init__(self, data_dir: str = "data"):
+        self.directory = os.path.join(data_dir, QUARANTINE_DIR)
+        self.incidents_path = os.path.join(self.directory, INCIDENTS_FILE)
+
+    def quarantine(self, path: str, session_id: str, error: str, force: bool = False) -> Optional[Dict]:
+        """
+        Move a corrupt session file into the quarantine directory and salvage its contents.
+
+        Args:
+            force: Quarantine the file even though it parses (it failed validation instead)
+
+        Returns:
+            The incident (with the salvaged session under "salvaged"), or None if another
+            request already dealt with the file
+        """
+        with _lock:
+            try:
+                with open(path, "rb") as f:
+                    raw = f.read()
+            except FileNotFoundError:
+                return None
+            # Another request may have repaired it while we waited for the lock
+            try:
+                if not force and isinstance(json.loads(raw.decode("utf-8")), dict):
+                    return None
+            except (UnicodeDecodeError, json.JSONDecodeError):
+                pass
+
+            os.makedirs(self.directory, exist_ok=True)
+            stamp = datetime.now().strftime("%Y%m%dT%H%M%S%f")
+            quarantined_as = f"{session_id}.{stamp}.json"
+            os.replace(path, os.path.join(self.directory, quarantined_as))
+
+            salvaged = salvage_session(raw.decode("utf-8", errors="replace"))
+            incident = {
+                "timestamp": datetime.now().isoformat(),
+                "session_id": session_id,
+                "error": error,
+                "quarantined_as": quarantined_as,
+                "bytes": len(raw),
+                "messages_recovered": len(salvaged["messages"]),
+            }
+            with open(self.incidents_path, "a", encoding="utf-8") as f:
+                f.write(json.dumps(incident, ensure_ascii=False) + "\n")
+        return dict(incident, salvaged=salvaged)
+
+    def list_incidents(self, limit: int = 100) -> List[Dict]:
+        """Quarantine incidents, newest first."""
+        incidents = []
+        try:
+            with open(self.incidents_path, "r", encoding="utf-8") as f:
+                for line in f:
+                    try:
+                        incidents.append(json.loads(line))
+                    except json.JSONDecodeError:
+                        continue
+        except FileNotFoundError:
+            return []
+        incidents.reverse()
+        return incidents[:limit] if limit > 0 else incidents
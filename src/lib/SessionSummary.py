@@ -0,0 +1,183 @@
+"""
+Conversation summaries and titles for ArchieAI sessions.
+A short model-written summary of a chat, cached on the session file until new messages
+arrive. Used for previews in the session list and to stand in for older messages when
+a long conversation's history is compressed.
+Titles are a few words written once, after a chat's first exchange, so the chat list
+doesn't have to show the truncated first question; one the user set themselves is never
+replaced (SessionManager.rename_session).
+The history summary is a separate, rolling one for the model: only the last
+limits.history_messages messages go into the prompt, so once older ones pile up they're
+folded into it (with the summary so far), and the prompt gets the summary followed by
+the messages it doesn't cover yet. Unlike the chat-list summary it keeps the details a
+follow-up question might need.
+"""
+import re
+import asyncio
+from datetime import datetime
+from typing import Dict, List, Optional
+
+# Summaries longer than this are cut, in case the model ignores the length limit
+MAX_SUMMARY_CHARACTERS = 400
+MAX_HISTORY_SUMMARY_CHARACTERS = 1500
+# Each message is trimmed to this many characters in the prompt
+MAX_MESSAGE_CHARACTERS = 1000
+# Generated titles longer than this are cut (user-set ones are limited by validate_session_title)
+MAX_TITLE_CHARACTERS = 60
+_THINK_RE = re.compile(r"<think>.*?</think>", re.S)
+
+
+def clean_summary(output: str, max_characters: int = MAX_SUMMARY_CHARACTERS) -> str:
+    """The summary from the model's output: reasoning blocks, quotes, and extra whitespace removed."""
+    text = " ".join(_THINK_RE.sub("", output).split()).strip().strip('"').strip()
+    if len(text) > max_characters:
+        text = text[:max_characters].rsplit(" ", 1)[0] + "..."
+    return text
+
+
+def clean_title(output: str) -> str:
+    """The title from the model's output: reasoning, quotes, a "Title:" label, and trailing punctuation removed."""
+    text = " ".join(_THINK_RE.sub("", output).split()).strip()
+    text = re.sub(r"^title\s*:\s*", "", text, flags=re.I).strip().strip('"\'*').strip().rstrip(".!")
+    if len(text) > MAX_TITLE_CHARACTERS:
+        text = text[:MAX_TITLE_CHARACTERS].rsplit(" ", 1)[0]
+    return text
+
+
+def history_summary_coverage(session_data: Dict) -> int:
+    """
+    How many of a session's messages its history summary covers: 0 if it has none, or if
+    the messages it was written from have since changed (a resubmit, a checkpoint restore).
+    """
+    summary = session_data.get("history_summary")
+    messages = session_data.get("messages", [])
+    if not summary:
+        return 0
+    count = summary.get("message_count", 0)
+    if not 0 < count <= len(messages) or messages[count - 1].get("message_id") != summary.get("through_message_id"):
+        return 0
+    return count
+
+
+class SessionSummarizer:
+    """Writes and caches conversation summaries and titles."""
+
+    def __init__(self, ai, session_manager):
+        """
+        Args:
+            ai: AiInterface used to generate the summary
+            session_manager: Where the summary is cached
+        """
+        self.ai = ai
+        self.session_manager = session_manager
+
+    @staticmethod
+    def _conversation(messages: List[Dict]) -> str:
+        return "\n".join(
+            f"{message.get('role', 'user').upper()}: {message.get('content', '')[:MAX_MESSAGE_CHARACTERS]}"
+            for message in messages
+        )
+
+    async def _complete(self, prompt_name: str, messages: List[Dict], **fields) -> str:
+        prompt = self.ai.load_prompt(prompt_name).format(conversation=self._conversation(messages), **fields)
+        output = ""
+        async for token in self.ai.generate_text_streaming(prompt):
+            output += token
+        return output
+
+    async def generate(self, messages: List[Dict]) -> str:
+        """Ask the model to summarize a list of messages."""
+        return clean_summary(await self._complete("session_summary", messages))
+
+    async def generate_title(self, messages: List[Dict]) -> str:
+        """Ask the model for a short title for a list of messages."""
+        return clean_title(await self._complete("session_title", messages))
+
+    async def generate_history(self, previous: str, messages: List[Dict]) -> str:
+        """Ask the model to fold messages into the rolling history summary so far."""
+        return clean_summary(await self._complete("history_summary", messages, summary=previous),
+                             max_characters=MAX_HISTORY_SUMMARY_CHARACTERS)
+
+    def compress_history(self, session_id: str, keep: int, batch: int) -> Optional[Dict]:
+        """
+        Fold the messages that have dropped out of the last `keep` into the session's history
+        summary, once at least `batch` of them aren't covered yet.
+
+        Returns:
+            The new summary, or None if the session is gone or nothing was due
+
+        Raises:
+            Whatever the model call raises
+        """
+        session_data = self.session_manager.get_session(session_id)
+        if session_data is None:
+            return None
+        messages = session_data.get("messages", [])
+        older = len(messages) - keep
+        covered = history_summary_coverage(session_data)
+        # A summary of messages that have since been edited away starts over
+        previous = session_data["history_summary"]["text"] if covered else ""
+        if older - covered < batch:
+            return None
+
+        summary = {
+            "text": asyncio.run(self.generate_history(previous, messages[covered:older])),
+            "message_count": older,
+            "through_message_id": messages[older - 1].get("message_id"),
+            "generated_at": datetime.now().isoformat(),
+        }
+        if not summary["text"] or not self.session_manager.set_history_summary(session_id, summary):
+            return None
+        return summary
+
+    def title(self, session_id: str) -> Optional[str]:
+        """
+        Give a session a generated title if it has none yet (from its first exchange).
+
+        Returns:
+            The title, or None if the session is gone, has no messages, or already had one
+
+        Raises:
+            Whatever the model call raises
+        """
+        session_data = self.session_manager.get_session(session_id)
+        if session_data is None or session_data.get("title_source") or not session_data.get("messages"):
+            return None
+        title = asyncio.run(self.generate_title(session_data["messages"][:2]))
+        if not title or not self.session_manager.set_generated_title(session_id, title):
+            return None
+        return title
+
+    def summarize(self, session_id: str, refresh: bool = False) -> Optional[Dict]:
+        """
+        The session's summary, generated if there isn't one for its current messages.
+
+        Args:
+            refresh: Generate a new one even if the cached summary is current
+
+        Returns:
+            {"text", "message_count", "generated_at", "cached"}, or None for a session with no messages
+
+        Raises:
+            Whatever the model call raises, when a summary has to be generated
+        """
+        session_data = self.session_manager.get_session(session_id)
+        if session_data is None:
+            return None
+        messages = session_data.get("messages", [])
+        if not messages:
+            return None
+        cached = session_data.get("summary")
+        # The count alone can match a different conversation after an edit, so check the last message too
+        if (not refresh and cached and cached.get("message_count") == len(messages)
+                and cached.get("through_message_id") == messages[-1].get("message_id")):
+            return dict(cached, cached=True)
+
+        summary = {
+            "text": asyncio.run(self.generate(messages)),
+            "message_count": len(messages),
+            "through_message_id": messages[-1].get("message_id"),
+            "generated_at": datetime.now().isoformat(),
+        }
+        self.session_manager.set_summary(session_id, summary)
+        return dict(summary, cached=False)
@@ -0,0 +1,113 @@
+"""
+Signed session tokens for ArchieAI.
+The session cookie used to be the bare session ID, next to a user_email cookie that only
+the admin checks compared with the session file: setting user_email to someone else's
+address was enough to act as them everywhere else. Both now travel in one cookie, archie_session, holding {"sid", "email", "iat",
+"ver"} signed with HMAC-SHA256 under a server secret, so the browser can't change who it
+is. Tokens expire after cookies.session_max_age_days, and an account's tokens are revoked
+by bumping its token_version (done when its password changes). Cookies from before this
+(session_id + user_email) are accepted if the session file itself belongs to that email,
+and swapped for a signed token on the same response.
+"""
+import logging
+import os
+import hmac
+import json
+import time
+import base64
+import hashlib
+import secrets
+from typing import Callable, Dict, Optional
+
+logger = logging.getLogger(__name__)
+
+SESSION_COOKIE = "archie_session"
+SECRET_FILE = "session_secret"
+
+
+def _b64encode(data: bytes) -> str:
+    return base64.urlsafe_b64encode(data).rstrip(b"=").decode("ascii")
+
+
+def _b64decode(text: str) -> bytes:
+    return base64.urlsafe_b64decode(text + "=" * (-len(text) % 4))
+
+
+def load_secret(configured: str, data_dir: str, create: bool = True) -> bytes:
+    """
+    The signing key: cookies.secret if set, else the one kept in the data directory, made on
+    first start (instances sharing a data directory share it). Without create (a read-only
+    replica) a missing file gets a key for this process only, so its tokens don't outlive it.
+    """
+    if configured:
+        return configured.encode("utf-8")
+    path = os.path.join(data_dir, SECRET_FILE)
+    try:
+        with open(path, "r", encoding="utf-8") as f:
+            secret = f.read().strip()
+        if secret:
+            return secret.encode("utf-8")
+    except FileNotFoundError:
+        pass
+    secret = secrets.token_hex(32)
+    if not create:
+        logger.warning(f"{path} doesn't exist yet; session tokens from this instance won't work anywhere else")
+        return secret.encode("utf-8")
+    os.makedirs(data_dir, exist_ok=True)
+    # Readable by the server's user only
+    fd = os.open(path, os.O_WRONLY | os.O_CREAT | os.O_TRUNC, 0o600)
+    with os.fdopen(fd, "w", encoding="utf-8") as f:
+        f.write(secret)
+    return secret.encode("utf-8")
+
+
+class SessionTokens:
+    """Issues and checks session tokens."""
+
+    def __init__(self, secret: bytes, max_age_seconds: float = 30 * 86400):
+        self.secret = secret
+        self.max_age_seconds = max_age_seconds
+
+    def _sign(self, payload: str) -> str:
+        """Raises UnicodeEncodeError for a payload that isn't ASCII, which no issued token has."""
+        return _b64encode(hmac.new(self.secret, payload.encode("ascii"), hashlib.sha256).digest())
+
+    def issue(self, session_id: str, user_email: Optional[str] = None, version: int = 0) -> str:
+        """A token for a session, signed in as user_email (None for a guest or kiosk)."""
+        claims = {"sid": session_id, "email": user_email, "iat": int(time.time()), "ver": version}
+        payload = _b64encode(json.dumps(claims, separators=(",", ":")).encode("utf-8"))
+        return f"{payload}.{self._sign(payload)}"
+
+    def read(self, token: Optional[str], current_version: Optional[Callable[[str], int]] = None) -> Optional[Dict]:
+        """
+        The claims of a valid, unexpired token, or None for anything else: missing, malformed,
+        forged, expired, or revoked.
+
+        Args:
+            token: The archie_session cookie, exactly as the browser sent it
+            current_version: Looks up the token_version of a signed-in email; a token issued
+                under another version has been revoked
+        """
+        if not token or token.count(".") != 1:
+            return None
+        payload, signature = token.split(".")
+        try:
+            # compare_digest only takes ASCII strings, so compare bytes; a cookie with anything
+            # else in it can't be one we issued
+            if not hmac.compare_digest(signature.encode("ascii"), self._sign(payload).encode("ascii")):
+                return None
+        except UnicodeEncodeError:
+            return None
+        try:
+            claims = json.loads(_b64decode(payload))
+        except (ValueError, UnicodeDecodeError):
+            return None
+        if not isinstance(claims, dict) or not isinstance(claims.get("sid"), str):
+            return None
+        issued_at = claims.get("iat", 0)
+        if not isinstance(issued_at, (int, float)) or time.time() - issued_at > self.max_age_seconds:
+            return None
+        if claims.get("email") and current_version is not None and claims.get("ver", 0) != current_version(claims["email"]):
+            # Signed out everywhere since (the password was changed)
+            return None
+        return claims
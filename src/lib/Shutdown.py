@@ -0,0 +1,135 @@
+"""
+Graceful shutdown for ArchieAI.
+SIGTERM (systemd, Docker) or Ctrl+C used to kill the server wherever it was: halfway through
+saving a session or the analytics log, or in the middle of streaming an answer. Now the first
+signal starts draining:
+
+1. New requests get a 503 (and /health reports "draining", so the load balancer moves on)
+2. Open event streams (session updates, the admin live feed) are told to reconnect elsewhere
+3. Requests already running, answers included, get up to server.drain_seconds to finish
+4. Answers still generating after that are stopped, which saves what they had so far
+5. The server stops, pending writes are finished, and the data directory's lock is released
+
+A second signal skips the wait and exits right away.
+"""
+import logging
+import os
+import signal
+import threading
+import time
+from typing import Callable, List, Tuple
+
+logger = logging.getLogger(__name__)
+
+# How long stopped answers get to save what they have before the server goes down anyway
+STOP_GRACE_SECONDS = 5.0
+
+
+class ShutdownCoordinator:
+    """Counts requests in flight and runs the shutdown steps once a signal arrives."""
+
+    def __init__(self, drain_seconds: float = 30.0):
+        self.drain_seconds = drain_seconds
+        self._draining = threading.Event()
+        # Set once the wait is over and whatever is still running should wrap up now
+        self._stopping = threading.Event()
+        self._cond = threading.Condition()
+        self._in_flight = 0
+        # (name, callback), run in the order they were added
+        self._on_drain: List[Tuple[str, Callable[[], None]]] = []
+        self._on_stop: List[Tuple[str, Callable[[], None]]] = []
+        self._on_close: List[Tuple[str, Callable[[], None]]] = []
+
+    @property
+    def draining(self) -> bool:
+        return self._draining.is_set()
+
+    @property
+    def stopping(self) -> bool:
+        return self._stopping.is_set()
+
+    def in_flight(self) -> int:
+        with self._cond:
+            return self._in_flight
+
+    def request_started(self):
+        with self._cond:
+            self._in_flight += 1
+
+    def request_finished(self):
+        with self._cond:
+            self._in_flight = max(0, self._in_flight - 1)
+            self._cond.notify_all()
+
+    def on_drain(self, name: str, callback: Callable[[], None]):
+        """Run callback as soon as draining starts (ending long-lived streams, say)."""
+        self._on_drain.append((name, callback))
+
+    def on_stop(self, name: str, callback: Callable[[], None]):
+        """Run callback if requests are still running after drain_seconds (stopping answers, say)."""
+        self._on_stop.append((name, callback))
+
+    def on_close(self, name: str, callback: Callable[[], None]):
+        """Run callback once the requests are done or have been stopped, before the server exits."""
+        self._on_close.append((name, callback))
+
+    @staticmethod
+    def _run(callbacks: List[Tuple[str, Callable[[], None]]]):
+        for name, callback in callbacks:
+            try:
+                callback()
+            except Exception as e:
+                # Keep going: the rest of the shutdown matters more than any one step
+                logger.warning(f"Shutdown step {name} failed: {e}")
+
+    def wait_for_requests(self, timeout: float) -> bool:
+        """Wait until no requests are running; False if some still are after timeout seconds."""
+        deadline = time.monotonic() + timeout
+        with self._cond:
+            while self._in_flight > 0:
+                remaining = deadline - time.monotonic()
+                if remaining <= 0:
+                    return False
+                self._cond.wait(timeout=remaining)
+        return True
+
+    def drain(self) -> bool:
+        """
+        Stop taking requests and let the running ones finish, then run the close steps.
+
+        Returns:
+            True if every request finished within drain_seconds
+        """
+        self._draining.set()
+        self._run(self._on_drain)
+        running = self.in_flight()
+        if running:
+            logger.info(f"Waiting up to {self.drain_seconds:g} seconds for {running} request(s) to finish")
+        finished = self.wait_for_requests(self.drain_seconds)
+        if not finished:
+            logger.warning(f"{self.in_flight()} request(s) still running after {self.drain_seconds:g} seconds, stopping them")
+            self._stopping.set()
+            self._run(self._on_stop)
+            self.wait_for_requests(STOP_GRACE_SECONDS)
+        self._run(self._on_close)
+        return finished
+
+    def install_signal_handlers(self, stop_server: Callable[[], None]):
+        """
+        Drain on SIGTERM/SIGINT, then call stop_server. Must be called from the main thread.
+        The drain runs in its own thread so the server keeps serving the requests it's waiting on.
+        """
+        def handle(signum, frame):
+            if self.draining:
+                logger.warning("Second shutdown signal, exiting now")
+                os._exit(1)
+            logger.info(f"Received {signal.Signals(signum).name}, shutting down (send it again to exit now)")
+
+            def run():
+                self.drain()
+                stop_server()
+
+            threading.Thread(target=run, name="shutdown", daemon=True).start()
+
+        for signum in (signal.SIGTERM, signal.SIGINT):
+            signal.signal(signum, handle)
@@ -0,0 +1,94 @@
+"""
+Per-route latency SLOs for ArchieAI.
+The analytics only had the total generation time of each answer, which doesn't say whether
+a slow answer waited on retrieval, a long history, or the model. Every request's duration
+(for streamed answers, until the last byte was sent) is now checked against its route's
+threshold from [slo]. Requests over it are logged as a "slow_request" event whose fields
+say what the request was doing (the resolved model, the number of history messages, the
+retrieval and first-token times, as far as the route knows them; see lib/Logging.py), and /metrics exports
+the request and violation counts per route. Routes are counted by their URL rule
+("/api/sessions/<session_id>"), so the number of series stays small.
+"""
+import logging
+import threading
+from collections import defaultdict
+from datetime import datetime
+from typing import Dict, List, Optional
+
+from lib.Logging import fields
+
+logger = logging.getLogger(__name__)
+
+
+class SloTracker:
+    """Latency counters and SLO violations per route, in memory, per process."""
+
+    def __init__(self, default_seconds: float = 2.0, routes: Optional[Dict[str, float]] = None,
+                 exclude: Optional[List[str]] = None):
+        """
+        Args:
+            default_seconds: Threshold for routes without their own
+            routes: URL rule -> threshold in seconds
+            exclude: URL rules that aren't tracked at all (long-lived event streams)
+        """
+        self.default_seconds = default_seconds
+        self.routes = dict(routes or {})
+        self.exclude = set(exclude or [])
+        self._lock = threading.Lock()
+        self._stats = defaultdict(lambda: {"requests": 0, "violations": 0, "seconds": 0.0})
+
+    @classmethod
+    def from_config(cls, config) -> "SloTracker":
+        return cls(config.slo.default_seconds, config.slo.routes, config.slo.exclude)
+
+    def threshold(self, route: str) -> float:
+        return self.routes.get(route, self.default_seconds)
+
+    def tracks(self, route: str) -> bool:
+        return route not in self.exclude
+
+    def record(self, route: str, method: str, status: int, seconds: float, request_id: Optional[str] = None,
+               details: Optional[Dict] = None) -> Optional[Dict]:
+        """
+        Count a finished request.
+
+        Args:
+            route: URL rule the request matched ("unmatched" for 404s)
+            method: HTTP method
+            status: Response status code
+            seconds: How long the request took
+            request_id: The request's ID, for the log line
+            details: What the route noted about the request (model, history_messages, retrieval_seconds, ...)
+
+        Returns:
+            The slow-request event if the request broke its SLO (it's also logged), else None
+        """
+        threshold = self.threshold(route)
+        violated = seconds > threshold
+        with self._lock:
+            stats = self._stats[route]
+            stats["requests"] += 1
+            stats["seconds"] += seconds
+            if violated:
+                stats["violations"] += 1
+        if not violated:
+            return None
+        event = {
+            "event": "slow_request",
+            "timestamp": datetime.now().isoformat(),
+            "request_id": request_id,
+            "method": method,
+            "route": route,
+            "status": status,
+            "seconds": round(seconds, 3),
+            "threshold_seconds": threshold,
+            **(details or {}),
+        }
+        logger.warning(f"Slow request: {method} {route} took {seconds:.2f}s (SLO {threshold:g}s)",
+                       extra=fields(**{key: value for key, value in event.items() if key != "timestamp"}))
+        return event
+
+    def metrics(self) -> Dict[str, Dict]:
+        """route -> {"requests", "violations", "seconds", "threshold_seconds"}."""
+        with self._lock:
+            return {route: dict(stats, threshold_seconds=self.threshold(route)) for route, stats in sorted(self._stats.items())}
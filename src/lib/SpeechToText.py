@@ -0,0 +1,90 @@
+"""
+Speech-to-text backends for ArchieAI voice questions (kiosk stations, the mic button).
+Audio is forwarded to a transcription server and the recognized text comes back for the
+chat box. Two kinds of server are supported:
+  - whisper_cpp: whisper.cpp's bundled HTTP server (POST /inference)
+  - openai: anything that speaks the OpenAI audio API (POST /v1/audio/transcriptions),
+            e.g. faster-whisper-server, LocalAI, or an Ollama-compatible gateway
+"""
+from typing import Optional
+import requests
+
+STT_BACKENDS = ("whisper_cpp", "openai")
+
+# Containers browsers record in (MediaRecorder) plus the usual upload formats
+AUDIO_TYPES = {
+    "audio/webm", "audio/ogg", "audio/wav", "audio/x-wav", "audio/wave", "audio/mpeg",
+    "audio/mp3", "audio/mp4", "audio/m4a", "audio/x-m4a", "audio/flac", "video/webm",
+}
+
+
+class TranscriptionError(Exception):
+    """The STT backend failed or gave back something unusable."""
+
+
+def is_audio_type(mimetype: Optional[str]) -> bool:
+    # Browsers add codec parameters, e.g. "audio/webm;codecs=opus"
+    return (mimetype or "").split(";")[0].strip().lower() in AUDIO_TYPES
+
+
+class SpeechToText:
+    """Client for the configured transcription server."""
+
+    def __init__(self, backend: str, url: str, model: str = "", language: str = "", timeout: int = 60):
+        if backend not in STT_BACKENDS:
+            raise ValueError(f"Unknown speech-to-text backend {backend}; expected one of {', '.join(STT_BACKENDS)}")
+        self.backend = backend
+        self.url = url.rstrip("/")
+        self.model = model
+        self.language = language
+        self.timeout = timeout
+
+    @classmethod
+    def from_config(cls, config) -> Optional["SpeechToText"]:
+        """The configured client, or None if speech-to-text isn't set up."""
+        stt = config.stt
+        if not stt.url:
+            return None
+        return cls(stt.backend, stt.url, model=stt.model, language=stt.language, timeout=stt.timeout_seconds)
+
+    def transcribe(self, audio: bytes, filename: str = "audio.webm", mimetype: str = "audio/webm",
+                   language: Optional[str] = None) -> str:
+        """
+        Transcribe an audio clip.
+
+        Args:
+            audio: The raw audio file
+            filename, mimetype: Passed along so the server can pick a decoder
+            language: ISO 639-1 hint (e.g. "en"); defaults to the configured one, or auto-detect
+
+        Returns:
+            The recognized text, whitespace trimmed
+
+        Raises:
+            TranscriptionError: If the server can't be reached or answers with an error
+        """
+        language = language or self.language
+        files = {"file": (filename, audio, mimetype)}
+        if self.backend == "whisper_cpp":
+            endpoint = f"{self.url}/inference"
+            data = {"response_format": "json", "temperature": "0"}
+        else:
+            endpoint = f"{self.url}/v1/audio/transcriptions"
+            data = {"model": self.model or "whisper-1", "response_format": "json"}
+        if language:
+            data["language"] = language
+
+        try:
+            resp = requests.post(endpoint, files=files, data=data, timeout=self.timeout)
+        except requests.RequestException as e:
+            raise TranscriptionError(f"Could not reach {endpoint}: {e}")
+        if resp.status_code >= 400:
+            raise TranscriptionError(f"{endpoint} answered {resp.status_code}: {resp.text[:200]}")
+
+        try:
+            text = resp.json().get("text")
+        except ValueError:
+            raise TranscriptionError(f"{endpoint} didn't answer with JSON")
+        if not isinstance(text, str):
+            raise TranscriptionError(f"{endpoint} answered without a text field")
+        return text.strip()
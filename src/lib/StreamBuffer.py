@@ -0,0 +1,175 @@
+"""
+Resumable answer streams for ArchieAI.
+A Wi-Fi blip in the middle of an answer used to cut the stream, stop the model, and leave
+the user to ask again, which cost another generation. Now an SSE answer is generated in
+the background into a buffer, and every event gets an ID ("<stream id>:<n>"). A client that
+reconnects with the Last-Event-ID header gets the events it missed and then the rest of
+the answer. If nobody reconnects within the grace period, the generation is closed the
+same way a disconnect closed it before: the model stops and the partial answer is saved.
+"""
+import logging
+import time
+import secrets
+import contextvars
+import threading
+from typing import Dict, Iterator, Optional, Tuple
+
+logger = logging.getLogger(__name__)
+
+
+def parse_event_id(value: Optional[str]) -> Optional[Tuple[str, int]]:
+    """Split a Last-Event-ID ("<stream id>:<n>") into its parts, or None if it isn't one of ours."""
+    if not value or ":" not in value:
+        return None
+    stream_id, _, seq = value.strip().rpartition(":")
+    if not stream_id or not seq.isdigit():
+        return None
+    return stream_id, int(seq)
+
+
+class BufferedStream:
+    """One answer being generated in the background, with its recent events kept for replay."""
+
+    def __init__(self, events: Iterator[Dict], owner: Optional[str], grace_seconds: float, max_events: int):
+        self.stream_id = secrets.token_urlsafe(16)
+        self.owner = owner
+        self.grace_seconds = grace_seconds
+        self.max_events = max_events
+        self._events = events
+        self._buffer = []  # (seq, event), oldest first
+        self._next_seq = 1
+        self._cond = threading.Condition()
+        self._readers = 0
+        self._detached_at = time.monotonic()
+        self.done = False
+        # Stopped because nobody came back; the partial answer is saved, so it can't be resumed
+        self.abandoned = False
+        # Set when the server shuts down before the answer is done (lib/Shutdown.py)
+        self._stop = threading.Event()
+        self.finished_at: Optional[float] = None
+        # The request's log context (lib/Logging.py) goes along, so the answer's log lines keep its request ID
+        context = contextvars.copy_context()
+        self._thread = threading.Thread(target=context.run, args=(self._run,), daemon=True)
+
+    def start(self):
+        self._thread.start()
+
+    def _abandoned(self) -> bool:
+        return self._readers == 0 and time.monotonic() - self._detached_at > self.grace_seconds
+
+    def _run(self):
+        try:
+            for event in self._events:
+                with self._cond:
+                    self._buffer.append((self._next_seq, event))
+                    self._next_seq += 1
+                    if len(self._buffer) > self.max_events:
+                        del self._buffer[:len(self._buffer) - self.max_events]
+                    self._cond.notify_all()
+                    abandoned = self._abandoned()
+                if abandoned:
+                    self.abandoned = True
+                    logger.info(f"Answer stream {self.stream_id} wasn't resumed within {self.grace_seconds:g} seconds, stopping it")
+                    break
+                if self._stop.is_set():
+                    logger.info(f"Answer stream {self.stream_id} stopped by shutdown")
+                    break
+        except Exception as e:
+            logger.error(f"Answer stream {self.stream_id} failed: {e}")
+        finally:
+            # Stops the model and saves what was generated, if the answer isn't complete
+            self._events.close()
+            with self._cond:
+                self.done = True
+                self.finished_at = time.monotonic()
+                self._cond.notify_all()
+
+    def stop(self, timeout: float = 0) -> bool:
+        """Stop generating after the next event, saving the partial answer; True if that's done within timeout."""
+        self._stop.set()
+        self._thread.join(timeout)
+        return self.done
+
+    def can_resume_after(self, seq: int) -> bool:
+        """False if events after seq have already been dropped, or the answer was stopped for lack of a client."""
+        with self._cond:
+            oldest = self._buffer[0][0] if self._buffer else self._next_seq
+            return not self.abandoned and seq + 1 >= oldest
+
+    def read(self, after: int = 0) -> Iterator[Tuple[str, Dict]]:
+        """
+        Yield (event ID, event) for every event after seq `after`, waiting for new ones until
+        the answer is done. Closing the iterator (the client went away) starts the grace period.
+        """
+        with self._cond:
+            self._readers += 1
+        try:
+            position = after
+            while True:
+                with self._cond:
+                    pending = [(seq, event) for seq, event in self._buffer if seq > position]
+                    if not pending:
+                        if self.done:
+                            return
+                        self._cond.wait(timeout=1.0)
+                        continue
+                for seq, event in pending:
+                    position = seq
+                    yield f"{self.stream_id}:{seq}", event
+        finally:
+            with self._cond:
+                self._readers -= 1
+                self._detached_at = time.monotonic()
+
+
+class StreamRegistry:
+    """The answer streams that can still be resumed."""
+
+    def __init__(self, grace_seconds: float = 30.0, ttl_seconds: float = 120.0, max_events: int = 5000):
+        """
+        Args:
+            grace_seconds: How long a generation keeps running with no client attached
+            ttl_seconds: How long a finished stream's events stay available for a late reconnect
+            max_events: Events kept per stream; a reconnect from before the oldest one can't resume
+        """
+        self.grace_seconds = grace_seconds
+        self.ttl_seconds = ttl_seconds
+        self.max_events = max_events
+        self._lock = threading.Lock()
+        self._streams: Dict[str, BufferedStream] = {}
+
+    def _prune(self):
+        now = time.monotonic()
+        for stream_id, stream in list(self._streams.items()):
+            if stream.done and now - stream.finished_at > self.ttl_seconds:
+                del self._streams[stream_id]
+
+    def start(self, events: Iterator[Dict], owner: Optional[str] = None) -> BufferedStream:
+        """Start generating an answer in the background and register it for resuming."""
+        stream = BufferedStream(events, owner, self.grace_seconds, self.max_events)
+        with self._lock:
+            self._prune()
+            self._streams[stream.stream_id] = stream
+        stream.start()
+        return stream
+
+    def get(self, stream_id: str) -> Optional[BufferedStream]:
+        with self._lock:
+            self._prune()
+            return self._streams.get(stream_id)
+
+    def active(self) -> int:
+        """Streams still generating."""
+        with self._lock:
+            return sum(1 for stream in self._streams.values() if not stream.done)
+
+    def stop_all(self, timeout: float = 5.0) -> int:
+        """Stop every answer still generating, waiting up to timeout seconds in all. Returns how many were stopped."""
+        with self._lock:
+            running = [stream for stream in self._streams.values() if not stream.done]
+        deadline = time.monotonic() + timeout
+        for stream in running:
+            stream._stop.set()
+        for stream in running:
+            stream.stop(max(0, deadline - time.monotonic()))
+        return len(running)
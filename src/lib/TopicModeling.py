@@ -0,0 +1,229 @@
+"""
+Topic modeling for ArchieAI analytics.
+Groups every logged question into a handful of broad topics with k-means over the question
+embeddings, then labels each topic with its most distinctive words and the question closest
+to its centroid. Where QuestionClustering finds near-duplicates ("when is fall break?" asked
+five ways), this answers "what are students asking about, and how much?" for the data
+science project. The report is written to data/question_topics.json for the admin dashboard.
+"""
+import logging
+import os
+import json
+import math
+import random
+import asyncio
+from collections import Counter
+from datetime import datetime
+from typing import Dict, List, Optional
+
+from lib.DataCollector import normalize_question
+from lib.QuestionClustering import cosine_similarity
+from lib.Sentiment import score_sentiment
+
+logger = logging.getLogger(__name__)
+
+REPORT_FILE = "question_topics.json"
+MAX_TOPICS = 30
+# Words too common in questions to say anything about a topic
+STOPWORDS = set("""
+a about above after again all am an and any are as at be been before being below between both but by
+can could did do does doing down during each few for from further had has have having he her here hers
+him his how i if in into is it its itself just me more most my no nor not now of off on once only or
+other our out over own same she should so some such than that the their them then there these they this
+those through to too under until up very was we were what when where which while who whom why will with
+would you your yours archie arcadia university please tell know get need want thanks hi hello like
+""".split())
+
+
+def _words(question: str) -> List[str]:
+    return [word for word in normalize_question(question).split() if len(word) > 2 and word not in STOPWORDS]
+
+
+def _normalize(vector: List[float]) -> List[float]:
+    norm = math.sqrt(sum(x * x for x in vector))
+    return [x / norm for x in vector] if norm else vector
+
+
+def default_topic_count(unique_questions: int) -> int:
+    """A rule-of-thumb topic count, sqrt(n / 2), kept between 2 and 12."""
+    return max(2, min(12, round(math.sqrt(unique_questions / 2))))
+
+
+def kmeans(vectors: List[List[float]], weights: List[float], k: int, iterations: int = 50,
+           seed: int = 0) -> List[int]:
+    """
+    Weighted spherical k-means (cosine similarity) with k-means++ seeding.
+    Seeded, so the same questions give the same topics from one run to the next.
+
+    Returns:
+        The topic index of each vector
+    """
+    rng = random.Random(seed)
+    vectors = [_normalize(v) for v in vectors]
+    k = max(1, min(k, len(vectors)))
+
+    centroids = [vectors[rng.choices(range(len(vectors)), weights=weights)[0]]]
+    while len(centroids) < k:
+        distances = [w * (1 - max(cosine_similarity(v, c) for c in centroids)) for v, w in zip(vectors, weights)]
+        if sum(distances) <= 0:
+            break
+        centroids.append(vectors[rng.choices(range(len(vectors)), weights=distances)[0]])
+
+    assignment = [-1] * len(vectors)
+    for _ in range(iterations):
+        changed = False
+        for i, vector in enumerate(vectors):
+            best = max(range(len(centroids)), key=lambda c: cosine_similarity(vector, centroids[c]))
+            if best != assignment[i]:
+                assignment[i] = best
+                changed = True
+        if not changed:
+            break
+        for c in range(len(centroids)):
+            members = [i for i, a in enumerate(assignment) if a == c]
+            if members:
+                summed = [sum(weights[i] * vectors[i][d] for i in members) for d in range(len(vectors[0]))]
+                centroids[c] = _normalize(summed)
+    return assignment
+
+
+def bag_of_words_vectors(questions: List[str]) -> List[List[float]]:
+    """TF-IDF vectors over the question vocabulary, used when the embedding model isn't available."""
+    tokenized = [_words(q) for q in questions]
+    vocabulary = sorted({word for words in tokenized for word in words})
+    index = {word: i for i, word in enumerate(vocabulary)}
+    df = Counter(word for words in tokenized for word in set(words))
+    vectors = []
+    for words in tokenized:
+        vector = [0.0] * max(1, len(vocabulary))
+        for word, count in Counter(words).items():
+            vector[index[word]] = count * math.log(1 + len(questions) / df[word])
+        vectors.append(vector)
+    return vectors
+
+
+class TopicModeler:
+    """Groups logged questions into labeled topics and writes a report to disk."""
+
+    def __init__(self, ai_interface, data_collector, data_dir: str = "data"):
+        self.ai = ai_interface
+        self.data_collector = data_collector
+        self.report_file = os.path.join(data_dir, REPORT_FILE)
+
+    def _unique_questions(self) -> List[Dict]:
+        """Collapse exact duplicates (after normalization), keeping counts, categories, and sentiment."""
+        unique: Dict[str, Dict] = {}
+        for interaction in self.data_collector.load_interactions():
+            question = interaction.get("question", "")
+            key = normalize_question(question)
+            if not key:
+                continue
+            entry = unique.setdefault(key, {
+                "question": question, "count": 0, "categories": Counter(), "sentiment": [], "last_asked": None
+            })
+            entry["count"] += 1
+            if interaction.get("category"):
+                entry["categories"][interaction["category"]] += 1
+            sentiment = interaction.get("sentiment")
+            entry["sentiment"].append(sentiment if sentiment is not None else score_sentiment(question))
+            if interaction.get("timestamp", "") > (entry["last_asked"] or ""):
+                entry["last_asked"] = interaction.get("timestamp")
+        return list(unique.values())
+
+    @staticmethod
+    def _keywords(topics: List[List[Dict]], limit: int = 5) -> List[List[str]]:
+        """
+        The most distinctive words of each topic (class-based TF-IDF): frequent in
+        the topic's questions, rare in the other topics'.
+        """
+        term_counts = []
+        for members in topics:
+            counts = Counter()
+            for q in members:
+                for word in _words(q["question"]):
+                    counts[word] += q["count"]
+            term_counts.append(counts)
+        topic_frequency = Counter(word for counts in term_counts for word in counts)
+        keywords = []
+        for counts in term_counts:
+            scored = sorted(
+                counts.items(),
+                key=lambda item: (-item[1] * math.log(1 + len(topics) / topic_frequency[item[0]]), item[0]),
+            )
+            keywords.append([word for word, _ in scored[:limit]])
+        return keywords
+
+    def run(self, num_topics: Optional[int] = None, samples: int = 10) -> Dict:
+        """
+        Group all logged questions into topics and save the report.
+
+        Args:
+            num_topics: How many topics to find (defaults to default_topic_count)
+            samples: Example questions kept per topic, most asked first
+
+        Returns:
+            The report dict that was written to question_topics.json
+        """
+        unique = self._unique_questions()
+        total = sum(q["count"] for q in unique)
+        method = "embeddings"
+        topics: List[List[int]] = []
+        vectors: List[List[float]] = []
+
+        if unique:
+            try:
+                vectors = asyncio.run(self.ai.embed([q["question"] for q in unique]))
+            except Exception as e:
+                # Word overlap still finds rough topics without the embedding model
+                logger.warning(f"Embedding failed, falling back to keyword vectors: {e}")
+                vectors = bag_of_words_vectors([q["question"] for q in unique])
+                method = "keywords"
+            k = min(num_topics or default_topic_count(len(unique)), MAX_TOPICS, len(unique))
+            assignment = kmeans(vectors, [q["count"] for q in unique], k)
+            topics = [[i for i, a in enumerate(assignment) if a == topic] for topic in sorted(set(assignment))]
+
+        keywords = self._keywords([[unique[i] for i in members] for members in topics])
+        report_topics = []
+        for members, words in zip(topics, keywords):
+            centroid = _normalize([sum(vectors[i][d] * unique[i]["count"] for i in members) for d in range(len(vectors[0]))])
+            representative = max(members, key=lambda i: cosine_similarity(vectors[i], centroid))
+            count = sum(unique[i]["count"] for i in members)
+            sentiment = [score for i in members for score in unique[i]["sentiment"]]
+            categories = sum((unique[i]["categories"] for i in members), Counter())
+            by_count = sorted(members, key=lambda i: unique[i]["count"], reverse=True)
+            report_topics.append({
+                "label": " / ".join(words[:3]) or unique[representative]["question"],
+                "keywords": words,
+                "representative": unique[representative]["question"],
+                "count": count,
+                "share": round(count / total, 3),
+                "unique_questions": len(members),
+                "avg_sentiment": round(sum(sentiment) / len(sentiment), 3),
+                "categories": dict(categories.most_common(3)),
+                "last_asked": max((unique[i]["last_asked"] or "" for i in members), default="") or None,
+                "questions": [{"question": unique[i]["question"], "count": unique[i]["count"]} for i in by_count[:samples]],
+            })
+        report_topics.sort(key=lambda t: t["count"], reverse=True)
+        for topic_id, topic in enumerate(report_topics):
+            topic["topic_id"] = topic_id
+
+        report = {
+            "generated_at": datetime.now().isoformat(),
+            "method": method,
+            "total_questions": total,
+            "total_unique_questions": len(unique),
+            "topics": report_topics,
+        }
+
+        with open(self.report_file, "w", encoding="utf-8") as f:
+            json.dump(report, f, ensure_ascii=False, indent=2)
+
+        return report
+
+    def load_report(self) -> Optional[Dict]:
+        """Load the last generated topics report, if any."""
+        try:
+            with open(self.report_file, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return None
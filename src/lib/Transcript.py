@@ -0,0 +1,88 @@
+"""
+Conversation transcripts for ArchieAI.
+Formats a chat session as plain text and HTML for sending it to yourself by email
+(POST /api/sessions/<id>/email): each message with who said it and when, the pages an
+answer cited, and a note on answers that were cut off. SendQuota keeps that endpoint
+from being used to flood inboxes.
+"""
+import html
+import time
+import threading
+from typing import Dict, Optional
+
+from lib.RateLimiter import TokenBucket
+
+
+def _when(timestamp: Optional[str]) -> str:
+    """"2026-10-17T14:03:22.123" -> "2026-10-17 14:03"."""
+    return (timestamp or "")[:16].replace("T", " ")
+
+
+def _speaker(message: Dict) -> str:
+    return "You" if message.get("role") == "user" else "Archie"
+
+
+def _sources(message: Dict) -> list:
+    return [citation.get("url") or citation.get("source") for citation in message.get("citations") or []
+            if citation.get("url") or citation.get("source")]
+
+
+def transcript_subject(session: Dict) -> str:
+    return f"Your ArchieAI conversation from {_when(session.get('created_at'))[:10]}"
+
+
+def render_text(session: Dict) -> str:
+    """Plain-text transcript of a session's messages."""
+    lines = [f"ArchieAI conversation, started {_when(session.get('created_at'))}", ""]
+    for message in session.get("messages", []):
+        lines.append(f"{_speaker(message)} ({_when(message.get('timestamp'))}):")
+        lines.append(message.get("content", ""))
+        if message.get("partial") or message.get("interrupted"):
+            lines.append("(This answer was cut off.)")
+        sources = _sources(message)
+        if sources:
+            lines.append("Sources: " + ", ".join(sources))
+        lines.append("")
+    lines.append("Archie can make mistakes. Always make sure the info you are given is correct.")
+    return "\n".join(lines) + "\n"
+
+
+def render_html(session: Dict) -> str:
+    """HTML alternative of render_text(); message text is escaped, line breaks kept."""
+    parts = [f"<h2>ArchieAI conversation, started {html.escape(_when(session.get('created_at')))}</h2>"]
+    for message in session.get("messages", []):
+        content = html.escape(message.get("content", "")).replace("\n", "<br>")
+        parts.append(f"<p><strong>{_speaker(message)}</strong> "
+                     f"<small>{html.escape(_when(message.get('timestamp')))}</small><br>{content}</p>")
+        if message.get("partial") or message.get("interrupted"):
+            parts.append("<p><em>This answer was cut off.</em></p>")
+        sources = _sources(message)
+        if sources:
+            links = ", ".join(f'<a href="{html.escape(url)}">{html.escape(url)}</a>' for url in sources)
+            parts.append(f"<p><small>Sources: {links}</small></p>")
+    parts.append("<p><small>Archie can make mistakes. Always make sure the info you are given is correct.</small></p>")
+    return "<html><body>" + "\n".join(parts) + "</body></html>"
+
+
+class SendQuota:
+    """
+    At most max_sends per caller at once, earned back at max_sends per window_seconds: a
+    lib/RateLimiter.TokenBucket per caller. Counts live in memory, per process.
+    """
+
+    def __init__(self, max_sends: int = 5, window_seconds: int = 3600):
+        self.max_sends = max_sends
+        self.window_seconds = window_seconds
+        self._lock = threading.Lock()
+        self._buckets: Dict[str, TokenBucket] = {}
+
+    def acquire(self, key: str) -> Optional[float]:
+        """Count a send for key; returns None if it's allowed, else the seconds until it would be."""
+        now = time.monotonic()
+        with self._lock:
+            bucket = self._buckets.get(key)
+            if bucket is None:
+                bucket = self._buckets[key] = TokenBucket(self.max_sends, self.max_sends / self.window_seconds, now)
+            retry_after = bucket.take(now)
+        # With max_sends = 0 nothing is ever allowed; report the window rather than forever
+        return None if retry_after is None else min(retry_after, self.window_seconds)
@@ -0,0 +1,187 @@
+"""
+Long-term memory for ArchieAI users.
+For users who opt in, durable facts they mention about themselves ("I'm a sophomore CS
+major in Knight Hall") are pulled out of each exchange by the model (prompts/memory_extract.txt)
+and stored in data/user_memories.json. The ones relevant to a new question are added to the
+system prompt, so returning users don't have to repeat themselves every session.
+Two preferences control it: "memory" (remember new facts, off by default) and
+"personalization" (use remembered facts in answers, on once memory is on).
+"""
+import logging
+import os
+import re
+import json
+import uuid
+import asyncio
+import threading
+from datetime import datetime
+from typing import Callable, Dict, List, Optional
+
+from lib.DataCollector import normalize_question
+from lib.QuestionClustering import cosine_similarity
+
+logger = logging.getLogger(__name__)
+
+MEMORIES_FILE = "user_memories.json"
+# Facts longer than this are more likely rambling than a fact
+MAX_FACT_LENGTH = 200
+
+
+def parse_facts(output: str) -> List[str]:
+    """The JSON array of facts in the model's output (models like to wrap it in prose or code fences)."""
+    match = re.search(r"\[.*\]", output, re.DOTALL)
+    try:
+        facts = json.loads(match.group(0)) if match else []
+    except json.JSONDecodeError:
+        return []
+    if not isinstance(facts, list):
+        return []
+    return [fact.strip() for fact in facts if isinstance(fact, str) and 0 < len(fact.strip()) <= MAX_FACT_LENGTH]
+
+
+class UserMemory:
+    """Per-user fact store plus extraction and recall."""
+
+    def __init__(self, ai, data_dir: str = "data", max_per_user: int = 50,
+                 embed: Optional[Callable[[List[str]], List[List[float]]]] = None):
+        """
+        Args:
+            ai: AiInterface used to extract facts from exchanges
+            data_dir: Data directory the memories are saved in
+            max_per_user: Oldest memories are dropped past this many
+            embed: Optional function texts -> vectors used to rank memories by relevance
+        """
+        self.ai = ai
+        self.path = os.path.join(data_dir, MEMORIES_FILE)
+        self.max_per_user = max_per_user
+        self.embed = embed
+        self._lock = threading.Lock()
+
+    def _load(self) -> Dict[str, List[Dict]]:
+        try:
+            with open(self.path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except (FileNotFoundError, json.JSONDecodeError):
+            return {}
+
+    def _save(self, memories: Dict[str, List[Dict]]):
+        os.makedirs(os.path.dirname(self.path), exist_ok=True)
+        tmp_path = self.path + ".tmp"
+        with open(tmp_path, "w", encoding="utf-8") as f:
+            json.dump(memories, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_path, self.path)
+
+    def list_memories(self, email: str) -> List[Dict]:
+        """A user's memories, oldest first, without the cached embeddings."""
+        return [
+            {key: value for key, value in memory.items() if key != "embedding"}
+            for memory in self._load().get(email, [])
+        ]
+
+    def add(self, email: str, facts: List[str], session_id: Optional[str] = None) -> List[Dict]:
+        """Store new facts, skipping ones already remembered. Returns the memories that were added."""
+        added = []
+        with self._lock:
+            memories = self._load()
+            existing = memories.setdefault(email, [])
+            known = {normalize_question(memory["fact"]) for memory in existing}
+            for fact in facts:
+                key = normalize_question(fact)
+                if not key or key in known:
+                    continue
+                known.add(key)
+                memory = {
+                    "memory_id": uuid.uuid4().hex,
+                    "fact": fact,
+                    "session_id": session_id,
+                    "created_at": datetime.now().isoformat(),
+                }
+                existing.append(memory)
+                added.append(memory)
+            if len(existing) > self.max_per_user:
+                del existing[:len(existing) - self.max_per_user]
+            if added:
+                self._save(memories)
+        return added
+
+    def delete_memory(self, email: str, memory_id: str) -> bool:
+        with self._lock:
+            memories = self._load()
+            existing = memories.get(email, [])
+            remaining = [memory for memory in existing if memory["memory_id"] != memory_id]
+            if len(remaining) == len(existing):
+                return False
+            memories[email] = remaining
+            self._save(memories)
+        return True
+
+    def clear(self, email: str) -> int:
+        """Forget everything about a user. Returns how many memories were deleted."""
+        with self._lock:
+            memories = self._load()
+            removed = memories.pop(email, [])
+            if removed:
+                self._save(memories)
+        return len(removed)
+
+    def relevant(self, email: str, question: str, limit: int = 5) -> List[str]:
+        """
+        The facts most relevant to a question, by embedding similarity when available
+        (otherwise the most recent ones). Embeddings are cached in the file.
+        """
+        memories = self._load().get(email, [])
+        if len(memories) <= limit or self.embed is None:
+            return [memory["fact"] for memory in memories[-limit:]]
+
+        try:
+            missing = [memory for memory in memories if not memory.get("embedding")]
+            vectors = self.embed([question] + [memory["fact"] for memory in missing])
+        except Exception as e:
+            logger.warning(f"Could not rank memories by relevance: {e}")
+            return [memory["fact"] for memory in memories[-limit:]]
+
+        question_vector = vectors[0]
+        for memory, vector in zip(missing, vectors[1:]):
+            memory["embedding"] = vector
+        if missing:
+            with self._lock:
+                stored = self._load()
+                cached = {memory["memory_id"]: memory["embedding"] for memory in missing}
+                for memory in stored.get(email, []):
+                    if memory["memory_id"] in cached:
+                        memory["embedding"] = cached[memory["memory_id"]]
+                self._save(stored)
+
+        ranked = sorted(memories, key=lambda memory: cosine_similarity(question_vector, memory["embedding"]), reverse=True)
+        return [memory["fact"] for memory in ranked[:limit]]
+
+    async def extract(self, question: str, answer: str, known: List[str]) -> List[str]:
+        """Ask the model for durable facts the user stated in this exchange."""
+        prompt = self.ai.load_prompt("memory_extract").format(
+            known="\n".join(f"- {fact}" for fact in known) or "(nothing yet)",
+            question=question,
+            answer=answer,
+        )
+        output = ""
+        async for token in self.ai.generate_text_streaming(prompt):
+            output += token
+        return parse_facts(output)
+
+    def remember(self, email: str, question: str, answer: str, session_id: Optional[str] = None,
+                 still_allowed: Optional[Callable[[], bool]] = None) -> List[Dict]:
+        """
+        Extract and store facts from an exchange. Meant to run in a background thread.
+
+        Args:
+            still_allowed: Checked again once extraction finishes, so nothing is saved if the
+                           user turned memory off (or wiped it) in the meantime
+        """
+        known = [memory["fact"] for memory in self._load().get(email, [])]
+        try:
+            facts = asyncio.run(self.extract(question, answer, known))
+        except Exception as e:
+            logger.warning(f"Memory extraction failed: {e}")
+            return []
+        if still_allowed is not None and not still_allowed():
+            return []
+        return self.add(email, facts, session_id=session_id)
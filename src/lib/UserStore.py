@@ -0,0 +1,196 @@
+"""
+Per-user account storage for ArchieAI.
+Each account lives in its own file, data/users/<sha256 of the email>.json, with
+data/users/index.json mapping emails to files. A signup or a new session only rewrites
+that one user's file (atomically, through a temp file), instead of the whole users.json
+every account used to share, so concurrent writes can't clobber each other and a torn
+write can only damage one record. An existing users.json is split up on first start.
+"""
+import logging
+import os
+import json
+import hashlib
+import threading
+from typing import Callable, Dict, Iterator, List, Optional
+
+from lib.InstanceLock import ReadOnlyStorage
+
+logger = logging.getLogger(__name__)
+
+USERS_DIR = "users"
+INDEX_FILE = "index.json"
+LEGACY_FILE = "users.json"
+
+
+def user_filename(email: str) -> str:
+    """File name for an account; hashed so any email is a safe path."""
+    return hashlib.sha256(email.encode("utf-8")).hexdigest() + ".json"
+
+
+def _write_json(path: str, data):
+    tmp_path = f"{path}.{threading.get_ident()}.tmp"
+    with open(tmp_path, "w", encoding="utf-8") as f:
+        json.dump(data, f, indent=4, ensure_ascii=False)
+    os.replace(tmp_path, path)
+
+
+class UserStore:
+    """Account records keyed by email, one file each."""
+
+    def __init__(self, data_dir: str = "data", migrate: bool = True, read_only: bool = False):
+        """
+        Args:
+            migrate: Split up an old users.json right away
+            read_only: Refuse every write (a read-only replica, see lib/InstanceLock.py)
+        """
+        self.data_dir = data_dir
+        self.directory = os.path.join(data_dir, USERS_DIR)
+        self.index_path = os.path.join(self.directory, INDEX_FILE)
+        self.read_only = read_only
+        if not read_only:
+            os.makedirs(self.directory, exist_ok=True)
+        # Guards the index; record updates take the per-email lock
+        self._index_lock = threading.Lock()
+        self._locks: Dict[str, threading.Lock] = {}
+        if migrate and not read_only:
+            self.migrate_legacy_file()
+
+    def _write(self, path: str, data):
+        if self.read_only:
+            raise ReadOnlyStorage("accounts")
+        _write_json(path, data)
+
+    def _lock_for(self, email: str) -> threading.Lock:
+        with self._index_lock:
+            return self._locks.setdefault(email, threading.Lock())
+
+    def _path(self, email: str) -> str:
+        return os.path.join(self.directory, user_filename(email))
+
+    def _load_index(self) -> Dict[str, str]:
+        try:
+            with open(self.index_path, "r", encoding="utf-8") as f:
+                return json.load(f)
+        except FileNotFoundError:
+            return {}
+        except json.JSONDecodeError as e:
+            # The records are the source of truth; rebuild the index from them
+            logger.warning(f"{self.index_path} is corrupted ({e}), rebuilding it")
+            return self.rebuild_index()
+
+    def rebuild_index(self) -> Dict[str, str]:
+        """Recreate index.json from the user files on disk."""
+        index = {}
+        for name in os.listdir(self.directory):
+            if name == INDEX_FILE or not name.endswith(".json"):
+                continue
+            try:
+                with open(os.path.join(self.directory, name), "r", encoding="utf-8") as f:
+                    email = json.load(f).get("email")
+            except (OSError, json.JSONDecodeError, AttributeError):
+                continue
+            if email:
+                index[email] = name
+        if not self.read_only:
+            # A replica uses the rebuilt index without saving it
+            self._write(self.index_path, index)
+        return index
+
+    def migrate_legacy_file(self) -> int:
+        """
+        Split a users.json from before per-user files into the users directory.
+        The old file is kept as users.json.migrated.
+
+        Returns:
+            How many accounts were moved (0 if there was nothing to do)
+        """
+        legacy_path = os.path.join(self.data_dir, LEGACY_FILE)
+        try:
+            with open(legacy_path, "r", encoding="utf-8") as f:
+                users = json.load(f)
+        except FileNotFoundError:
+            return 0
+        except json.JSONDecodeError as e:
+            logger.warning(f"{legacy_path} is corrupted ({e}), not migrating it")
+            return 0
+
+        with self._index_lock:
+            index = self._load_index()
+            for email, user in users.items():
+                # Accounts already in the new store win over the old file
+                if email not in index:
+                    self._write(self._path(email), dict(user, email=email))
+                    index[email] = user_filename(email)
+            self._write(self.index_path, index)
+        os.replace(legacy_path, legacy_path + ".migrated")
+        logger.info(f"Moved {len(users)} accounts from {LEGACY_FILE} to {USERS_DIR}/")
+        return len(users)
+
+    def get(self, email: Optional[str]) -> Optional[Dict]:
+        """One account's record, or None."""
+        if not email:
+            return None
+        try:
+            with open(self._path(email), "r", encoding="utf-8") as f:
+                return json.load(f)
+        except FileNotFoundError:
+            return None
+        except json.JSONDecodeError as e:
+            logger.warning(f"The account file for {email} is corrupted: {e}")
+            return None
+
+    def create(self, email: str, record: Dict) -> bool:
+        """Add an account. Returns False if the email is already taken."""
+        with self._lock_for(email):
+            path = self._path(email)
+            if os.path.exists(path):
+                return False
+            self._write(path, record)
+        with self._index_lock:
+            index = self._load_index()
+            index[email] = user_filename(email)
+            self._write(self.index_path, index)
+        return True
+
+    def update(self, email: str, change: Callable[[Dict], None]) -> Optional[Dict]:
+        """
+        Change an account in place: change(record) edits the record, which is then saved.
+        Concurrent updates to the same account run one after another.
+
+        Returns:
+            The saved record, or None if there's no such account
+        """
+        with self._lock_for(email):
+            record = self.get(email)
+            if record is None:
+                return None
+            change(record)
+            self._write(self._path(email), record)
+            return record
+
+    def delete(self, email: str) -> bool:
+        """Remove an account. Returns False if there's no such account."""
+        if self.read_only:
+            raise ReadOnlyStorage("accounts")
+        with self._lock_for(email):
+            try:
+                os.remove(self._path(email))
+            except FileNotFoundError:
+                return False
+        with self._index_lock:
+            index = self._load_index()
+            index.pop(email, None)
+            self._write(self.index_path, index)
+        return True
+
+    def emails(self) -> List[str]:
+        """Every account's email, in signup order."""
+        with self._index_lock:
+            return list(self._load_index())
+
+    def all(self) -> Iterator[Dict]:
+        """Every account's record."""
+        for email in self.emails():
+            user = self.get(email)
+            if user is not None:
+                yield user
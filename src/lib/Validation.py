@@ -0,0 +1,427 @@
+"""
+Request validation for ArchieAI.
+Checks incoming chat requests before anything is sent to the model, answer feedback,
+and admin-written FAQ entries and announcements.
+"""
+import re
+import unicodedata
+from datetime import datetime, timedelta
+from typing import Any, Optional
+from lib.Errors import ValidationError
+from lib.AnswerStyle import STYLES
+
+
+def _has_invalid_characters(text: str) -> bool:
+    """True if text has lone surrogates or control characters (other than whitespace)."""
+    for ch in text:
+        if ch in "\n\r\t":
+            continue
+        category = unicodedata.category(ch)
+        # Cs = surrogate (not valid UTF-8 on its own), Cc = control characters like NUL
+        if category in ("Cs", "Cc"):
+            return True
+    return False
+
+
+def validate_question(data: Any, max_length: int) -> str:
+    """
+    Validate the JSON body of a chat request and return the cleaned question.
+
+    Args:
+        data: Parsed JSON body (may be None if the body wasn't JSON)
+        max_length: Maximum allowed question length in characters
+
+    Returns:
+        The question with surrounding whitespace stripped
+
+    Raises:
+        ValidationError: If the body or question is invalid
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    question = data.get("question")
+    if not isinstance(question, str):
+        raise ValidationError("question must be a string", field="question")
+
+    question = question.strip()
+    if not question:
+        raise ValidationError("question must not be empty", field="question")
+
+    if len(question) > max_length:
+        raise ValidationError("question must be at most {max_length} characters", field="question",
+                              params={"max_length": max_length})
+
+    if _has_invalid_characters(question):
+        raise ValidationError("question contains invalid characters", field="question")
+
+    return question
+
+
+FEEDBACK_RATINGS = ("up", "down")
+MAX_FEEDBACK_COMMENT_LENGTH = 500
+
+
+def validate_feedback(data: Any) -> dict:
+    """
+    Validate the JSON body for rating an answer.
+
+    Returns:
+        {"rating": "up" | "down" | None, "comment": str | None}; a null rating clears earlier feedback
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    rating = data.get("rating")
+    if rating is not None and rating not in FEEDBACK_RATINGS:
+        raise ValidationError("rating must be up, down, or null", field="rating")
+
+    comment = data.get("comment")
+    if comment is not None:
+        if not isinstance(comment, str):
+            raise ValidationError("comment must be a string", field="comment")
+        comment = comment.strip() or None
+        if comment and len(comment) > MAX_FEEDBACK_COMMENT_LENGTH:
+            raise ValidationError("{field} must be at most {max_length} characters", field="comment",
+                                  params={"field": "comment", "max_length": MAX_FEEDBACK_COMMENT_LENGTH})
+        if comment and _has_invalid_characters(comment):
+            raise ValidationError("comment contains invalid characters", field="comment")
+
+    return {"rating": rating, "comment": comment}
+
+
+def validate_model(value: Any, allowed: list, field: str = "model") -> Optional[str]:
+    """
+    Check a chat model picked by the user against the configured ones.
+
+    Returns:
+        The model, or None if none was given
+    """
+    if value is None:
+        return None
+    if not isinstance(value, str) or value not in allowed:
+        raise ValidationError("{field} must be one of {models}", field=field,
+                              params={"field": field, "models": ", ".join(allowed)})
+    return value
+
+
+def validate_style(value: Any, field: str = "style") -> Optional[str]:
+    """
+    Check an answer style ("concise", "detailed", "bullet").
+
+    Returns:
+        The style, or None if none was given
+    """
+    if value is None:
+        return None
+    if not isinstance(value, str) or value not in STYLES:
+        raise ValidationError("{field} must be one of {models}", field=field,
+                              params={"field": field, "models": ", ".join(STYLES)})
+    return value
+
+
+
+MAX_COMPARE_MODELS = 4
+
+
+def validate_comparison(data: Any, allowed: list, max_length: int) -> dict:
+    """
+    Validate a model comparison request: {"question", "models": [two or more configured models], "style"}.
+
+    Returns:
+        {"question", "models", "style"}
+    """
+    question = validate_question(data, max_length)
+    models = data.get("models")
+    if (not isinstance(models, list) or not 2 <= len(models) <= MAX_COMPARE_MODELS
+            or len(set(map(str, models))) != len(models)):
+        raise ValidationError("models must be a list of 2 to {max_models} different models", field="models",
+                              params={"max_models": MAX_COMPARE_MODELS})
+    for model in models:
+        validate_model(model, allowed, field="models")
+    return {"question": question, "models": models, "style": validate_style(data.get("style"))}
+
+
+
+def validate_canary_percent(value: Any) -> int:
+    """Check the share of sessions a canary gets (1-100)."""
+    if isinstance(value, bool) or not isinstance(value, int) or not 1 <= value <= 100:
+        raise ValidationError("percent must be a whole number from 1 to 100", field="percent")
+    return value
+
+
+def validate_canary(data: Any, allowed: list, prompt_exists) -> dict:
+    """
+    Validate a request starting a canary: {"model", "prompt", "percent"}, with a candidate model
+    and/or prompt.
+
+    Args:
+        data: Parsed JSON body
+        allowed: The configured chat models
+        prompt_exists: Callable telling whether a prompt name is a file in the prompts directory
+
+    Returns:
+        {"model", "prompt", "percent"} (percent defaults to 10)
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    model = validate_model(data.get("model"), allowed)
+    prompt = data.get("prompt")
+    if prompt is not None and (not isinstance(prompt, str) or not prompt_exists(prompt)):
+        raise ValidationError("prompt must be the name of a file in the prompts directory", field="prompt")
+    if model is None and prompt is None:
+        raise ValidationError("Give a candidate model, prompt, or both")
+    return {"model": model, "prompt": prompt, "percent": validate_canary_percent(data.get("percent", 10))}
+
+
+
+def validate_flag(value: Any, field: str) -> bool:
+    """Check an optional true/false field of a request body (missing or null is false)."""
+    if value is None:
+        return False
+    if not isinstance(value, bool):
+        raise ValidationError("{field} must be true or false", field=field, params={"field": field})
+    return value
+
+
+
+def validate_merge(data: Any) -> dict:
+    """
+    Validate a session merge request: {"session_ids": [two different IDs], "delete_originals": bool}.
+
+    Returns:
+        {"session_ids": [...], "delete_originals": bool}
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    session_ids = data.get("session_ids")
+    if (not isinstance(session_ids, list) or len(session_ids) != 2
+            or not all(isinstance(session_id, str) and session_id for session_id in session_ids)
+            or session_ids[0] == session_ids[1]):
+        raise ValidationError("session_ids must be a list of two different session IDs", field="session_ids")
+    return {"session_ids": session_ids, "delete_originals": validate_flag(data.get("delete_originals"), "delete_originals")}
+
+
+MAX_CHECKPOINT_LABEL_LENGTH = 100
+MAX_STATUS_REASON_LENGTH = 500
+
+
+def validate_user_status(data: Any, statuses) -> tuple:
+    """
+    Validate the JSON body for changing an account's status: {"status": ..., "reason": "..."}.
+
+    Returns:
+        (status, reason), reason "" if none was given
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+    status = data.get("status")
+    if status not in statuses:
+        raise ValidationError("{field} must be one of {models}", field="status",
+                              params={"field": "status", "models": ", ".join(statuses)})
+    reason = data.get("reason") or ""
+    if not isinstance(reason, str) or len(reason) > MAX_STATUS_REASON_LENGTH:
+        raise ValidationError("reason must be text of at most 500 characters", field="reason")
+    if _has_invalid_characters(reason):
+        raise ValidationError("reason contains invalid characters", field="reason")
+    return status, reason.strip()
+
+
+def validate_checkpoint(data: Any) -> Optional[str]:
+    """
+    Validate the (optional) JSON body for checkpointing a conversation.
+
+    Returns:
+        The cleaned label, or None if none was given
+    """
+    if data is None:
+        return None
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    label = data.get("label")
+    if label is None:
+        return None
+    if not isinstance(label, str):
+        raise ValidationError("label must be a string", field="label")
+    label = label.strip()
+    if len(label) > MAX_CHECKPOINT_LABEL_LENGTH:
+        raise ValidationError("{field} must be at most {max_length} characters", field="label",
+                              params={"field": "label", "max_length": MAX_CHECKPOINT_LABEL_LENGTH})
+    if _has_invalid_characters(label):
+        raise ValidationError("label contains invalid characters", field="label")
+    return label or None
+
+
+MAX_SESSION_TITLE_LENGTH = 100
+
+
+def validate_session_title(value: Any) -> Optional[str]:
+    """
+    Check a title the user gave a chat.
+
+    Returns:
+        The trimmed title, or None (back to no title) for null or a blank string
+    """
+    if value is None:
+        return None
+    if not isinstance(value, str):
+        raise ValidationError("title must be a string", field="title")
+    title = " ".join(value.split())
+    if len(title) > MAX_SESSION_TITLE_LENGTH:
+        raise ValidationError("{field} must be at most {max_length} characters", field="title",
+                              params={"field": "title", "max_length": MAX_SESSION_TITLE_LENGTH})
+    if _has_invalid_characters(title):
+        raise ValidationError("title contains invalid characters", field="title")
+    return title or None
+
+
+def parse_window(value: Optional[str]) -> Optional[timedelta]:
+    """
+    Parse a reporting window like "24h", "7d", or "4w"; "all" (or nothing) means no limit.
+
+    Raises:
+        ValidationError: If the window isn't in that form
+    """
+    if value is None or value.strip().lower() in ("", "all"):
+        return None
+    match = re.fullmatch(r"(\d{1,4})([hdw])", value.strip().lower())
+    if match is None or int(match.group(1)) == 0:
+        raise ValidationError("window must look like 24h, 7d, 4w, or all", field="window")
+    amount, unit = int(match.group(1)), match.group(2)
+    return timedelta(hours=amount) if unit == "h" else timedelta(days=amount * (7 if unit == "w" else 1))
+
+
+def validate_faq_entry(data: Any, max_length: int, partial: bool = False) -> dict:
+    """
+    Validate the JSON body for creating (or, with partial=True, updating) a curated FAQ entry.
+
+    Returns:
+        The cleaned question/answer/alternate_questions fields that were given
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    entry = {}
+    if "question" in data or not partial:
+        entry["question"] = validate_question(data, max_length=max_length)
+
+    if "answer" in data or not partial:
+        answer = data.get("answer")
+        if not isinstance(answer, str) or not answer.strip():
+            raise ValidationError("answer must be a non-empty string", field="answer")
+        entry["answer"] = answer.strip()
+
+    if "alternate_questions" in data:
+        alternates = data["alternate_questions"]
+        if not isinstance(alternates, list) or not all(isinstance(q, str) for q in alternates):
+            raise ValidationError("alternate_questions must be a list of strings", field="alternate_questions")
+        entry["alternate_questions"] = [q.strip() for q in alternates if q.strip()]
+
+    if not entry:
+        raise ValidationError("Nothing to update")
+    return entry
+
+
+def _parse_datetime_field(value: Any, field: str) -> Optional[str]:
+    """ISO date/datetime -> naive local ISO string (what the stores compare against), None stays None."""
+    if value is None:
+        return None
+    try:
+        parsed = datetime.fromisoformat(value) if isinstance(value, str) else None
+    except ValueError:
+        parsed = None
+    if parsed is None:
+        raise ValidationError("{field} must be an ISO date or datetime", field=field, params={"field": field})
+    if parsed.tzinfo is not None:
+        parsed = parsed.astimezone().replace(tzinfo=None)
+    return parsed.isoformat()
+
+
+def validate_date_range(args: Any) -> tuple:
+    """
+    Validate ?from= and ?to= (ISO dates or datetimes, either optional; a bare date as to covers
+    the whole day). Returns (start, end) as ISO strings or None.
+    """
+    start = _parse_datetime_field(args.get("from") or None, "from")
+    end = _parse_datetime_field(args.get("to") or None, "to")
+    if end is not None and len(args["to"]) == 10:
+        end = end[:10] + "T23:59:59.999999"
+    if start and end and start > end:
+        raise ValidationError("from must be before to", field="from")
+    return start, end
+
+
+def validate_timeseries(args: Any, metrics, intervals: dict, max_buckets: int) -> dict:
+    """
+    Validate the query of a chart series request (metric, interval, from, to).
+
+    Args:
+        args: The query string arguments
+        metrics: The allowed metric names
+        intervals: Interval name -> (bucket length, buckets covered when from isn't given)
+        max_buckets: Most buckets one series may have
+
+    Returns:
+        {"metric", "interval", "start", "end"} with start and end as naive datetimes
+    """
+    metric = args.get("metric", "questions")
+    if metric not in metrics:
+        raise ValidationError("{field} must be one of {models}", field="metric",
+                              params={"field": "metric", "models": ", ".join(metrics)})
+    interval = args.get("interval", "hour")
+    if interval not in intervals:
+        raise ValidationError("{field} must be one of {models}", field="interval",
+                              params={"field": "interval", "models": ", ".join(intervals)})
+    step, default_buckets = intervals[interval]
+
+    end = _parse_datetime_field(args.get("to") or None, "to")
+    if end is not None and len(args["to"]) == 10:
+        # A bare date as the upper bound covers the whole day
+        end = end[:10] + "T23:59:59.999999"
+    end = datetime.fromisoformat(end) if end else datetime.now()
+    start = _parse_datetime_field(args.get("from") or None, "from")
+    start = datetime.fromisoformat(start) if start else end - step * (default_buckets - 1)
+    if start > end:
+        raise ValidationError("from must be before to", field="from")
+    if (end - start) / step >= max_buckets:
+        raise ValidationError("That range has more than {max_buckets} buckets, use a longer interval",
+                              field="interval", params={"max_buckets": max_buckets})
+    return {"metric": metric, "interval": interval, "start": start, "end": end}
+
+
+def validate_announcement(data: Any, partial: bool = False) -> dict:
+    """
+    Validate the JSON body for creating (or, with partial=True, updating) an announcement.
+
+    Returns:
+        The cleaned title/body/starts_at/ends_at/in_prompt fields that were given
+    """
+    if not isinstance(data, dict):
+        raise ValidationError("Request body must be a JSON object")
+
+    announcement = {}
+    for key, max_length in (("title", 200), ("body", 2000)):
+        if key in data or not partial:
+            value = data.get(key)
+            if not isinstance(value, str) or not value.strip():
+                raise ValidationError("{field} must be a non-empty string", field=key, params={"field": key})
+            if len(value) > max_length:
+                raise ValidationError("{field} must be at most {max_length} characters", field=key,
+                                      params={"field": key, "max_length": max_length})
+            announcement[key] = value.strip()
+
+    for key in ("starts_at", "ends_at"):
+        if key in data:
+            announcement[key] = _parse_datetime_field(data[key], key)
+
+    if "in_prompt" in data:
+        if not isinstance(data["in_prompt"], bool):
+            raise ValidationError("in_prompt must be true or false", field="in_prompt")
+        announcement["in_prompt"] = data["in_prompt"]
+
+    if announcement.get("starts_at") and announcement.get("ends_at") and announcement["ends_at"] <= announcement["starts_at"]:
+        raise ValidationError("ends_at must be after starts_at", field="ends_at")
+    if not announcement:
+        raise ValidationError("Nothing to update")
+    return announcement
@@ -0,0 +1,224 @@
+"""
+Server side of the WebSocket protocol (RFC 6455) for ArchieAI, just enough for pushing events.
+ArchieAI runs on Werkzeug's threaded server, which hands over the raw connection of a request
+as environ["werkzeug.socket"] (Gunicorn's sync workers as "gunicorn.socket"), so a view can
+answer the upgrade handshake itself and keep the connection for as long as it likes without
+another dependency. Messages from the client are read only to answer pings and closes; the
+feeds built on this are one way. When the view is done it returns WebSocket.response(), which
+tells the server the connection is no longer an HTTP one.
+
+The view holds its worker for as long as the connection is open: a thread of the built-in
+server, or a whole process with Gunicorn's sync workers. Every open feed is one fewer request
+the server can take at the same time, so size the workers for the admins who keep it open.
+"""
+import base64
+import hashlib
+import json
+import select
+import socket
+import struct
+from typing import Optional
+
+from werkzeug.wrappers import Response
+
+GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+
+OP_CONTINUATION = 0x0
+OP_TEXT = 0x1
+OP_BINARY = 0x2
+OP_CLOSE = 0x8
+OP_PING = 0x9
+OP_PONG = 0xA
+
+# Clients only send control frames and short messages on these feeds; a message split into
+# fragments can't be longer than this in total either
+MAX_CLIENT_FRAME = 64 * 1024
+
+# Close codes (RFC 6455 section 7.4.1)
+CLOSE_NORMAL = 1000
+CLOSE_PROTOCOL_ERROR = 1002
+CLOSE_TOO_BIG = 1009
+
+
+class WebSocketClosed(ConnectionError):
+    """The client closed the connection or it dropped."""
+
+
+def is_upgrade_request(environ) -> bool:
+    return ("websocket" in environ.get("HTTP_UPGRADE", "").lower()
+            and bool(environ.get("HTTP_SEC_WEBSOCKET_KEY")))
+
+
+def accept_key(key: str) -> str:
+    """Sec-WebSocket-Accept for a client's Sec-WebSocket-Key."""
+    return base64.b64encode(hashlib.sha1((key + GUID).encode("ascii")).digest()).decode("ascii")
+
+
+def encode_frame(opcode: int, payload: bytes) -> bytes:
+    """One unfragmented, unmasked (server to client) frame."""
+    length = len(payload)
+    if length < 126:
+        header = struct.pack("!BB", 0x80 | opcode, length)
+    elif length < 1 << 16:
+        header = struct.pack("!BBH", 0x80 | opcode, 126, length)
+    else:
+        header = struct.pack("!BBQ", 0x80 | opcode, 127, length)
+    return header + payload
+
+
+class _Upgraded(Response):
+    """Returned once the view is done with the socket; the server must not write an HTTP response on it."""
+
+    def __call__(self, environ, start_response):
+        # Werkzeug and Gunicorn treat this as the client having gone away
+        raise ConnectionError("WebSocket connection closed")
+
+
+class WebSocket:
+    """A WebSocket connection taken over from the WSGI server."""
+
+    def __init__(self, sock: socket.socket):
+        self.sock = sock
+        self.closed = False
+        # Opcode and payload so far of a message that arrives in fragments
+        self._fragments: Optional[tuple] = None
+
+    @classmethod
+    def accept(cls, environ) -> "WebSocket":
+        """
+        Answer the opening handshake of a request.
+
+        Raises:
+            ValueError: It isn't a WebSocket upgrade, or the server can't hand over the connection
+        """
+        if not is_upgrade_request(environ):
+            raise ValueError("Not a WebSocket upgrade request")
+        sock = environ.get("werkzeug.socket") or environ.get("gunicorn.socket")
+        if sock is None:
+            raise ValueError("This server can't hand the connection over for a WebSocket")
+        sock.sendall((
+            "HTTP/1.1 101 Switching Protocols\r\n"
+            "Upgrade: websocket\r\n"
+            "Connection: Upgrade\r\n"
+            f"Sec-WebSocket-Accept: {accept_key(environ['HTTP_SEC_WEBSOCKET_KEY'])}\r\n"
+            "\r\n"
+        ).encode("ascii"))
+        return cls(sock)
+
+    @staticmethod
+    def response() -> Response:
+        return _Upgraded()
+
+    def _send(self, opcode: int, payload: bytes):
+        if self.closed:
+            raise WebSocketClosed("Connection already closed")
+        try:
+            self.sock.sendall(encode_frame(opcode, payload))
+        except OSError as e:
+            self.closed = True
+            raise WebSocketClosed(str(e)) from e
+
+    def send(self, text: str):
+        self._send(OP_TEXT, text.encode("utf-8"))
+
+    def send_json(self, payload):
+        self.send(json.dumps(payload, default=str))
+
+    def ping(self):
+        self._send(OP_PING, b"")
+
+    def _recv_exact(self, n: int) -> bytes:
+        data = b""
+        while len(data) < n:
+            chunk = self.sock.recv(n - len(data))
+            if not chunk:
+                raise WebSocketClosed("Connection dropped")
+            data += chunk
+        return data
+
+    def _fail(self, code: int, reason: str):
+        self.close(code)
+        raise WebSocketClosed(reason)
+
+    def _read_frame(self) -> tuple:
+        """The next frame as (fin, opcode, unmasked payload), closing the connection if it breaks the protocol."""
+        first, second = self._recv_exact(2)
+        fin = bool(first & 0x80)
+        opcode = first & 0x0F
+        length = second & 0x7F
+        if length == 126:
+            length = struct.unpack("!H", self._recv_exact(2))[0]
+        elif length == 127:
+            length = struct.unpack("!Q", self._recv_exact(8))[0]
+        if first & 0x70:
+            # No extensions were negotiated, so the reserved bits must be clear
+            self._fail(CLOSE_PROTOCOL_ERROR, "Reserved bits set")
+        if not second & 0x80:
+            self._fail(CLOSE_PROTOCOL_ERROR, "Client frames must be masked")
+        if opcode >= OP_CLOSE and (not fin or length > 125):
+            self._fail(CLOSE_PROTOCOL_ERROR, "Control frames can't be fragmented or longer than 125 bytes")
+        if length > MAX_CLIENT_FRAME:
+            # Big frames aren't expected here
+            self._fail(CLOSE_TOO_BIG, "Frame too big")
+        mask = self._recv_exact(4)
+        payload = bytes(b ^ mask[i % 4] for i, b in enumerate(self._recv_exact(length)))
+        return fin, opcode, payload
+
+    def _message(self, fin: bool, opcode: int, payload: bytes) -> Optional[tuple]:
+        """Put fragments back together; returns (opcode, payload) once a data message is complete."""
+        if opcode == OP_CONTINUATION:
+            if self._fragments is None:
+                self._fail(CLOSE_PROTOCOL_ERROR, "Continuation without a message to continue")
+            opcode, payload = self._fragments[0], self._fragments[1] + payload
+        elif self._fragments is not None:
+            self._fail(CLOSE_PROTOCOL_ERROR, "New message before the last one's final fragment")
+        if len(payload) > MAX_CLIENT_FRAME:
+            self._fail(CLOSE_TOO_BIG, "Message too big")
+        if not fin:
+            self._fragments = (opcode, payload)
+            return None
+        self._fragments = None
+        return opcode, payload
+
+    def poll(self, timeout: float = 0.0) -> Optional[str]:
+        """
+        Handle what the client sent, waiting up to timeout seconds for it: pings are answered and
+        a close is returned. Returns the text of a message, if one was completed, else None.
+
+        Raises:
+            WebSocketClosed: The client closed the connection, broke the protocol, or it dropped
+        """
+        if self.closed:
+            raise WebSocketClosed("Connection already closed")
+        try:
+            readable, _, _ = select.select([self.sock], [], [], timeout)
+            if not readable:
+                return None
+            fin, opcode, payload = self._read_frame()
+        except OSError as e:
+            self.closed = True
+            raise WebSocketClosed(str(e)) from e
+        if opcode == OP_CLOSE:
+            self.close(struct.unpack("!H", payload[:2])[0] if len(payload) >= 2 else CLOSE_NORMAL)
+            raise WebSocketClosed("Closed by the client")
+        if opcode == OP_PING:
+            self._send(OP_PONG, payload)
+            return None
+        if opcode == OP_PONG:
+            return None
+        if opcode not in (OP_CONTINUATION, OP_TEXT, OP_BINARY):
+            self._fail(CLOSE_PROTOCOL_ERROR, f"Unknown opcode {opcode}")
+        message = self._message(fin, opcode, payload)
+        if message is not None and message[0] == OP_TEXT:
+            return message[1].decode("utf-8", errors="replace")
+        return None
+
+    def close(self, code: int = CLOSE_NORMAL):
+        """Send a close frame; the server closes the socket once the view returns."""
+        if self.closed:
+            return
+        try:
+            self.sock.sendall(encode_frame(OP_CLOSE, struct.pack("!H", code)))
+        except OSError:
+            pass
+        self.closed = True
@@ -0,0 +1,114 @@
+"""
+Outbound webhook notifications for operational events.
+Posts Slack/Discord compatible JSON to the configured URL when something operators
+should know about happens (new users, repeated generation failures, quota errors,
+abuse suspensions, students stuck in a conversation).
+"""
+import logging
+import time
+import threading
+from collections import deque
+from datetime import datetime
+from typing import Optional
+import requests
+
+logger = logging.getLogger(__name__)
+
+
+class WebhookNotifier:
+    """Sends operational events to an incoming webhook, in the background and rate limited."""
+
+    def __init__(self, config=None):
+        webhook = config.webhook if config is not None else None
+        self.url = webhook.url if webhook else ""
+        self.events = set(webhook.events) if webhook else set()
+        self.failure_threshold = webhook.failure_threshold if webhook else 3
+        self.failure_window_seconds = webhook.failure_window_seconds if webhook else 300
+        self.cooldown_seconds = webhook.cooldown_seconds if webhook else 900
+
+        self._lock = threading.Lock()
+        self._last_sent = {}
+        self._failures = deque()
+
+    @property
+    def enabled(self) -> bool:
+        return bool(self.url)
+
+    def notify(self, event: str, text: str, cooldown_key: Optional[str] = None, **fields) -> bool:
+        """
+        Send an event to the webhook unless it's disabled, filtered out, or in its cooldown.
+
+        Args:
+            event: Event name, e.g. "user_registered"
+            text: Human readable summary shown in Slack/Discord
+            cooldown_key: Keep a separate cooldown per key (e.g. per session) instead of one for the event
+            **fields: Extra details included in the payload
+
+        Returns:
+            True if a notification was queued
+        """
+        if not self.enabled or event not in self.events:
+            return False
+
+        now = time.time()
+        key = (event, cooldown_key)
+        with self._lock:
+            last = self._last_sent.get(key)
+            if last is not None and now - last < self.cooldown_seconds:
+                return False
+            self._last_sent[key] = now
+
+        details = "".join(f"\n• {key}: {value}" for key, value in fields.items() if value is not None)
+        message = f"[ArchieAI] {text}{details}"
+        payload = {
+            # Slack reads "text", Discord reads "content"
+            "text": message,
+            "content": message,
+            "event": event,
+            "timestamp": datetime.now().isoformat(),
+            "fields": fields,
+        }
+        threading.Thread(target=self._post, args=(payload,), daemon=True).start()
+        return True
+
+    def _post(self, payload: dict):
+        try:
+            resp = requests.post(self.url, json=payload, timeout=10)
+            if resp.status_code >= 400:
+                logger.warning(f"Webhook returned {resp.status_code} for {payload['event']}")
+        except requests.RequestException as e:
+            logger.warning(f"Failed to send webhook for {payload['event']}: {e}")
+
+    def user_registered(self, email: str):
+        self.notify("user_registered", f"New user registered: {email}", email=email)
+
+    def account_suspended(self, email: str, reasons: list):
+        self.notify("account_suspended", f"Account suspended pending review: {email}",
+                    email=email, reasons=", ".join(reasons))
+
+    def session_frustrated(self, session_id: str, user_email: Optional[str], score: float, signals: dict):
+        # Every stuck student matters, so the cooldown is per session rather than for the event
+        self.notify("frustrated_session", f"A student seems stuck in session {session_id}", cooldown_key=session_id,
+                    user=user_email or "guest", score=score,
+                    signals=", ".join(f"{name} x{count}" for name, count in signals.items() if count))
+
+    def generation_failed(self, error: Exception, request_id: Optional[str] = None):
+        """Record a failed generation, alerting on quota errors or once failures pile up."""
+        if getattr(error, "status_code", None) == 429:
+            self.notify("quota_exhausted", "The Ollama API quota is exhausted (HTTP 429)",
+                        error=str(error), request_id=request_id)
+
+        now = time.time()
+        with self._lock:
+            self._failures.append(now)
+            while self._failures and now - self._failures[0] > self.failure_window_seconds:
+                self._failures.popleft()
+            count = len(self._failures)
+
+        if count >= self.failure_threshold:
+            self.notify(
+                "generation_failures",
+                f"{count} generation failures in the last {self.failure_window_seconds} seconds",
+                last_error=str(error),
+                request_id=request_id,
+            )
@@ -0,0 +1,93 @@
+"""
+Tokens that lib/SessionTokens.py must refuse: forged, expired, revoked, malformed, non-ASCII.
+Run from the repository root: python -m unittest discover tests
+"""
+import json
+import os
+import sys
+import time
+import unittest
+from unittest import mock
+
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "src"))
+
+from lib.SessionTokens import SessionTokens, _b64encode  # noqa: E402
+
+SECRET = b"test secret"
+
+
+def forge(claims: dict, tokens: SessionTokens) -> str:
+    """A correctly signed token with whatever claims, as if the secret had leaked."""
+    payload = _b64encode(json.dumps(claims).encode("utf-8"))
+    return f"{payload}.{tokens._sign(payload)}"
+
+
+class SessionTokenTests(unittest.TestCase):
+    def setUp(self):
+        self.tokens = SessionTokens(SECRET, max_age_seconds=3600)
+
+    def test_round_trip(self):
+        claims = self.tokens.read(self.tokens.issue("abc123", "student@arcadia.edu", version=2))
+        self.assertEqual(claims["sid"], "abc123")
+        self.assertEqual(claims["email"], "student@arcadia.edu")
+        self.assertEqual(claims["ver"], 2)
+
+    def test_guest_token_has_no_email(self):
+        claims = self.tokens.read(self.tokens.issue("abc123"))
+        self.assertEqual(claims["sid"], "abc123")
+        self.assertIsNone(claims["email"])
+
+    def test_changed_payload_is_refused(self):
+        payload, signature = self.tokens.issue("abc123", "student@arcadia.edu").split(".")
+        forged = _b64encode(json.dumps({"sid": "abc123", "email": "admin@arcadia.edu", "iat": int(time.time()), "ver": 0}).encode("utf-8"))
+        self.assertIsNone(self.tokens.read(f"{forged}.{signature}"))
+
+    def test_changed_signature_is_refused(self):
+        payload, signature = self.tokens.issue("abc123", "student@arcadia.edu").split(".")
+        tampered = ("A" if signature[0] != "A" else "B") + signature[1:]
+        self.assertIsNone(self.tokens.read(f"{payload}.{tampered}"))
+        self.assertIsNone(self.tokens.read(f"{payload}."))
+
+    def test_token_signed_with_another_secret_is_refused(self):
+        other = SessionTokens(b"someone else's secret")
+        self.assertIsNone(self.tokens.read(other.issue("abc123", "admin@arcadia.edu")))
+
+    def test_expired_token_is_refused(self):
+        token = self.tokens.issue("abc123", "student@arcadia.edu")
+        with mock.patch("lib.SessionTokens.time.time", return_value=time.time() + 3601):
+            self.assertIsNone(self.tokens.read(token))
+        with mock.patch("lib.SessionTokens.time.time", return_value=time.time() + 3500):
+            self.assertIsNotNone(self.tokens.read(token))
+
+    def test_revoked_version_is_refused(self):
+        token = self.tokens.issue("abc123", "student@arcadia.edu", version=1)
+        self.assertIsNone(self.tokens.read(token, current_version=lambda email: 2))
+        self.assertIsNotNone(self.tokens.read(token, current_version=lambda email: 1))
+
+    def test_guest_token_isnt_checked_against_a_version(self):
+        token = self.tokens.issue("abc123")
+        self.assertIsNotNone(self.tokens.read(token, current_version=lambda email: 5))
+
+    def test_malformed_tokens_are_refused(self):
+        valid = self.tokens.issue("abc123", "student@arcadia.edu")
+        for token in [None, "", ".", "abc", "a.b.c", valid + ".extra", "%%%.%%%", "not base64!.sig"]:
+            with self.subTest(token=token):
+                self.assertIsNone(self.tokens.read(token))
+
+    def test_signed_garbage_is_refused(self):
+        now = int(time.time())
+        for claims in [["sid"], {"email": "a@b.c", "iat": now}, {"sid": 5, "iat": now}, {"sid": "abc", "iat": "yesterday"}]:
+            with self.subTest(claims=claims):
+                self.assertIsNone(self.tokens.read(forge(claims, self.tokens)))
+        payload = _b64encode(b"\xff\xfe not json")
+        self.assertIsNone(self.tokens.read(f"{payload}.{self.tokens._sign(payload)}"))
+
+    def test_non_ascii_tokens_are_refused(self):
+        payload, signature = self.tokens.issue("abc123", "student@arcadia.edu").split(".")
+        for token in ["é.abc", f"{payload}é.{signature}", f"{payload}.{signature[:-1]}é", "😀.😀"]:
+            with self.subTest(token=token):
+                self.assertIsNone(self.tokens.read(token))
+
+
+if __name__ == "__main__":
+    unittest.main()
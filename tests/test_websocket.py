@@ -0,0 +1,160 @@
+"""
+The WebSocket protocol handling in lib/WebSocket.py, driven over a socket pair.
+Run from the repository root: python -m unittest discover tests
+"""
+import os
+import socket
+import struct
+import sys
+import unittest
+
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "src"))
+
+from lib.WebSocket import (  # noqa: E402
+    MAX_CLIENT_FRAME, OP_BINARY, OP_CLOSE, OP_CONTINUATION, OP_PING, OP_PONG, OP_TEXT,
+    WebSocket, WebSocketClosed, accept_key,
+)
+
+MASK = b"\x37\xfa\x21\x3d"
+
+
+def client_frame(opcode: int, payload: bytes = b"", fin: bool = True, masked: bool = True, rsv: int = 0) -> bytes:
+    """A frame as a browser would send it."""
+    first = (0x80 if fin else 0) | rsv | opcode
+    mask_bit = 0x80 if masked else 0
+    length = len(payload)
+    if length < 126:
+        header = struct.pack("!BB", first, mask_bit | length)
+    elif length < 1 << 16:
+        header = struct.pack("!BBH", first, mask_bit | 126, length)
+    else:
+        header = struct.pack("!BBQ", first, mask_bit | 127, length)
+    if not masked:
+        return header + payload
+    return header + MASK + bytes(b ^ MASK[i % 4] for i, b in enumerate(payload))
+
+
+def read_server_frame(sock: socket.socket) -> tuple:
+    first, second = sock.recv(2)
+    length = second & 0x7F
+    if length == 126:
+        length = struct.unpack("!H", sock.recv(2))[0]
+    elif length == 127:
+        length = struct.unpack("!Q", sock.recv(8))[0]
+    payload = b""
+    while len(payload) < length:
+        payload += sock.recv(length - len(payload))
+    return first & 0x0F, payload
+
+
+class WebSocketTests(unittest.TestCase):
+    def setUp(self):
+        self.server_sock, self.client = socket.socketpair()
+        self.client.settimeout(2)
+        self.ws = WebSocket(self.server_sock)
+
+    def tearDown(self):
+        self.server_sock.close()
+        self.client.close()
+
+    def assertClosedWith(self, code: int):
+        opcode, payload = read_server_frame(self.client)
+        self.assertEqual(opcode, OP_CLOSE)
+        self.assertEqual(struct.unpack("!H", payload)[0], code)
+        self.assertTrue(self.ws.closed)
+
+    def test_accept_key_matches_the_rfc_example(self):
+        self.assertEqual(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+
+    def test_masked_text_message(self):
+        self.client.sendall(client_frame(OP_TEXT, "héllo".encode("utf-8")))
+        self.assertEqual(self.ws.poll(timeout=1), "héllo")
+
+    def test_nothing_sent_returns_none(self):
+        self.assertIsNone(self.ws.poll(timeout=0))
+
+    def test_unmasked_frame_is_a_protocol_error(self):
+        self.client.sendall(client_frame(OP_TEXT, b"hi", masked=False))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1002)
+
+    def test_reserved_bits_are_a_protocol_error(self):
+        self.client.sendall(client_frame(OP_TEXT, b"hi", rsv=0x40))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1002)
+
+    def test_fragmented_message_is_reassembled(self):
+        self.client.sendall(client_frame(OP_TEXT, b"hel", fin=False))
+        self.client.sendall(client_frame(OP_CONTINUATION, b"lo ", fin=False))
+        # Control frames may come between fragments
+        self.client.sendall(client_frame(OP_PING, b"p"))
+        self.client.sendall(client_frame(OP_CONTINUATION, b"there"))
+        results = [self.ws.poll(timeout=1) for _ in range(4)]
+        self.assertEqual(results, [None, None, None, "hello there"])
+        self.assertEqual(read_server_frame(self.client), (OP_PONG, b"p"))
+
+    def test_continuation_without_a_message_is_a_protocol_error(self):
+        self.client.sendall(client_frame(OP_CONTINUATION, b"stray"))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1002)
+
+    def test_new_message_inside_a_fragmented_one_is_a_protocol_error(self):
+        self.client.sendall(client_frame(OP_TEXT, b"first", fin=False))
+        self.client.sendall(client_frame(OP_TEXT, b"second"))
+        self.assertIsNone(self.ws.poll(timeout=1))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1002)
+
+    def test_fragmented_control_frame_is_a_protocol_error(self):
+        self.client.sendall(client_frame(OP_PING, b"p", fin=False))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1002)
+
+    def test_oversized_frame_is_refused(self):
+        self.client.sendall(client_frame(OP_BINARY, b"x" * (MAX_CLIENT_FRAME + 1))[:16])
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1009)
+
+    def test_oversized_fragmented_message_is_refused(self):
+        half = b"x" * (MAX_CLIENT_FRAME // 2 + 1)
+        self.client.sendall(client_frame(OP_TEXT, half, fin=False))
+        self.assertIsNone(self.ws.poll(timeout=1))
+        self.client.sendall(client_frame(OP_CONTINUATION, half))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1009)
+
+    def test_ping_is_answered_with_pong(self):
+        self.client.sendall(client_frame(OP_PING, b"are you there"))
+        self.assertIsNone(self.ws.poll(timeout=1))
+        self.assertEqual(read_server_frame(self.client), (OP_PONG, b"are you there"))
+
+    def test_close_is_echoed(self):
+        self.client.sendall(client_frame(OP_CLOSE, struct.pack("!H", 1001)))
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+        self.assertClosedWith(1001)
+        with self.assertRaises(WebSocketClosed):
+            self.ws.send("too late")
+
+    def test_dropped_connection_raises(self):
+        self.client.close()
+        with self.assertRaises(WebSocketClosed):
+            self.ws.poll(timeout=1)
+
+    def test_server_frames_are_unmasked(self):
+        self.ws.send_json({"event": "ready"})
+        self.assertEqual(read_server_frame(self.client), (OP_TEXT, b'{"event": "ready"}'))
+        long_text = "y" * 70000
+        self.ws.send(long_text)
+        self.assertEqual(read_server_frame(self.client), (OP_TEXT, long_text.encode("ascii")))
+
+
+if __name__ == "__main__":
+    unittest.main()